@@ -1,21 +1,48 @@
 from ._native import __version__
-from ._wrapper import analyze, analyze_and_write, write_report
+from ._wrapper import Session, analyze, analyze_and_write, write_report
 from .models import (
     AnalysisResult,
+    AssemblyMode,
     ChainSummary,
+    ConfidenceGroup,
+    ConfidenceSummary,
+    CorpusScore,
+    DomainSummary,
     FrameScore,
     FrameStatus,
+    HeteroEnvironment,
+    InteractionArrays,
+    InterfaceResidue,
+    InterfaceSummary,
     OutputFormat,
+    ProblemRegion,
     ReportPaths,
+    ResidueConfidence,
+    ResidueProbability,
+    SecondaryStructure,
 )
 
 __all__ = [
     "AnalysisResult",
+    "AssemblyMode",
     "ChainSummary",
+    "ConfidenceGroup",
+    "ConfidenceSummary",
+    "CorpusScore",
+    "DomainSummary",
     "FrameScore",
     "FrameStatus",
+    "HeteroEnvironment",
+    "InteractionArrays",
+    "InterfaceResidue",
+    "InterfaceSummary",
     "OutputFormat",
+    "ProblemRegion",
     "ReportPaths",
+    "ResidueConfidence",
+    "ResidueProbability",
+    "SecondaryStructure",
+    "Session",
     "__version__",
     "analyze",
     "analyze_and_write",
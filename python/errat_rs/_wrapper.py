@@ -2,16 +2,29 @@ from __future__ import annotations
 
 from os import PathLike, fspath
 from pathlib import Path
-from typing import Any, Dict, Iterable, Optional, Union, cast
+from typing import Any, Dict, Iterable, Optional, Sequence, Tuple, Union, cast
 
 from . import _native
 from .models import (
     AnalysisResult,
+    AssemblyMode,
     ChainSummary,
+    ConfidenceGroup,
+    ConfidenceSummary,
+    CorpusScore,
+    DomainSummary,
     FrameScore,
     FrameStatus,
+    HeteroEnvironment,
+    InteractionArrays,
+    InterfaceResidue,
+    InterfaceSummary,
     OutputFormat,
+    ProblemRegion,
     ReportPaths,
+    ResidueConfidence,
+    ResidueProbability,
+    SecondaryStructure,
 )
 
 Pathish = Union[str, PathLike[str]]
@@ -22,6 +35,22 @@ def analyze(
     *,
     protein_id: Optional[str] = None,
     use_mmap: bool = False,
+    calibration_path: Optional[Pathish] = None,
+    detailed: bool = False,
+    window_length: Optional[int] = None,
+    radius: Optional[float] = None,
+    radmin: Optional[float] = None,
+    domains: Sequence[Tuple[str, str, int, int]] = (),
+    region_gap: int = 0,
+    crystal_contacts: bool = False,
+    assembly: Optional[str] = None,
+    assembly_mode: AssemblyMode = "full",
+    interface_distance: Optional[float] = None,
+    hetero_environment: Optional[HeteroEnvironment] = None,
+    resolution_reference_path: Optional[Pathish] = None,
+    reference_path: Optional[Pathish] = None,
+    plddt_cutoff: Optional[float] = None,
+    secondary_structure: bool = False,
 ) -> AnalysisResult:
     payload = cast(
         Dict[str, Any],
@@ -29,6 +58,31 @@ def analyze(
             fspath(input_path),
             protein_id=protein_id,
             use_mmap=use_mmap,
+            calibration_path=(
+                None if calibration_path is None else fspath(calibration_path)
+            ),
+            detailed=detailed,
+            window_length=window_length,
+            radius=radius,
+            radmin=radmin,
+            domains=[
+                (str(name), str(chain), int(start), int(end))
+                for name, chain, start, end in domains
+            ],
+            region_gap=region_gap,
+            crystal_contacts=crystal_contacts,
+            assembly=assembly,
+            assembly_mode=assembly_mode,
+            interface_distance=interface_distance,
+            hetero_environment=hetero_environment,
+            resolution_reference_path=(
+                None
+                if resolution_reference_path is None
+                else fspath(resolution_reference_path)
+            ),
+            reference_path=None if reference_path is None else fspath(reference_path),
+            plddt_cutoff=plddt_cutoff,
+            secondary_structure=secondary_structure,
         ),
     )
     return _analysis_from_payload(payload)
@@ -82,24 +136,121 @@ def analyze_and_write(
     return _analysis_from_payload(payload)
 
 
+class Session:
+    def __init__(
+        self,
+        input_path: Pathish,
+        *,
+        protein_id: Optional[str] = None,
+        use_mmap: bool = False,
+        calibration_path: Optional[Pathish] = None,
+    ) -> None:
+        self._native = _native.Session(
+            fspath(input_path),
+            protein_id=protein_id,
+            use_mmap=use_mmap,
+            calibration_path=(
+                None if calibration_path is None else fspath(calibration_path)
+            ),
+        )
+
+    @property
+    def atom_count(self) -> int:
+        return int(self._native.atom_count())
+
+    @property
+    def overall_quality_factor(self) -> Optional[float]:
+        return _optional_float(self._native.overall_quality_factor())
+
+    def atom_index(self, chain_id: str, residue: int, atom_name: str) -> Optional[int]:
+        index = self._native.atom_index(chain_id, residue, atom_name)
+        return None if index is None else int(index)
+
+    def coordinates(self, atom: int) -> Optional[Tuple[float, float, float]]:
+        value = self._native.coordinates(atom)
+        if value is None:
+            return None
+        x, y, z = value
+        return (float(x), float(y), float(z))
+
+    def update_coordinates(
+        self, updates: Iterable[Tuple[int, Sequence[float]]]
+    ) -> int:
+        return int(
+            self._native.update_coordinates(
+                [
+                    (int(atom), [float(xyz[0]), float(xyz[1]), float(xyz[2])])
+                    for atom, xyz in updates
+                ]
+            )
+        )
+
+    def analysis(self) -> AnalysisResult:
+        return _analysis_from_payload(
+            cast(Dict[str, Any], self._native.analysis())
+        )
+
+
 def _analysis_from_payload(payload: Dict[str, Any]) -> AnalysisResult:
     chain_summaries = tuple(
         ChainSummary(
             chain_id=str(item["chain_id"]),
             start_residue=int(item["start_residue"]),
             end_residue=int(item["end_residue"]),
+            min_p_value=_optional_float(item["min_p_value"]),
+            scored_frame_count=int(item["scored_frame_count"]),
+            rejected_frame_count=int(item["rejected_frame_count"]),
+            quality_factor=_optional_float(item["quality_factor"]),
+            average_error_value=_optional_float(item["average_error_value"]),
         )
         for item in cast(Iterable[Dict[str, Any]], payload["chain_summaries"])
     )
+    domain_summaries = tuple(
+        DomainSummary(
+            name=str(item["name"]),
+            chain_id=str(item["chain_id"]),
+            start_residue=int(item["start_residue"]),
+            end_residue=int(item["end_residue"]),
+            scored_frame_count=int(item["scored_frame_count"]),
+            rejected_frame_count=int(item["rejected_frame_count"]),
+            quality_factor=_optional_float(item["quality_factor"]),
+            average_error_value=_optional_float(item["average_error_value"]),
+        )
+        for item in cast(Iterable[Dict[str, Any]], payload["domain_summaries"])
+    )
+    problem_regions = tuple(
+        ProblemRegion(
+            chain_id=str(item["chain_id"]),
+            start_residue=int(item["start_residue"]),
+            end_residue=int(item["end_residue"]),
+            length=int(item["length"]),
+            peak_error_value=float(item["peak_error_value"]),
+            mean_error_value=float(item["mean_error_value"]),
+            severity=cast(FrameStatus, item["severity"]),
+        )
+        for item in cast(Iterable[Dict[str, Any]], payload["problem_regions"])
+    )
     frame_scores = tuple(
         FrameScore(
             chain_id=str(item["chain_id"]),
             center_residue=int(item["center_residue"]),
             error_value=float(item["error_value"]),
             status=cast(FrameStatus, item["status"]),
+            p_value=float(item["p_value"]),
+            secondary_structure=cast(
+                Optional[SecondaryStructure], item.get("secondary_structure")
+            ),
         )
         for item in cast(Iterable[Dict[str, Any]], payload["frame_scores"])
     )
+    residue_p_values = tuple(
+        ResidueProbability(
+            chain_id=str(item["chain_id"]),
+            residue=int(item["residue"]),
+            p_value=float(item["p_value"]),
+        )
+        for item in cast(Iterable[Dict[str, Any]], payload["residue_p_values"])
+    )
     return AnalysisResult(
         protein_id=str(payload["protein_id"]),
         input_path=Path(payload["input_path"]),
@@ -108,6 +259,7 @@ def _analysis_from_payload(payload: Dict[str, Any]) -> AnalysisResult:
         rejected_frame_ratio=_optional_float(payload["rejected_frame_ratio"]),
         overall_quality_factor=_optional_float(payload["overall_quality_factor"]),
         average_probability=_optional_float(payload["average_probability"]),
+        average_error_value=_optional_float(payload["average_error_value"]),
         below_interaction_limit_frames=tuple(
             int(frame)
             for frame in cast(Iterable[Any], payload["below_interaction_limit_frames"])
@@ -119,6 +271,105 @@ def _analysis_from_payload(payload: Dict[str, Any]) -> AnalysisResult:
         ),
         log_text=str(payload["log_text"]),
         report_paths=_optional_report_paths(payload.get("report_paths")),
+        interactions=_optional_interactions(payload.get("interactions")),
+        residue_p_values=residue_p_values,
+        domain_summaries=domain_summaries,
+        problem_regions=problem_regions,
+        interface=_optional_interface(payload.get("interface")),
+        hetero_environment=cast(
+            Optional[HeteroEnvironment], payload.get("hetero_environment")
+        ),
+        experimental_method=payload.get("experimental_method"),
+        resolution=_optional_float(payload.get("resolution")),
+        quality_percentile=_optional_float(payload.get("quality_percentile")),
+        corpus_score=_optional_corpus_score(payload.get("corpus_score")),
+        confidence=_optional_confidence(payload.get("confidence")),
+    )
+
+
+def _confidence_group(value: Any) -> ConfidenceGroup:
+    payload = cast(Dict[str, Any], value)
+    return ConfidenceGroup(
+        scored_frame_count=int(payload["scored_frame_count"]),
+        rejected_frame_count=int(payload["rejected_frame_count"]),
+        quality_factor=_optional_float(payload["quality_factor"]),
+        average_error_value=_optional_float(payload["average_error_value"]),
+    )
+
+
+def _optional_confidence(value: Any) -> Optional[ConfidenceSummary]:
+    if value is None:
+        return None
+    payload = cast(Dict[str, Any], value)
+    return ConfidenceSummary(
+        cutoff=float(payload["cutoff"]),
+        mean_plddt=_optional_float(payload["mean_plddt"]),
+        residues=tuple(
+            ResidueConfidence(
+                chain_id=str(chain_id), residue=int(residue), plddt=float(plddt)
+            )
+            for chain_id, residue, plddt in cast(
+                Iterable[Tuple[Any, Any, Any]], payload["residues"]
+            )
+        ),
+        confident=_confidence_group(payload["confident"]),
+        low_confidence=_confidence_group(payload["low_confidence"]),
+    )
+
+
+def _optional_corpus_score(value: Any) -> Optional[CorpusScore]:
+    if value is None:
+        return None
+    payload = cast(Dict[str, Any], value)
+    max_residues = payload["max_residues"]
+    return CorpusScore(
+        method=str(payload["method"]),
+        residue_count=int(payload["residue_count"]),
+        min_residues=int(payload["min_residues"]),
+        max_residues=None if max_residues is None else int(max_residues),
+        structure_count=int(payload["structure_count"]),
+        mean_quality_factor=float(payload["mean_quality_factor"]),
+        z_score=_optional_float(payload["z_score"]),
+        percentile=float(payload["percentile"]),
+    )
+
+
+def _optional_interface(value: Any) -> Optional[InterfaceSummary]:
+    if value is None:
+        return None
+    payload = cast(Dict[str, Any], value)
+    return InterfaceSummary(
+        distance=float(payload["distance"]),
+        residues=tuple(
+            InterfaceResidue(chain_id=str(chain_id), residue=int(residue))
+            for chain_id, residue in cast(Iterable[Tuple[Any, Any]], payload["residues"])
+        ),
+        scored_frame_count=int(payload["scored_frame_count"]),
+        rejected_frame_count=int(payload["rejected_frame_count"]),
+        quality_factor=_optional_float(payload["quality_factor"]),
+        average_error_value=_optional_float(payload["average_error_value"]),
+        inter_chain_fraction=_optional_float(payload["inter_chain_fraction"]),
+    )
+
+
+def _optional_interactions(value: Any) -> Optional[InteractionArrays]:
+    if value is None:
+        return None
+    payload = cast(Dict[str, Any], value)
+    return InteractionArrays(
+        counts=tuple(
+            tuple(float(v) for v in row)
+            for row in cast(Iterable[Iterable[Any]], payload["counts"])
+        ),
+        totals=tuple(float(v) for v in cast(Iterable[Any], payload["totals"])),
+        fractions=tuple(
+            tuple(float(v) for v in row)
+            for row in cast(Iterable[Iterable[Any]], payload["fractions"])
+        ),
+        inter_chain_counts=tuple(
+            tuple(float(v) for v in row)
+            for row in cast(Iterable[Iterable[Any]], payload["inter_chain_counts"])
+        ),
     )
 
 
@@ -6,6 +6,9 @@ from typing import Literal, Optional, Tuple
 
 FrameStatus = Literal["ok", "warning95", "warning99"]
 OutputFormat = Literal["ps", "pdf"]
+AssemblyMode = Literal["full", "context"]
+HeteroEnvironment = Literal["ligands", "ligands+water"]
+SecondaryStructure = Literal["helix", "strand", "loop"]
 
 
 @dataclass(frozen=True)
@@ -13,6 +16,87 @@ class ChainSummary:
     chain_id: str
     start_residue: int
     end_residue: int
+    min_p_value: Optional[float] = None
+    scored_frame_count: int = 0
+    rejected_frame_count: int = 0
+    quality_factor: Optional[float] = None
+    average_error_value: Optional[float] = None
+
+
+@dataclass(frozen=True)
+class DomainSummary:
+    name: str
+    chain_id: str
+    start_residue: int
+    end_residue: int
+    scored_frame_count: int
+    rejected_frame_count: int
+    quality_factor: Optional[float]
+    average_error_value: Optional[float]
+
+
+@dataclass(frozen=True)
+class InterfaceResidue:
+    chain_id: str
+    residue: int
+
+
+@dataclass(frozen=True)
+class InterfaceSummary:
+    distance: float
+    residues: Tuple[InterfaceResidue, ...]
+    scored_frame_count: int
+    rejected_frame_count: int
+    quality_factor: Optional[float]
+    average_error_value: Optional[float]
+    inter_chain_fraction: Optional[float]
+
+
+@dataclass(frozen=True)
+class ResidueConfidence:
+    chain_id: str
+    residue: int
+    plddt: float
+
+
+@dataclass(frozen=True)
+class ConfidenceGroup:
+    scored_frame_count: int
+    rejected_frame_count: int
+    quality_factor: Optional[float]
+    average_error_value: Optional[float]
+
+
+@dataclass(frozen=True)
+class ConfidenceSummary:
+    cutoff: float
+    mean_plddt: Optional[float]
+    residues: Tuple[ResidueConfidence, ...]
+    confident: ConfidenceGroup
+    low_confidence: ConfidenceGroup
+
+
+@dataclass(frozen=True)
+class CorpusScore:
+    method: str
+    residue_count: int
+    min_residues: int
+    max_residues: Optional[int]
+    structure_count: int
+    mean_quality_factor: float
+    z_score: Optional[float]
+    percentile: float
+
+
+@dataclass(frozen=True)
+class ProblemRegion:
+    chain_id: str
+    start_residue: int
+    end_residue: int
+    length: int
+    peak_error_value: float
+    mean_error_value: float
+    severity: FrameStatus
 
 
 @dataclass(frozen=True)
@@ -21,6 +105,15 @@ class FrameScore:
     center_residue: int
     error_value: float
     status: FrameStatus
+    p_value: float = 1.0
+    secondary_structure: Optional[SecondaryStructure] = None
+
+
+@dataclass(frozen=True)
+class ResidueProbability:
+    chain_id: str
+    residue: int
+    p_value: float
 
 
 @dataclass(frozen=True)
@@ -30,6 +123,14 @@ class ReportPaths:
     output_format: OutputFormat
 
 
+@dataclass(frozen=True)
+class InteractionArrays:
+    counts: Tuple[Tuple[float, ...], ...]
+    totals: Tuple[float, ...]
+    fractions: Tuple[Tuple[float, ...], ...]
+    inter_chain_counts: Tuple[Tuple[float, ...], ...] = ()
+
+
 @dataclass(frozen=True)
 class AnalysisResult:
     protein_id: str
@@ -45,7 +146,22 @@ class AnalysisResult:
     messages: Tuple[str, ...]
     log_text: str
     report_paths: Optional[ReportPaths] = None
+    interactions: Optional[InteractionArrays] = None
+    residue_p_values: Tuple[ResidueProbability, ...] = ()
+    average_error_value: Optional[float] = None
+    domain_summaries: Tuple[DomainSummary, ...] = ()
+    problem_regions: Tuple[ProblemRegion, ...] = ()
+    interface: Optional[InterfaceSummary] = None
+    hetero_environment: Optional[HeteroEnvironment] = None
+    experimental_method: Optional[str] = None
+    resolution: Optional[float] = None
+    quality_percentile: Optional[float] = None
+    corpus_score: Optional[CorpusScore] = None
+    confidence: Optional[ConfidenceSummary] = None
 
     @property
     def flagged_frames(self) -> Tuple[FrameScore, ...]:
         return tuple(frame for frame in self.frame_scores if frame.status != "ok")
+
+    def significant_frames(self, alpha: float) -> Tuple[FrameScore, ...]:
+        return tuple(frame for frame in self.frame_scores if frame.p_value < alpha)
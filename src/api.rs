@@ -1,6 +1,13 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io;
 use std::path::{Path, PathBuf};
 
-use crate::model::{ErratStats, LMT_95, LMT_99};
+use crate::calibration::Calibration;
+use crate::confidence::is_low_confidence;
+use crate::corpus::{CorpusReference, CorpusScore};
+use crate::model::{ErratStats, FrameScoreRaw};
+use crate::resolution::ResolutionReference;
+use crate::secondary::residue_secondary;
 
 #[derive(Clone, Debug)]
 pub struct RunOutput {
@@ -8,6 +15,206 @@ pub struct RunOutput {
     pub plot: PathBuf,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InputFormat {
+    Pdb,
+    Mmcif,
+    BinaryCif,
+}
+
+impl InputFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "pdb" | "ent" => Some(InputFormat::Pdb),
+            "cif" | "mmcif" => Some(InputFormat::Mmcif),
+            "bcif" => Some(InputFormat::BinaryCif),
+            _ => None,
+        }
+    }
+
+    pub fn from_path(path: &Path) -> Self {
+        path.extension()
+            .and_then(|s| s.to_str())
+            .and_then(InputFormat::from_name)
+            .unwrap_or(InputFormat::Pdb)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReportFormat {
+    Log,
+    Ps,
+    Pdf,
+    Svg,
+    Json,
+    Csv,
+    SummaryCsv,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DomainRange {
+    pub name: String,
+    pub chain_id: String,
+    pub start_residue: i32,
+    pub end_residue: i32,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AssemblyMode {
+    #[default]
+    Full,
+    Context,
+}
+
+impl AssemblyMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "full" => Some(AssemblyMode::Full),
+            "context" => Some(AssemblyMode::Context),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AssemblySelection {
+    pub id: String,
+    pub mode: AssemblyMode,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HeteroEnvironment {
+    Ligands,
+    LigandsAndWater,
+}
+
+impl HeteroEnvironment {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "ligands" => Some(HeteroEnvironment::Ligands),
+            "ligands+water" => Some(HeteroEnvironment::LigandsAndWater),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            HeteroEnvironment::Ligands => "ligands",
+            HeteroEnvironment::LigandsAndWater => "ligands+water",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SecondaryStructure {
+    Helix,
+    Strand,
+    Loop,
+}
+
+impl SecondaryStructure {
+    pub fn name(self) -> &'static str {
+        match self {
+            SecondaryStructure::Helix => "helix",
+            SecondaryStructure::Strand => "strand",
+            SecondaryStructure::Loop => "loop",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct AnalysisOptions {
+    pub calibration: Calibration,
+    pub detailed: bool,
+    pub window_length: Option<usize>,
+    pub radius: Option<f64>,
+    pub radmin: Option<f64>,
+    pub domains: Vec<DomainRange>,
+    pub region_gap: usize,
+    pub crystal_contacts: bool,
+    pub assembly: Option<AssemblySelection>,
+    pub interface_distance: Option<f64>,
+    pub hetero_environment: Option<HeteroEnvironment>,
+    pub resolution_reference: ResolutionReference,
+    pub corpus_reference: Option<CorpusReference>,
+    pub plddt_cutoff: Option<f64>,
+    pub secondary_structure: bool,
+}
+
+impl AnalysisOptions {
+    pub fn validate(&self) -> io::Result<()> {
+        self.calibration.validate()?;
+        self.resolution_reference.validate()?;
+        if let Some(reference) = &self.corpus_reference {
+            reference.validate()?;
+        }
+        let calibration = &self.calibration;
+        let mismatch = |name: &str, requested: String, calibrated: String| {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "{name} {requested} requires a calibration trained with the same value (calibration has {calibrated})"
+                ),
+            ))
+        };
+        if let Some(window_length) = self.window_length
+            && window_length != calibration.window_length
+        {
+            return mismatch(
+                "window_length",
+                window_length.to_string(),
+                calibration.window_length.to_string(),
+            );
+        }
+        if let Some(radius) = self.radius
+            && radius != calibration.radius
+        {
+            return mismatch("radius", radius.to_string(), calibration.radius.to_string());
+        }
+        if let Some(radmin) = self.radmin
+            && radmin != calibration.radmin
+        {
+            return mismatch("radmin", radmin.to_string(), calibration.radmin.to_string());
+        }
+        if let Some(domain) = self
+            .domains
+            .iter()
+            .find(|domain| domain.start_residue > domain.end_residue)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "domain {} ends before it starts ({} > {})",
+                    domain.name, domain.start_residue, domain.end_residue
+                ),
+            ));
+        }
+        if let Some(distance) = self.interface_distance
+            && !(distance.is_finite() && distance > 0.0)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("interface distance must be positive (got {distance})"),
+            ));
+        }
+        if let Some(cutoff) = self.plddt_cutoff
+            && !(cutoff > 0.0 && cutoff <= 100.0)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("pLDDT cutoff must be between 0 and 100 (got {cutoff})"),
+            ));
+        }
+        if self.crystal_contacts && self.assembly.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "crystal contacts and biological assemblies cannot be combined",
+            ));
+        }
+        Ok(())
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum FrameStatus {
     Ok,
@@ -15,19 +222,116 @@ pub enum FrameStatus {
     Warning99,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WindowInteractions {
+    pub counts: [f64; 5],
+    pub total: f64,
+    pub fractions: [f64; 5],
+    pub inter_chain_counts: [f64; 5],
+}
+
+impl WindowInteractions {
+    pub fn intra_chain_counts(&self) -> [f64; 5] {
+        std::array::from_fn(|k| self.counts[k] - self.inter_chain_counts[k])
+    }
+
+    pub fn inter_chain_total(&self) -> f64 {
+        self.inter_chain_counts.iter().sum()
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct FrameScore {
     pub chain_id: String,
     pub center_residue: i32,
     pub error_value: f64,
     pub status: FrameStatus,
+    pub p_value: f64,
+    pub interactions: Option<WindowInteractions>,
+    pub secondary_structure: Option<SecondaryStructure>,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ResidueProbability {
+    pub chain_id: String,
+    pub residue: i32,
+    pub p_value: f64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct ChainSummary {
     pub chain_id: String,
     pub start_residue: i32,
     pub end_residue: i32,
+    pub min_p_value: Option<f64>,
+    pub scored_frame_count: usize,
+    pub rejected_frame_count: usize,
+    pub quality_factor: Option<f64>,
+    pub average_error_value: Option<f64>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DomainSummary {
+    pub name: String,
+    pub chain_id: String,
+    pub start_residue: i32,
+    pub end_residue: i32,
+    pub scored_frame_count: usize,
+    pub rejected_frame_count: usize,
+    pub quality_factor: Option<f64>,
+    pub average_error_value: Option<f64>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct InterfaceResidue {
+    pub chain_id: String,
+    pub residue: i32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct InterfaceSummary {
+    pub distance: f64,
+    pub residues: Vec<InterfaceResidue>,
+    pub scored_frame_count: usize,
+    pub rejected_frame_count: usize,
+    pub quality_factor: Option<f64>,
+    pub average_error_value: Option<f64>,
+    pub inter_chain_fraction: Option<f64>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ResidueConfidence {
+    pub chain_id: String,
+    pub residue: i32,
+    pub plddt: f64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConfidenceGroup {
+    pub scored_frame_count: usize,
+    pub rejected_frame_count: usize,
+    pub quality_factor: Option<f64>,
+    pub average_error_value: Option<f64>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConfidenceSummary {
+    pub cutoff: f64,
+    pub mean_plddt: Option<f64>,
+    pub residues: Vec<ResidueConfidence>,
+    pub confident: ConfidenceGroup,
+    pub low_confidence: ConfidenceGroup,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProblemRegion {
+    pub chain_id: String,
+    pub start_residue: i32,
+    pub end_residue: i32,
+    pub length: usize,
+    pub peak_error_value: f64,
+    pub mean_error_value: f64,
+    pub severity: FrameStatus,
 }
 
 #[derive(Clone, Debug)]
@@ -38,14 +342,39 @@ pub struct AnalysisResult {
     pub rejected_frame_count: usize,
     pub rejected_frame_ratio: Option<f64>,
     pub overall_quality_factor: Option<f64>,
+    /// Mean error value of the scored windows.
+    pub average_error_value: Option<f64>,
+    /// Same value as `average_error_value`; despite the name it is not a probability.
+    /// Per-window probabilities are in `FrameScore::p_value` and `residue_p_values`.
+    #[deprecated(
+        note = "use average_error_value; this is the mean error value, not a probability"
+    )]
     pub average_probability: Option<f64>,
+    pub experimental_method: Option<String>,
+    pub resolution: Option<f64>,
+    pub quality_percentile: Option<f64>,
+    pub corpus_score: Option<CorpusScore>,
     pub below_interaction_limit_frames: Vec<i32>,
     pub chain_summaries: Vec<ChainSummary>,
+    pub domain_summaries: Vec<DomainSummary>,
+    pub interface: Option<InterfaceSummary>,
+    pub hetero_environment: Option<HeteroEnvironment>,
+    pub confidence: Option<ConfidenceSummary>,
+    pub problem_regions: Vec<ProblemRegion>,
     pub frame_scores: Vec<FrameScore>,
+    pub residue_p_values: Vec<ResidueProbability>,
     pub messages: Vec<String>,
     pub log_text: String,
 }
 
+impl AnalysisResult {
+    pub fn significant_frames(&self, alpha: f64) -> impl Iterator<Item = &FrameScore> {
+        self.frame_scores
+            .iter()
+            .filter(move |frame| frame.p_value < alpha)
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 pub(crate) struct ChainRange {
     pub(crate) chain_id: u8,
@@ -67,7 +396,6 @@ pub(crate) fn derive_file_string(input_pdb: &Path, protein_id: Option<&str>) ->
         .unwrap_or_else(|| "errat".to_string())
 }
 
-#[cfg(feature = "python")]
 pub(crate) fn frame_status_name(status: FrameStatus) -> &'static str {
     match status {
         FrameStatus::Ok => "ok",
@@ -76,43 +404,138 @@ pub(crate) fn frame_status_name(status: FrameStatus) -> &'static str {
     }
 }
 
-fn chain_label(chain_id: u8) -> String {
+pub(crate) fn chain_label(chain_id: u8) -> String {
     match chain_id {
         b' ' => String::new(),
         id => (id as char).to_string(),
     }
 }
 
-fn classify_frame(error_value: f64) -> FrameStatus {
-    if error_value > LMT_99 {
+pub(crate) fn classify_frame(error_value: f64, calibration: &Calibration) -> FrameStatus {
+    if error_value > calibration.limit_99 {
         FrameStatus::Warning99
-    } else if error_value > LMT_95 {
+    } else if error_value > calibration.limit_95 {
         FrameStatus::Warning95
     } else {
         FrameStatus::Ok
     }
 }
 
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct FrameTally {
+    pub(crate) scored: usize,
+    pub(crate) rejected: usize,
+    error_sum: f64,
+}
+
+impl FrameTally {
+    pub(crate) fn of<'a>(
+        frames: impl IntoIterator<Item = &'a FrameScoreRaw>,
+        calibration: &Calibration,
+    ) -> Self {
+        let mut tally = FrameTally::default();
+        for frame in frames {
+            tally.scored += 1;
+            tally.error_sum += frame.error_value;
+            if frame.error_value > calibration.limit_95 {
+                tally.rejected += 1;
+            }
+        }
+        tally
+    }
+
+    fn group(&self) -> ConfidenceGroup {
+        ConfidenceGroup {
+            scored_frame_count: self.scored,
+            rejected_frame_count: self.rejected,
+            quality_factor: self.quality_factor(),
+            average_error_value: self.average_error_value(),
+        }
+    }
+
+    pub(crate) fn quality_factor(&self) -> Option<f64> {
+        (self.scored > 0).then(|| 100.0 - (100.0 * self.rejected as f64 / self.scored as f64))
+    }
+
+    pub(crate) fn average_error_value(&self) -> Option<f64> {
+        (self.scored > 0).then(|| self.error_sum / self.scored as f64)
+    }
+}
+
+pub(crate) fn frames_in<'a>(
+    stats: &'a ErratStats,
+    range: &ChainRange,
+) -> impl Iterator<Item = &'a FrameScoreRaw> {
+    let (start, end) = (range.start_residue, range.end_residue);
+    stats
+        .scored_frames
+        .iter()
+        .filter(move |frame| frame.center_residue >= start && frame.center_residue <= end)
+}
+
+pub(crate) fn problem_regions(stats: &ErratStats, gap: usize) -> Vec<ProblemRegion> {
+    let calibration = &stats.calibration;
+    let mut regions = Vec::new();
+    for range in compute_chain_ranges(stats) {
+        let frames: Vec<&FrameScoreRaw> = frames_in(stats, &range).collect();
+        let flagged: Vec<usize> = (0..frames.len())
+            .filter(|&idx| {
+                frames[idx].error_value > calibration.limit_95
+                    && !is_low_confidence(stats, frames[idx])
+            })
+            .collect();
+        let mut start = 0usize;
+        for (pos, &idx) in flagged.iter().enumerate() {
+            let last = flagged.get(pos + 1).is_none_or(|&next| {
+                frames[next].center_residue - frames[idx].center_residue - 1 > gap as i32
+            });
+            if !last {
+                continue;
+            }
+            let members = &frames[flagged[start]..=idx];
+            let peak_error_value = members
+                .iter()
+                .map(|frame| frame.error_value)
+                .fold(f64::MIN, f64::max);
+            let start_residue = members[0].center_residue;
+            let end_residue = members[members.len() - 1].center_residue;
+            regions.push(ProblemRegion {
+                chain_id: chain_label(range.chain_id),
+                start_residue,
+                end_residue,
+                length: (end_residue - start_residue + 1) as usize,
+                peak_error_value,
+                mean_error_value: members.iter().map(|frame| frame.error_value).sum::<f64>()
+                    / members.len() as f64,
+                severity: classify_frame(peak_error_value, calibration),
+            });
+            start = pos + 1;
+        }
+    }
+    regions
+}
+
 pub(crate) fn compute_chain_ranges(stats: &ErratStats) -> Vec<ChainRange> {
     if stats.atmnum == 0 {
         return Vec::new();
     }
 
+    let half = stats.calibration.half_window();
     let mut starts = [0i32; 100];
     let mut ends = [0i32; 100];
     let mut ids = [b' '; 100];
     let mut count = 1usize;
 
-    starts[count] = stats.resnum[1] + 4;
+    starts[count] = stats.resnum[1] + half;
     ids[count] = stats.chain_id[1];
 
     for idx in 1..stats.atmnum {
         if idx == stats.atmnum - 1 {
-            ends[count] = stats.resnum[stats.atmnum] - 4;
-        } else if stats.chain_id[idx] != stats.chain_id[idx + 1] && stats.resnum[idx] > 4 {
-            ends[count] = stats.resnum[idx] - 4;
+            ends[count] = stats.resnum[stats.atmnum] - half;
+        } else if stats.chain_id[idx] != stats.chain_id[idx + 1] && stats.resnum[idx] > half {
+            ends[count] = stats.resnum[idx] - half;
             count += 1;
-            starts[count] = stats.resnum[idx + 1] + 4;
+            starts[count] = stats.resnum[idx + 1] + half;
             ids[count] = stats.chain_id[idx + 1];
         }
     }
@@ -130,23 +553,16 @@ pub(crate) fn compute_chain_ranges(stats: &ErratStats) -> Vec<ChainRange> {
         .collect()
 }
 
+#[allow(deprecated)]
 pub(crate) fn build_analysis_result(
     input_path: PathBuf,
     protein_id: String,
     stats: &ErratStats,
     log_text: String,
+    options: &AnalysisOptions,
 ) -> AnalysisResult {
     let chain_ranges = compute_chain_ranges(stats);
-    let chain_summaries = chain_ranges
-        .iter()
-        .map(|range| ChainSummary {
-            chain_id: chain_label(range.chain_id),
-            start_residue: range.start_residue,
-            end_residue: range.end_residue,
-        })
-        .collect();
-
-    let frame_scores = stats
+    let frame_scores: Vec<FrameScore> = stats
         .scored_frames
         .iter()
         .map(|frame| {
@@ -162,11 +578,149 @@ pub(crate) fn build_analysis_result(
                 chain_id,
                 center_residue: frame.center_residue,
                 error_value: frame.error_value,
-                status: classify_frame(frame.error_value),
+                status: classify_frame(frame.error_value, &stats.calibration),
+                p_value: stats.calibration.p_value(frame.error_value),
+                interactions: options.detailed.then_some(frame.interactions),
+                secondary_structure: residue_secondary(stats, frame.center_residue),
+            }
+        })
+        .collect();
+
+    let chain_summaries = chain_ranges
+        .iter()
+        .map(|range| {
+            let tally = FrameTally::of(frames_in(stats, range), &stats.calibration);
+            ChainSummary {
+                chain_id: chain_label(range.chain_id),
+                start_residue: range.start_residue,
+                end_residue: range.end_residue,
+                min_p_value: frames_in(stats, range)
+                    .map(|frame| stats.calibration.p_value(frame.error_value))
+                    .reduce(f64::min),
+                scored_frame_count: tally.scored,
+                rejected_frame_count: tally.rejected,
+                quality_factor: tally.quality_factor(),
+                average_error_value: tally.average_error_value(),
+            }
+        })
+        .collect();
+
+    let domain_summaries = options
+        .domains
+        .iter()
+        .map(|domain| {
+            let frames = stats
+                .scored_frames
+                .iter()
+                .zip(&frame_scores)
+                .filter(|(raw, frame)| {
+                    let residue = raw.center_residue - raw.residue_offset;
+                    frame.chain_id == domain.chain_id
+                        && residue >= domain.start_residue
+                        && residue <= domain.end_residue
+                })
+                .map(|(raw, _)| raw);
+            let tally = FrameTally::of(frames, &stats.calibration);
+            DomainSummary {
+                name: domain.name.clone(),
+                chain_id: domain.chain_id.clone(),
+                start_residue: domain.start_residue,
+                end_residue: domain.end_residue,
+                scored_frame_count: tally.scored,
+                rejected_frame_count: tally.rejected,
+                quality_factor: tally.quality_factor(),
+                average_error_value: tally.average_error_value(),
             }
         })
         .collect();
 
+    let interface = options.interface_distance.map(|distance| {
+        let half = stats.calibration.half_window();
+        let residues: BTreeSet<i32> = stats
+            .interface_residues
+            .iter()
+            .map(|&(residue, _)| residue)
+            .collect();
+        let frames: Vec<&FrameScoreRaw> = stats
+            .scored_frames
+            .iter()
+            .filter(|frame| {
+                residues
+                    .range(frame.center_residue - half..=frame.center_residue + half)
+                    .next()
+                    .is_some()
+            })
+            .collect();
+        let tally = FrameTally::of(frames.iter().copied(), &stats.calibration);
+        let total: f64 = frames.iter().map(|frame| frame.interactions.total).sum();
+        let inter_chain: f64 = frames
+            .iter()
+            .map(|frame| frame.interactions.inter_chain_total())
+            .sum();
+        InterfaceSummary {
+            distance,
+            residues: stats
+                .interface_residues
+                .iter()
+                .map(|&(residue, chain_id)| InterfaceResidue {
+                    chain_id: chain_label(chain_id),
+                    residue,
+                })
+                .collect(),
+            scored_frame_count: tally.scored,
+            rejected_frame_count: tally.rejected,
+            quality_factor: tally.quality_factor(),
+            average_error_value: tally.average_error_value(),
+            inter_chain_fraction: (total > 0.0).then_some(inter_chain / total),
+        }
+    });
+
+    let confidence = stats.plddt_cutoff.map(|cutoff| {
+        let (low, confident): (Vec<&FrameScoreRaw>, Vec<&FrameScoreRaw>) = stats
+            .scored_frames
+            .iter()
+            .partition(|frame| is_low_confidence(stats, frame));
+        ConfidenceSummary {
+            cutoff,
+            mean_plddt: (!stats.plddt.is_empty()).then(|| {
+                stats.plddt.iter().map(|&(_, _, plddt)| plddt).sum::<f64>()
+                    / stats.plddt.len() as f64
+            }),
+            residues: stats
+                .plddt
+                .iter()
+                .map(|&(residue, chain_id, plddt)| ResidueConfidence {
+                    chain_id: chain_label(chain_id),
+                    residue,
+                    plddt,
+                })
+                .collect(),
+            confident: FrameTally::of(confident, &stats.calibration).group(),
+            low_confidence: FrameTally::of(low, &stats.calibration).group(),
+        }
+    });
+
+    let problem_regions = problem_regions(stats, options.region_gap);
+
+    let half = stats.calibration.half_window();
+    let mut residues: BTreeMap<i32, (&str, f64)> = BTreeMap::new();
+    for frame in &frame_scores {
+        for residue in frame.center_residue - half..=frame.center_residue + half {
+            let entry = residues
+                .entry(residue)
+                .or_insert((&frame.chain_id, frame.p_value));
+            entry.1 = entry.1.min(frame.p_value);
+        }
+    }
+    let residue_p_values = residues
+        .into_iter()
+        .map(|(residue, (chain_id, p_value))| ResidueProbability {
+            chain_id: chain_id.to_string(),
+            residue,
+            p_value,
+        })
+        .collect();
+
     let messages = log_text
         .lines()
         .filter(|line| !line.is_empty())
@@ -184,11 +738,195 @@ pub(crate) fn build_analysis_result(
             None
         },
         overall_quality_factor: stats.overall_quality_factor,
+        average_error_value: stats.avg_probability,
         average_probability: stats.avg_probability,
+        experimental_method: stats.experiment.method.clone(),
+        resolution: stats.experiment.resolution,
+        quality_percentile: stats.quality_percentile,
+        corpus_score: options
+            .corpus_reference
+            .as_ref()
+            .and_then(|reference| reference.score(stats)),
         below_interaction_limit_frames: stats.warning_frames.clone(),
         chain_summaries,
+        domain_summaries,
+        interface,
+        hetero_environment: options.hetero_environment,
+        confidence,
+        problem_regions,
         frame_scores,
+        residue_p_values,
         messages,
         log_text,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{Experiment, FrameScoreRaw};
+
+    fn two_frame_stats() -> ErratStats {
+        let mut resnum = vec![0; 30];
+        for (i, r) in resnum.iter_mut().enumerate().take(21).skip(1) {
+            *r = i as i32;
+        }
+        let interactions = WindowInteractions {
+            counts: [0.0; 5],
+            total: 0.0,
+            fractions: [0.0; 5],
+            inter_chain_counts: [0.0; 5],
+        };
+        let frame = |center_residue, error_value| FrameScoreRaw {
+            center_residue,
+            residue_offset: 0,
+            error_value,
+            interactions,
+        };
+        ErratStats {
+            stat: 2.0,
+            pstat: 1.0,
+            avg_probability: Some(12.5),
+            overall_quality_factor: Some(50.0),
+            errat: vec![0.0; 40],
+            resnum,
+            chain_id: vec![b'A'; 30],
+            atmnum: 20,
+            warning_frames: Vec::new(),
+            scored_frames: vec![frame(6, 5.0), frame(8, 20.0)],
+            interface_residues: Vec::new(),
+            experiment: Experiment::default(),
+            quality_percentile: None,
+            plddt: Vec::new(),
+            plddt_cutoff: None,
+            secondary: Vec::new(),
+            calibration: Calibration::classic(),
+        }
+    }
+
+    #[test]
+    fn p_values_roll_up_to_residues_and_chains() {
+        let result = build_analysis_result(
+            PathBuf::from("-"),
+            "demo".into(),
+            &two_frame_stats(),
+            String::new(),
+            &AnalysisOptions::default(),
+        );
+
+        let calibration = Calibration::classic();
+        let low = calibration.p_value(20.0);
+        assert_eq!(result.frame_scores[1].p_value, low);
+        assert!(result.frame_scores[0].p_value > 0.05 && low < 0.01);
+        assert_eq!(result.chain_summaries[0].min_p_value, Some(low));
+
+        let residues: Vec<(i32, f64)> = result
+            .residue_p_values
+            .iter()
+            .map(|r| (r.residue, r.p_value))
+            .collect();
+        assert_eq!(residues.len(), 11);
+        assert_eq!(residues[0], (2, calibration.p_value(5.0)));
+        assert_eq!(residues[4], (6, low));
+        assert_eq!(residues[10], (12, low));
+        assert_eq!(result.significant_frames(0.05).count(), 1);
+    }
+
+    #[test]
+    fn chain_and_domain_summaries_tally_frames() {
+        let domain = |name: &str, chain_id: &str, start_residue, end_residue| DomainRange {
+            name: name.to_string(),
+            chain_id: chain_id.to_string(),
+            start_residue,
+            end_residue,
+        };
+        let options = AnalysisOptions {
+            domains: vec![
+                domain("n", "A", 1, 6),
+                domain("c", "A", 7, 20),
+                domain("other", "B", 1, 20),
+            ],
+            ..AnalysisOptions::default()
+        };
+        let result = build_analysis_result(
+            PathBuf::from("-"),
+            "demo".into(),
+            &two_frame_stats(),
+            String::new(),
+            &options,
+        );
+
+        let chain = &result.chain_summaries[0];
+        assert_eq!(
+            (chain.scored_frame_count, chain.rejected_frame_count),
+            (2, 1)
+        );
+        assert_eq!(chain.quality_factor, Some(50.0));
+        assert_eq!(chain.average_error_value, Some(12.5));
+
+        let domains = &result.domain_summaries;
+        assert_eq!(domains[0].quality_factor, Some(100.0));
+        assert_eq!(domains[1].rejected_frame_count, 1);
+        assert_eq!(domains[1].average_error_value, Some(20.0));
+        assert_eq!(domains[2].scored_frame_count, 0);
+        assert_eq!(domains[2].quality_factor, None);
+    }
+
+    #[test]
+    fn problem_regions_merge_across_gaps() {
+        let mut stats = two_frame_stats();
+        let frame = |center_residue, error_value| FrameScoreRaw {
+            center_residue,
+            residue_offset: 0,
+            error_value,
+            interactions: stats.scored_frames[0].interactions,
+        };
+        stats.scored_frames = vec![
+            frame(5, 12.0),
+            frame(6, 14.0),
+            frame(7, 5.0),
+            frame(8, 20.0),
+            frame(9, 3.0),
+            frame(10, 4.0),
+            frame(11, 13.0),
+        ];
+
+        let regions = problem_regions(&stats, 0);
+        let spans: Vec<(i32, i32)> = regions
+            .iter()
+            .map(|r| (r.start_residue, r.end_residue))
+            .collect();
+        assert_eq!(spans, vec![(5, 6), (8, 8), (11, 11)]);
+        assert_eq!(regions[0].severity, FrameStatus::Warning95);
+        assert_eq!(regions[0].mean_error_value, 13.0);
+        assert_eq!(regions[1].severity, FrameStatus::Warning99);
+
+        let regions = problem_regions(&stats, 1);
+        assert_eq!(regions.len(), 2);
+        assert_eq!((regions[0].start_residue, regions[0].end_residue), (5, 8));
+        assert_eq!(regions[0].length, 4);
+        assert_eq!(regions[0].peak_error_value, 20.0);
+        assert_eq!(regions[0].mean_error_value, 51.0 / 4.0);
+        assert_eq!(regions[0].severity, FrameStatus::Warning99);
+
+        assert_eq!(problem_regions(&stats, 2).len(), 1);
+    }
+
+    #[test]
+    fn options_require_matching_calibration_geometry() {
+        let mut options = AnalysisOptions {
+            window_length: Some(7),
+            ..AnalysisOptions::default()
+        };
+        let err = options.validate().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(err.to_string().contains("window_length 7"));
+
+        options.calibration.window_length = 7;
+        assert!(options.validate().is_ok());
+        options.radius = Some(4.5);
+        assert!(options.validate().is_err());
+        options.calibration.radius = 4.5;
+        assert!(options.validate().is_ok());
+    }
+}
@@ -0,0 +1,619 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use crate::api::{AssemblyMode, AssemblySelection};
+use crate::calibration::Calibration;
+use crate::model::{Assembly, AssemblyPart, AtomData, CHAINDIF, HeteroAtom, SecondaryRange};
+use crate::sink::empty_atom_data;
+use crate::symmetry::{
+    ENVIRONMENT_RESNUM, NeighbourCells, Operator, compose, grow, is_identity,
+    push_environment_atom, transform,
+};
+
+const CHAIN_IDS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+
+fn invalid_input(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+fn push_chains(list: &str, part: &mut AssemblyPart) {
+    for chain in list
+        .split(',')
+        .filter_map(|chain| chain.trim().bytes().next())
+    {
+        if !part.chains.contains(&chain) {
+            part.chains.push(chain);
+        }
+    }
+}
+
+pub(crate) fn assembly_record(line: &[u8], assemblies: &mut Vec<Assembly>) {
+    let text = String::from_utf8_lossy(line);
+    let Some(body) = text.strip_prefix("REMARK 350") else {
+        return;
+    };
+    let body = body.trim();
+    if let Some(id) = body.strip_prefix("BIOMOLECULE:") {
+        assemblies.push(Assembly {
+            id: id.trim().to_string(),
+            parts: Vec::new(),
+        });
+        return;
+    }
+    let Some(assembly) = assemblies.last_mut() else {
+        return;
+    };
+    if let Some(list) = body.strip_prefix("APPLY THE FOLLOWING TO CHAINS:") {
+        let mut part = AssemblyPart::default();
+        push_chains(list, &mut part);
+        assembly.parts.push(part);
+    } else if let Some(list) = body.strip_prefix("AND CHAINS:") {
+        if let Some(part) = assembly.parts.last_mut() {
+            push_chains(list, part);
+        }
+    } else if let Some(rest) = body.strip_prefix("BIOMT") {
+        let fields: Vec<&str> = rest.split_whitespace().collect();
+        let Some(row) = fields
+            .first()
+            .and_then(|row| row.parse::<usize>().ok())
+            .filter(|row| (1..=3).contains(row))
+        else {
+            return;
+        };
+        let values: Vec<f64> = fields
+            .iter()
+            .skip(2)
+            .filter_map(|value| value.parse().ok())
+            .collect();
+        if values.len() != 4 {
+            return;
+        }
+        let Some(part) = assembly.parts.last_mut() else {
+            return;
+        };
+        if row == 1 || part.operators.is_empty() {
+            part.operators.push([[0.0; 4]; 3]);
+        }
+        if let Some(op) = part.operators.last_mut() {
+            op[row - 1].copy_from_slice(&values);
+        }
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct AssemblyRecords {
+    generators: Vec<(String, String, String)>,
+    operators: HashMap<String, Operator>,
+    label_chains: HashMap<String, u8>,
+}
+
+impl AssemblyRecords {
+    pub(crate) fn wants(category: &str) -> bool {
+        category.eq_ignore_ascii_case("_pdbx_struct_assembly_gen")
+            || category.eq_ignore_ascii_case("_pdbx_struct_oper_list")
+    }
+
+    pub(crate) fn label_chain(&mut self, label: &str, chain: u8) {
+        self.label_chains.entry(label.to_string()).or_insert(chain);
+    }
+
+    pub(crate) fn row(&mut self, category: &str, items: &[&str], row: &[String]) {
+        let value = |name: &str| {
+            items
+                .iter()
+                .position(|item| item.eq_ignore_ascii_case(name))
+                .and_then(|k| row.get(k))
+                .map(|value| value.as_str())
+        };
+        if category.eq_ignore_ascii_case("_pdbx_struct_assembly_gen") {
+            if let (Some(id), Some(expression), Some(chains)) = (
+                value("assembly_id"),
+                value("oper_expression"),
+                value("asym_id_list"),
+            ) {
+                self.generators
+                    .push((id.to_string(), expression.to_string(), chains.to_string()));
+            }
+            return;
+        }
+        let Some(id) = value("id") else {
+            return;
+        };
+        let mut op = [[0.0; 4]; 3];
+        for (i, row) in op.iter_mut().enumerate() {
+            for (j, cell) in row.iter_mut().enumerate() {
+                let name = if j < 3 {
+                    format!("matrix[{}][{}]", i + 1, j + 1)
+                } else {
+                    format!("vector[{}]", i + 1)
+                };
+                let Some(number) = value(&name).and_then(|v| v.parse::<f64>().ok()) else {
+                    return;
+                };
+                *cell = number;
+            }
+        }
+        self.operators.insert(id.to_string(), op);
+    }
+
+    fn expand(&self, expression: &str) -> Option<Vec<Operator>> {
+        let groups: Vec<&str> = if expression.contains('(') {
+            expression
+                .split(['(', ')'])
+                .map(str::trim)
+                .filter(|group| !group.is_empty())
+                .collect()
+        } else {
+            vec![expression]
+        };
+        let mut result: Vec<Operator> = vec![[
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+        ]];
+        for group in groups {
+            let mut ids = Vec::new();
+            for item in group.split(',').map(str::trim) {
+                match item.split_once('-') {
+                    Some((first, last)) => {
+                        let first = first.parse::<i64>().ok()?;
+                        let last = last.parse::<i64>().ok()?;
+                        ids.extend((first..=last).map(|id| id.to_string()));
+                    }
+                    None => ids.push(item.to_string()),
+                }
+            }
+            let ops = ids
+                .iter()
+                .map(|id| self.operators.get(id).copied())
+                .collect::<Option<Vec<_>>>()?;
+            result = result
+                .iter()
+                .flat_map(|outer| ops.iter().map(move |inner| compose(outer, inner)))
+                .collect();
+        }
+        Some(result)
+    }
+
+    pub(crate) fn resolve(self) -> Vec<Assembly> {
+        let mut assemblies: Vec<Assembly> = Vec::new();
+        for (id, expression, list) in &self.generators {
+            let Some(operators) = self.expand(expression) else {
+                continue;
+            };
+            let mut part = AssemblyPart {
+                chains: Vec::new(),
+                operators,
+            };
+            for label in list.split(',').map(str::trim) {
+                if let Some(&chain) = self.label_chains.get(label)
+                    && !part.chains.contains(&chain)
+                {
+                    part.chains.push(chain);
+                }
+            }
+            match assemblies.iter_mut().find(|assembly| assembly.id == *id) {
+                Some(assembly) => assembly.parts.push(part),
+                None => assemblies.push(Assembly {
+                    id: id.clone(),
+                    parts: vec![part],
+                }),
+            }
+        }
+        assemblies
+    }
+}
+
+fn chain_slots(data: &AtomData) -> Vec<(u8, Vec<usize>)> {
+    let mut chains: Vec<(u8, Vec<usize>)> = Vec::new();
+    for slot in 1..=data.atmnum {
+        let chain = data.chain_id[slot];
+        match chains.iter_mut().find(|(id, _)| *id == chain) {
+            Some((_, slots)) => slots.push(slot),
+            None => chains.push((chain, vec![slot])),
+        }
+    }
+    chains
+}
+
+fn build_full_assembly<W: Write>(
+    data: &AtomData,
+    assembly: &Assembly,
+    calibration: &Calibration,
+    logw: &mut W,
+) -> io::Result<AtomData> {
+    let chains = chain_slots(data);
+    let mut used: Vec<u8> = Vec::new();
+    let mut spare = CHAIN_IDS
+        .iter()
+        .copied()
+        .filter(|id| chains.iter().all(|(chain, _)| chain != id));
+    let mut copies = Vec::new();
+    for part in &assembly.parts {
+        for (k, op) in part.operators.iter().enumerate() {
+            for (chain, slots) in chains.iter().filter(|(id, _)| part.chains.contains(id)) {
+                let id = if is_identity(op) && !used.contains(chain) {
+                    *chain
+                } else {
+                    spare.next().ok_or_else(|| {
+                        invalid_input(format!(
+                            "assembly {} needs more than {} chain IDs",
+                            assembly.id,
+                            CHAIN_IDS.len()
+                        ))
+                    })?
+                };
+                used.push(id);
+                writeln!(
+                    logw,
+                    "Assembly chain {} from chain {} operator {}",
+                    id as char,
+                    *chain as char,
+                    k + 1
+                )?;
+                copies.push((id, *chain, op, slots));
+            }
+        }
+    }
+
+    let mut full = empty_atom_data();
+    full.records = data.records;
+    full.crystal = data.crystal.clone();
+    full.assemblies = data.assemblies.clone();
+    full.experiment = data.experiment.clone();
+    full.secondary = copies
+        .iter()
+        .flat_map(|&(id, chain, _, _)| {
+            data.secondary
+                .iter()
+                .filter(move |range| range.chain_id == chain)
+                .map(move |range| SecondaryRange {
+                    chain_id: id,
+                    ..*range
+                })
+        })
+        .collect();
+    let mut idx = 0;
+    let mut kadd = 0;
+    for &(id, chain, op, slots) in &copies {
+        full.hetero_atoms.extend(
+            data.hetero_atoms
+                .iter()
+                .filter(|atom| atom.chain_id == chain)
+                .map(|atom| HeteroAtom {
+                    chain_id: id,
+                    point: transform(op, atom.point),
+                    ..*atom
+                }),
+        );
+        for &slot in slots {
+            idx += 1;
+            grow(&mut full, idx + 1);
+            if idx >= 2 && full.chain_id[idx - 1] != id {
+                kadd += 1;
+            }
+            let [x, y, z] = transform(op, [data.xyz_x[slot], data.xyz_y[slot], data.xyz_z[slot]]);
+            full.name[idx] = data.name[slot];
+            full.bnam[idx] = data.bnam[slot];
+            full.atom_name[idx] = data.atom_name[slot];
+            full.res_name[idx] = data.res_name[slot];
+            full.chain_id[idx] = id;
+            full.res_seq[idx] = data.res_seq[slot];
+            full.resnum[idx] = data.res_seq[slot] + kadd * CHAINDIF;
+            full.source_index[idx] = data.source_index[slot];
+            full.b_factor[idx] = data.b_factor[slot];
+            full.xyz_x[idx] = x;
+            full.xyz_y[idx] = y;
+            full.xyz_z[idx] = z;
+            let errat = (full.resnum[idx] + calibration.half_window()) as usize;
+            if errat >= full.errat.len() {
+                full.errat.resize(errat + 1, 0.0);
+            }
+        }
+    }
+    full.atmnum = idx;
+    writeln!(
+        logw,
+        "Assembly {}: {} chains, {} atoms",
+        assembly.id,
+        used.len(),
+        idx
+    )?;
+    Ok(full)
+}
+
+fn add_assembly_neighbours<W: Write>(
+    data: &mut AtomData,
+    assembly: &Assembly,
+    calibration: &Calibration,
+    logw: &mut W,
+) -> io::Result<usize> {
+    let rsq = calibration.radius * calibration.radius;
+    let cells = NeighbourCells::new(data, calibration.radius);
+    let mut neighbours = Vec::new();
+    for part in &assembly.parts {
+        for op in part.operators.iter().filter(|op| !is_identity(op)) {
+            for slot in 1..=data.atmnum {
+                if !part.chains.contains(&data.chain_id[slot]) {
+                    continue;
+                }
+                let point = transform(op, [data.xyz_x[slot], data.xyz_y[slot], data.xyz_z[slot]]);
+                let dsq = cells.nearest_sq(point);
+                if dsq < rsq && dsq > 0.01 {
+                    neighbours.push((slot, point));
+                }
+            }
+        }
+    }
+    for &(from, point) in &neighbours {
+        push_environment_atom(data, from, point, ENVIRONMENT_RESNUM);
+    }
+    writeln!(
+        logw,
+        "Assembly {} context: {} neighbour atoms",
+        assembly.id,
+        neighbours.len()
+    )?;
+    Ok(neighbours.len())
+}
+
+pub(crate) fn apply_assembly<W: Write>(
+    data: &mut AtomData,
+    selection: &AssemblySelection,
+    calibration: &Calibration,
+    logw: &mut W,
+) -> io::Result<()> {
+    let Some(assembly) = data
+        .assemblies
+        .iter()
+        .find(|assembly| assembly.id == selection.id)
+        .cloned()
+    else {
+        return Err(invalid_input(format!(
+            "assembly {} not found (REMARK 350 or _pdbx_struct_assembly_gen)",
+            selection.id
+        )));
+    };
+    match selection.mode {
+        AssemblyMode::Full => *data = build_full_assembly(data, &assembly, calibration, logw)?,
+        AssemblyMode::Context => {
+            add_assembly_neighbours(data, &assembly, calibration, logw)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{AnalysisOptions, InputFormat};
+    use crate::fixtures;
+    use crate::parser::parse_reader;
+    use crate::stats::compute_errat;
+    use std::io::Cursor;
+
+    const BIOMT: &str = "\
+REMARK 350 BIOMOLECULE: 1
+REMARK 350 APPLY THE FOLLOWING TO CHAINS: A
+REMARK 350   BIOMT1   1  1.000000  0.000000  0.000000        0.00000
+REMARK 350   BIOMT2   1  0.000000  1.000000  0.000000        0.00000
+REMARK 350   BIOMT3   1  0.000000  0.000000  1.000000        0.00000
+REMARK 350   BIOMT1   2  1.000000  0.000000  0.000000        0.00000
+REMARK 350   BIOMT2   2  0.000000  1.000000  0.000000        6.50000
+REMARK 350   BIOMT3   2  0.000000  0.000000  1.000000        0.00000
+REMARK 350 BIOMOLECULE: 2
+REMARK 350 APPLY THE FOLLOWING TO CHAINS: A,
+REMARK 350                    AND CHAINS: B
+REMARK 350   BIOMT1   1  1.000000  0.000000  0.000000        0.00000
+REMARK 350   BIOMT2   1  0.000000  1.000000  0.000000      200.00000
+REMARK 350   BIOMT3   1  0.000000  0.000000  1.000000        0.00000
+";
+
+    fn ribbon(residues: i32) -> String {
+        format!("{BIOMT}{}", fixtures::ribbon(residues))
+    }
+
+    fn parse(text: &str, format: InputFormat) -> AtomData {
+        parse_reader(&mut Cursor::new(text), format, &mut io::sink()).unwrap()
+    }
+
+    fn select(id: &str, mode: AssemblyMode) -> AssemblySelection {
+        AssemblySelection {
+            id: id.to_string(),
+            mode,
+        }
+    }
+
+    #[test]
+    fn reads_biomt_and_struct_oper_records() {
+        let data = parse(&ribbon(2), InputFormat::Pdb);
+        let assemblies = &data.assemblies;
+        assert_eq!(assemblies.len(), 2);
+        assert_eq!(assemblies[0].parts[0].chains, b"A");
+        assert_eq!(assemblies[0].parts[0].operators.len(), 2);
+        assert_eq!(assemblies[0].parts[0].operators[1][1][3], 6.5);
+        assert_eq!(assemblies[1].id, "2");
+        assert_eq!(assemblies[1].parts[0].chains, b"AB");
+
+        let cif = "data_test
+loop_
+_pdbx_struct_assembly_gen.assembly_id
+_pdbx_struct_assembly_gen.oper_expression
+_pdbx_struct_assembly_gen.asym_id_list
+1 '(1,2)(3)' C,D
+loop_
+_pdbx_struct_oper_list.id
+_pdbx_struct_oper_list.matrix[1][1]
+_pdbx_struct_oper_list.matrix[1][2]
+_pdbx_struct_oper_list.matrix[1][3]
+_pdbx_struct_oper_list.vector[1]
+_pdbx_struct_oper_list.matrix[2][1]
+_pdbx_struct_oper_list.matrix[2][2]
+_pdbx_struct_oper_list.matrix[2][3]
+_pdbx_struct_oper_list.vector[2]
+_pdbx_struct_oper_list.matrix[3][1]
+_pdbx_struct_oper_list.matrix[3][2]
+_pdbx_struct_oper_list.matrix[3][3]
+_pdbx_struct_oper_list.vector[3]
+1 1 0 0 0 0 1 0 0 0 0 1 0
+2 -1 0 0 0 0 -1 0 0 0 0 1 0
+3 1 0 0 10 0 1 0 0 0 0 1 0
+loop_
+_atom_site.group_PDB
+_atom_site.id
+_atom_site.type_symbol
+_atom_site.label_atom_id
+_atom_site.label_comp_id
+_atom_site.label_asym_id
+_atom_site.auth_asym_id
+_atom_site.auth_seq_id
+_atom_site.Cartn_x
+_atom_site.Cartn_y
+_atom_site.Cartn_z
+ATOM 1 N N ALA C A 1 1.0 2.0 3.0
+HETATM 2 O O HOH D W 1 4.0 5.0 6.0
+";
+        let data = parse(cif, InputFormat::Mmcif);
+        let part = &data.assemblies[0].parts[0];
+        assert_eq!(part.chains, b"A");
+        assert_eq!(part.operators.len(), 2);
+        assert_eq!(
+            transform(&part.operators[0], [1.0, 2.0, 3.0]),
+            [11.0, 2.0, 3.0]
+        );
+        assert_eq!(
+            transform(&part.operators[1], [1.0, 2.0, 3.0]),
+            [-11.0, -2.0, 3.0]
+        );
+    }
+
+    #[test]
+    fn full_assembly_renames_copies_and_scores_them() {
+        let calibration = Calibration::classic();
+        let mut data = parse(&ribbon(20), InputFormat::Pdb);
+        let asu = compute_errat(&data, &calibration, &mut io::sink()).unwrap();
+        let mut log = Vec::new();
+        apply_assembly(
+            &mut data,
+            &select("1", AssemblyMode::Full),
+            &calibration,
+            &mut log,
+        )
+        .unwrap();
+        assert_eq!(data.atmnum, 160);
+        assert_eq!(data.environment_atoms, 0);
+        assert_eq!(data.chain_id[80], b'A');
+        assert_eq!(data.chain_id[81], b'B');
+        assert_eq!(data.resnum[81], CHAINDIF + 1);
+        assert_eq!(data.xyz_y[81], data.xyz_y[1] + 6.5);
+        assert_eq!(data.source_index[81], data.source_index[1]);
+        let log = String::from_utf8(log).unwrap();
+        assert!(log.contains("Assembly chain B from chain A operator 2"));
+
+        let full = compute_errat(&data, &calibration, &mut io::sink()).unwrap();
+        assert!(full.scored_frames.len() > 2 * asu.scored_frames.len());
+        assert!(
+            full.scored_frames
+                .iter()
+                .any(|frame| frame.center_residue > CHAINDIF)
+        );
+
+        let mut data = parse(&ribbon(20), InputFormat::Pdb);
+        let missing = select("3", AssemblyMode::Full);
+        assert!(apply_assembly(&mut data, &missing, &calibration, &mut io::sink()).is_err());
+    }
+
+    #[test]
+    fn full_assembly_renames_secondary_structure_ranges() {
+        let pdb = format!(
+            "HELIX    1   1 ALA A    3  ALA A    8  1                                   6\n{}",
+            ribbon(20)
+        );
+        let mut data = parse(&pdb, InputFormat::Pdb);
+        apply_assembly(
+            &mut data,
+            &select("1", AssemblyMode::Full),
+            &Calibration::classic(),
+            &mut io::sink(),
+        )
+        .unwrap();
+        let chains: Vec<(u8, i32, i32)> = data
+            .secondary
+            .iter()
+            .map(|range| (range.chain_id, range.start, range.end))
+            .collect();
+        assert_eq!(chains, [(b'A', 3, 8), (b'B', 3, 8)]);
+    }
+
+    #[test]
+    fn full_assembly_keeps_resolution_and_percentile() {
+        let pdb = format!(
+            "EXPDTA    X-RAY DIFFRACTION\nREMARK   2 RESOLUTION.    1.80 ANGSTROMS.\n{}",
+            ribbon(20)
+        );
+        let options = AnalysisOptions {
+            assembly: Some(select("1", AssemblyMode::Full)),
+            ..AnalysisOptions::default()
+        };
+        let (stats, _) =
+            crate::pipeline::process_reader(&mut Cursor::new(pdb), InputFormat::Pdb, &options)
+                .unwrap();
+        assert_eq!(stats.experiment.resolution, Some(1.8));
+        assert!(stats.quality_percentile.is_some());
+    }
+
+    #[test]
+    fn full_assembly_copies_hetero_atoms_with_their_chain() {
+        let calibration = Calibration::classic();
+        let pdb = format!(
+            "{}HETATM  121  O   HOH A 101      10.000   1.500  -3.000  1.00 20.00           O\n",
+            ribbon(20)
+        );
+        let mut data = parse(&pdb, InputFormat::Pdb);
+        apply_assembly(
+            &mut data,
+            &select("1", AssemblyMode::Full),
+            &calibration,
+            &mut io::sink(),
+        )
+        .unwrap();
+        let copies: Vec<(u8, [f64; 3])> = data
+            .hetero_atoms
+            .iter()
+            .map(|atom| (atom.chain_id, atom.point))
+            .collect();
+        assert_eq!(
+            copies,
+            [(b'A', [10.0, 1.5, -3.0]), (b'B', [10.0, 8.0, -3.0])]
+        );
+    }
+
+    #[test]
+    fn context_mode_adds_neighbours_without_scoring_them() {
+        let calibration = Calibration::classic();
+        let mut data = parse(&ribbon(20), InputFormat::Pdb);
+        let asu = compute_errat(&data, &calibration, &mut io::sink()).unwrap();
+        let context = select("1", AssemblyMode::Context);
+        apply_assembly(&mut data, &context, &calibration, &mut io::sink()).unwrap();
+        assert_eq!(data.atmnum, 80);
+        assert!(data.environment_atoms > 0);
+
+        let scored = compute_errat(&data, &calibration, &mut io::sink()).unwrap();
+        assert!(scored.scored_frames.len() > asu.scored_frames.len());
+        assert!(
+            scored
+                .scored_frames
+                .iter()
+                .all(|frame| frame.center_residue < CHAINDIF)
+        );
+        let total = |frames: &[crate::model::FrameScoreRaw]| -> f64 {
+            frames.iter().map(|frame| frame.interactions.total).sum()
+        };
+        assert!(total(&scored.scored_frames) > total(&asu.scored_frames));
+
+        let mut data = parse(&ribbon(20), InputFormat::Pdb);
+        let distant = select("2", AssemblyMode::Context);
+        apply_assembly(&mut data, &distant, &calibration, &mut io::sink()).unwrap();
+        assert_eq!(data.environment_atoms, 0);
+    }
+}
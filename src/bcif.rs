@@ -0,0 +1,837 @@
+use std::fmt::Write as FmtWrite;
+use std::io;
+
+use crate::cif::{CifVisitor, Flow};
+
+#[derive(Clone, Debug, PartialEq)]
+enum Value<'a> {
+    Nil,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Str(&'a str),
+    Bin(&'a [u8]),
+    Array(Vec<Value<'a>>),
+    Map(Vec<(Value<'a>, Value<'a>)>),
+}
+
+impl<'a> Value<'a> {
+    fn get(&self, key: &str) -> Option<&Value<'a>> {
+        match self {
+            Value::Map(entries) => entries
+                .iter()
+                .find(|(k, _)| matches!(k, Value::Str(s) if *s == key))
+                .map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn field(&self, key: &str) -> io::Result<&Value<'a>> {
+        self.get(key)
+            .ok_or_else(|| invalid(&format!("missing field `{key}`")))
+    }
+
+    fn as_str(&self) -> io::Result<&'a str> {
+        match self {
+            Value::Str(s) => Ok(s),
+            _ => Err(invalid("expected string")),
+        }
+    }
+
+    fn as_bin(&self) -> io::Result<&'a [u8]> {
+        match self {
+            Value::Bin(b) => Ok(b),
+            _ => Err(invalid("expected binary data")),
+        }
+    }
+
+    fn as_array(&self) -> io::Result<&[Value<'a>]> {
+        match self {
+            Value::Array(items) => Ok(items),
+            _ => Err(invalid("expected array")),
+        }
+    }
+
+    fn as_i64(&self) -> io::Result<i64> {
+        match self {
+            Value::Int(v) => Ok(*v),
+            Value::Float(v) if v.fract() == 0.0 => Ok(*v as i64),
+            _ => Err(invalid("expected integer")),
+        }
+    }
+
+    fn as_f64(&self) -> io::Result<f64> {
+        match self {
+            Value::Int(v) => Ok(*v as f64),
+            Value::Float(v) => Ok(*v),
+            _ => Err(invalid("expected number")),
+        }
+    }
+
+    fn as_bool(&self) -> bool {
+        matches!(self, Value::Bool(true))
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("BinaryCIF: {message}"))
+}
+
+struct MsgPack<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> MsgPack<'a> {
+    fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| invalid("unexpected end of data"))?;
+        let out = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(out)
+    }
+
+    fn uint(&mut self, len: usize) -> io::Result<u64> {
+        Ok(self
+            .take(len)?
+            .iter()
+            .fold(0u64, |acc, b| (acc << 8) | *b as u64))
+    }
+
+    fn str(&mut self, len: usize) -> io::Result<Value<'a>> {
+        let raw = self.take(len)?;
+        std::str::from_utf8(raw)
+            .map(Value::Str)
+            .map_err(|_| invalid("invalid UTF-8 string"))
+    }
+
+    fn array(&mut self, len: usize) -> io::Result<Value<'a>> {
+        let mut items = Vec::with_capacity(len.min(1 << 16));
+        for _ in 0..len {
+            items.push(self.value()?);
+        }
+        Ok(Value::Array(items))
+    }
+
+    fn map(&mut self, len: usize) -> io::Result<Value<'a>> {
+        let mut entries = Vec::with_capacity(len.min(1 << 16));
+        for _ in 0..len {
+            let key = self.value()?;
+            let value = self.value()?;
+            entries.push((key, value));
+        }
+        Ok(Value::Map(entries))
+    }
+
+    fn value(&mut self) -> io::Result<Value<'a>> {
+        let tag = self.take(1)?[0];
+        match tag {
+            0x00..=0x7f => Ok(Value::Int(tag as i64)),
+            0x80..=0x8f => self.map((tag & 0x0f) as usize),
+            0x90..=0x9f => self.array((tag & 0x0f) as usize),
+            0xa0..=0xbf => self.str((tag & 0x1f) as usize),
+            0xc0 => Ok(Value::Nil),
+            0xc2 => Ok(Value::Bool(false)),
+            0xc3 => Ok(Value::Bool(true)),
+            0xc4..=0xc6 => {
+                let len = self.uint(1 << (tag - 0xc4))? as usize;
+                Ok(Value::Bin(self.take(len)?))
+            }
+            0xca => Ok(Value::Float(f32::from_bits(self.uint(4)? as u32) as f64)),
+            0xcb => Ok(Value::Float(f64::from_bits(self.uint(8)?))),
+            0xcc..=0xcf => Ok(Value::Int(self.uint(1 << (tag - 0xcc))? as i64)),
+            0xd0 => Ok(Value::Int(self.uint(1)? as u8 as i8 as i64)),
+            0xd1 => Ok(Value::Int(self.uint(2)? as u16 as i16 as i64)),
+            0xd2 => Ok(Value::Int(self.uint(4)? as u32 as i32 as i64)),
+            0xd3 => Ok(Value::Int(self.uint(8)? as i64)),
+            0xd9..=0xdb => {
+                let len = self.uint(1 << (tag - 0xd9))? as usize;
+                self.str(len)
+            }
+            0xdc | 0xdd => {
+                let len = self.uint(if tag == 0xdc { 2 } else { 4 })? as usize;
+                self.array(len)
+            }
+            0xde | 0xdf => {
+                let len = self.uint(if tag == 0xde { 2 } else { 4 })? as usize;
+                self.map(len)
+            }
+            0xe0..=0xff => Ok(Value::Int(tag as i8 as i64)),
+            _ => Err(invalid(&format!(
+                "unsupported MessagePack type 0x{tag:02x}"
+            ))),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Decoded {
+    Int(Vec<i64>),
+    Float(Vec<f64>),
+    Str(Vec<String>),
+}
+
+impl Decoded {
+    fn ints(self) -> io::Result<Vec<i64>> {
+        match self {
+            Decoded::Int(v) => Ok(v),
+            _ => Err(invalid("expected integer column data")),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Decoded::Int(v) => v.len(),
+            Decoded::Float(v) => v.len(),
+            Decoded::Str(v) => v.len(),
+        }
+    }
+}
+
+fn decode_byte_array(data: &[u8], kind: i64) -> io::Result<Decoded> {
+    fn chunks<const N: usize>(data: &[u8]) -> io::Result<impl Iterator<Item = [u8; N]> + '_> {
+        if !data.len().is_multiple_of(N) {
+            return Err(invalid("byte array length does not match element size"));
+        }
+        Ok(data
+            .chunks_exact(N)
+            .map(|c| c.try_into().expect("exact chunk")))
+    }
+
+    Ok(match kind {
+        1 => Decoded::Int(data.iter().map(|b| *b as i8 as i64).collect()),
+        2 => Decoded::Int(
+            chunks::<2>(data)?
+                .map(|c| i16::from_le_bytes(c) as i64)
+                .collect(),
+        ),
+        3 => Decoded::Int(
+            chunks::<4>(data)?
+                .map(|c| i32::from_le_bytes(c) as i64)
+                .collect(),
+        ),
+        4 => Decoded::Int(data.iter().map(|b| *b as i64).collect()),
+        5 => Decoded::Int(
+            chunks::<2>(data)?
+                .map(|c| u16::from_le_bytes(c) as i64)
+                .collect(),
+        ),
+        6 => Decoded::Int(
+            chunks::<4>(data)?
+                .map(|c| u32::from_le_bytes(c) as i64)
+                .collect(),
+        ),
+        32 => Decoded::Float(
+            chunks::<4>(data)?
+                .map(|c| f32::from_le_bytes(c) as f64)
+                .collect(),
+        ),
+        33 => Decoded::Float(chunks::<8>(data)?.map(f64::from_le_bytes).collect()),
+        other => return Err(invalid(&format!("unknown ByteArray type {other}"))),
+    })
+}
+
+fn src_size(encoding: &Value<'_>, limit: usize) -> io::Result<usize> {
+    Ok((encoding.field("srcSize")?.as_i64()?.max(0) as usize).min(limit))
+}
+
+fn decode_integer_packing(
+    data: Vec<i64>,
+    encoding: &Value<'_>,
+    limit: usize,
+) -> io::Result<Vec<i64>> {
+    let byte_count = encoding.field("byteCount")?.as_i64()?;
+    let unsigned = encoding.get("isUnsigned").is_some_and(Value::as_bool);
+    let src_size = src_size(encoding, limit)?;
+    let upper: i64 = match (byte_count, unsigned) {
+        (1, true) => 0xff,
+        (1, false) => 0x7f,
+        (2, true) => 0xffff,
+        (2, false) => 0x7fff,
+        _ => return Err(invalid("unsupported IntegerPacking byteCount")),
+    };
+    let lower = if unsigned { i64::MIN } else { -upper - 1 };
+
+    let mut out = Vec::with_capacity(src_size.min(1 << 16));
+    let mut iter = data.into_iter();
+    while let Some(mut t) = iter.next() {
+        if out.len() == src_size {
+            return Err(invalid("IntegerPacking data exceeds srcSize"));
+        }
+        let mut value = 0i64;
+        while t == upper || t == lower {
+            value += t;
+            t = iter
+                .next()
+                .ok_or_else(|| invalid("truncated IntegerPacking data"))?;
+        }
+        out.push(value + t);
+    }
+    Ok(out)
+}
+
+fn decode_data(data: &Value<'_>, limit: usize) -> io::Result<Decoded> {
+    let encodings = data.field("encoding")?.as_array()?;
+    let raw = data.field("data")?.as_bin()?;
+    let mut current: Option<Decoded> = None;
+
+    for encoding in encodings.iter().rev() {
+        let kind = encoding.field("kind")?.as_str()?;
+        current = Some(match (kind, current.take()) {
+            ("ByteArray", None) => decode_byte_array(raw, encoding.field("type")?.as_i64()?)?,
+            ("FixedPoint", Some(prev)) => {
+                let factor = encoding.field("factor")?.as_f64()?;
+                Decoded::Float(
+                    prev.ints()?
+                        .into_iter()
+                        .map(|v| v as f64 / factor)
+                        .collect(),
+                )
+            }
+            ("IntervalQuantization", Some(prev)) => {
+                let min = encoding.field("min")?.as_f64()?;
+                let max = encoding.field("max")?.as_f64()?;
+                let steps = encoding.field("numSteps")?.as_f64()?;
+                if steps.is_nan() || steps <= 1.0 {
+                    return Err(invalid("IntervalQuantization needs more than one step"));
+                }
+                let delta = (max - min) / (steps - 1.0);
+                Decoded::Float(
+                    prev.ints()?
+                        .into_iter()
+                        .map(|v| min + delta * v as f64)
+                        .collect(),
+                )
+            }
+            ("RunLength", Some(prev)) => {
+                let src_size = src_size(encoding, limit)?;
+                let pairs = prev.ints()?;
+                if !pairs.len().is_multiple_of(2) {
+                    return Err(invalid("RunLength data has odd length"));
+                }
+                let mut out = Vec::with_capacity(src_size.min(1 << 16));
+                for pair in pairs.chunks_exact(2) {
+                    let count = pair[1].max(0) as usize;
+                    if count > src_size - out.len() {
+                        return Err(invalid("RunLength data exceeds srcSize"));
+                    }
+                    out.extend(std::iter::repeat_n(pair[0], count));
+                }
+                Decoded::Int(out)
+            }
+            ("Delta", Some(prev)) => {
+                let origin = encoding.get("origin").map(Value::as_i64).transpose()?;
+                let mut acc = origin.unwrap_or(0);
+                let mut out = prev.ints()?;
+                for value in out.iter_mut() {
+                    acc += *value;
+                    *value = acc;
+                }
+                Decoded::Int(out)
+            }
+            ("IntegerPacking", Some(prev)) => {
+                Decoded::Int(decode_integer_packing(prev.ints()?, encoding, limit)?)
+            }
+            ("StringArray", None) => decode_string_array(raw, encoding, limit)?,
+            (kind, _) => {
+                return Err(invalid(&format!("unexpected `{kind}` encoding step")));
+            }
+        });
+    }
+
+    current.ok_or_else(|| invalid("column has no encodings"))
+}
+
+fn decode_string_array(raw: &[u8], encoding: &Value<'_>, limit: usize) -> io::Result<Decoded> {
+    let string_data = encoding.field("stringData")?.as_str()?;
+    let offsets = decode_data(
+        &Value::Map(vec![
+            (Value::Str("data"), encoding.field("offsets")?.clone()),
+            (
+                Value::Str("encoding"),
+                encoding.field("offsetEncoding")?.clone(),
+            ),
+        ]),
+        string_data.len() + limit + 1,
+    )?
+    .ints()?;
+    let indices = decode_data(
+        &Value::Map(vec![
+            (Value::Str("data"), Value::Bin(raw)),
+            (
+                Value::Str("encoding"),
+                encoding.field("dataEncoding")?.clone(),
+            ),
+        ]),
+        limit,
+    )?
+    .ints()?;
+
+    let char_starts: Option<Vec<usize>> = (!string_data.is_ascii()).then(|| {
+        string_data
+            .char_indices()
+            .map(|(pos, _)| pos)
+            .chain(std::iter::once(string_data.len()))
+            .collect()
+    });
+    let byte_pos = |offset: i64| -> io::Result<usize> {
+        let offset = usize::try_from(offset).map_err(|_| invalid("negative string offset"))?;
+        match &char_starts {
+            Some(starts) => starts.get(offset).copied(),
+            None => (offset <= string_data.len()).then_some(offset),
+        }
+        .ok_or_else(|| invalid("string offset out of range"))
+    };
+
+    let mut strings = Vec::with_capacity(offsets.len().saturating_sub(1));
+    for pair in offsets.windows(2) {
+        let (start, end) = (byte_pos(pair[0])?, byte_pos(pair[1])?);
+        let text = if start <= end {
+            string_data.get(start..end)
+        } else {
+            None
+        };
+        strings.push(text.ok_or_else(|| invalid("string offsets out of order"))?);
+    }
+
+    indices
+        .into_iter()
+        .map(|idx| {
+            if idx < 0 {
+                Ok(String::new())
+            } else {
+                strings
+                    .get(idx as usize)
+                    .map(|s| s.to_string())
+                    .ok_or_else(|| invalid("string index out of range"))
+            }
+        })
+        .collect::<io::Result<Vec<_>>>()
+        .map(Decoded::Str)
+}
+
+struct Column {
+    values: Decoded,
+    mask: Option<Vec<i64>>,
+}
+
+impl Column {
+    fn write_value(&self, row: usize, out: &mut String) {
+        out.clear();
+        match self.mask.as_ref().and_then(|mask| mask.get(row)) {
+            Some(1) => out.push('.'),
+            Some(2) => out.push('?'),
+            _ => match &self.values {
+                Decoded::Int(v) => {
+                    let _ = write!(out, "{}", v[row]);
+                }
+                Decoded::Float(v) => {
+                    let _ = write!(out, "{}", v[row]);
+                }
+                Decoded::Str(v) => out.push_str(&v[row]),
+            },
+        }
+    }
+}
+
+fn decode_column(column: &Value<'_>, row_count: usize) -> io::Result<Column> {
+    let values = decode_data(column.field("data")?, row_count)?;
+    if values.len() != row_count {
+        return Err(invalid("column length does not match rowCount"));
+    }
+    let mask = match column.get("mask") {
+        None | Some(Value::Nil) => None,
+        Some(mask) => Some(decode_data(mask, row_count)?.ints()?),
+    };
+    Ok(Column { values, mask })
+}
+
+pub(crate) fn read_bcif<V: CifVisitor>(bytes: &[u8], visitor: &mut V) -> io::Result<()> {
+    let mut reader = MsgPack { bytes, pos: 0 };
+    let root = reader.value()?;
+
+    for block in root.field("dataBlocks")?.as_array()? {
+        let header = block.get("header").map(Value::as_str).transpose()?;
+        if visitor.begin_block(header.unwrap_or(""))? == Flow::Stop {
+            return Ok(());
+        }
+
+        for category in block.field("categories")?.as_array()? {
+            let name = category.field("name")?.as_str()?;
+            let category_name = if name.starts_with('_') {
+                name.to_string()
+            } else {
+                format!("_{name}")
+            };
+            if !visitor.wants(&category_name) {
+                continue;
+            }
+
+            let row_count = category.field("rowCount")?.as_i64()?.max(0) as usize;
+            let mut tags = Vec::new();
+            let mut columns = Vec::new();
+            for column in category.field("columns")?.as_array()? {
+                let column_name = column.field("name")?.as_str()?;
+                tags.push(format!("{category_name}.{column_name}"));
+                columns.push(decode_column(column, row_count)?);
+            }
+
+            visitor.begin_category(&category_name, &tags)?;
+            let mut row = vec![String::new(); columns.len()];
+            for idx in 0..row_count {
+                for (slot, column) in row.iter_mut().zip(&columns) {
+                    column.write_value(idx, slot);
+                }
+                if visitor.row(&category_name, &tags, &row)? == Flow::Stop {
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    pub(crate) enum Pack<'a> {
+        Int(i64),
+        Float(f64),
+        Bool(bool),
+        Str(&'a str),
+        Text(String),
+        Bin(Vec<u8>),
+        Nil,
+        Array(Vec<Pack<'a>>),
+        Map(Vec<(&'a str, Pack<'a>)>),
+    }
+
+    impl Pack<'_> {
+        pub(crate) fn encode(&self, out: &mut Vec<u8>) {
+            match self {
+                Pack::Nil => out.push(0xc0),
+                Pack::Bool(v) => out.push(if *v { 0xc3 } else { 0xc2 }),
+                Pack::Int(v) => {
+                    out.push(0xd3);
+                    out.extend_from_slice(&v.to_be_bytes());
+                }
+                Pack::Float(v) => {
+                    out.push(0xcb);
+                    out.extend_from_slice(&v.to_bits().to_be_bytes());
+                }
+                Pack::Text(s) => Pack::Str(s).encode(out),
+                Pack::Str(s) => {
+                    out.push(0xdb);
+                    out.extend_from_slice(&(s.len() as u32).to_be_bytes());
+                    out.extend_from_slice(s.as_bytes());
+                }
+                Pack::Bin(b) => {
+                    out.push(0xc6);
+                    out.extend_from_slice(&(b.len() as u32).to_be_bytes());
+                    out.extend_from_slice(b);
+                }
+                Pack::Array(items) => {
+                    out.push(0xdd);
+                    out.extend_from_slice(&(items.len() as u32).to_be_bytes());
+                    for item in items {
+                        item.encode(out);
+                    }
+                }
+                Pack::Map(entries) => {
+                    out.push(0xdf);
+                    out.extend_from_slice(&(entries.len() as u32).to_be_bytes());
+                    for (key, value) in entries {
+                        Pack::Str(key).encode(out);
+                        value.encode(out);
+                    }
+                }
+            }
+        }
+    }
+
+    pub(crate) fn int32_column<'a>(name: &'a str, values: &[i32]) -> Pack<'a> {
+        let bytes = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+        Pack::Map(vec![
+            ("name", Pack::Str(name)),
+            (
+                "data",
+                Pack::Map(vec![
+                    ("data", Pack::Bin(bytes)),
+                    (
+                        "encoding",
+                        Pack::Array(vec![Pack::Map(vec![
+                            ("kind", Pack::Str("ByteArray")),
+                            ("type", Pack::Int(3)),
+                        ])]),
+                    ),
+                ]),
+            ),
+            ("mask", Pack::Nil),
+        ])
+    }
+
+    pub(crate) fn fixed_point_column<'a>(name: &'a str, values: &[f64]) -> Pack<'a> {
+        let deltas: Vec<i64> = values
+            .iter()
+            .map(|v| (v * 1000.0).round() as i64)
+            .scan(0i64, |prev, v| {
+                let d = v - *prev;
+                *prev = v;
+                Some(d)
+            })
+            .collect();
+        let bytes = deltas
+            .iter()
+            .flat_map(|v| (*v as i16).to_le_bytes())
+            .collect();
+        Pack::Map(vec![
+            ("name", Pack::Str(name)),
+            (
+                "data",
+                Pack::Map(vec![
+                    ("data", Pack::Bin(bytes)),
+                    (
+                        "encoding",
+                        Pack::Array(vec![
+                            Pack::Map(vec![
+                                ("kind", Pack::Str("FixedPoint")),
+                                ("factor", Pack::Float(1000.0)),
+                                ("srcType", Pack::Int(33)),
+                            ]),
+                            Pack::Map(vec![
+                                ("kind", Pack::Str("Delta")),
+                                ("origin", Pack::Int(0)),
+                                ("srcType", Pack::Int(3)),
+                            ]),
+                            Pack::Map(vec![
+                                ("kind", Pack::Str("IntegerPacking")),
+                                ("byteCount", Pack::Int(2)),
+                                ("isUnsigned", Pack::Bool(false)),
+                                ("srcSize", Pack::Int(values.len() as i64)),
+                            ]),
+                            Pack::Map(vec![
+                                ("kind", Pack::Str("ByteArray")),
+                                ("type", Pack::Int(2)),
+                            ]),
+                        ]),
+                    ),
+                ]),
+            ),
+        ])
+    }
+
+    pub(crate) fn string_column<'a>(name: &'a str, values: &[&str], mask: &[u8]) -> Pack<'a> {
+        let mut unique: Vec<&str> = Vec::new();
+        let mut indices = Vec::new();
+        for value in values {
+            let idx = unique.iter().position(|u| u == value).unwrap_or_else(|| {
+                unique.push(value);
+                unique.len() - 1
+            });
+            indices.push(idx as u8);
+        }
+        let mut offsets = vec![0u8];
+        for value in &unique {
+            offsets.push(offsets.last().unwrap() + value.len() as u8);
+        }
+        let byte_array = |kind: i64| {
+            Pack::Array(vec![Pack::Map(vec![
+                ("kind", Pack::Str("ByteArray")),
+                ("type", Pack::Int(kind)),
+            ])])
+        };
+        let run_length_mask = if mask.is_empty() {
+            Pack::Nil
+        } else {
+            let mut pairs: Vec<i32> = Vec::new();
+            for value in mask {
+                match pairs.len() {
+                    n if n >= 2 && pairs[n - 2] == *value as i32 => pairs[n - 1] += 1,
+                    _ => pairs.extend([*value as i32, 1]),
+                }
+            }
+            Pack::Map(vec![
+                (
+                    "data",
+                    Pack::Bin(pairs.iter().flat_map(|v| v.to_le_bytes()).collect()),
+                ),
+                (
+                    "encoding",
+                    Pack::Array(vec![
+                        Pack::Map(vec![
+                            ("kind", Pack::Str("RunLength")),
+                            ("srcType", Pack::Int(4)),
+                            ("srcSize", Pack::Int(mask.len() as i64)),
+                        ]),
+                        Pack::Map(vec![
+                            ("kind", Pack::Str("ByteArray")),
+                            ("type", Pack::Int(3)),
+                        ]),
+                    ]),
+                ),
+            ])
+        };
+        Pack::Map(vec![
+            ("name", Pack::Str(name)),
+            (
+                "data",
+                Pack::Map(vec![
+                    ("data", Pack::Bin(indices)),
+                    (
+                        "encoding",
+                        Pack::Array(vec![Pack::Map(vec![
+                            ("kind", Pack::Str("StringArray")),
+                            ("dataEncoding", byte_array(4)),
+                            ("stringData", Pack::Text(unique.concat())),
+                            ("offsetEncoding", byte_array(4)),
+                            ("offsets", Pack::Bin(offsets)),
+                        ])]),
+                    ),
+                ]),
+            ),
+            ("mask", run_length_mask),
+        ])
+    }
+
+    pub(crate) fn bcif_file(categories: Vec<(&str, usize, Vec<Pack<'_>>)>) -> Vec<u8> {
+        let categories = categories
+            .into_iter()
+            .map(|(name, rows, columns)| {
+                Pack::Map(vec![
+                    ("name", Pack::Str(name)),
+                    ("rowCount", Pack::Int(rows as i64)),
+                    ("columns", Pack::Array(columns)),
+                ])
+            })
+            .collect();
+        let file = Pack::Map(vec![
+            ("version", Pack::Str("0.3.0")),
+            ("encoder", Pack::Str("test")),
+            (
+                "dataBlocks",
+                Pack::Array(vec![Pack::Map(vec![
+                    ("header", Pack::Str("TEST")),
+                    ("categories", Pack::Array(categories)),
+                ])]),
+            ),
+        ]);
+        let mut out = Vec::new();
+        file.encode(&mut out);
+        out
+    }
+
+    #[test]
+    fn integer_packing_accumulates_sentinels() {
+        let encoding = Value::Map(vec![
+            (Value::Str("byteCount"), Value::Int(1)),
+            (Value::Str("isUnsigned"), Value::Bool(false)),
+            (Value::Str("srcSize"), Value::Int(3)),
+        ]);
+        let out = decode_integer_packing(vec![127, 3, -128, -2, 5], &encoding, 3).unwrap();
+        assert_eq!(out, vec![130, -130, 5]);
+        let err = decode_integer_packing(vec![1, 2, 3, 4], &encoding, 3).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let err = decode_integer_packing(vec![1, 2, 3], &encoding, 2).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn run_length_and_quantization_reject_bad_sizes() {
+        let data = |encodings: Vec<Value<'static>>, raw: &'static [u8]| {
+            Value::Map(vec![
+                (Value::Str("encoding"), Value::Array(encodings)),
+                (Value::Str("data"), Value::Bin(raw)),
+            ])
+        };
+        let byte_array = Value::Map(vec![
+            (Value::Str("kind"), Value::Str("ByteArray")),
+            (Value::Str("type"), Value::Int(4)),
+        ]);
+        let run_length = |src_size| {
+            Value::Map(vec![
+                (Value::Str("kind"), Value::Str("RunLength")),
+                (Value::Str("srcSize"), Value::Int(src_size)),
+            ])
+        };
+        let runs = data(vec![run_length(5), byte_array.clone()], &[7, 3, 8, 2]);
+        assert_eq!(
+            decode_data(&runs, 5).unwrap(),
+            Decoded::Int(vec![7, 7, 7, 8, 8])
+        );
+        assert!(decode_data(&runs, 4).is_err());
+        let runs = data(vec![run_length(4), byte_array.clone()], &[7, 3, 8, 2]);
+        assert!(decode_data(&runs, 5).is_err());
+
+        let quantized = |steps| {
+            data(
+                vec![
+                    Value::Map(vec![
+                        (Value::Str("kind"), Value::Str("IntervalQuantization")),
+                        (Value::Str("min"), Value::Float(0.0)),
+                        (Value::Str("max"), Value::Float(1.0)),
+                        (Value::Str("numSteps"), Value::Int(steps)),
+                    ]),
+                    byte_array.clone(),
+                ],
+                &[0, 1],
+            )
+        };
+        assert_eq!(
+            decode_data(&quantized(2), 2).unwrap(),
+            Decoded::Float(vec![0.0, 1.0])
+        );
+        for steps in [0, 1] {
+            let err = decode_data(&quantized(steps), 2).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn string_array_rejects_bad_offsets() {
+        let byte_array = |kind| {
+            Value::Array(vec![Value::Map(vec![
+                (Value::Str("kind"), Value::Str("ByteArray")),
+                (Value::Str("type"), Value::Int(kind)),
+            ])])
+        };
+        let encoding = |data, offsets| {
+            Value::Map(vec![
+                (Value::Str("stringData"), Value::Str(data)),
+                (Value::Str("offsets"), Value::Bin(offsets)),
+                (Value::Str("offsetEncoding"), byte_array(1)),
+                (Value::Str("dataEncoding"), byte_array(1)),
+            ])
+        };
+        let Decoded::Str(values) =
+            decode_string_array(&[1, 0], &encoding("CAÅN", &[0, 2, 4]), 2).unwrap()
+        else {
+            panic!("expected strings");
+        };
+        assert_eq!(values, ["ÅN", "CA"]);
+
+        let err = decode_string_array(&[0], &encoding("CAN", &[2, 1, 3]), 1).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("out of order"));
+    }
+
+    #[test]
+    fn msgpack_decodes_compact_forms() {
+        let bytes = [0x82, 0xa1, b'a', 0xff, 0xa1, b'b', 0x92, 0xcc, 200, 0xc3];
+        let value = MsgPack {
+            bytes: &bytes,
+            pos: 0,
+        }
+        .value()
+        .unwrap();
+        assert_eq!(value.get("a"), Some(&Value::Int(-1)));
+        assert_eq!(
+            value.get("b"),
+            Some(&Value::Array(vec![Value::Int(200), Value::Bool(true)]))
+        );
+    }
+}
@@ -0,0 +1,349 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+pub(crate) const CHI2_5_95: f64 = 11.070_497_693_516_351;
+pub(crate) const CHI2_5_99: f64 = 15.086_272_469_388_987;
+const LN_GAMMA_5_2: f64 = 0.284_682_870_472_919_6;
+
+fn chi2_5_survival(chi2: f64) -> f64 {
+    let a = 2.5f64;
+    let x = chi2 / 2.0;
+    if x <= 0.0 {
+        return 1.0;
+    }
+    let prefactor = (-x + a * x.ln() - LN_GAMMA_5_2).exp();
+    if x < a + 1.0 {
+        let mut term = 1.0 / a;
+        let mut sum = term;
+        let mut ap = a;
+        while term.abs() > sum.abs() * f64::EPSILON {
+            ap += 1.0;
+            term *= x / ap;
+            sum += term;
+        }
+        (1.0 - sum * prefactor).max(0.0)
+    } else {
+        let tiny = f64::MIN_POSITIVE / f64::EPSILON;
+        let mut b = x + 1.0 - a;
+        let mut c = 1.0 / tiny;
+        let mut d = 1.0 / b;
+        let mut h = d;
+        for i in 1..1000 {
+            let an = -(i as f64) * (i as f64 - a);
+            b += 2.0;
+            d = an * d + b;
+            if d.abs() < tiny {
+                d = tiny;
+            }
+            c = b + an / c;
+            if c.abs() < tiny {
+                c = tiny;
+            }
+            d = 1.0 / d;
+            let delta = d * c;
+            h *= delta;
+            if (delta - 1.0).abs() <= f64::EPSILON {
+                break;
+            }
+        }
+        prefactor * h
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Calibration {
+    pub mean: [f64; 5],
+    pub inverse_covariance: [[f64; 5]; 5],
+    pub limit_95: f64,
+    pub limit_99: f64,
+    pub maxwin: f64,
+    pub radius: f64,
+    pub radmin: f64,
+    pub box_size: f64,
+    pub window_length: usize,
+}
+
+impl Default for Calibration {
+    fn default() -> Self {
+        Calibration::classic()
+    }
+}
+
+fn calibration_error(line: usize, message: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("calibration file line {line}: {message}"),
+    )
+}
+
+fn parse_values<const N: usize>(line: usize, fields: &[&str]) -> io::Result<[f64; N]> {
+    if fields.len() != N {
+        return Err(calibration_error(
+            line,
+            &format!("expected {N} values, found {}", fields.len()),
+        ));
+    }
+    let mut values = [0.0f64; N];
+    for (value, field) in values.iter_mut().zip(fields) {
+        *value = field
+            .parse::<f64>()
+            .ok()
+            .filter(|v| v.is_finite())
+            .ok_or_else(|| calibration_error(line, &format!("invalid number {field}")))?;
+    }
+    Ok(values)
+}
+
+impl Calibration {
+    pub fn classic() -> Self {
+        Calibration {
+            mean: [
+                0.192765509919262,
+                0.195575208778518,
+                0.275322406824210,
+                0.059102357035642,
+                0.233154192767480,
+            ],
+            inverse_covariance: [
+                [
+                    5040.279078850848,
+                    3408.8051415836494,
+                    4152.904423767301,
+                    4236.20000417189,
+                    5054.7812102046255,
+                ],
+                [
+                    3408.805141583649,
+                    8491.90609401022,
+                    5958.88177787795,
+                    1521.3873527184862,
+                    4304.078200827222,
+                ],
+                [
+                    4152.9044237673015,
+                    5958.881777877952,
+                    7637.16708933505,
+                    6620.715738223073,
+                    5287.691183798411,
+                ],
+                [
+                    4236.20000417189,
+                    1521.3873527184862,
+                    6620.715738223073,
+                    18368.34377429841,
+                    4050.7978111188067,
+                ],
+                [
+                    5054.7812102046255,
+                    4304.078200827221,
+                    5287.69118379841,
+                    4050.7978111188067,
+                    6666.856740479165,
+                ],
+            ],
+            limit_95: 11.526_684_477_428_809,
+            limit_99: 17.190_823_041_860_433,
+            maxwin: 100.694,
+            radius: 3.75,
+            radmin: 3.25,
+            box_size: 4.0,
+            window_length: 9,
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = File::open(path)?;
+        Calibration::read_from(BufReader::new(file))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        self.write_to(&mut out)?;
+        out.flush()
+    }
+
+    pub fn read_from<R: BufRead>(reader: R) -> io::Result<Self> {
+        let mut calibration = Calibration::classic();
+        let mut covariance_rows = 0usize;
+        for (idx, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line_no = idx + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields[0] {
+                "mean" => calibration.mean = parse_values(line_no, &fields[1..])?,
+                "inverse_covariance" => {
+                    if covariance_rows == 5 {
+                        return Err(calibration_error(
+                            line_no,
+                            "more than 5 inverse_covariance rows",
+                        ));
+                    }
+                    calibration.inverse_covariance[covariance_rows] =
+                        parse_values(line_no, &fields[1..])?;
+                    covariance_rows += 1;
+                }
+                "limit_95" => calibration.limit_95 = parse_values::<1>(line_no, &fields[1..])?[0],
+                "limit_99" => calibration.limit_99 = parse_values::<1>(line_no, &fields[1..])?[0],
+                "maxwin" => calibration.maxwin = parse_values::<1>(line_no, &fields[1..])?[0],
+                "radius" => calibration.radius = parse_values::<1>(line_no, &fields[1..])?[0],
+                "radmin" => calibration.radmin = parse_values::<1>(line_no, &fields[1..])?[0],
+                "box_size" => calibration.box_size = parse_values::<1>(line_no, &fields[1..])?[0],
+                "window_length" => {
+                    let value = parse_values::<1>(line_no, &fields[1..])?[0];
+                    if value < 0.0 || value.fract() != 0.0 {
+                        return Err(calibration_error(
+                            line_no,
+                            "window_length must be a whole number",
+                        ));
+                    }
+                    calibration.window_length = value as usize;
+                }
+                key => {
+                    return Err(calibration_error(line_no, &format!("unknown key {key}")));
+                }
+            }
+        }
+        if covariance_rows != 0 && covariance_rows != 5 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "calibration file has {covariance_rows} inverse_covariance rows, expected 5"
+                ),
+            ));
+        }
+        calibration.validate()?;
+        Ok(calibration)
+    }
+
+    pub fn validate(&self) -> io::Result<()> {
+        let invalid = |message: &str| {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("calibration {message}"),
+            ))
+        };
+        if self.limit_95 <= 0.0 || self.limit_99 < self.limit_95 {
+            return invalid("requires 0 < limit_95 <= limit_99");
+        }
+        if self.radmin <= 0.0 || self.radius <= self.radmin {
+            return invalid("requires 0 < radmin < radius");
+        }
+        if self.box_size <= 0.0 || self.maxwin < 0.0 {
+            return invalid("requires a positive box_size and non-negative maxwin");
+        }
+        if self.window_length < 3 || self.window_length.is_multiple_of(2) {
+            return invalid("requires an odd window_length of at least 3");
+        }
+        Ok(())
+    }
+
+    pub fn write_to<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let join = |values: &[f64]| {
+            values
+                .iter()
+                .map(|v| v.to_string())
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        writeln!(out, "mean {}", join(&self.mean))?;
+        for row in &self.inverse_covariance {
+            writeln!(out, "inverse_covariance {}", join(row))?;
+        }
+        writeln!(out, "limit_95 {}", self.limit_95)?;
+        writeln!(out, "limit_99 {}", self.limit_99)?;
+        writeln!(out, "maxwin {}", self.maxwin)?;
+        writeln!(out, "radius {}", self.radius)?;
+        writeln!(out, "radmin {}", self.radmin)?;
+        writeln!(out, "box_size {}", self.box_size)?;
+        writeln!(out, "window_length {}", self.window_length)?;
+        Ok(())
+    }
+
+    pub(crate) fn half_window(&self) -> i32 {
+        (self.window_length / 2) as i32
+    }
+
+    pub fn contributions(&self, fractions: &[f64; 5]) -> [f64; 5] {
+        let v: [f64; 5] = std::array::from_fn(|u| fractions[u] - self.mean[u]);
+        let c: [f64; 5] = std::array::from_fn(|j| {
+            v.iter()
+                .zip(&self.inverse_covariance)
+                .fold(0.0, |x, (vk, row)| x + vk * row[j])
+        });
+        std::array::from_fn(|k| c[k] * v[k])
+    }
+
+    pub fn score(&self, fractions: &[f64; 5]) -> f64 {
+        self.contributions(fractions)
+            .iter()
+            .fold(0.0, |total, term| total + term)
+    }
+
+    pub fn p_value(&self, error_value: f64) -> f64 {
+        let exponent = if self.limit_99 > self.limit_95 {
+            (CHI2_5_99 / CHI2_5_95).ln() / (self.limit_99 / self.limit_95).ln()
+        } else {
+            1.0
+        };
+        let ratio = error_value.max(0.0) / self.limit_95;
+        chi2_5_survival(CHI2_5_95 * ratio.powf(exponent))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn calibration_round_trips_through_text() {
+        let mut calibration = Calibration::classic();
+        calibration.limit_95 = 10.5;
+        calibration.mean[2] = 0.3;
+        calibration.radius = 4.0;
+        calibration.window_length = 7;
+        let mut out = Vec::new();
+        calibration.write_to(&mut out).unwrap();
+        let loaded = Calibration::read_from(Cursor::new(out)).unwrap();
+        assert_eq!(loaded, calibration);
+    }
+
+    #[test]
+    fn calibration_reports_bad_lines() {
+        let err = Calibration::read_from(Cursor::new("# limits only\nlimit_95 1 2\n")).unwrap_err();
+        assert!(err.to_string().contains("line 2"));
+        let partial = Calibration::read_from(Cursor::new("limit_99 20\n")).unwrap();
+        assert_eq!(partial.limit_99, 20.0);
+        assert_eq!(partial.mean, Calibration::classic().mean);
+        let err = Calibration::read_from(Cursor::new("radius 3\n")).unwrap_err();
+        assert!(err.to_string().contains("radmin < radius"));
+        let err = Calibration::read_from(Cursor::new("window_length 8\n")).unwrap_err();
+        assert!(err.to_string().contains("odd window_length"));
+    }
+
+    #[test]
+    fn p_values_match_calibrated_limits() {
+        assert!((chi2_5_survival(CHI2_5_95) - 0.05).abs() < 1e-12);
+        assert!((chi2_5_survival(CHI2_5_99) - 0.01).abs() < 1e-12);
+        assert!((chi2_5_survival(2.0) - 0.849_145_036_084_609_6).abs() < 1e-12);
+
+        let classic = Calibration::classic();
+        assert!((classic.p_value(classic.limit_95) - 0.05).abs() < 1e-12);
+        assert!((classic.p_value(classic.limit_99) - 0.01).abs() < 1e-12);
+        assert_eq!(classic.p_value(0.0), 1.0);
+        assert!(classic.p_value(14.0) < 0.05 && classic.p_value(14.0) > 0.01);
+
+        let trained = Calibration {
+            limit_95: CHI2_5_95,
+            limit_99: CHI2_5_99,
+            ..Calibration::classic()
+        };
+        assert!((trained.p_value(8.0) - chi2_5_survival(8.0)).abs() < 1e-15);
+        assert!((chi2_5_survival(40.0) - 1.493_367_900_050_395e-7).abs() < 1e-18);
+    }
+}
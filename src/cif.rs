@@ -0,0 +1,427 @@
+use std::io::{self, BufRead};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Token<'a> {
+    DataBlock(&'a str),
+    Save(&'a str),
+    Global,
+    Stop,
+    Loop,
+    Tag(&'a str),
+    Value(&'a str),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Flow {
+    Continue,
+    Stop,
+}
+
+pub(crate) fn syntax_error(line: usize, message: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("mmCIF syntax error at line {line}: {message}"),
+    )
+}
+
+pub(crate) struct Tokenizer<R> {
+    reader: R,
+    line: Vec<u8>,
+    pos: usize,
+    line_no: usize,
+    text: Vec<u8>,
+}
+
+impl<R: BufRead> Tokenizer<R> {
+    pub(crate) fn new(reader: R) -> Self {
+        Tokenizer {
+            reader,
+            line: Vec::new(),
+            pos: 0,
+            line_no: 0,
+            text: Vec::new(),
+        }
+    }
+
+    fn fill_line(&mut self) -> io::Result<bool> {
+        self.line.clear();
+        self.pos = 0;
+        let read = self.reader.read_until(b'\n', &mut self.line)?;
+        if read == 0 {
+            return Ok(false);
+        }
+        self.line_no += 1;
+        while matches!(self.line.last(), Some(b'\n' | b'\r')) {
+            self.line.pop();
+        }
+        Ok(true)
+    }
+
+    fn read_text_field(&mut self) -> io::Result<()> {
+        let start_line = self.line_no;
+        self.text.clear();
+        self.text.extend_from_slice(&self.line[1..]);
+        loop {
+            if !self.fill_line()? {
+                return Err(syntax_error(start_line, "unterminated text field"));
+            }
+            if self.line.first() == Some(&b';') {
+                self.pos = 1;
+                return Ok(());
+            }
+            self.text.push(b'\n');
+            self.text.extend_from_slice(&self.line);
+        }
+    }
+
+    pub(crate) fn next_token(&mut self) -> io::Result<Option<(Token<'_>, usize)>> {
+        loop {
+            if self.pos == 0 && self.line_no > 0 && self.line.first() == Some(&b';') {
+                let token_line = self.line_no;
+                self.read_text_field()?;
+                let text = as_str(&self.text, token_line)?;
+                return Ok(Some((Token::Value(text), token_line)));
+            }
+
+            while self.pos < self.line.len() && self.line[self.pos].is_ascii_whitespace() {
+                self.pos += 1;
+            }
+            if self.pos >= self.line.len() || self.line[self.pos] == b'#' {
+                if !self.fill_line()? {
+                    return Ok(None);
+                }
+                continue;
+            }
+
+            let token_line = self.line_no;
+            let start = self.pos;
+            let first = self.line[start];
+            if first == b'\'' || first == b'"' {
+                let mut end = start + 1;
+                loop {
+                    if end >= self.line.len() {
+                        return Err(syntax_error(self.line_no, "unterminated quoted value"));
+                    }
+                    if self.line[end] == first
+                        && self
+                            .line
+                            .get(end + 1)
+                            .is_none_or(|next| next.is_ascii_whitespace())
+                    {
+                        break;
+                    }
+                    end += 1;
+                }
+                self.pos = end + 1;
+                let value = as_str(&self.line[start + 1..end], token_line)?;
+                return Ok(Some((Token::Value(value), token_line)));
+            }
+
+            let mut end = start;
+            while end < self.line.len() && !self.line[end].is_ascii_whitespace() {
+                end += 1;
+            }
+            self.pos = end;
+            let word = as_str(&self.line[start..end], token_line)?;
+            return Ok(Some((classify_word(word), token_line)));
+        }
+    }
+}
+
+fn as_str(bytes: &[u8], line: usize) -> io::Result<&str> {
+    std::str::from_utf8(bytes).map_err(|_| syntax_error(line, "invalid UTF-8"))
+}
+
+fn classify_word(word: &str) -> Token<'_> {
+    if word.starts_with('_') {
+        return Token::Tag(word);
+    }
+    let prefix = |p: &str| word.len() >= p.len() && word[..p.len()].eq_ignore_ascii_case(p);
+    if prefix("data_") {
+        Token::DataBlock(&word[5..])
+    } else if prefix("save_") {
+        Token::Save(&word[5..])
+    } else if word.eq_ignore_ascii_case("loop_") {
+        Token::Loop
+    } else if word.eq_ignore_ascii_case("global_") {
+        Token::Global
+    } else if word.eq_ignore_ascii_case("stop_") {
+        Token::Stop
+    } else {
+        Token::Value(word)
+    }
+}
+
+pub(crate) fn split_tag(tag: &str) -> (&str, &str) {
+    match tag.find('.') {
+        Some(dot) => (&tag[..dot], &tag[dot + 1..]),
+        None => (tag, ""),
+    }
+}
+
+pub(crate) trait CifVisitor {
+    fn begin_block(&mut self, name: &str) -> io::Result<Flow>;
+
+    fn wants(&self, category: &str) -> bool;
+
+    fn begin_category(&mut self, category: &str, columns: &[String]) -> io::Result<()>;
+
+    fn row(&mut self, category: &str, columns: &[String], values: &[String]) -> io::Result<Flow>;
+}
+
+#[derive(Default)]
+struct RowBuffer {
+    values: Vec<String>,
+    filled: usize,
+}
+
+impl RowBuffer {
+    fn push(&mut self, value: &str) {
+        if self.filled == self.values.len() {
+            self.values.push(String::new());
+        }
+        let slot = &mut self.values[self.filled];
+        slot.clear();
+        slot.push_str(value);
+        self.filled += 1;
+    }
+
+    fn as_slice(&self) -> &[String] {
+        &self.values[..self.filled]
+    }
+}
+
+enum State {
+    Idle,
+    LoopHeader,
+    LoopBody { start_line: usize },
+    PairTag,
+}
+
+fn flush_pairs<V: CifVisitor>(
+    visitor: &mut V,
+    category: &str,
+    columns: &[String],
+    row: &RowBuffer,
+) -> io::Result<Flow> {
+    visitor.begin_category(category, columns)?;
+    visitor.row(category, columns, row.as_slice())
+}
+
+pub(crate) fn read_cif<R: BufRead, V: CifVisitor>(reader: R, visitor: &mut V) -> io::Result<()> {
+    let mut tokens = Tokenizer::new(reader);
+    let mut state = State::Idle;
+    let mut category = String::new();
+    let mut columns: Vec<String> = Vec::new();
+    let mut row = RowBuffer::default();
+    let mut wanted = false;
+    let mut loop_values = 0usize;
+    let mut line = 0usize;
+
+    loop {
+        let next = tokens.next_token()?;
+        if let Some((_, token_line)) = next {
+            line = token_line;
+        }
+        let token = next.map(|(token, _)| token);
+
+        if let State::LoopHeader = state {
+            if let Some(Token::Tag(tag)) = token {
+                let (cat, _) = split_tag(tag);
+                if columns.is_empty() {
+                    category.clear();
+                    category.push_str(cat);
+                    wanted = visitor.wants(&category);
+                } else if !cat.eq_ignore_ascii_case(&category) {
+                    return Err(syntax_error(line, "loop mixes categories"));
+                }
+                columns.push(tag.to_string());
+                continue;
+            }
+            if columns.is_empty() {
+                return Err(syntax_error(line, "loop_ without tags"));
+            }
+            state = State::LoopBody { start_line: line };
+            loop_values = 0;
+            row.filled = 0;
+            if wanted {
+                visitor.begin_category(&category, &columns)?;
+            }
+        }
+
+        if let State::LoopBody { start_line } = state {
+            if let Some(Token::Value(value)) = token {
+                loop_values += 1;
+                if wanted {
+                    row.push(value);
+                    if row.filled == columns.len() {
+                        let flow = visitor.row(&category, &columns, row.as_slice())?;
+                        row.filled = 0;
+                        if flow == Flow::Stop {
+                            return Ok(());
+                        }
+                    }
+                }
+                continue;
+            }
+            if !loop_values.is_multiple_of(columns.len()) {
+                return Err(syntax_error(
+                    start_line,
+                    &format!(
+                        "loop has {loop_values} values, not a multiple of {} columns",
+                        columns.len()
+                    ),
+                ));
+            }
+            columns.clear();
+            state = State::Idle;
+        }
+
+        if let State::PairTag = state {
+            match token {
+                Some(Token::Value(value)) => {
+                    if wanted {
+                        row.push(value);
+                    }
+                    state = State::Idle;
+                    continue;
+                }
+                _ => return Err(syntax_error(line, "tag without value")),
+            }
+        }
+
+        match token {
+            Some(Token::Tag(tag)) => {
+                let (cat, _) = split_tag(tag);
+                if !columns.is_empty() && !cat.eq_ignore_ascii_case(&category) {
+                    if wanted && flush_pairs(visitor, &category, &columns, &row)? == Flow::Stop {
+                        return Ok(());
+                    }
+                    columns.clear();
+                }
+                if columns.is_empty() {
+                    category.clear();
+                    category.push_str(cat);
+                    wanted = visitor.wants(&category);
+                    row.filled = 0;
+                }
+                columns.push(tag.to_string());
+                state = State::PairTag;
+                continue;
+            }
+            Some(Token::Value(_)) => {
+                return Err(syntax_error(line, "value without tag"));
+            }
+            _ => {}
+        }
+
+        if !columns.is_empty() {
+            if wanted && flush_pairs(visitor, &category, &columns, &row)? == Flow::Stop {
+                return Ok(());
+            }
+            columns.clear();
+        }
+
+        match token {
+            None => return Ok(()),
+            Some(Token::Loop) => state = State::LoopHeader,
+            Some(Token::DataBlock(name)) => {
+                if visitor.begin_block(name)? == Flow::Stop {
+                    return Ok(());
+                }
+            }
+            Some(_) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn tokens(input: &str) -> Vec<String> {
+        let mut tokenizer = Tokenizer::new(Cursor::new(input.as_bytes()));
+        let mut out = Vec::new();
+        while let Some((token, _)) = tokenizer.next_token().unwrap() {
+            out.push(match token {
+                Token::DataBlock(name) => format!("data_{name}"),
+                Token::Value(value) | Token::Tag(value) => value.to_string(),
+                other => format!("{other:?}"),
+            });
+        }
+        out
+    }
+
+    #[derive(Default)]
+    struct Collect {
+        blocks: Vec<String>,
+        rows: Vec<Vec<String>>,
+    }
+
+    impl CifVisitor for Collect {
+        fn begin_block(&mut self, name: &str) -> io::Result<Flow> {
+            self.blocks.push(name.to_string());
+            Ok(Flow::Continue)
+        }
+
+        fn wants(&self, category: &str) -> bool {
+            category == "_atom_site"
+        }
+
+        fn begin_category(&mut self, _: &str, _: &[String]) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn row(&mut self, _: &str, _: &[String], values: &[String]) -> io::Result<Flow> {
+            self.rows.push(values.to_vec());
+            Ok(Flow::Continue)
+        }
+    }
+
+    #[test]
+    fn tokenizer_preserves_semicolon_blocks() {
+        let tokens = tokens("data_demo\n;hello\nworld\n;\n");
+        assert_eq!(tokens[0], "data_demo");
+        assert_eq!(tokens[1], "hello\nworld");
+    }
+
+    #[test]
+    fn tokenizer_allows_embedded_quotes() {
+        let tokens = tokens("_a.b 'O5'' \"it's\" 'N'A'\n");
+        assert_eq!(tokens, vec!["_a.b", "O5'", "it's", "N'A"]);
+    }
+
+    #[test]
+    fn read_cif_handles_pairs_loops_and_blocks() {
+        let input = "\
+data_first
+_atom_site.id 1
+_atom_site.label_atom_id CA
+_cell.length_a 10.0
+data_second
+loop_
+_atom_site.id
+_atom_site.label_atom_id
+2 N 3 'C'
+";
+        let mut visitor = Collect::default();
+        read_cif(Cursor::new(input.as_bytes()), &mut visitor).unwrap();
+        assert_eq!(visitor.blocks, vec!["first", "second"]);
+        assert_eq!(
+            visitor.rows,
+            vec![vec!["1", "CA"], vec!["2", "N"], vec!["3", "C"]]
+        );
+    }
+
+    #[test]
+    fn read_cif_reports_line_numbers() {
+        let input = "data_x\nloop_\n_atom_site.id\n_atom_site.x\n1 2\n3\n";
+        let err = read_cif(Cursor::new(input.as_bytes()), &mut Collect::default()).unwrap_err();
+        assert!(err.to_string().contains("line 5"), "{err}");
+
+        let input = "data_x\n_atom_site.id 'open\n";
+        let err = read_cif(Cursor::new(input.as_bytes()), &mut Collect::default()).unwrap_err();
+        assert!(err.to_string().contains("line 2"), "{err}");
+    }
+}
@@ -0,0 +1,497 @@
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::api::{
+    AnalysisOptions, AnalysisResult, FrameStatus, ReportFormat, build_analysis_result, chain_label,
+    compute_chain_ranges, derive_file_string, frame_status_name,
+};
+use crate::export::{csv_field, json_number, json_optional, json_string};
+use crate::model::ErratStats;
+use crate::pipeline::process_structure_data;
+use crate::render::{Overlay, write_pdf, write_ps, write_svg};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FrameDelta {
+    pub chain_id: String,
+    pub residue: i32,
+    pub before_error_value: Option<f64>,
+    pub after_error_value: Option<f64>,
+    pub delta: Option<f64>,
+    pub before_status: Option<FrameStatus>,
+    pub after_status: Option<FrameStatus>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChangedRegion {
+    pub chain_id: String,
+    pub start_residue: i32,
+    pub end_residue: i32,
+    pub length: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct Comparison {
+    pub before: AnalysisResult,
+    pub after: AnalysisResult,
+    pub frame_deltas: Vec<FrameDelta>,
+    pub newly_flagged: Vec<ChangedRegion>,
+    pub newly_cleared: Vec<ChangedRegion>,
+    pub escalated: Vec<ChangedRegion>,
+    pub eased: Vec<ChangedRegion>,
+    pub quality_factor_change: Option<f64>,
+}
+
+type FrameKey = (String, i32);
+
+fn keyed_frames(stats: &ErratStats, analysis: &AnalysisResult) -> BTreeMap<FrameKey, usize> {
+    stats
+        .scored_frames
+        .iter()
+        .zip(&analysis.frame_scores)
+        .enumerate()
+        .map(|(idx, (raw, frame))| {
+            (
+                (
+                    frame.chain_id.clone(),
+                    raw.center_residue - raw.residue_offset,
+                ),
+                idx,
+            )
+        })
+        .collect()
+}
+
+fn align_frames(
+    before: (&ErratStats, &AnalysisResult),
+    after: (&ErratStats, &AnalysisResult),
+) -> Vec<FrameDelta> {
+    let mut keys: BTreeMap<FrameKey, (Option<usize>, Option<usize>)> = BTreeMap::new();
+    for (key, idx) in keyed_frames(before.0, before.1) {
+        keys.entry(key).or_default().0 = Some(idx);
+    }
+    for (key, idx) in keyed_frames(after.0, after.1) {
+        keys.entry(key).or_default().1 = Some(idx);
+    }
+    keys.into_iter()
+        .map(|((chain_id, residue), (b, a))| {
+            let before_frame = b.map(|idx| &before.1.frame_scores[idx]);
+            let after_frame = a.map(|idx| &after.1.frame_scores[idx]);
+            let before_error_value = before_frame.map(|frame| frame.error_value);
+            let after_error_value = after_frame.map(|frame| frame.error_value);
+            FrameDelta {
+                chain_id,
+                residue,
+                before_error_value,
+                after_error_value,
+                delta: after_error_value
+                    .zip(before_error_value)
+                    .map(|(a, b)| a - b),
+                before_status: before_frame.map(|frame| frame.status),
+                after_status: after_frame.map(|frame| frame.status),
+            }
+        })
+        .collect()
+}
+
+fn changed_regions(
+    deltas: &[FrameDelta],
+    changed: impl Fn(FrameStatus, FrameStatus) -> bool,
+) -> Vec<ChangedRegion> {
+    let mut regions: Vec<ChangedRegion> = Vec::new();
+    for delta in deltas {
+        let (Some(before), Some(after)) = (delta.before_status, delta.after_status) else {
+            continue;
+        };
+        if !changed(before, after) {
+            continue;
+        }
+        match regions.last_mut() {
+            Some(last)
+                if last.chain_id == delta.chain_id && last.end_residue + 1 == delta.residue =>
+            {
+                last.end_residue = delta.residue;
+                last.length += 1;
+            }
+            _ => regions.push(ChangedRegion {
+                chain_id: delta.chain_id.clone(),
+                start_residue: delta.residue,
+                end_residue: delta.residue,
+                length: 1,
+            }),
+        }
+    }
+    regions
+}
+
+fn build_overlay(
+    before_stats: &ErratStats,
+    before: &AnalysisResult,
+    after_stats: &ErratStats,
+    after: &AnalysisResult,
+) -> Overlay {
+    let before_frames = keyed_frames(before_stats, before);
+    let errat = keyed_frames(after_stats, after)
+        .into_iter()
+        .filter_map(|(key, idx)| {
+            let before_idx = before_frames.get(&key)?;
+            Some((
+                after_stats.scored_frames[idx].center_residue,
+                before.frame_scores[*before_idx].error_value,
+            ))
+        })
+        .collect();
+    let chain_quality_factors = compute_chain_ranges(after_stats)
+        .into_iter()
+        .map(|range| {
+            let label = chain_label(range.chain_id);
+            let quality_factor = before
+                .chain_summaries
+                .iter()
+                .find(|chain| chain.chain_id == label)
+                .and_then(|chain| chain.quality_factor);
+            (range.chain_id, quality_factor)
+        })
+        .collect();
+    Overlay {
+        errat,
+        overall_quality_factor: before.overall_quality_factor,
+        chain_quality_factors,
+    }
+}
+
+pub fn compare_files<P: AsRef<Path>, Q: AsRef<Path>>(
+    before: P,
+    after: Q,
+    use_mmap: bool,
+    options: &AnalysisOptions,
+    outputs: &mut [(ReportFormat, &mut dyn Write)],
+) -> io::Result<Comparison> {
+    let analyze = |path: &Path| -> io::Result<(ErratStats, AnalysisResult)> {
+        let input_path = path.to_path_buf();
+        let (stats, log) = process_structure_data(&input_path, use_mmap, options)?;
+        let log_text = String::from_utf8_lossy(&log).into_owned();
+        let protein_id = derive_file_string(&input_path, None);
+        let analysis = build_analysis_result(input_path, protein_id, &stats, log_text, options);
+        Ok((stats, analysis))
+    };
+    let (before_stats, before) = analyze(before.as_ref())?;
+    let (after_stats, after) = analyze(after.as_ref())?;
+
+    let frame_deltas = align_frames((&before_stats, &before), (&after_stats, &after));
+    let flagged = |status: FrameStatus| status != FrameStatus::Ok;
+    let comparison = Comparison {
+        newly_flagged: changed_regions(&frame_deltas, |b, a| !flagged(b) && flagged(a)),
+        newly_cleared: changed_regions(&frame_deltas, |b, a| flagged(b) && !flagged(a)),
+        escalated: changed_regions(&frame_deltas, |b, a| {
+            b == FrameStatus::Warning95 && a == FrameStatus::Warning99
+        }),
+        eased: changed_regions(&frame_deltas, |b, a| {
+            b == FrameStatus::Warning99 && a == FrameStatus::Warning95
+        }),
+        quality_factor_change: after
+            .overall_quality_factor
+            .zip(before.overall_quality_factor)
+            .map(|(a, b)| a - b),
+        frame_deltas,
+        before,
+        after,
+    };
+
+    let overlay = build_overlay(
+        &before_stats,
+        &comparison.before,
+        &after_stats,
+        &comparison.after,
+    );
+    let file_string = format!(
+        "{} -> {}",
+        comparison.before.protein_id, comparison.after.protein_id
+    );
+    let regions = &comparison.after.problem_regions;
+    for (format, out) in outputs.iter_mut() {
+        match format {
+            ReportFormat::Json => write_comparison_json(out, &comparison)?,
+            ReportFormat::Csv => write_comparison_csv(out, &comparison)?,
+            ReportFormat::Log => write_comparison_summary(out, &comparison)?,
+            ReportFormat::Ps | ReportFormat::Pdf | ReportFormat::Svg if after_stats.stat > 0.0 => {
+                let mut sink = io::sink();
+                let (stats, overlay) = (&after_stats, Some(&overlay));
+                match format {
+                    ReportFormat::Ps => {
+                        write_ps(out, &mut sink, &file_string, stats, regions, overlay)?
+                    }
+                    ReportFormat::Pdf => {
+                        write_pdf(out, &mut sink, &file_string, stats, regions, overlay)?
+                    }
+                    _ => write_svg(out, &mut sink, &file_string, stats, regions, overlay)?,
+                }
+            }
+            ReportFormat::Ps | ReportFormat::Pdf | ReportFormat::Svg => {}
+            ReportFormat::SummaryCsv => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "compare does not write summary CSV reports",
+                ));
+            }
+        }
+        out.flush()?;
+    }
+    Ok(comparison)
+}
+
+fn json_regions(regions: &[ChangedRegion]) -> String {
+    let items = regions
+        .iter()
+        .map(|region| {
+            format!(
+                "{{\"chain_id\": {}, \"start_residue\": {}, \"end_residue\": {}, \"length\": {}}}",
+                json_string(&region.chain_id),
+                region.start_residue,
+                region.end_residue,
+                region.length
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!("[{items}]")
+}
+
+fn json_side(analysis: &AnalysisResult) -> String {
+    format!(
+        "{{\"protein_id\": {}, \"input_path\": {}, \"scored_frame_count\": {}, \"rejected_frame_count\": {}, \"overall_quality_factor\": {}}}",
+        json_string(&analysis.protein_id),
+        json_string(&analysis.input_path.to_string_lossy()),
+        analysis.scored_frame_count,
+        analysis.rejected_frame_count,
+        json_optional(analysis.overall_quality_factor)
+    )
+}
+
+fn status_json(status: Option<FrameStatus>) -> String {
+    status.map_or_else(
+        || "null".to_string(),
+        |status| format!("\"{}\"", frame_status_name(status)),
+    )
+}
+
+fn write_comparison_json<W: Write>(out: &mut W, comparison: &Comparison) -> io::Result<()> {
+    writeln!(out, "{{")?;
+    writeln!(out, "  \"before\": {},", json_side(&comparison.before))?;
+    writeln!(out, "  \"after\": {},", json_side(&comparison.after))?;
+    writeln!(
+        out,
+        "  \"quality_factor_change\": {},",
+        json_optional(comparison.quality_factor_change)
+    )?;
+    writeln!(
+        out,
+        "  \"newly_flagged\": {},",
+        json_regions(&comparison.newly_flagged)
+    )?;
+    writeln!(
+        out,
+        "  \"newly_cleared\": {},",
+        json_regions(&comparison.newly_cleared)
+    )?;
+    writeln!(
+        out,
+        "  \"escalated\": {},",
+        json_regions(&comparison.escalated)
+    )?;
+    writeln!(out, "  \"eased\": {},", json_regions(&comparison.eased))?;
+    writeln!(out, "  \"frame_deltas\": [")?;
+    for (idx, delta) in comparison.frame_deltas.iter().enumerate() {
+        let sep = if idx + 1 < comparison.frame_deltas.len() {
+            ","
+        } else {
+            ""
+        };
+        writeln!(
+            out,
+            "    {{\"chain_id\": {}, \"residue\": {}, \"before_error_value\": {}, \"after_error_value\": {}, \"delta\": {}, \"before_status\": {}, \"after_status\": {}}}{sep}",
+            json_string(&delta.chain_id),
+            delta.residue,
+            json_optional(delta.before_error_value),
+            json_optional(delta.after_error_value),
+            json_optional(delta.delta),
+            status_json(delta.before_status),
+            status_json(delta.after_status)
+        )?;
+    }
+    writeln!(out, "  ]")?;
+    writeln!(out, "}}")?;
+    Ok(())
+}
+
+fn write_comparison_csv<W: Write>(out: &mut W, comparison: &Comparison) -> io::Result<()> {
+    writeln!(
+        out,
+        "chain_id,residue,before_error_value,after_error_value,delta,before_status,after_status"
+    )?;
+    let number = |value: Option<f64>| value.map(json_number).unwrap_or_default();
+    let status = |value: Option<FrameStatus>| value.map(frame_status_name).unwrap_or_default();
+    for delta in &comparison.frame_deltas {
+        writeln!(
+            out,
+            "{},{},{},{},{},{},{}",
+            csv_field(&delta.chain_id),
+            delta.residue,
+            number(delta.before_error_value),
+            number(delta.after_error_value),
+            number(delta.delta),
+            status(delta.before_status),
+            status(delta.after_status)
+        )?;
+    }
+    Ok(())
+}
+
+fn write_comparison_summary<W: Write>(out: &mut W, comparison: &Comparison) -> io::Result<()> {
+    let quality =
+        |value: Option<f64>| value.map_or_else(|| "n/a".to_string(), |v| format!("{v:.3}"));
+    writeln!(
+        out,
+        "# Quality factor: {} -> {}    Change {}",
+        quality(comparison.before.overall_quality_factor),
+        quality(comparison.after.overall_quality_factor),
+        quality(comparison.quality_factor_change)
+    )?;
+    for (label, regions) in [
+        ("Newly flagged", &comparison.newly_flagged),
+        ("Newly cleared", &comparison.newly_cleared),
+        ("Escalated to 99%", &comparison.escalated),
+        ("Eased to 95%", &comparison.eased),
+    ] {
+        for region in regions {
+            writeln!(
+                out,
+                "# {label} {}:    Residue range {} to {}    Length {}",
+                region.chain_id, region.start_residue, region.end_residue, region.length
+            )?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::WindowInteractions;
+    use crate::calibration::Calibration;
+    use crate::model::{Experiment, FrameScoreRaw};
+    use crate::render::write_ps;
+    use std::path::PathBuf;
+
+    fn stats(offset: i32, errors: &[f64]) -> ErratStats {
+        let mut resnum = vec![0; 30];
+        for (i, r) in resnum.iter_mut().enumerate().take(21).skip(1) {
+            *r = i as i32;
+        }
+        let interactions = WindowInteractions {
+            counts: [0.0; 5],
+            total: 0.0,
+            fractions: [0.0; 5],
+            inter_chain_counts: [0.0; 5],
+        };
+        let scored_frames: Vec<FrameScoreRaw> = errors
+            .iter()
+            .enumerate()
+            .map(|(idx, &error_value)| FrameScoreRaw {
+                center_residue: 5 + idx as i32,
+                residue_offset: offset,
+                error_value,
+                interactions,
+            })
+            .collect();
+        let mut errat = vec![0.0; 40];
+        for frame in &scored_frames {
+            errat[frame.center_residue as usize] = frame.error_value;
+        }
+        let limit = Calibration::classic().limit_95;
+        let rejected = errors.iter().filter(|&&e| e > limit).count() as f64;
+        ErratStats {
+            stat: errors.len() as f64,
+            pstat: rejected,
+            avg_probability: None,
+            overall_quality_factor: Some(100.0 - 100.0 * rejected / errors.len() as f64),
+            errat,
+            resnum,
+            chain_id: vec![b'A'; 30],
+            atmnum: 20,
+            warning_frames: Vec::new(),
+            scored_frames,
+            interface_residues: Vec::new(),
+            experiment: Experiment::default(),
+            quality_percentile: None,
+            plddt: Vec::new(),
+            plddt_cutoff: None,
+            secondary: Vec::new(),
+            calibration: Calibration::classic(),
+        }
+    }
+
+    fn analysis(stats: &ErratStats) -> AnalysisResult {
+        build_analysis_result(
+            PathBuf::from("-"),
+            "demo".into(),
+            stats,
+            String::new(),
+            &AnalysisOptions::default(),
+        )
+    }
+
+    #[test]
+    fn frames_align_on_author_residue_numbers() {
+        let before_stats = stats(0, &[5.0, 20.0, 20.0, 5.0, 5.0]);
+        let after_stats = stats(-1, &[20.0, 5.0, 5.0, 15.0, 15.0, 5.0]);
+        let (before, after) = (analysis(&before_stats), analysis(&after_stats));
+
+        let deltas = align_frames((&before_stats, &before), (&after_stats, &after));
+        let residues: Vec<i32> = deltas.iter().map(|d| d.residue).collect();
+        assert_eq!(residues, vec![5, 6, 7, 8, 9, 10, 11]);
+        assert_eq!(deltas[0].after_error_value, None);
+        assert_eq!(deltas[1].delta, Some(0.0));
+        assert_eq!(deltas[2].delta, Some(-15.0));
+        assert_eq!(deltas[6].before_status, None);
+
+        let flagged = |status: FrameStatus| status != FrameStatus::Ok;
+        let cleared = changed_regions(&deltas, |b, a| flagged(b) && !flagged(a));
+        let newly = changed_regions(&deltas, |b, a| !flagged(b) && flagged(a));
+        assert_eq!(
+            cleared,
+            vec![ChangedRegion {
+                chain_id: "A".into(),
+                start_residue: 7,
+                end_residue: 7,
+                length: 1,
+            }]
+        );
+        assert_eq!((newly[0].start_residue, newly[0].length), (9, 1));
+        let (warned_stats, worse_stats) = (stats(0, &[12.0, 12.0]), stats(0, &[20.0, 12.0]));
+        let (warned, worse) = (analysis(&warned_stats), analysis(&worse_stats));
+        let severity = align_frames((&warned_stats, &warned), (&worse_stats, &worse));
+        let escalated = changed_regions(&severity, |b, a| {
+            b == FrameStatus::Warning95 && a == FrameStatus::Warning99
+        });
+        assert_eq!((escalated[0].start_residue, escalated[0].length), (5, 1));
+        assert!(changed_regions(&severity, |b, a| flagged(b) != flagged(a)).is_empty());
+
+        let overlay = build_overlay(&before_stats, &before, &after_stats, &after);
+        assert_eq!(overlay.errat.get(&5), Some(&20.0));
+        assert_eq!(overlay.errat.get(&10), None);
+        let mut ps = Vec::new();
+        write_ps(
+            &mut ps,
+            &mut io::sink(),
+            "a -> b",
+            &after_stats,
+            &[],
+            Some(&overlay),
+        )
+        .unwrap();
+        let ps = String::from_utf8(ps).unwrap();
+        assert!(ps.contains("0.5\t20.000 bar0\n1\t20.000 hbar3"));
+        assert!(ps.contains("(Overall quality factor**: 60.000 -> 50.000)show"));
+    }
+}
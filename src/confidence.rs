@@ -0,0 +1,162 @@
+use std::io::{self, Write};
+
+use crate::api::AnalysisOptions;
+use crate::model::{AtomData, ErratStats, FrameScoreRaw};
+
+pub(crate) fn residue_plddt(data: &AtomData) -> Vec<(i32, u8, f64)> {
+    let mut residues: Vec<(i32, u8, f64, usize)> = Vec::new();
+    for atom in 1..=data.atmnum {
+        let (resnum, chain_id) = (data.resnum[atom], data.chain_id[atom]);
+        match residues.last_mut() {
+            Some((r, c, sum, count)) if *r == resnum && *c == chain_id => {
+                *sum += data.b_factor[atom];
+                *count += 1;
+            }
+            _ => residues.push((resnum, chain_id, data.b_factor[atom], 1)),
+        }
+    }
+    let fractional = residues
+        .iter()
+        .all(|&(_, _, sum, count)| sum / count as f64 <= 1.0);
+    let scale = if fractional { 100.0 } else { 1.0 };
+    residues
+        .into_iter()
+        .map(|(resnum, chain_id, sum, count)| (resnum, chain_id, scale * sum / count as f64))
+        .collect()
+}
+
+pub(crate) fn window_plddt(stats: &ErratStats, frame: &FrameScoreRaw) -> Option<f64> {
+    let half = stats.calibration.half_window();
+    let start = stats
+        .plddt
+        .partition_point(|&(resnum, _, _)| resnum < frame.center_residue - half);
+    let values: Vec<f64> = stats.plddt[start..]
+        .iter()
+        .take_while(|&&(resnum, _, _)| resnum <= frame.center_residue + half)
+        .map(|&(_, _, plddt)| plddt)
+        .collect();
+    (!values.is_empty()).then(|| values.iter().sum::<f64>() / values.len() as f64)
+}
+
+pub(crate) fn is_low_confidence(stats: &ErratStats, frame: &FrameScoreRaw) -> bool {
+    stats
+        .plddt_cutoff
+        .is_some_and(|cutoff| window_plddt(stats, frame).is_some_and(|plddt| plddt < cutoff))
+}
+
+pub(crate) fn residue_confidence(stats: &ErratStats, resnum: i32) -> Option<f64> {
+    stats
+        .plddt
+        .binary_search_by_key(&resnum, |&(resnum, _, _)| resnum)
+        .ok()
+        .map(|idx| stats.plddt[idx].2)
+}
+
+pub(crate) fn mark_confidence<W: Write>(
+    data: &AtomData,
+    stats: &mut ErratStats,
+    options: &AnalysisOptions,
+    logw: &mut W,
+) -> io::Result<()> {
+    let Some(cutoff) = options.plddt_cutoff else {
+        return Ok(());
+    };
+    stats.plddt = residue_plddt(data);
+    stats.plddt_cutoff = Some(cutoff);
+    let low = stats
+        .plddt
+        .iter()
+        .filter(|&&(_, _, plddt)| plddt < cutoff)
+        .count();
+    let masked = stats
+        .scored_frames
+        .iter()
+        .filter(|frame| is_low_confidence(stats, frame))
+        .count();
+    writeln!(
+        logw,
+        "pLDDT cutoff {cutoff}: {low} of {} residues and {masked} of {} windows below cutoff",
+        stats.plddt.len(),
+        stats.scored_frames.len()
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{InputFormat, build_analysis_result, problem_regions};
+    use crate::fixtures::Ribbon;
+    use crate::parser::parse_reader;
+    use crate::render::write_svg;
+    use crate::stats::compute_errat;
+    use std::io::Cursor;
+    use std::path::PathBuf;
+
+    fn predicted_model(scale: f64) -> String {
+        Ribbon {
+            residues: 40,
+            b_factor: Box::new(move |residue| if residue <= 20 { 0.92 } else { 0.35 } * scale),
+            ..Ribbon::default()
+        }
+        .pdb()
+    }
+
+    #[test]
+    fn plddt_splits_windows_and_masks_regions() {
+        let options = AnalysisOptions {
+            plddt_cutoff: Some(70.0),
+            ..AnalysisOptions::default()
+        };
+        for scale in [100.0, 1.0] {
+            let data = parse_reader(
+                &mut Cursor::new(predicted_model(scale)),
+                InputFormat::Pdb,
+                &mut io::sink(),
+            )
+            .unwrap();
+            assert_eq!(data.b_factor[1], 0.92 * scale);
+
+            let mut stats = compute_errat(&data, &options.calibration, &mut io::sink()).unwrap();
+            let unmasked = problem_regions(&stats, 0);
+            let mut log = Vec::new();
+            mark_confidence(&data, &mut stats, &options, &mut log).unwrap();
+            let log = String::from_utf8(log).unwrap();
+            assert!(log.contains("pLDDT cutoff 70: 20 of 40 residues"));
+            assert_eq!(stats.plddt[0], (1, b'A', 92.0));
+            assert_eq!(residue_confidence(&stats, 30), Some(35.0));
+
+            let result = build_analysis_result(
+                PathBuf::from("-"),
+                "model".into(),
+                &stats,
+                String::new(),
+                &options,
+            );
+            let confidence = result.confidence.unwrap();
+            assert_eq!(confidence.residues.len(), 40);
+            assert!((confidence.mean_plddt.unwrap() - 63.5).abs() < 1e-9);
+            assert!(confidence.confident.scored_frame_count > 0);
+            assert!(confidence.low_confidence.scored_frame_count > 0);
+            assert_eq!(
+                confidence.confident.scored_frame_count
+                    + confidence.low_confidence.scored_frame_count,
+                result.scored_frame_count
+            );
+            assert!(
+                result
+                    .problem_regions
+                    .iter()
+                    .all(|region| region.end_residue <= 20 + stats.calibration.half_window())
+            );
+            assert!(unmasked.len() >= result.problem_regions.len());
+
+            let mut svg = Vec::new();
+            write_svg(&mut svg, &mut io::sink(), "model", &stats, &[], None).unwrap();
+            let svg = String::from_utf8(svg).unwrap();
+            assert!(svg.contains(">pLDDT</text>"));
+            assert!(svg.contains("fill=\"#0053d6\""));
+            assert!(svg.contains("fill=\"#ff7e45\""));
+        }
+    }
+}
@@ -0,0 +1,373 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use rayon::prelude::*;
+
+use crate::api::AnalysisOptions;
+use crate::model::ErratStats;
+use crate::pipeline::process_structure_data;
+use crate::resolution::decile_percentile;
+
+const SIZE_EDGES: [usize; 6] = [0, 100, 200, 400, 800, 1600];
+
+type Sample = (String, usize, f64);
+type BucketKey = (String, usize, Option<usize>);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CorpusBucket {
+    pub method: String,
+    pub min_residues: usize,
+    pub max_residues: Option<usize>,
+    pub structure_count: usize,
+    pub mean_quality_factor: f64,
+    pub sd_quality_factor: f64,
+    pub quality_factors: [f64; 11],
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CorpusReference {
+    pub buckets: Vec<CorpusBucket>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CorpusScore {
+    pub method: String,
+    pub residue_count: usize,
+    pub min_residues: usize,
+    pub max_residues: Option<usize>,
+    pub structure_count: usize,
+    pub mean_quality_factor: f64,
+    pub z_score: Option<f64>,
+    pub percentile: f64,
+}
+
+#[derive(Clone, Debug)]
+pub struct CorpusReport {
+    pub reference: CorpusReference,
+    pub structure_count: usize,
+    pub skipped: Vec<String>,
+}
+
+pub(crate) fn method_class(method: Option<&str>) -> &'static str {
+    let Some(method) = method else {
+        return "unknown";
+    };
+    let method = method.to_ascii_uppercase();
+    if method.contains("X-RAY") {
+        "x-ray"
+    } else if method.contains("ELECTRON MICROSCOPY") || method.contains("CRYO") {
+        "em"
+    } else if method.contains("NMR") {
+        "nmr"
+    } else {
+        "other"
+    }
+}
+
+pub(crate) fn residue_count(stats: &ErratStats) -> usize {
+    (1..=stats.atmnum)
+        .filter(|&i| {
+            i == 1
+                || stats.resnum[i] != stats.resnum[i - 1]
+                || stats.chain_id[i] != stats.chain_id[i - 1]
+        })
+        .count()
+}
+
+fn size_range(residues: usize) -> (usize, Option<usize>) {
+    let k = SIZE_EDGES
+        .iter()
+        .rposition(|&edge| residues >= edge)
+        .unwrap_or(0);
+    (SIZE_EDGES[k], SIZE_EDGES.get(k + 1).copied())
+}
+
+fn reference_error(line: usize, message: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("reference file line {line}: {message}"),
+    )
+}
+
+impl CorpusReference {
+    pub fn from_samples(samples: &[Sample]) -> Self {
+        let mut groups: Vec<(BucketKey, Vec<f64>)> = Vec::new();
+        for (method, residues, quality) in samples {
+            let (min, max) = size_range(*residues);
+            let key = (method.clone(), min, max);
+            match groups.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, values)) => values.push(*quality),
+                None => groups.push((key, vec![*quality])),
+            }
+        }
+        groups.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let buckets = groups
+            .into_iter()
+            .map(|((method, min_residues, max_residues), mut values)| {
+                values.sort_by(f64::total_cmp);
+                let n = values.len() as f64;
+                let mean = values.iter().sum::<f64>() / n;
+                let sd = if values.len() > 1 {
+                    (values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1.0)).sqrt()
+                } else {
+                    0.0
+                };
+                let quality_factors = std::array::from_fn(|k| {
+                    let position = k as f64 / 10.0 * (n - 1.0);
+                    let low = position.floor() as usize;
+                    let high = position.ceil() as usize;
+                    values[low] + (values[high] - values[low]) * (position - low as f64)
+                });
+                CorpusBucket {
+                    method,
+                    min_residues,
+                    max_residues,
+                    structure_count: values.len(),
+                    mean_quality_factor: mean,
+                    sd_quality_factor: sd,
+                    quality_factors,
+                }
+            })
+            .collect();
+        CorpusReference { buckets }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = File::open(path)?;
+        CorpusReference::read_from(BufReader::new(file))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        self.write_to(&mut out)?;
+        out.flush()
+    }
+
+    pub fn read_from<R: BufRead>(reader: R) -> io::Result<Self> {
+        let mut buckets = Vec::new();
+        for (idx, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line_no = idx + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields[0] != "bucket" {
+                return Err(reference_error(
+                    line_no,
+                    &format!("unknown key {}", fields[0]),
+                ));
+            }
+            if fields.len() != 18 {
+                return Err(reference_error(
+                    line_no,
+                    &format!("expected 17 values, found {}", fields.len() - 1),
+                ));
+            }
+            let count = |field: &str| {
+                field
+                    .parse::<usize>()
+                    .map_err(|_| reference_error(line_no, &format!("invalid count {field}")))
+            };
+            let number = |field: &str| {
+                field
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|v| v.is_finite())
+                    .ok_or_else(|| reference_error(line_no, &format!("invalid number {field}")))
+            };
+            let mut quality_factors = [0.0f64; 11];
+            for (value, field) in quality_factors.iter_mut().zip(&fields[7..]) {
+                *value = number(field)?;
+            }
+            buckets.push(CorpusBucket {
+                method: fields[1].to_string(),
+                min_residues: count(fields[2])?,
+                max_residues: match fields[3] {
+                    "inf" => None,
+                    field => Some(count(field)?),
+                },
+                structure_count: count(fields[4])?,
+                mean_quality_factor: number(fields[5])?,
+                sd_quality_factor: number(fields[6])?,
+                quality_factors,
+            });
+        }
+        let reference = CorpusReference { buckets };
+        reference.validate()?;
+        Ok(reference)
+    }
+
+    pub fn validate(&self) -> io::Result<()> {
+        let invalid = |message: &str| {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("reference {message}"),
+            ))
+        };
+        if self.buckets.iter().any(|bucket| {
+            bucket.structure_count == 0
+                || bucket.sd_quality_factor < 0.0
+                || bucket
+                    .max_residues
+                    .is_some_and(|max| max <= bucket.min_residues)
+                || bucket
+                    .quality_factors
+                    .windows(2)
+                    .any(|pair| pair[0] > pair[1])
+        }) {
+            return invalid(
+                "buckets need structures, a non-negative sd, a valid size range and non-decreasing quality factors",
+            );
+        }
+        Ok(())
+    }
+
+    pub fn write_to<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        writeln!(
+            out,
+            "# method min_residues max_residues structures mean sd quality factor at percentiles 0 10 ... 100"
+        )?;
+        for bucket in &self.buckets {
+            let values = bucket
+                .quality_factors
+                .iter()
+                .map(|v| v.to_string())
+                .collect::<Vec<_>>()
+                .join(" ");
+            writeln!(
+                out,
+                "bucket {} {} {} {} {} {} {values}",
+                bucket.method,
+                bucket.min_residues,
+                bucket
+                    .max_residues
+                    .map_or_else(|| "inf".to_string(), |max| max.to_string()),
+                bucket.structure_count,
+                bucket.mean_quality_factor,
+                bucket.sd_quality_factor
+            )?;
+        }
+        Ok(())
+    }
+
+    pub fn bucket(&self, method: &str, residues: usize) -> Option<&CorpusBucket> {
+        self.buckets.iter().find(|bucket| {
+            bucket.method == method
+                && residues >= bucket.min_residues
+                && bucket.max_residues.is_none_or(|max| residues < max)
+        })
+    }
+
+    pub(crate) fn score(&self, stats: &ErratStats) -> Option<CorpusScore> {
+        let quality = stats.overall_quality_factor?;
+        let method = method_class(stats.experiment.method.as_deref());
+        let residues = residue_count(stats);
+        let bucket = self.bucket(method, residues)?;
+        Some(CorpusScore {
+            method: method.to_string(),
+            residue_count: residues,
+            min_residues: bucket.min_residues,
+            max_residues: bucket.max_residues,
+            structure_count: bucket.structure_count,
+            mean_quality_factor: bucket.mean_quality_factor,
+            z_score: (bucket.sd_quality_factor > 0.0)
+                .then(|| (quality - bucket.mean_quality_factor) / bucket.sd_quality_factor),
+            percentile: decile_percentile(&bucket.quality_factors, quality),
+        })
+    }
+}
+
+fn structure_sample(
+    path: &PathBuf,
+    options: &AnalysisOptions,
+    use_mmap: bool,
+) -> io::Result<Sample> {
+    let (stats, _) = process_structure_data(path, use_mmap, options)?;
+    let quality = stats
+        .overall_quality_factor
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "no scored windows"))?;
+    Ok((
+        method_class(stats.experiment.method.as_deref()).to_string(),
+        residue_count(&stats),
+        quality,
+    ))
+}
+
+pub fn build_corpus_reference(
+    inputs: &[PathBuf],
+    options: &AnalysisOptions,
+    use_mmap: bool,
+) -> io::Result<CorpusReport> {
+    options.validate()?;
+    let results: Vec<(&PathBuf, io::Result<Sample>)> = inputs
+        .par_iter()
+        .map(|path| (path, structure_sample(path, options, use_mmap)))
+        .collect();
+
+    let mut samples = Vec::new();
+    let mut skipped = Vec::new();
+    for (path, result) in results {
+        match result {
+            Ok(sample) => samples.push(sample),
+            Err(err) => skipped.push(format!("{}: {err}", path.display())),
+        }
+    }
+    if samples.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "reference needs at least one scored structure",
+        ));
+    }
+    Ok(CorpusReport {
+        reference: CorpusReference::from_samples(&samples),
+        structure_count: samples.len(),
+        skipped,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn reference_buckets_round_trip_and_score() {
+        let mut samples = Vec::new();
+        for k in 0..11 {
+            samples.push(("em".to_string(), 650, 80.0 + k as f64));
+        }
+        samples.push(("x-ray".to_string(), 120, 96.0));
+        samples.push(("x-ray".to_string(), 2000, 93.0));
+        let reference = CorpusReference::from_samples(&samples);
+        assert_eq!(reference.buckets.len(), 3);
+
+        let em = reference.bucket("em", 600).unwrap();
+        assert_eq!((em.min_residues, em.max_residues), (400, Some(800)));
+        assert_eq!(em.structure_count, 11);
+        assert_eq!(em.mean_quality_factor, 85.0);
+        assert_eq!(em.quality_factors[3], 83.0);
+        assert!(reference.bucket("em", 900).is_none());
+        assert_eq!(reference.bucket("x-ray", 5000).unwrap().max_residues, None);
+
+        let mut text = Vec::new();
+        reference.write_to(&mut text).unwrap();
+        let reread = CorpusReference::read_from(Cursor::new(text)).unwrap();
+        assert_eq!(reread, reference);
+
+        let err = CorpusReference::read_from(Cursor::new("bucket em 400 800 1\n")).unwrap_err();
+        assert!(err.to_string().contains("line 1"));
+    }
+
+    #[test]
+    fn method_classes_cover_common_techniques() {
+        assert_eq!(method_class(Some("X-RAY DIFFRACTION")), "x-ray");
+        assert_eq!(method_class(Some("ELECTRON MICROSCOPY")), "em");
+        assert_eq!(method_class(Some("SOLUTION NMR")), "nmr");
+        assert_eq!(method_class(Some("NEUTRON DIFFRACTION")), "other");
+        assert_eq!(method_class(None), "unknown");
+    }
+}
@@ -0,0 +1,599 @@
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+use crate::api::{
+    AnalysisOptions, FrameStatus, InputFormat, WindowInteractions, chain_label, classify_frame,
+    frame_status_name,
+};
+use crate::export::{json_interactions, json_number, json_string};
+use crate::model::AtomData;
+use crate::parser::{parse_reader, parse_structure};
+use crate::stats::{
+    WindowOutcome, build_grid, compute_window, for_each_contact, window_end, window_starts,
+};
+use crate::symmetry::add_environment_atoms;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ContactClass {
+    CarbonCarbon,
+    CarbonNitrogen,
+    CarbonOxygen,
+    NitrogenNitrogen,
+    NitrogenOxygen,
+    OxygenOxygen,
+}
+
+impl ContactClass {
+    pub const SCORED: [ContactClass; 5] = [
+        ContactClass::CarbonCarbon,
+        ContactClass::CarbonNitrogen,
+        ContactClass::CarbonOxygen,
+        ContactClass::NitrogenNitrogen,
+        ContactClass::NitrogenOxygen,
+    ];
+
+    fn from_codes(a: i32, b: i32) -> Option<Self> {
+        match (a.min(b), a.max(b)) {
+            (1, 1) => Some(ContactClass::CarbonCarbon),
+            (1, 2) => Some(ContactClass::CarbonNitrogen),
+            (1, 3) => Some(ContactClass::CarbonOxygen),
+            (2, 2) => Some(ContactClass::NitrogenNitrogen),
+            (2, 3) => Some(ContactClass::NitrogenOxygen),
+            (3, 3) => Some(ContactClass::OxygenOxygen),
+            _ => None,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ContactClass::CarbonCarbon => "CC",
+            ContactClass::CarbonNitrogen => "CN",
+            ContactClass::CarbonOxygen => "CO",
+            ContactClass::NitrogenNitrogen => "NN",
+            ContactClass::NitrogenOxygen => "NO",
+            ContactClass::OxygenOxygen => "OO",
+        }
+    }
+
+    fn color(self) -> &'static str {
+        match self {
+            ContactClass::CarbonCarbon => "gray",
+            ContactClass::CarbonNitrogen => "blue",
+            ContactClass::CarbonOxygen => "red",
+            ContactClass::NitrogenNitrogen => "cyan",
+            ContactClass::NitrogenOxygen => "magenta",
+            ContactClass::OxygenOxygen => "orange",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AtomRef {
+    pub chain_id: String,
+    pub residue_number: i32,
+    pub residue_name: String,
+    pub atom_name: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ContactPair {
+    pub class: ContactClass,
+    pub window_atom: AtomRef,
+    pub partner_atom: AtomRef,
+    pub distance: f64,
+    pub weight: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ComponentContribution {
+    pub class: ContactClass,
+    pub fraction: f64,
+    pub expected: f64,
+    pub contribution: f64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WindowCenter {
+    pub chain_id: String,
+    pub residue: i32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct WindowExplanation {
+    pub chain_id: String,
+    pub center_residue: i32,
+    pub error_value: f64,
+    pub status: FrameStatus,
+    pub interactions: WindowInteractions,
+    pub components: Vec<ComponentContribution>,
+    pub drivers: Vec<ContactClass>,
+    pub contacts: Vec<ContactPair>,
+}
+
+fn atom_ref(data: &AtomData, idx: usize) -> AtomRef {
+    let text = |bytes: &[u8]| String::from_utf8_lossy(bytes.trim_ascii()).into_owned();
+    AtomRef {
+        chain_id: chain_label(data.chain_id[idx]),
+        residue_number: data.res_seq[idx],
+        residue_name: text(&data.res_name[idx]),
+        atom_name: text(&data.atom_name[idx]),
+    }
+}
+
+fn drivers(components: &[ComponentContribution], excess: f64) -> Vec<ContactClass> {
+    let mut ranked: Vec<&ComponentContribution> = components
+        .iter()
+        .filter(|component| component.contribution > 0.0)
+        .collect();
+    ranked.sort_by(|a, b| b.contribution.total_cmp(&a.contribution));
+    let mut removed = 0.0;
+    let mut out = Vec::new();
+    for component in ranked {
+        if removed >= excess {
+            break;
+        }
+        removed += component.contribution;
+        out.push(component.class);
+    }
+    out
+}
+
+pub(crate) fn explain_atoms(
+    data: &AtomData,
+    centers: &[WindowCenter],
+    options: &AnalysisOptions,
+) -> io::Result<Vec<WindowExplanation>> {
+    options.validate()?;
+    let calibration = &options.calibration;
+    let Some(grid) = build_grid(data, calibration, &mut io::sink())? else {
+        return Ok(Vec::new());
+    };
+
+    let mut explanations = Vec::new();
+    for i in window_starts(data) {
+        let chain_id = chain_label(data.chain_id[i]);
+        let center_residue = data.res_seq[i] + calibration.half_window();
+        if !centers.is_empty()
+            && !centers
+                .iter()
+                .any(|center| center.chain_id == chain_id && center.residue == center_residue)
+        {
+            continue;
+        }
+        let (Some(v), Some(WindowOutcome::Value { interactions, .. })) = (
+            window_end(i, data, calibration),
+            compute_window(i, data, calibration, &grid),
+        ) else {
+            continue;
+        };
+        let error_value = calibration.score(&interactions.fractions);
+        let status = classify_frame(error_value, calibration);
+        if centers.is_empty() && status == FrameStatus::Ok {
+            continue;
+        }
+
+        let contributions = calibration.contributions(&interactions.fractions);
+        let components: Vec<ComponentContribution> = ContactClass::SCORED
+            .iter()
+            .enumerate()
+            .map(|(k, &class)| ComponentContribution {
+                class,
+                fraction: interactions.fractions[k],
+                expected: calibration.mean[k],
+                contribution: contributions[k],
+            })
+            .collect();
+        let limit = match status {
+            FrameStatus::Warning99 => Some(calibration.limit_99),
+            FrameStatus::Warning95 => Some(calibration.limit_95),
+            FrameStatus::Ok => None,
+        };
+        let drivers = limit
+            .map(|limit| drivers(&components, error_value - limit))
+            .unwrap_or_default();
+
+        let mut contacts = Vec::new();
+        for_each_contact(i, v, data, calibration, &grid, |rer, n, dsq, weight| {
+            if let Some(class) = ContactClass::from_codes(data.name[rer], data.name[n]) {
+                contacts.push(ContactPair {
+                    class,
+                    window_atom: atom_ref(data, rer),
+                    partner_atom: atom_ref(data, n),
+                    distance: dsq.sqrt(),
+                    weight,
+                });
+            }
+        });
+
+        explanations.push(WindowExplanation {
+            chain_id,
+            center_residue,
+            error_value,
+            status,
+            interactions,
+            components,
+            drivers,
+            contacts,
+        });
+    }
+
+    if let Some(missing) = centers.iter().find(|center| {
+        !explanations
+            .iter()
+            .any(|e| e.chain_id == center.chain_id && e.center_residue == center.residue)
+    }) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "no scored window is centred on chain {:?} residue {}",
+                missing.chain_id, missing.residue
+            ),
+        ));
+    }
+    Ok(explanations)
+}
+
+pub fn explain_file<P: AsRef<Path>>(
+    input: P,
+    use_mmap: bool,
+    centers: &[WindowCenter],
+    options: &AnalysisOptions,
+) -> io::Result<Vec<WindowExplanation>> {
+    let mut data = parse_structure(&input.as_ref().to_path_buf(), &mut io::sink(), use_mmap)?;
+    add_environment_atoms(&mut data, options, &mut io::sink())?;
+    explain_atoms(&data, centers, options)
+}
+
+pub fn explain_reader<R: BufRead>(
+    mut reader: R,
+    format: InputFormat,
+    centers: &[WindowCenter],
+    options: &AnalysisOptions,
+) -> io::Result<Vec<WindowExplanation>> {
+    let mut data = parse_reader(&mut reader, format, &mut io::sink())?;
+    add_environment_atoms(&mut data, options, &mut io::sink())?;
+    explain_atoms(&data, centers, options)
+}
+
+fn json_atom(atom: &AtomRef) -> String {
+    format!(
+        "{{\"chain_id\": {}, \"residue_number\": {}, \"residue_name\": {}, \"atom_name\": {}}}",
+        json_string(&atom.chain_id),
+        atom.residue_number,
+        json_string(&atom.residue_name),
+        json_string(&atom.atom_name)
+    )
+}
+
+pub fn write_explanations_json<W: Write>(
+    out: &mut W,
+    explanations: &[WindowExplanation],
+) -> io::Result<()> {
+    writeln!(out, "[")?;
+    for (idx, window) in explanations.iter().enumerate() {
+        writeln!(out, "  {{")?;
+        writeln!(
+            out,
+            "    \"chain_id\": {}, \"center_residue\": {}, \"error_value\": {}, \"status\": \"{}\"{},",
+            json_string(&window.chain_id),
+            window.center_residue,
+            json_number(window.error_value),
+            frame_status_name(window.status),
+            json_interactions(&window.interactions)
+        )?;
+        let components = window
+            .components
+            .iter()
+            .map(|component| {
+                format!(
+                    "{{\"class\": \"{}\", \"fraction\": {}, \"expected\": {}, \"contribution\": {}}}",
+                    component.class.label(),
+                    json_number(component.fraction),
+                    json_number(component.expected),
+                    json_number(component.contribution)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        writeln!(out, "    \"components\": [{components}],")?;
+        let drivers = window
+            .drivers
+            .iter()
+            .map(|class| format!("\"{}\"", class.label()))
+            .collect::<Vec<_>>()
+            .join(", ");
+        writeln!(out, "    \"drivers\": [{drivers}],")?;
+        writeln!(out, "    \"contacts\": [")?;
+        for (cidx, contact) in window.contacts.iter().enumerate() {
+            let sep = if cidx + 1 < window.contacts.len() {
+                ","
+            } else {
+                ""
+            };
+            writeln!(
+                out,
+                "      {{\"class\": \"{}\", \"window_atom\": {}, \"partner_atom\": {}, \"distance\": {}, \"weight\": {}}}{sep}",
+                contact.class.label(),
+                json_atom(&contact.window_atom),
+                json_atom(&contact.partner_atom),
+                json_number(contact.distance),
+                json_number(contact.weight)
+            )?;
+        }
+        writeln!(out, "    ]")?;
+        let sep = if idx + 1 < explanations.len() {
+            ","
+        } else {
+            ""
+        };
+        writeln!(out, "  }}{sep}")?;
+    }
+    writeln!(out, "]")?;
+    Ok(())
+}
+
+fn chimerax_atom(atom: &AtomRef) -> String {
+    if atom.chain_id.is_empty() {
+        format!(":{}@{}", atom.residue_number, atom.atom_name)
+    } else {
+        format!(
+            "/{}:{}@{}",
+            atom.chain_id, atom.residue_number, atom.atom_name
+        )
+    }
+}
+
+pub fn write_chimerax_pseudobonds<W: Write>(
+    out: &mut W,
+    explanations: &[WindowExplanation],
+) -> io::Result<()> {
+    writeln!(out, "; halfbond = false")?;
+    writeln!(out, "; radius = 0.05")?;
+    writeln!(out, "; dashes = 6")?;
+    for window in explanations {
+        for contact in &window.contacts {
+            writeln!(
+                out,
+                "{} {} {}",
+                chimerax_atom(&contact.window_atom),
+                chimerax_atom(&contact.partner_atom),
+                contact.class.color()
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn pymol_atom(atom: &AtomRef) -> String {
+    let resi = if atom.residue_number < 0 {
+        format!("\\{}", atom.residue_number)
+    } else {
+        atom.residue_number.to_string()
+    };
+    let chain = if atom.chain_id.is_empty() {
+        "\"\"".to_string()
+    } else {
+        atom.chain_id.clone()
+    };
+    format!(
+        "(chain {chain} and resi {resi} and name {})",
+        atom.atom_name
+    )
+}
+
+pub fn write_pymol_script<W: Write>(
+    out: &mut W,
+    explanations: &[WindowExplanation],
+) -> io::Result<()> {
+    for window in explanations {
+        let chain = if window.chain_id.is_empty() {
+            "_".to_string()
+        } else {
+            window.chain_id.clone()
+        };
+        let group = format!("errat_{chain}{}", window.center_residue);
+        for (idx, contact) in window.contacts.iter().enumerate() {
+            let name = format!("{group}_{}_{}", contact.class.label(), idx + 1);
+            writeln!(
+                out,
+                "distance {name}, {}, {}",
+                pymol_atom(&contact.window_atom),
+                pymol_atom(&contact.partner_atom)
+            )?;
+            writeln!(out, "color {}, {name}", contact.class.color())?;
+        }
+        if !window.contacts.is_empty() {
+            writeln!(out, "group {group}, {group}_*")?;
+        }
+    }
+    writeln!(out, "hide labels, errat_*")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::Ribbon;
+    use std::io::Cursor;
+
+    fn ribbon_pdb() -> String {
+        ribbon_chains(&[('A', 0.0)])
+    }
+
+    fn ribbon_chains(chains: &[(char, f64)]) -> String {
+        let mut pdb = String::new();
+        for &(chain_id, shift) in chains {
+            Ribbon {
+                chain_id,
+                residues: 40,
+                offset: [shift, 0.0],
+                scale: 0.9,
+                ..Ribbon::default()
+            }
+            .push_to(&mut pdb);
+        }
+        pdb
+    }
+
+    fn center(chain_id: &str, residue: i32) -> WindowCenter {
+        WindowCenter {
+            chain_id: chain_id.to_string(),
+            residue,
+        }
+    }
+
+    fn explain(centers: &[WindowCenter]) -> Vec<WindowExplanation> {
+        explain_reader(
+            Cursor::new(ribbon_pdb()),
+            InputFormat::Pdb,
+            centers,
+            &AnalysisOptions::default(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn explanations_decompose_score_and_contacts() {
+        let windows = explain(&[center("A", 5), center("A", 6)]);
+        assert_eq!(windows.len(), 2);
+        for window in &windows {
+            let total: f64 = window.components.iter().map(|c| c.contribution).sum();
+            assert!((total - window.error_value).abs() < 1e-6 * window.error_value.max(1.0));
+            for (k, class) in ContactClass::SCORED.iter().enumerate() {
+                let weight: f64 = window
+                    .contacts
+                    .iter()
+                    .filter(|contact| contact.class == *class)
+                    .map(|contact| contact.weight)
+                    .sum();
+                assert!((weight - window.interactions.counts[k]).abs() < 1e-9);
+            }
+            assert_eq!(window.drivers.is_empty(), window.status == FrameStatus::Ok);
+        }
+        let flagged = explain(&[]);
+        assert!(!flagged.is_empty());
+        let calibration = AnalysisOptions::default().calibration;
+        for window in flagged {
+            assert_ne!(window.status, FrameStatus::Ok);
+            let removed: f64 = window
+                .components
+                .iter()
+                .filter(|c| window.drivers.contains(&c.class))
+                .map(|c| c.contribution)
+                .sum();
+            assert!(window.error_value - removed <= calibration.limit_99);
+        }
+    }
+
+    #[test]
+    fn shorter_windows_shift_centers() {
+        let mut options = AnalysisOptions {
+            window_length: Some(7),
+            ..AnalysisOptions::default()
+        };
+        options.calibration.window_length = 7;
+        let windows =
+            explain_reader(Cursor::new(ribbon_pdb()), InputFormat::Pdb, &[], &options).unwrap();
+        assert_eq!(windows.first().map(|w| w.center_residue), Some(4));
+        assert_eq!(windows.last().map(|w| w.center_residue), Some(37));
+        let windows = explain_reader(
+            Cursor::new(ribbon_pdb()),
+            InputFormat::Pdb,
+            &[center("A", 10)],
+            &options,
+        )
+        .unwrap();
+        let residues = windows[0]
+            .contacts
+            .iter()
+            .map(|c| c.window_atom.residue_number);
+        assert_eq!(residues.clone().min(), Some(7));
+        assert_eq!(residues.max(), Some(13));
+    }
+
+    #[test]
+    fn explain_rejects_unscored_window() {
+        let err = explain_reader(
+            Cursor::new(ribbon_pdb()),
+            InputFormat::Pdb,
+            &[center("A", 500)],
+            &AnalysisOptions::default(),
+        )
+        .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn windows_use_chain_and_author_numbering() {
+        let pdb = ribbon_chains(&[('A', 0.0), ('B', 100.0)]);
+        let explain = |centers: &[WindowCenter]| {
+            explain_reader(
+                Cursor::new(pdb.clone()),
+                InputFormat::Pdb,
+                centers,
+                &AnalysisOptions::default(),
+            )
+        };
+        let flagged = explain(&[]).unwrap();
+        assert!(flagged.iter().any(|window| window.chain_id == "B"));
+        assert!(flagged.iter().all(|window| window.center_residue <= 40));
+
+        let windows = explain(&[center("B", 5)]).unwrap();
+        assert_eq!(windows.len(), 1);
+        assert_eq!(
+            (windows[0].chain_id.as_str(), windows[0].center_residue),
+            ("B", 5)
+        );
+        assert!(
+            windows[0]
+                .contacts
+                .iter()
+                .all(|contact| contact.window_atom.chain_id == "B")
+        );
+        let err = explain(&[center("C", 5)]).unwrap_err();
+        assert!(err.to_string().contains("chain \"C\" residue 5"));
+    }
+
+    #[test]
+    fn pseudobond_scripts_name_atoms() {
+        let atom = |chain_id: &str, residue_number, atom_name: &str| AtomRef {
+            chain_id: chain_id.to_string(),
+            residue_number,
+            residue_name: "ALA".to_string(),
+            atom_name: atom_name.to_string(),
+        };
+        let window = WindowExplanation {
+            chain_id: "A".to_string(),
+            center_residue: 12,
+            error_value: 20.0,
+            status: FrameStatus::Warning99,
+            interactions: WindowInteractions {
+                counts: [0.0; 5],
+                total: 0.0,
+                fractions: [0.0; 5],
+                inter_chain_counts: [0.0; 5],
+            },
+            components: Vec::new(),
+            drivers: vec![ContactClass::CarbonOxygen],
+            contacts: vec![ContactPair {
+                class: ContactClass::CarbonOxygen,
+                window_atom: atom("A", 12, "CA"),
+                partner_atom: atom("", -3, "O"),
+                distance: 3.4,
+                weight: 0.7,
+            }],
+        };
+        let mut chimerax = Vec::new();
+        write_chimerax_pseudobonds(&mut chimerax, std::slice::from_ref(&window)).unwrap();
+        let chimerax = String::from_utf8(chimerax).unwrap();
+        assert!(chimerax.contains("/A:12@CA :-3@O red\n"));
+
+        let mut pymol = Vec::new();
+        write_pymol_script(&mut pymol, &[window]).unwrap();
+        let pymol = String::from_utf8(pymol).unwrap();
+        assert!(pymol.contains(
+            "distance errat_A12_CO_1, (chain A and resi 12 and name CA), (chain \"\" and resi \\-3 and name O)\n"
+        ));
+        assert!(pymol.contains("group errat_A12, errat_A12_*\n"));
+    }
+}
@@ -0,0 +1,727 @@
+use std::io::{self, Write};
+
+use crate::api::{
+    AnalysisResult, ConfidenceGroup, FrameStatus, ProblemRegion, WindowInteractions,
+    frame_status_name,
+};
+
+pub(crate) fn json_string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for ch in value.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+pub(crate) fn json_number(value: f64) -> String {
+    if value.is_finite() {
+        format!("{value}")
+    } else {
+        "null".to_string()
+    }
+}
+
+pub(crate) fn json_optional(value: Option<f64>) -> String {
+    value.map_or_else(|| "null".to_string(), json_number)
+}
+
+pub(crate) fn json_array(values: &[f64]) -> String {
+    let items = values
+        .iter()
+        .map(|value| json_number(*value))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!("[{items}]")
+}
+
+pub(crate) fn json_interactions(interactions: &WindowInteractions) -> String {
+    format!(
+        ", \"interactions\": {{\"counts\": {}, \"total\": {}, \"fractions\": {}, \"inter_chain_counts\": {}}}",
+        json_array(&interactions.counts),
+        json_number(interactions.total),
+        json_array(&interactions.fractions),
+        json_array(&interactions.inter_chain_counts)
+    )
+}
+
+fn json_tally(
+    scored: usize,
+    rejected: usize,
+    quality_factor: Option<f64>,
+    average_error_value: Option<f64>,
+) -> String {
+    format!(
+        "\"scored_frame_count\": {scored}, \"rejected_frame_count\": {rejected}, \"quality_factor\": {}, \"average_error_value\": {}",
+        json_optional(quality_factor),
+        json_optional(average_error_value)
+    )
+}
+
+pub(crate) fn write_problem_regions<W: Write>(
+    logw: &mut W,
+    regions: &[ProblemRegion],
+) -> io::Result<()> {
+    for region in regions {
+        let level = match region.severity {
+            FrameStatus::Warning99 => 99,
+            _ => 95,
+        };
+        writeln!(
+            logw,
+            "# Problem region {}:    Residue range {} to {}    Length {}    Peak {:.3}    Mean {:.3}    {}%",
+            region.chain_id,
+            region.start_residue,
+            region.end_residue,
+            region.length,
+            region.peak_error_value,
+            region.mean_error_value,
+            level
+        )?;
+    }
+    Ok(())
+}
+
+pub(crate) fn write_json<W: Write>(out: &mut W, analysis: &AnalysisResult) -> io::Result<()> {
+    writeln!(out, "{{")?;
+    writeln!(
+        out,
+        "  \"protein_id\": {},",
+        json_string(&analysis.protein_id)
+    )?;
+    writeln!(
+        out,
+        "  \"input_path\": {},",
+        json_string(&analysis.input_path.to_string_lossy())
+    )?;
+    writeln!(
+        out,
+        "  \"scored_frame_count\": {},",
+        analysis.scored_frame_count
+    )?;
+    writeln!(
+        out,
+        "  \"rejected_frame_count\": {},",
+        analysis.rejected_frame_count
+    )?;
+    writeln!(
+        out,
+        "  \"rejected_frame_ratio\": {},",
+        json_optional(analysis.rejected_frame_ratio)
+    )?;
+    writeln!(
+        out,
+        "  \"overall_quality_factor\": {},",
+        json_optional(analysis.overall_quality_factor)
+    )?;
+    writeln!(
+        out,
+        "  \"average_error_value\": {},",
+        json_optional(analysis.average_error_value)
+    )?;
+    writeln!(
+        out,
+        "  \"average_probability\": {},",
+        json_optional(analysis.average_error_value)
+    )?;
+    writeln!(
+        out,
+        "  \"experimental_method\": {},",
+        analysis
+            .experimental_method
+            .as_deref()
+            .map_or_else(|| "null".to_string(), json_string)
+    )?;
+    writeln!(
+        out,
+        "  \"resolution\": {},",
+        json_optional(analysis.resolution)
+    )?;
+    writeln!(
+        out,
+        "  \"quality_percentile\": {},",
+        json_optional(analysis.quality_percentile)
+    )?;
+    match &analysis.corpus_score {
+        Some(score) => writeln!(
+            out,
+            "  \"corpus_score\": {{\"method\": {}, \"residue_count\": {}, \"min_residues\": {}, \"max_residues\": {}, \"structure_count\": {}, \"mean_quality_factor\": {}, \"z_score\": {}, \"percentile\": {}}},",
+            json_string(&score.method),
+            score.residue_count,
+            score.min_residues,
+            score
+                .max_residues
+                .map_or_else(|| "null".to_string(), |max| max.to_string()),
+            score.structure_count,
+            json_number(score.mean_quality_factor),
+            json_optional(score.z_score),
+            json_number(score.percentile)
+        )?,
+        None => writeln!(out, "  \"corpus_score\": null,")?,
+    }
+    let below = analysis
+        .below_interaction_limit_frames
+        .iter()
+        .map(|frame| frame.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    writeln!(out, "  \"below_interaction_limit_frames\": [{below}],")?;
+
+    writeln!(out, "  \"chain_summaries\": [")?;
+    for (idx, chain) in analysis.chain_summaries.iter().enumerate() {
+        let sep = if idx + 1 < analysis.chain_summaries.len() {
+            ","
+        } else {
+            ""
+        };
+        writeln!(
+            out,
+            "    {{\"chain_id\": {}, \"start_residue\": {}, \"end_residue\": {}, \"min_p_value\": {}, {}}}{sep}",
+            json_string(&chain.chain_id),
+            chain.start_residue,
+            chain.end_residue,
+            json_optional(chain.min_p_value),
+            json_tally(
+                chain.scored_frame_count,
+                chain.rejected_frame_count,
+                chain.quality_factor,
+                chain.average_error_value
+            )
+        )?;
+    }
+    writeln!(out, "  ],")?;
+
+    writeln!(out, "  \"domain_summaries\": [")?;
+    for (idx, domain) in analysis.domain_summaries.iter().enumerate() {
+        let sep = if idx + 1 < analysis.domain_summaries.len() {
+            ","
+        } else {
+            ""
+        };
+        writeln!(
+            out,
+            "    {{\"name\": {}, \"chain_id\": {}, \"start_residue\": {}, \"end_residue\": {}, {}}}{sep}",
+            json_string(&domain.name),
+            json_string(&domain.chain_id),
+            domain.start_residue,
+            domain.end_residue,
+            json_tally(
+                domain.scored_frame_count,
+                domain.rejected_frame_count,
+                domain.quality_factor,
+                domain.average_error_value
+            )
+        )?;
+    }
+    writeln!(out, "  ],")?;
+
+    match &analysis.interface {
+        Some(interface) => {
+            let residues = interface
+                .residues
+                .iter()
+                .map(|residue| {
+                    format!(
+                        "{{\"chain_id\": {}, \"residue\": {}}}",
+                        json_string(&residue.chain_id),
+                        residue.residue
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            writeln!(
+                out,
+                "  \"interface\": {{\"distance\": {}, \"residues\": [{residues}], {}, \"inter_chain_fraction\": {}}},",
+                json_number(interface.distance),
+                json_tally(
+                    interface.scored_frame_count,
+                    interface.rejected_frame_count,
+                    interface.quality_factor,
+                    interface.average_error_value
+                ),
+                json_optional(interface.inter_chain_fraction)
+            )?;
+        }
+        None => writeln!(out, "  \"interface\": null,")?,
+    }
+    writeln!(
+        out,
+        "  \"hetero_environment\": {},",
+        analysis
+            .hetero_environment
+            .map_or_else(|| "null".to_string(), |mode| json_string(mode.name()))
+    )?;
+
+    match &analysis.confidence {
+        Some(confidence) => {
+            let residues = confidence
+                .residues
+                .iter()
+                .map(|residue| {
+                    format!(
+                        "{{\"chain_id\": {}, \"residue\": {}, \"plddt\": {}}}",
+                        json_string(&residue.chain_id),
+                        residue.residue,
+                        json_number(residue.plddt)
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            let group = |group: &ConfidenceGroup| {
+                json_tally(
+                    group.scored_frame_count,
+                    group.rejected_frame_count,
+                    group.quality_factor,
+                    group.average_error_value,
+                )
+            };
+            writeln!(
+                out,
+                "  \"confidence\": {{\"cutoff\": {}, \"mean_plddt\": {}, \"residues\": [{residues}], \"confident\": {{{}}}, \"low_confidence\": {{{}}}}},",
+                json_number(confidence.cutoff),
+                json_optional(confidence.mean_plddt),
+                group(&confidence.confident),
+                group(&confidence.low_confidence)
+            )?;
+        }
+        None => writeln!(out, "  \"confidence\": null,")?,
+    }
+
+    writeln!(out, "  \"problem_regions\": [")?;
+    for (idx, region) in analysis.problem_regions.iter().enumerate() {
+        let sep = if idx + 1 < analysis.problem_regions.len() {
+            ","
+        } else {
+            ""
+        };
+        writeln!(
+            out,
+            "    {{\"chain_id\": {}, \"start_residue\": {}, \"end_residue\": {}, \"length\": {}, \"peak_error_value\": {}, \"mean_error_value\": {}, \"severity\": \"{}\"}}{sep}",
+            json_string(&region.chain_id),
+            region.start_residue,
+            region.end_residue,
+            region.length,
+            json_number(region.peak_error_value),
+            json_number(region.mean_error_value),
+            frame_status_name(region.severity)
+        )?;
+    }
+    writeln!(out, "  ],")?;
+
+    writeln!(out, "  \"frame_scores\": [")?;
+    for (idx, frame) in analysis.frame_scores.iter().enumerate() {
+        let sep = if idx + 1 < analysis.frame_scores.len() {
+            ","
+        } else {
+            ""
+        };
+        writeln!(
+            out,
+            "    {{\"chain_id\": {}, \"center_residue\": {}, \"error_value\": {}, \"status\": \"{}\", \"p_value\": {}{}{}}}{sep}",
+            json_string(&frame.chain_id),
+            frame.center_residue,
+            json_number(frame.error_value),
+            frame_status_name(frame.status),
+            json_number(frame.p_value),
+            frame
+                .secondary_structure
+                .map(|kind| format!(", \"secondary_structure\": \"{}\"", kind.name()))
+                .unwrap_or_default(),
+            frame
+                .interactions
+                .as_ref()
+                .map(json_interactions)
+                .unwrap_or_default()
+        )?;
+    }
+    writeln!(out, "  ],")?;
+
+    writeln!(out, "  \"residue_p_values\": [")?;
+    for (idx, residue) in analysis.residue_p_values.iter().enumerate() {
+        let sep = if idx + 1 < analysis.residue_p_values.len() {
+            ","
+        } else {
+            ""
+        };
+        writeln!(
+            out,
+            "    {{\"chain_id\": {}, \"residue\": {}, \"p_value\": {}}}{sep}",
+            json_string(&residue.chain_id),
+            residue.residue,
+            json_number(residue.p_value)
+        )?;
+    }
+    writeln!(out, "  ],")?;
+
+    let messages = analysis
+        .messages
+        .iter()
+        .map(|message| json_string(message))
+        .collect::<Vec<_>>()
+        .join(", ");
+    writeln!(out, "  \"messages\": [{messages}]")?;
+    writeln!(out, "}}")?;
+    Ok(())
+}
+
+const INTERACTION_COLUMNS: [&str; 12] = [
+    "cc_count",
+    "cn_count",
+    "co_count",
+    "nn_count",
+    "no_count",
+    "total_interactions",
+    "cc_fraction",
+    "cn_fraction",
+    "co_fraction",
+    "nn_fraction",
+    "no_fraction",
+    "inter_chain_interactions",
+];
+
+pub(crate) fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+pub(crate) fn write_csv<W: Write>(out: &mut W, analysis: &AnalysisResult) -> io::Result<()> {
+    let detailed = analysis
+        .frame_scores
+        .iter()
+        .any(|frame| frame.interactions.is_some());
+    let secondary = analysis
+        .frame_scores
+        .iter()
+        .any(|frame| frame.secondary_structure.is_some());
+    write!(
+        out,
+        "protein_id,chain_id,center_residue,error_value,status,p_value"
+    )?;
+    if secondary {
+        write!(out, ",secondary_structure")?;
+    }
+    if detailed {
+        write!(out, ",{}", INTERACTION_COLUMNS.join(","))?;
+    }
+    writeln!(out)?;
+    let protein_id = csv_field(&analysis.protein_id);
+    for frame in &analysis.frame_scores {
+        write!(
+            out,
+            "{},{},{},{},{},{}",
+            protein_id,
+            csv_field(&frame.chain_id),
+            frame.center_residue,
+            frame.error_value,
+            frame_status_name(frame.status),
+            frame.p_value
+        )?;
+        if secondary {
+            write!(
+                out,
+                ",{}",
+                frame.secondary_structure.map_or("", |kind| kind.name())
+            )?;
+        }
+        if detailed {
+            match &frame.interactions {
+                Some(interactions) => {
+                    for value in interactions
+                        .counts
+                        .iter()
+                        .chain([&interactions.total])
+                        .chain(&interactions.fractions)
+                        .chain([&interactions.inter_chain_total()])
+                    {
+                        write!(out, ",{value}")?;
+                    }
+                }
+                None => write!(out, "{}", ",".repeat(INTERACTION_COLUMNS.len()))?,
+            }
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn write_summary_csv<W: Write>(
+    out: &mut W,
+    analysis: &AnalysisResult,
+) -> io::Result<()> {
+    writeln!(
+        out,
+        "protein_id,scope,name,chain_id,start_residue,end_residue,scored_frames,rejected_frames,quality_factor,average_error_value,resolution,quality_percentile,reference_z_score,reference_percentile"
+    )?;
+    let protein_id = csv_field(&analysis.protein_id);
+    let optional = |value: Option<f64>| value.map(|v| v.to_string()).unwrap_or_default();
+    writeln!(
+        out,
+        "{protein_id},overall,,,,,{},{},{},{},{},{},{},{}",
+        analysis.scored_frame_count,
+        analysis.rejected_frame_count,
+        optional(analysis.overall_quality_factor),
+        optional(analysis.average_error_value),
+        optional(analysis.resolution),
+        optional(analysis.quality_percentile),
+        optional(
+            analysis
+                .corpus_score
+                .as_ref()
+                .and_then(|score| score.z_score)
+        ),
+        optional(analysis.corpus_score.as_ref().map(|score| score.percentile))
+    )?;
+    for chain in &analysis.chain_summaries {
+        writeln!(
+            out,
+            "{protein_id},chain,,{},{},{},{},{},{},{},,,,",
+            csv_field(&chain.chain_id),
+            chain.start_residue,
+            chain.end_residue,
+            chain.scored_frame_count,
+            chain.rejected_frame_count,
+            optional(chain.quality_factor),
+            optional(chain.average_error_value)
+        )?;
+    }
+    for domain in &analysis.domain_summaries {
+        writeln!(
+            out,
+            "{protein_id},domain,{},{},{},{},{},{},{},{},,,,",
+            csv_field(&domain.name),
+            csv_field(&domain.chain_id),
+            domain.start_residue,
+            domain.end_residue,
+            domain.scored_frame_count,
+            domain.rejected_frame_count,
+            optional(domain.quality_factor),
+            optional(domain.average_error_value)
+        )?;
+    }
+    if let Some(interface) = &analysis.interface {
+        writeln!(
+            out,
+            "{protein_id},interface,,,,,{},{},{},{},,,,",
+            interface.scored_frame_count,
+            interface.rejected_frame_count,
+            optional(interface.quality_factor),
+            optional(interface.average_error_value)
+        )?;
+    }
+    if let Some(confidence) = &analysis.confidence {
+        for (scope, group) in [
+            ("confident", &confidence.confident),
+            ("low_confidence", &confidence.low_confidence),
+        ] {
+            writeln!(
+                out,
+                "{protein_id},{scope},,,,,{},{},{},{},,,,",
+                group.scored_frame_count,
+                group.rejected_frame_count,
+                optional(group.quality_factor),
+                optional(group.average_error_value)
+            )?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{ChainSummary, DomainSummary, FrameScore, ResidueProbability};
+    use crate::corpus::CorpusScore;
+    use std::path::PathBuf;
+
+    #[allow(deprecated)]
+    fn sample() -> AnalysisResult {
+        AnalysisResult {
+            protein_id: "demo \"x\"".to_string(),
+            input_path: PathBuf::from("-"),
+            scored_frame_count: 1,
+            rejected_frame_count: 1,
+            rejected_frame_ratio: Some(1.0),
+            overall_quality_factor: Some(0.0),
+            average_error_value: None,
+            average_probability: None,
+            experimental_method: Some("X-RAY DIFFRACTION".to_string()),
+            resolution: Some(2.1),
+            quality_percentile: Some(0.0),
+            corpus_score: Some(CorpusScore {
+                method: "em".to_string(),
+                residue_count: 600,
+                min_residues: 400,
+                max_residues: Some(800),
+                structure_count: 12,
+                mean_quality_factor: 85.0,
+                z_score: Some(-1.5),
+                percentile: 4.0,
+            }),
+            below_interaction_limit_frames: vec![7],
+            chain_summaries: vec![ChainSummary {
+                chain_id: "A".to_string(),
+                start_residue: 5,
+                end_residue: 9,
+                min_p_value: Some(0.04),
+                scored_frame_count: 1,
+                rejected_frame_count: 1,
+                quality_factor: Some(0.0),
+                average_error_value: Some(12.5),
+            }],
+            domain_summaries: Vec::new(),
+            interface: None,
+            hetero_environment: None,
+            confidence: None,
+            problem_regions: vec![ProblemRegion {
+                chain_id: "A".to_string(),
+                start_residue: 5,
+                end_residue: 5,
+                length: 1,
+                peak_error_value: 12.5,
+                mean_error_value: 12.5,
+                severity: FrameStatus::Warning95,
+            }],
+            frame_scores: vec![FrameScore {
+                chain_id: "A".to_string(),
+                center_residue: 5,
+                error_value: 12.5,
+                status: FrameStatus::Warning95,
+                p_value: 0.04,
+                interactions: None,
+                secondary_structure: None,
+            }],
+            residue_p_values: vec![ResidueProbability {
+                chain_id: "A".to_string(),
+                residue: 1,
+                p_value: 0.04,
+            }],
+            messages: vec!["line\tone".to_string()],
+            log_text: String::new(),
+        }
+    }
+
+    #[test]
+    fn json_escapes_and_uses_null() {
+        let mut out = Vec::new();
+        write_json(&mut out, &sample()).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.contains("\"protein_id\": \"demo \\\"x\\\"\""));
+        assert!(text.contains("\"average_error_value\": null"));
+        assert!(text.contains("\"average_probability\": null"));
+        assert!(text.contains("\"status\": \"warning95\", \"p_value\": 0.04"));
+        assert!(text.contains("\"min_p_value\": 0.04"));
+        assert!(text.contains("{\"chain_id\": \"A\", \"residue\": 1, \"p_value\": 0.04}"));
+        assert!(text.contains("[\"line\\tone\"]"));
+    }
+
+    #[test]
+    fn problem_regions_reach_json_and_log() {
+        let analysis = sample();
+        let mut json = Vec::new();
+        write_json(&mut json, &analysis).unwrap();
+        let json = String::from_utf8(json).unwrap();
+        assert!(json.contains(
+            "{\"chain_id\": \"A\", \"start_residue\": 5, \"end_residue\": 5, \"length\": 1, \"peak_error_value\": 12.5, \"mean_error_value\": 12.5, \"severity\": \"warning95\"}"
+        ));
+
+        let mut log = Vec::new();
+        write_problem_regions(&mut log, &analysis.problem_regions).unwrap();
+        assert_eq!(
+            String::from_utf8(log).unwrap(),
+            "# Problem region A:    Residue range 5 to 5    Length 1    Peak 12.500    Mean 12.500    95%\n"
+        );
+    }
+
+    #[test]
+    fn csv_quotes_fields() {
+        let mut out = Vec::new();
+        write_csv(&mut out, &sample()).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert_eq!(
+            text.lines().nth(1),
+            Some("\"demo \"\"x\"\"\",A,5,12.5,warning95,0.04")
+        );
+    }
+
+    #[test]
+    fn detailed_frames_export_interactions() {
+        let mut analysis = sample();
+        analysis.frame_scores[0].interactions = Some(WindowInteractions {
+            counts: [40.0, 30.0, 20.0, 8.0, 2.0],
+            total: 110.0,
+            fractions: [0.4, 0.3, 0.2, 0.08, 0.02],
+            inter_chain_counts: [4.0, 3.0, 0.0, 1.0, 0.0],
+        });
+
+        let mut json = Vec::new();
+        write_json(&mut json, &analysis).unwrap();
+        let json = String::from_utf8(json).unwrap();
+        assert!(json.contains(
+            "\"interactions\": {\"counts\": [40, 30, 20, 8, 2], \"total\": 110, \"fractions\": [0.4, 0.3, 0.2, 0.08, 0.02], \"inter_chain_counts\": [4, 3, 0, 1, 0]}"
+        ));
+
+        let mut csv = Vec::new();
+        write_csv(&mut csv, &analysis).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let mut lines = csv.lines();
+        assert!(lines.next().unwrap().ends_with(
+            ",total_interactions,cc_fraction,cn_fraction,co_fraction,nn_fraction,no_fraction,inter_chain_interactions"
+        ));
+        assert!(
+            lines
+                .next()
+                .unwrap()
+                .ends_with(",40,30,20,8,2,110,0.4,0.3,0.2,0.08,0.02,8")
+        );
+    }
+
+    #[test]
+    fn summary_csv_lists_overall_chain_and_domain_rows() {
+        let mut analysis = sample();
+        analysis.domain_summaries.push(DomainSummary {
+            name: "core, N".to_string(),
+            chain_id: "A".to_string(),
+            start_residue: 1,
+            end_residue: 40,
+            scored_frame_count: 0,
+            rejected_frame_count: 0,
+            quality_factor: None,
+            average_error_value: None,
+        });
+        let mut out = Vec::new();
+        write_summary_csv(&mut out, &analysis).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(
+            lines[0]
+                .ends_with(",resolution,quality_percentile,reference_z_score,reference_percentile")
+        );
+        assert!(lines[1].ends_with(",overall,,,,,1,1,0,,2.1,0,-1.5,4"));
+        assert!(lines[2].ends_with(",chain,,A,5,9,1,1,0,12.5,,,,"));
+        assert!(lines[3].ends_with(",domain,\"core, N\",A,1,40,0,0,,,,,,"));
+
+        let mut json = Vec::new();
+        write_json(&mut json, &analysis).unwrap();
+        let json = String::from_utf8(json).unwrap();
+        assert!(json.contains("\"quality_factor\": 0, \"average_error_value\": 12.5}"));
+        assert!(json.contains("{\"name\": \"core, N\", \"chain_id\": \"A\""));
+        assert!(
+            json.contains(
+                "\"experimental_method\": \"X-RAY DIFFRACTION\",\n  \"resolution\": 2.1,"
+            )
+        );
+    }
+}
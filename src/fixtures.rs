@@ -0,0 +1,60 @@
+/// Backbone ribbon used by the unit tests: consecutive residues alternate
+/// between two rows 3 A apart, so every window has close inter-residue contacts.
+pub(crate) struct Ribbon {
+    pub chain_id: char,
+    pub residues: i32,
+    pub offset: [f64; 2],
+    pub scale: f64,
+    pub hydrogens: bool,
+    pub b_factor: Box<dyn Fn(i32) -> f64>,
+}
+
+impl Default for Ribbon {
+    fn default() -> Self {
+        Self {
+            chain_id: 'A',
+            residues: 30,
+            offset: [0.0, 0.0],
+            scale: 1.0,
+            hydrogens: false,
+            b_factor: Box::new(|_| 20.0),
+        }
+    }
+}
+
+impl Ribbon {
+    pub(crate) fn push_to(&self, pdb: &mut String) {
+        let atoms: &[(&str, &str)] = if self.hydrogens {
+            &[("N", "N"), ("CA", "C"), ("C", "C"), ("O", "O"), ("H", "H")]
+        } else {
+            &[("N", "N"), ("CA", "C"), ("C", "C"), ("O", "O")]
+        };
+        for residue in 1..=self.residues {
+            let b_factor = (self.b_factor)(residue);
+            for (k, (name, element)) in atoms.iter().enumerate() {
+                let serial = (residue as usize - 1) * atoms.len() + k + 1;
+                let x = self.offset[0] + ((residue / 2) as f64 * 3.0 + k as f64 * 0.4) * self.scale;
+                let y = self.offset[1] + (residue % 2) as f64 * 3.0 * self.scale;
+                let z = k as f64 * 1.6 * self.scale;
+                pdb.push_str(&format!(
+                    "ATOM  {serial:>5} {name:<4} ALA {}{residue:>4}    {x:>8.3}{y:>8.3}{z:>8.3}  1.00{b_factor:>6.2}          {element:>2}\n",
+                    self.chain_id
+                ));
+            }
+        }
+    }
+
+    pub(crate) fn pdb(&self) -> String {
+        let mut pdb = String::new();
+        self.push_to(&mut pdb);
+        pdb
+    }
+}
+
+pub(crate) fn ribbon(residues: i32) -> String {
+    Ribbon {
+        residues,
+        ..Ribbon::default()
+    }
+    .pdb()
+}
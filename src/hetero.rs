@@ -0,0 +1,151 @@
+use std::io::{self, Write};
+
+use crate::api::HeteroEnvironment;
+use crate::model::AtomData;
+use crate::symmetry::{ENVIRONMENT_RESNUM, grow};
+
+pub(crate) const HETERO_RESNUM: i32 = ENVIRONMENT_RESNUM + 1;
+
+fn is_water(res_name: &[u8; 3]) -> bool {
+    matches!(
+        res_name,
+        b"HOH" | b"WAT" | b"DOD" | b"H2O" | b"TIP" | b"SOL"
+    )
+}
+
+pub(crate) fn add_hetero_atoms<W: Write>(
+    data: &mut AtomData,
+    mode: HeteroEnvironment,
+    logw: &mut W,
+) -> io::Result<usize> {
+    let atoms = std::mem::take(&mut data.hetero_atoms);
+    let mut ligands = 0;
+    let mut waters = 0;
+    for atom in &atoms {
+        if is_water(&atom.res_name) {
+            if mode == HeteroEnvironment::Ligands {
+                continue;
+            }
+            waters += 1;
+        } else {
+            ligands += 1;
+        }
+        let idx = data.atmnum + data.environment_atoms + 1;
+        grow(data, idx + 1);
+        data.name[idx] = atom.name;
+        data.bnam[idx] = 0;
+        data.atom_name[idx] = atom.atom_name;
+        data.res_name[idx] = atom.res_name;
+        data.chain_id[idx] = atom.chain_id;
+        data.res_seq[idx] = atom.res_seq;
+        data.resnum[idx] = HETERO_RESNUM;
+        data.source_index[idx] = atom.source_index;
+        data.xyz_x[idx] = atom.point[0];
+        data.xyz_y[idx] = atom.point[1];
+        data.xyz_z[idx] = atom.point[2];
+        data.environment_atoms += 1;
+    }
+    data.hetero_atoms = atoms;
+    writeln!(
+        logw,
+        "Hetero environment: {ligands} ligand atoms, {waters} water atoms"
+    )?;
+    Ok(ligands + waters)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{AnalysisOptions, InputFormat, build_analysis_result};
+    use crate::fixtures;
+    use crate::parser::parse_reader;
+    use crate::stats::compute_errat;
+    use crate::symmetry::add_environment_atoms;
+    use std::io::Cursor;
+    use std::path::PathBuf;
+
+    fn pocket() -> String {
+        let mut pdb = fixtures::ribbon(30);
+        let hetero = [
+            ("C1", "LIG", 6.0, -3.0, "C"),
+            ("N2", "LIG", 9.0, -3.0, "N"),
+            ("O3", "LIG", 12.0, -3.0, "O"),
+            ("H4", "LIG", 10.5, -3.0, "H"),
+            ("FE", "HEM", 15.0, -3.0, "FE"),
+            ("O", "HOH", 20.0, -3.0, "O"),
+            ("O", "HOH", 24.0, -3.0, "O"),
+        ];
+        for (residue, (name, res_name, x, z, element)) in hetero.into_iter().enumerate() {
+            pdb.push_str(&format!(
+                "HETATM{:>5} {name:<4} {res_name} A{:>4}    {x:>8.3}{:>8.3}{z:>8.3}  1.00 20.00          {element:>2}\n",
+                121 + residue,
+                101 + residue,
+                1.5
+            ));
+        }
+        pdb
+    }
+
+    #[test]
+    fn hetero_atoms_join_environment_but_not_windows() {
+        let data = parse_reader(
+            &mut Cursor::new(pocket()),
+            InputFormat::Pdb,
+            &mut io::sink(),
+        )
+        .unwrap();
+        assert_eq!(data.atmnum, 120);
+        assert_eq!(data.hetero_atoms.len(), 5);
+        assert_eq!(data.hetero_atoms[0].source_index, 120);
+
+        let plain = compute_errat(
+            &data,
+            &AnalysisOptions::default().calibration,
+            &mut io::sink(),
+        )
+        .unwrap();
+        for (mode, added) in [
+            (HeteroEnvironment::Ligands, 3),
+            (HeteroEnvironment::LigandsAndWater, 5),
+        ] {
+            let options = AnalysisOptions {
+                hetero_environment: Some(mode),
+                ..AnalysisOptions::default()
+            };
+            let mut data = data.clone();
+            let mut log = Vec::new();
+            add_environment_atoms(&mut data, &options, &mut log).unwrap();
+            assert_eq!(data.environment_atoms, added);
+            assert_eq!(data.atmnum, 120);
+            let log = String::from_utf8(log).unwrap();
+            assert!(log.contains(&format!(
+                "Hetero environment: 3 ligand atoms, {} water atoms",
+                added - 3
+            )));
+
+            let stats = compute_errat(&data, &options.calibration, &mut io::sink()).unwrap();
+            assert_eq!(stats.scored_frames.len(), plain.scored_frames.len());
+            assert!(
+                stats
+                    .scored_frames
+                    .iter()
+                    .zip(&plain.scored_frames)
+                    .any(|(with, without)| with.interactions.total > without.interactions.total)
+            );
+            assert!(
+                stats
+                    .scored_frames
+                    .iter()
+                    .all(|frame| frame.interactions.inter_chain_total() == 0.0)
+            );
+            let result = build_analysis_result(
+                PathBuf::from("-"),
+                "pocket".into(),
+                &stats,
+                String::new(),
+                &options,
+            );
+            assert_eq!(result.hetero_environment, Some(mode));
+        }
+    }
+}
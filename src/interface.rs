@@ -0,0 +1,118 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::api::AnalysisOptions;
+use crate::model::{AtomData, ErratStats};
+use crate::stats::is_inter_chain;
+
+pub(crate) fn interface_residues(data: &AtomData, distance: f64) -> Vec<(i32, u8)> {
+    let total = data.atmnum + data.environment_atoms;
+    let cell = |atom: usize| {
+        [data.xyz_x[atom], data.xyz_y[atom], data.xyz_z[atom]]
+            .map(|v| (v / distance).floor() as i32)
+    };
+    let mut cells: HashMap<[i32; 3], Vec<usize>> = HashMap::new();
+    for atom in 1..=total {
+        cells.entry(cell(atom)).or_default().push(atom);
+    }
+
+    let dsq_max = distance * distance;
+    let mut residues = BTreeSet::new();
+    for atom in 1..=data.atmnum {
+        if residues.contains(&(data.resnum[atom], data.chain_id[atom])) {
+            continue;
+        }
+        let [cx, cy, cz] = cell(atom);
+        let near = (-1..=1)
+            .flat_map(|dx| (-1..=1).flat_map(move |dy| (-1..=1).map(move |dz| [dx, dy, dz])))
+            .filter_map(|[dx, dy, dz]| cells.get(&[cx + dx, cy + dy, cz + dz]))
+            .flatten()
+            .any(|&n| {
+                is_inter_chain(data, atom, n)
+                    && (data.xyz_x[n] - data.xyz_x[atom]).powi(2)
+                        + (data.xyz_y[n] - data.xyz_y[atom]).powi(2)
+                        + (data.xyz_z[n] - data.xyz_z[atom]).powi(2)
+                        <= dsq_max
+            });
+        if near {
+            residues.insert((data.resnum[atom], data.chain_id[atom]));
+        }
+    }
+    residues.into_iter().collect()
+}
+
+pub(crate) fn mark_interface(data: &AtomData, stats: &mut ErratStats, options: &AnalysisOptions) {
+    if let Some(distance) = options.interface_distance {
+        stats.interface_residues = interface_residues(data, distance);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{InputFormat, build_analysis_result};
+    use crate::fixtures::Ribbon;
+    use crate::parser::parse_reader;
+    use crate::stats::compute_errat;
+    use std::io::{self, Cursor};
+    use std::path::PathBuf;
+
+    #[test]
+    fn interface_windows_and_inter_chain_counts() {
+        let mut pdb = Ribbon::default().pdb();
+        Ribbon {
+            chain_id: 'B',
+            residues: 12,
+            offset: [0.0, 6.5],
+            ..Ribbon::default()
+        }
+        .push_to(&mut pdb);
+        let data = parse_reader(&mut Cursor::new(pdb), InputFormat::Pdb, &mut io::sink()).unwrap();
+        let options = AnalysisOptions {
+            interface_distance: Some(4.0),
+            ..AnalysisOptions::default()
+        };
+        let mut stats = compute_errat(&data, &options.calibration, &mut io::sink()).unwrap();
+        mark_interface(&data, &mut stats, &options);
+
+        let residues = &stats.interface_residues;
+        assert!(residues.contains(&(3, b'A')));
+        assert!(residues.contains(&(10_004, b'B')));
+        assert!(!residues.contains(&(10_003, b'B')));
+        assert!(!residues.iter().any(|&(residue, _)| residue == 25));
+
+        for frame in &stats.scored_frames {
+            let interactions = &frame.interactions;
+            if frame.center_residue >= 24 && frame.center_residue < 10_000 {
+                assert_eq!(interactions.inter_chain_total(), 0.0);
+            } else if frame.center_residue <= 10 || frame.center_residue > 10_000 {
+                assert!(interactions.inter_chain_total() > 0.0);
+            }
+            let intra = interactions.intra_chain_counts();
+            assert!(intra.iter().all(|&count| count >= 0.0));
+        }
+
+        let result = build_analysis_result(
+            PathBuf::from("-"),
+            "pair".into(),
+            &stats,
+            String::new(),
+            &options,
+        );
+        let interface = result.interface.unwrap();
+        assert_eq!(interface.distance, 4.0);
+        assert_eq!(interface.residues.len(), residues.len());
+        assert!(interface.scored_frame_count > 0);
+        assert!(interface.scored_frame_count < result.scored_frame_count);
+        let fraction = interface.inter_chain_fraction.unwrap();
+        assert!(fraction > 0.0 && fraction < 1.0);
+
+        let plain = build_analysis_result(
+            PathBuf::from("-"),
+            "pair".into(),
+            &stats,
+            String::new(),
+            &AnalysisOptions::default(),
+        );
+        assert!(plain.interface.is_none());
+    }
+}
@@ -1,19 +1,69 @@
 mod api;
+mod assembly;
+mod bcif;
+mod calibration;
+mod cif;
+mod compare;
+mod confidence;
+mod corpus;
+mod explain;
+mod export;
+#[cfg(test)]
+mod fixtures;
+mod hetero;
+mod interface;
 mod model;
 mod parser;
 mod pipeline;
 #[cfg(feature = "python")]
 mod python;
+mod rank;
 mod render;
+mod resolution;
+mod secondary;
+mod session;
+mod sink;
 mod stats;
+mod symmetry;
+mod train;
+mod trajectory;
 
-pub use api::{AnalysisResult, ChainSummary, FrameScore, FrameStatus, RunOutput};
+pub use api::{
+    AnalysisOptions, AnalysisResult, AssemblyMode, AssemblySelection, ChainSummary,
+    ConfidenceGroup, ConfidenceSummary, DomainRange, DomainSummary, FrameScore, FrameStatus,
+    HeteroEnvironment, InputFormat, InterfaceResidue, InterfaceSummary, ProblemRegion,
+    ReportFormat, ResidueConfidence, ResidueProbability, RunOutput, SecondaryStructure,
+    WindowInteractions,
+};
+pub use calibration::Calibration;
+pub use compare::{ChangedRegion, Comparison, FrameDelta, compare_files};
+pub use corpus::{
+    CorpusBucket, CorpusReference, CorpusReport, CorpusScore, build_corpus_reference,
+};
+pub use explain::{
+    AtomRef, ComponentContribution, ContactClass, ContactPair, WindowCenter, WindowExplanation,
+    explain_file, explain_reader, write_chimerax_pseudobonds, write_explanations_json,
+    write_pymol_script,
+};
+pub use rank::{
+    RankCriterion, RankedModel, RankingReport, rank_models, write_annotated_structure,
+    write_ranking_csv,
+};
+pub use resolution::{ResolutionBin, ResolutionReference};
+pub use session::ErratSession;
+pub use train::{TrainingReport, train_calibration};
+pub use trajectory::{
+    TrajectoryAnalysis, TrajectoryFormat, TrajectoryFrame, TrajectoryResidue, analyze_trajectory,
+    write_trajectory_matrix_csv, write_trajectory_series_csv,
+};
 
-use std::io;
+use std::io::{self, BufRead, Write};
 use std::path::{Path, PathBuf};
 
 use api::{build_analysis_result, derive_file_string};
-use pipeline::{persist_outputs, process_structure_data, resolve_paths};
+use pipeline::{
+    persist_outputs, process_reader, process_structure_data, resolve_paths, write_reports,
+};
 
 #[cfg(feature = "python")]
 use pyo3::prelude::*;
@@ -43,13 +93,22 @@ pub fn analyze_file<P: AsRef<Path>>(
     input_pdb: P,
     protein_id: Option<&str>,
     use_mmap: bool,
+) -> io::Result<AnalysisResult> {
+    analyze_file_with(input_pdb, protein_id, use_mmap, &AnalysisOptions::default())
+}
+
+pub fn analyze_file_with<P: AsRef<Path>>(
+    input_pdb: P,
+    protein_id: Option<&str>,
+    use_mmap: bool,
+    options: &AnalysisOptions,
 ) -> io::Result<AnalysisResult> {
     let input_path = input_pdb.as_ref().to_path_buf();
     let protein_id = derive_file_string(&input_path, protein_id);
-    let (stats, log) = process_structure_data(&input_path, use_mmap)?;
+    let (stats, log) = process_structure_data(&input_path, use_mmap, options)?;
     let log_text = String::from_utf8_lossy(&log).into_owned();
     Ok(build_analysis_result(
-        input_path, protein_id, &stats, log_text,
+        input_path, protein_id, &stats, log_text, options,
     ))
 }
 
@@ -72,9 +131,16 @@ pub fn analyze_and_write<P: AsRef<Path>, Q: AsRef<Path>>(
         output_pdf,
     };
     let paths = resolve_paths(&config);
-    let (stats, mut log) = process_structure_data(&input_path, use_mmap)?;
+    let (stats, mut log) =
+        process_structure_data(&input_path, use_mmap, &AnalysisOptions::default())?;
     let log_text = String::from_utf8_lossy(&log).into_owned();
-    let analysis = build_analysis_result(input_path, protein_id, &stats, log_text);
+    let analysis = build_analysis_result(
+        input_path,
+        protein_id,
+        &stats,
+        log_text,
+        &AnalysisOptions::default(),
+    );
     persist_outputs(
         &paths,
         &config.file_string,
@@ -108,7 +174,8 @@ pub fn write_report<P: AsRef<Path>, Q: AsRef<Path>>(
         output_pdf,
     };
     let paths = resolve_paths(&config);
-    let (stats, mut log) = process_structure_data(&paths.pdb, config.use_mmap)?;
+    let options = AnalysisOptions::default();
+    let (stats, mut log) = process_structure_data(&paths.pdb, config.use_mmap, &options)?;
     persist_outputs(
         &paths,
         &config.file_string,
@@ -132,9 +199,31 @@ pub fn run_file<P: AsRef<Path>, Q: AsRef<Path>>(
     write_report(input_pdb, output_dir, protein_id, use_mmap, output_pdf)
 }
 
+pub fn analyze_reader<R: BufRead, P: AsRef<Path>>(
+    mut reader: R,
+    format: InputFormat,
+    input_path: P,
+    protein_id: &str,
+    options: &AnalysisOptions,
+    outputs: &mut [(ReportFormat, &mut dyn Write)],
+) -> io::Result<AnalysisResult> {
+    let (stats, mut log) = process_reader(&mut reader, format, options)?;
+    let log_text = String::from_utf8_lossy(&log).into_owned();
+    let analysis = build_analysis_result(
+        input_path.as_ref().to_path_buf(),
+        protein_id.to_string(),
+        &stats,
+        log_text,
+        options,
+    );
+    write_reports(outputs, protein_id, &stats, &analysis, &mut log)?;
+    Ok(analysis)
+}
+
 pub fn run(config: Config) -> io::Result<()> {
     let paths = resolve_paths(&config);
-    let (stats, mut log) = process_structure_data(&paths.pdb, config.use_mmap)?;
+    let options = AnalysisOptions::default();
+    let (stats, mut log) = process_structure_data(&paths.pdb, config.use_mmap, &options)?;
     persist_outputs(
         &paths,
         &config.file_string,
@@ -216,4 +305,44 @@ mod tests {
 
         let _ = fs::remove_dir_all(&temp_dir);
     }
+
+    #[test]
+    fn problem_regions_stay_out_of_legacy_outputs() {
+        let temp_dir = temp_test_dir("errat_rs_regions");
+        let input_path = temp_dir.join("ribbon.pdb");
+        fs::write(&input_path, fixtures::ribbon(30)).unwrap();
+
+        let (analysis, report) =
+            analyze_and_write(&input_path, temp_dir.join("out"), None, false, false).unwrap();
+        assert!(!analysis.problem_regions.is_empty());
+        assert!(
+            !fs::read_to_string(&report.logf)
+                .unwrap()
+                .contains("Problem region")
+        );
+        assert!(
+            !fs::read_to_string(&report.plot)
+                .unwrap()
+                .contains("27 sce mul rectfill")
+        );
+
+        let mut log = Vec::new();
+        let mut outputs: Vec<(ReportFormat, &mut dyn Write)> = vec![(ReportFormat::Log, &mut log)];
+        analyze_reader(
+            io::Cursor::new(fs::read(&input_path).unwrap()),
+            InputFormat::Pdb,
+            &input_path,
+            "ribbon",
+            &AnalysisOptions::default(),
+            &mut outputs,
+        )
+        .unwrap();
+        assert!(
+            String::from_utf8(log)
+                .unwrap()
+                .contains("# Problem region A:")
+        );
+
+        let _ = fs::remove_dir_all(&temp_dir);
+    }
 }
@@ -1,13 +1,21 @@
 use std::env;
-use std::io;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
 use std::path::{Path, PathBuf};
 
 use rayon::ThreadPoolBuilder;
 use rayon::prelude::*;
 
+use errat::{
+    AnalysisOptions, AssemblyMode, AssemblySelection, Calibration, CorpusReference, DomainRange,
+    HeteroEnvironment, InputFormat, RankCriterion, ReportFormat, ResolutionReference, WindowCenter,
+};
+
+const DEFAULT_PLDDT_CUTOFF: f64 = 70.0;
+
 fn print_usage() {
     eprintln!(
-        "\nUsage:\n  errat <ProteinID> <JobID>\n  errat --input <pdb|cif> --out-dir <dir> [--protein-id <id>] [--mmap] [--pdf]\n  errat --input-dir <dir> --out-dir <dir> [--recursive] [--threads <n>] [--mmap] [--pdf]\n  errat --jobs-dir <dir> [--threads <n>] [--mmap] [--pdf]\n\nEnvironment:\n  ERRAT_JOBS_PATH   base directory for job folders (default: ./outputs)\n"
+        "\nUsage:\n  errat <ProteinID> <JobID>\n  errat analyze <pdb|cif|bcif|-> [--format <pdb|cif|bcif>] [--protein-id <id>] [--json [path]] [--csv [path]] [--summary-csv [path]] [--svg [path]] [--ps [path]] [--pdf [path]] [--log <path>] [--domain <name=chain:start-end>]... [--detailed] [analysis options]\n  errat compare <before> <after> [--json [path]] [--csv [path]] [--svg [path]] [--ps [path]] [--pdf [path]] [--log <path>] [--mmap] [analysis options]\n  errat explain <pdb|cif|bcif|-> [--format <pdb|cif|bcif>] [--window <chain:residue>]... [--json [path]] [--chimerax <path>] [--pymol <path>] [analysis options]\n  errat rank <dir> [--by <quality|mean|worst-region|frames99>] [--top <n>] [--out-dir <dir>] [--reports] [--pdf] [--recursive] [--threads <n>] [--mmap] [analysis options]\n  errat trajectory <topology> <dcd|xtc|pdb> [--matrix [path]] [--series [path]] [--stride <n>] [--mmap] [analysis options]\n  errat reference <dir> --output <file> [--recursive] [--threads <n>] [--mmap] [analysis options]\n  errat train <dir> --output <file> [--base <file>] [--window-length <n>] [--radius <A>] [--radmin <A>] [--recursive] [--threads <n>]\n  errat --input <pdb|cif|bcif> --out-dir <dir> [--protein-id <id>] [--mmap] [--pdf]\n  errat --input-dir <dir> --out-dir <dir> [--recursive] [--threads <n>] [--mmap] [--pdf]\n  errat --jobs-dir <dir> [--threads <n>] [--mmap] [--pdf]\n\nAnalysis options (analyze, compare, explain, rank, trajectory, reference):\n  --calibration <file>  --window-length <n>  --radius <A>  --radmin <A>  --region-gap <n>\n  --crystal-contacts    count contacts with lattice neighbours (not for trajectory)\n                        built-in space groups: P1 P21 C2 P212121 P21212 C2221 I222 P31 P32 P3121 P3221\n                        P41 P43 P41212 P43212 P61 P65 P6122 P6522; others need SMTRY or symop records\n  --assembly <id>       score all of biological assembly <id> (not for trajectory)\n  --assembly-context <id>\n                        score the deposited model with contacts from assembly <id>\n  --interface <A>       report the chain interface within <A> (analyze, compare, rank)\n  --ligands | --ligands-water\n                        count contacts with ligands, and optionally waters (not for trajectory)\n  --resolution-reference <file>\n                        resolution percentile table (analyze, compare, rank)\n  --reference <file>    compare with a distribution built by errat reference (analyze, compare, rank)\n  --predicted | --plddt-cutoff <value>\n                        mask residues below pLDDT 70, or <value>, in predicted models (analyze, compare, rank)\n  --secondary-structure add a helix/strand/loop track (analyze, compare, rank)\n\nEnvironment:\n  ERRAT_JOBS_PATH   base directory for job folders (default: ./outputs)\n"
     );
 }
 
@@ -22,7 +30,7 @@ fn is_structure_file(path: &Path) -> bool {
         .and_then(|s| s.to_str())
         .unwrap_or("")
         .to_ascii_lowercase();
-    matches!(ext.as_str(), "pdb" | "cif" | "mmcif")
+    matches!(ext.as_str(), "pdb" | "cif" | "mmcif" | "bcif")
 }
 
 fn collect_inputs(dir: &Path, recursive: bool) -> io::Result<Vec<PathBuf>> {
@@ -45,23 +53,26 @@ fn collect_inputs(dir: &Path, recursive: bool) -> io::Result<Vec<PathBuf>> {
     Ok(inputs)
 }
 
+fn in_pool<T: Send>(threads: Option<usize>, work: impl FnOnce() -> T + Send) -> io::Result<T> {
+    match threads {
+        Some(threads) => {
+            let pool = ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .map_err(|e| io::Error::other(e.to_string()))?;
+            Ok(pool.install(work))
+        }
+        None => Ok(work()),
+    }
+}
+
 fn run_batch(items: Vec<BatchItem>, threads: Option<usize>) -> io::Result<(usize, Vec<String>)> {
-    let run_all = || {
+    let results = in_pool(threads, || {
         items
             .par_iter()
             .map(|item| (item.label.clone(), errat::run(item.config.clone())))
             .collect::<Vec<_>>()
-    };
-
-    let results = if let Some(threads) = threads {
-        let pool = ThreadPoolBuilder::new()
-            .num_threads(threads)
-            .build()
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
-        pool.install(run_all)
-    } else {
-        run_all()
-    };
+    })?;
 
     let mut success = 0usize;
     let mut errors = Vec::new();
@@ -74,6 +85,733 @@ fn run_batch(items: Vec<BatchItem>, threads: Option<usize>) -> io::Result<(usize
     Ok((success, errors))
 }
 
+fn invalid_input(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message.to_string())
+}
+
+fn flag_value<T: std::str::FromStr>(args: &[String], i: usize, flag: &str) -> io::Result<T> {
+    args.get(i)
+        .and_then(|v| v.parse::<T>().ok())
+        .ok_or_else(|| invalid_input(&format!("{flag} expects a number")))
+}
+
+fn assembly_flag(
+    args: &[String],
+    i: usize,
+    flag: &str,
+    mode: AssemblyMode,
+) -> io::Result<Option<AssemblySelection>> {
+    let id = args
+        .get(i)
+        .ok_or_else(|| invalid_input(&format!("{flag} expects an assembly id")))?;
+    Ok(Some(AssemblySelection {
+        id: id.clone(),
+        mode,
+    }))
+}
+
+fn parse_analysis_flag(
+    args: &[String],
+    i: &mut usize,
+    options: &mut AnalysisOptions,
+) -> io::Result<bool> {
+    match args[*i].as_str() {
+        "--calibration" => {
+            *i += 1;
+            let path = args
+                .get(*i)
+                .ok_or_else(|| invalid_input("--calibration expects a file path"))?;
+            options.calibration = Calibration::load(path)?;
+        }
+        "--window-length" => {
+            *i += 1;
+            options.window_length = Some(flag_value(args, *i, "--window-length")?);
+        }
+        "--radius" => {
+            *i += 1;
+            options.radius = Some(flag_value(args, *i, "--radius")?);
+        }
+        "--radmin" => {
+            *i += 1;
+            options.radmin = Some(flag_value(args, *i, "--radmin")?);
+        }
+        "--region-gap" => {
+            *i += 1;
+            options.region_gap = flag_value(args, *i, "--region-gap")?;
+        }
+        "--crystal-contacts" => options.crystal_contacts = true,
+        "--assembly" => {
+            *i += 1;
+            options.assembly = assembly_flag(args, *i, "--assembly", AssemblyMode::Full)?;
+        }
+        "--assembly-context" => {
+            *i += 1;
+            options.assembly =
+                assembly_flag(args, *i, "--assembly-context", AssemblyMode::Context)?;
+        }
+        "--interface" => {
+            *i += 1;
+            options.interface_distance = Some(flag_value(args, *i, "--interface")?);
+        }
+        "--ligands" => options.hetero_environment = Some(HeteroEnvironment::Ligands),
+        "--ligands-water" => options.hetero_environment = Some(HeteroEnvironment::LigandsAndWater),
+        "--resolution-reference" => {
+            *i += 1;
+            let path = args
+                .get(*i)
+                .ok_or_else(|| invalid_input("--resolution-reference expects a file path"))?;
+            options.resolution_reference = ResolutionReference::load(path)?;
+        }
+        "--reference" => {
+            *i += 1;
+            let path = args
+                .get(*i)
+                .ok_or_else(|| invalid_input("--reference expects a file path"))?;
+            options.corpus_reference = Some(CorpusReference::load(path)?);
+        }
+        "--predicted" => {
+            options.plddt_cutoff = options.plddt_cutoff.or(Some(DEFAULT_PLDDT_CUTOFF));
+        }
+        "--plddt-cutoff" => {
+            *i += 1;
+            options.plddt_cutoff = Some(flag_value(args, *i, "--plddt-cutoff")?);
+        }
+        "--secondary-structure" => options.secondary_structure = true,
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
+
+fn parse_domain(spec: &str) -> io::Result<DomainRange> {
+    let invalid = || {
+        invalid_input(&format!(
+            "--domain expects NAME=CHAIN:START-END, got {spec}"
+        ))
+    };
+    let (name, range) = spec.split_once('=').ok_or_else(invalid)?;
+    let (chain_id, residues) = range.split_once(':').ok_or_else(invalid)?;
+    let split = residues
+        .char_indices()
+        .skip(1)
+        .find(|&(_, c)| c == '-')
+        .map(|(idx, _)| idx)
+        .ok_or_else(invalid)?;
+    let start_residue = residues[..split].parse().map_err(|_| invalid())?;
+    let end_residue = residues[split + 1..].parse().map_err(|_| invalid())?;
+    Ok(DomainRange {
+        name: name.to_string(),
+        chain_id: chain_id.to_string(),
+        start_residue,
+        end_residue,
+    })
+}
+
+fn parse_window(spec: &str) -> io::Result<WindowCenter> {
+    let invalid = || invalid_input(&format!("--window expects CHAIN:RESIDUE, got {spec}"));
+    let (chain_id, residue) = spec.split_once(':').ok_or_else(invalid)?;
+    Ok(WindowCenter {
+        chain_id: chain_id.to_string(),
+        residue: residue.parse().map_err(|_| invalid())?,
+    })
+}
+
+fn open_output(path: Option<&Path>) -> io::Result<Box<dyn Write>> {
+    match path {
+        Some(path) => Ok(Box::new(BufWriter::new(File::create(path)?))),
+        None => Ok(Box::new(BufWriter::new(io::stdout().lock()))),
+    }
+}
+
+fn report_target(args: &[String], i: &mut usize) -> Option<(ReportFormat, Option<PathBuf>)> {
+    let report = match args[*i].as_str() {
+        "--json" => ReportFormat::Json,
+        "--csv" => ReportFormat::Csv,
+        "--summary-csv" => ReportFormat::SummaryCsv,
+        "--svg" => ReportFormat::Svg,
+        "--ps" => ReportFormat::Ps,
+        "--pdf" => ReportFormat::Pdf,
+        _ => return None,
+    };
+    let path = match args.get(*i + 1) {
+        Some(next) if next == "-" => {
+            *i += 1;
+            None
+        }
+        Some(next) if !next.starts_with("--") => {
+            *i += 1;
+            Some(PathBuf::from(next))
+        }
+        _ => None,
+    };
+    Some((report, path))
+}
+
+type ReportWriters = Vec<(ReportFormat, Box<dyn Write>)>;
+
+fn open_reports(
+    targets: &[(ReportFormat, Option<PathBuf>)],
+    log_path: Option<PathBuf>,
+) -> io::Result<ReportWriters> {
+    let mut writers = Vec::with_capacity(targets.len() + 1);
+    for (report, path) in targets {
+        writers.push((*report, open_output(path.as_deref())?));
+    }
+    let log_writer: Box<dyn Write> = match log_path {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(io::stderr()),
+    };
+    writers.push((ReportFormat::Log, log_writer));
+    Ok(writers)
+}
+
+fn run_analyze(args: &[String]) -> io::Result<()> {
+    let mut input: Option<&str> = None;
+    let mut format: Option<InputFormat> = None;
+    let mut protein_id: Option<String> = None;
+    let mut log_path: Option<PathBuf> = None;
+    let mut options = AnalysisOptions::default();
+    let mut targets: Vec<(ReportFormat, Option<PathBuf>)> = Vec::new();
+
+    let mut i = 0usize;
+    while i < args.len() {
+        if parse_analysis_flag(args, &mut i, &mut options)? {
+            i += 1;
+            continue;
+        }
+        if let Some(target) = report_target(args, &mut i) {
+            targets.push(target);
+            i += 1;
+            continue;
+        }
+
+        match args[i].as_str() {
+            "--format" => {
+                i += 1;
+                format = Some(
+                    args.get(i)
+                        .and_then(|v| InputFormat::from_name(v))
+                        .ok_or_else(|| invalid_input("--format expects pdb, cif or bcif"))?,
+                );
+            }
+            "--protein-id" => {
+                i += 1;
+                protein_id = args.get(i).cloned();
+            }
+            "--log" => {
+                i += 1;
+                log_path = args.get(i).map(PathBuf::from);
+            }
+            "--detailed" => options.detailed = true,
+            "--domain" => {
+                i += 1;
+                let spec = args
+                    .get(i)
+                    .ok_or_else(|| invalid_input("--domain expects NAME=CHAIN:START-END"))?;
+                options.domains.push(parse_domain(spec)?);
+            }
+            arg if input.is_none() && (arg == "-" || !arg.starts_with("--")) => {
+                input = Some(arg);
+            }
+            arg => return Err(invalid_input(&format!("unexpected argument {arg}"))),
+        }
+        i += 1;
+    }
+
+    let input = input.ok_or_else(|| invalid_input("analyze requires an input path or -"))?;
+    if targets.is_empty() {
+        targets.push((ReportFormat::Json, None));
+    }
+    if targets.iter().filter(|(_, path)| path.is_none()).count() > 1 {
+        return Err(invalid_input("only one report can be written to stdout"));
+    }
+
+    let protein_id = protein_id.unwrap_or_else(|| {
+        Path::new(input)
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .filter(|s| !s.is_empty() && input != "-")
+            .unwrap_or("errat")
+            .to_string()
+    });
+
+    let mut writers = open_reports(&targets, log_path)?;
+    let mut outputs: Vec<(ReportFormat, &mut dyn Write)> = writers
+        .iter_mut()
+        .map(|(report, writer)| (*report, writer.as_mut() as &mut dyn Write))
+        .collect();
+
+    if input == "-" {
+        let format = format.ok_or_else(|| invalid_input("reading from stdin requires --format"))?;
+        let stdin = io::stdin().lock();
+        errat::analyze_reader(stdin, format, input, &protein_id, &options, &mut outputs)?;
+    } else {
+        let format = format.unwrap_or_else(|| InputFormat::from_path(Path::new(input)));
+        let reader = BufReader::new(File::open(input)?);
+        errat::analyze_reader(reader, format, input, &protein_id, &options, &mut outputs)?;
+    }
+    Ok(())
+}
+
+fn run_compare(args: &[String]) -> io::Result<()> {
+    let mut inputs: Vec<&str> = Vec::new();
+    let mut use_mmap = false;
+    let mut log_path: Option<PathBuf> = None;
+    let mut options = AnalysisOptions::default();
+    let mut targets: Vec<(ReportFormat, Option<PathBuf>)> = Vec::new();
+
+    let mut i = 0usize;
+    while i < args.len() {
+        if parse_analysis_flag(args, &mut i, &mut options)? {
+            i += 1;
+            continue;
+        }
+        if let Some(target) = report_target(args, &mut i) {
+            if target.0 == ReportFormat::SummaryCsv {
+                return Err(invalid_input("compare does not support --summary-csv"));
+            }
+            targets.push(target);
+            i += 1;
+            continue;
+        }
+
+        match args[i].as_str() {
+            "--mmap" => use_mmap = true,
+            "--log" => {
+                i += 1;
+                log_path = args.get(i).map(PathBuf::from);
+            }
+            arg if inputs.len() < 2 && !arg.starts_with("--") => inputs.push(arg),
+            arg => return Err(invalid_input(&format!("unexpected argument {arg}"))),
+        }
+        i += 1;
+    }
+
+    let [before, after] = inputs[..] else {
+        return Err(invalid_input("compare requires two input paths"));
+    };
+    if targets.is_empty() {
+        targets.push((ReportFormat::Json, None));
+    }
+    if targets.iter().filter(|(_, path)| path.is_none()).count() > 1 {
+        return Err(invalid_input("only one report can be written to stdout"));
+    }
+
+    let mut writers = open_reports(&targets, log_path)?;
+    let mut outputs: Vec<(ReportFormat, &mut dyn Write)> = writers
+        .iter_mut()
+        .map(|(report, writer)| (*report, writer.as_mut() as &mut dyn Write))
+        .collect();
+    errat::compare_files(before, after, use_mmap, &options, &mut outputs)?;
+    Ok(())
+}
+
+fn run_explain(args: &[String]) -> io::Result<()> {
+    let mut input: Option<&str> = None;
+    let mut format: Option<InputFormat> = None;
+    let mut options = AnalysisOptions::default();
+    let mut centers: Vec<WindowCenter> = Vec::new();
+    let mut json_path: Option<PathBuf> = None;
+    let mut chimerax_path: Option<PathBuf> = None;
+    let mut pymol_path: Option<PathBuf> = None;
+
+    let mut i = 0usize;
+    while i < args.len() {
+        if parse_analysis_flag(args, &mut i, &mut options)? {
+            i += 1;
+            continue;
+        }
+        match args[i].as_str() {
+            "--format" => {
+                i += 1;
+                format = Some(
+                    args.get(i)
+                        .and_then(|v| InputFormat::from_name(v))
+                        .ok_or_else(|| invalid_input("--format expects pdb, cif or bcif"))?,
+                );
+            }
+            "--window" => {
+                i += 1;
+                let spec = args
+                    .get(i)
+                    .ok_or_else(|| invalid_input("--window expects CHAIN:RESIDUE"))?;
+                centers.push(parse_window(spec)?);
+            }
+            "--json" => match args.get(i + 1) {
+                Some(next) if !next.starts_with("--") && next != "-" => {
+                    i += 1;
+                    json_path = Some(PathBuf::from(next));
+                }
+                Some(next) if next == "-" => i += 1,
+                _ => {}
+            },
+            "--chimerax" => {
+                i += 1;
+                chimerax_path =
+                    Some(PathBuf::from(args.get(i).ok_or_else(|| {
+                        invalid_input("--chimerax expects a file path")
+                    })?));
+            }
+            "--pymol" => {
+                i += 1;
+                pymol_path =
+                    Some(PathBuf::from(args.get(i).ok_or_else(|| {
+                        invalid_input("--pymol expects a file path")
+                    })?));
+            }
+            arg if input.is_none() && (arg == "-" || !arg.starts_with("--")) => {
+                input = Some(arg);
+            }
+            arg => return Err(invalid_input(&format!("unexpected argument {arg}"))),
+        }
+        i += 1;
+    }
+
+    let input = input.ok_or_else(|| invalid_input("explain requires an input path or -"))?;
+    let explanations = if input == "-" {
+        let format = format.ok_or_else(|| invalid_input("reading from stdin requires --format"))?;
+        errat::explain_reader(io::stdin().lock(), format, &centers, &options)?
+    } else {
+        let format = format.unwrap_or_else(|| InputFormat::from_path(Path::new(input)));
+        let reader = BufReader::new(File::open(input)?);
+        errat::explain_reader(reader, format, &centers, &options)?
+    };
+
+    if let Some(path) = chimerax_path {
+        let mut out = BufWriter::new(File::create(path)?);
+        errat::write_chimerax_pseudobonds(&mut out, &explanations)?;
+        out.flush()?;
+    }
+    if let Some(path) = pymol_path {
+        let mut out = BufWriter::new(File::create(path)?);
+        errat::write_pymol_script(&mut out, &explanations)?;
+        out.flush()?;
+    }
+    let mut out = open_output(json_path.as_deref())?;
+    errat::write_explanations_json(&mut out, &explanations)?;
+    out.flush()
+}
+
+fn run_rank(args: &[String]) -> io::Result<()> {
+    let mut input_dir: Option<PathBuf> = None;
+    let mut out_dir: Option<PathBuf> = None;
+    let mut criterion = RankCriterion::QualityFactor;
+    let mut top = 5usize;
+    let mut recursive = false;
+    let mut threads: Option<usize> = None;
+    let mut use_mmap = false;
+    let mut reports = false;
+    let mut output_pdf = false;
+    let mut options = AnalysisOptions::default();
+
+    let mut i = 0usize;
+    while i < args.len() {
+        if parse_analysis_flag(args, &mut i, &mut options)? {
+            i += 1;
+            continue;
+        }
+        match args[i].as_str() {
+            "--by" => {
+                i += 1;
+                criterion = args
+                    .get(i)
+                    .and_then(|v| RankCriterion::from_name(v))
+                    .ok_or_else(|| {
+                        invalid_input("--by expects quality, mean, worst-region or frames99")
+                    })?;
+            }
+            "--top" => {
+                i += 1;
+                top = flag_value(args, i, "--top")?;
+            }
+            "--out-dir" => {
+                i += 1;
+                out_dir = args.get(i).map(PathBuf::from);
+            }
+            "--reports" => reports = true,
+            "--pdf" => output_pdf = true,
+            "--mmap" => use_mmap = true,
+            "--recursive" => recursive = true,
+            "--threads" => {
+                i += 1;
+                threads = args.get(i).and_then(|v| v.parse::<usize>().ok());
+            }
+            arg if input_dir.is_none() && !arg.starts_with("--") => {
+                input_dir = Some(PathBuf::from(arg));
+            }
+            arg => return Err(invalid_input(&format!("unexpected argument {arg}"))),
+        }
+        i += 1;
+    }
+
+    let input_dir = input_dir.ok_or_else(|| invalid_input("rank requires an input directory"))?;
+    if reports && out_dir.is_none() {
+        return Err(invalid_input("--reports requires --out-dir"));
+    }
+    let inputs = collect_inputs(&input_dir, recursive)?;
+    if inputs.is_empty() {
+        return Err(invalid_input("no structure files found in input directory"));
+    }
+
+    let report_dir = out_dir
+        .as_ref()
+        .filter(|_| reports)
+        .map(|dir| dir.join("reports"));
+    let ranking = in_pool(threads, || {
+        errat::rank_models(
+            &inputs,
+            criterion,
+            &options,
+            use_mmap,
+            report_dir.as_deref(),
+            output_pdf,
+        )
+    })??;
+    for skipped in &ranking.skipped {
+        eprintln!("Skipped {skipped}");
+    }
+
+    let Some(out_dir) = out_dir else {
+        let mut out = BufWriter::new(io::stdout().lock());
+        errat::write_ranking_csv(&mut out, &ranking.models)?;
+        return out.flush();
+    };
+    std::fs::create_dir_all(&out_dir)?;
+    let mut out = BufWriter::new(File::create(out_dir.join("ranking.csv"))?);
+    errat::write_ranking_csv(&mut out, &ranking.models)?;
+    out.flush()?;
+    for model in ranking.models.iter().take(top) {
+        let analysis = &model.analysis;
+        let path = out_dir.join(format!("{:03}_{}.pdb", model.rank, analysis.protein_id));
+        let mut out = BufWriter::new(File::create(path)?);
+        errat::write_annotated_structure(&mut out, &analysis.input_path, use_mmap, &options)?;
+        out.flush()?;
+    }
+    println!(
+        "Ranked {} models; table and top {} structures written to {}",
+        ranking.models.len(),
+        top.min(ranking.models.len()),
+        out_dir.display()
+    );
+    Ok(())
+}
+
+fn run_trajectory(args: &[String]) -> io::Result<()> {
+    let mut paths: Vec<PathBuf> = Vec::new();
+    let mut matrix: Option<Option<PathBuf>> = None;
+    let mut series: Option<Option<PathBuf>> = None;
+    let mut stride = 1usize;
+    let mut use_mmap = false;
+    let mut options = AnalysisOptions::default();
+
+    let mut i = 0usize;
+    while i < args.len() {
+        if parse_analysis_flag(args, &mut i, &mut options)? {
+            i += 1;
+            continue;
+        }
+        match args[i].as_str() {
+            flag @ ("--matrix" | "--series") => {
+                let path = match args.get(i + 1) {
+                    Some(next) if next == "-" => {
+                        i += 1;
+                        None
+                    }
+                    Some(next) if !next.starts_with("--") => {
+                        i += 1;
+                        Some(PathBuf::from(next))
+                    }
+                    _ => None,
+                };
+                if flag == "--matrix" {
+                    matrix = Some(path);
+                } else {
+                    series = Some(path);
+                }
+            }
+            "--stride" => {
+                i += 1;
+                stride = flag_value(args, i, "--stride")?;
+                if stride == 0 {
+                    return Err(invalid_input("--stride must be at least 1"));
+                }
+            }
+            "--mmap" => use_mmap = true,
+            arg if paths.len() < 2 && !arg.starts_with("--") => {
+                paths.push(PathBuf::from(arg));
+            }
+            arg => return Err(invalid_input(&format!("unexpected argument {arg}"))),
+        }
+        i += 1;
+    }
+
+    let [topology, trajectory] = paths.as_slice() else {
+        return Err(invalid_input(
+            "trajectory requires a topology and a trajectory file",
+        ));
+    };
+    if matrix.is_none() && series.is_none() {
+        series = Some(None);
+    }
+    let analysis = errat::analyze_trajectory(topology, trajectory, use_mmap, &options, stride)?;
+    if let Some(path) = matrix {
+        let mut out = open_output(path.as_deref())?;
+        errat::write_trajectory_matrix_csv(&mut out, &analysis)?;
+        out.flush()?;
+    }
+    if let Some(path) = series {
+        let mut out = open_output(path.as_deref())?;
+        errat::write_trajectory_series_csv(&mut out, &analysis)?;
+        out.flush()?;
+    }
+    Ok(())
+}
+
+fn run_reference(args: &[String]) -> io::Result<()> {
+    let mut input_dir: Option<PathBuf> = None;
+    let mut output: Option<PathBuf> = None;
+    let mut recursive = false;
+    let mut threads: Option<usize> = None;
+    let mut use_mmap = false;
+    let mut options = AnalysisOptions::default();
+
+    let mut i = 0usize;
+    while i < args.len() {
+        if parse_analysis_flag(args, &mut i, &mut options)? {
+            i += 1;
+            continue;
+        }
+        match args[i].as_str() {
+            "--output" => {
+                i += 1;
+                output = args.get(i).map(PathBuf::from);
+            }
+            "--mmap" => use_mmap = true,
+            "--recursive" => recursive = true,
+            "--threads" => {
+                i += 1;
+                threads = args.get(i).and_then(|v| v.parse::<usize>().ok());
+            }
+            arg if input_dir.is_none() && !arg.starts_with("--") => {
+                input_dir = Some(PathBuf::from(arg));
+            }
+            arg => return Err(invalid_input(&format!("unexpected argument {arg}"))),
+        }
+        i += 1;
+    }
+
+    let input_dir =
+        input_dir.ok_or_else(|| invalid_input("reference requires an input directory"))?;
+    let output = output.ok_or_else(|| invalid_input("reference requires --output <file>"))?;
+    let inputs = collect_inputs(&input_dir, recursive)?;
+    if inputs.is_empty() {
+        return Err(invalid_input("no structure files found in input directory"));
+    }
+
+    let report = in_pool(threads, || {
+        errat::build_corpus_reference(&inputs, &options, use_mmap)
+    })??;
+
+    for skipped in &report.skipped {
+        eprintln!("Skipped {skipped}");
+    }
+    let mut out = BufWriter::new(File::create(&output)?);
+    writeln!(
+        out,
+        "# ERRAT reference distribution from {} structures",
+        report.structure_count
+    )?;
+    report.reference.write_to(&mut out)?;
+    out.flush()?;
+    println!(
+        "Reference written to {} from {} structures ({} buckets).",
+        output.display(),
+        report.structure_count,
+        report.reference.buckets.len()
+    );
+    Ok(())
+}
+
+fn run_train(args: &[String]) -> io::Result<()> {
+    let mut input_dir: Option<PathBuf> = None;
+    let mut output: Option<PathBuf> = None;
+    let mut recursive = false;
+    let mut threads: Option<usize> = None;
+    let mut base = Calibration::classic();
+    let mut window_length: Option<usize> = None;
+    let mut radius: Option<f64> = None;
+    let mut radmin: Option<f64> = None;
+
+    let mut i = 0usize;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--base" => {
+                i += 1;
+                let path = args
+                    .get(i)
+                    .ok_or_else(|| invalid_input("--base expects a calibration file"))?;
+                base = Calibration::load(path)?;
+            }
+            "--window-length" => {
+                i += 1;
+                window_length = Some(flag_value(args, i, "--window-length")?);
+            }
+            "--radius" => {
+                i += 1;
+                radius = Some(flag_value(args, i, "--radius")?);
+            }
+            "--radmin" => {
+                i += 1;
+                radmin = Some(flag_value(args, i, "--radmin")?);
+            }
+            "--output" => {
+                i += 1;
+                output = args.get(i).map(PathBuf::from);
+            }
+            "--recursive" => recursive = true,
+            "--threads" => {
+                i += 1;
+                threads = args.get(i).and_then(|v| v.parse::<usize>().ok());
+            }
+            arg if input_dir.is_none() && !arg.starts_with("--") => {
+                input_dir = Some(PathBuf::from(arg));
+            }
+            arg => return Err(invalid_input(&format!("unexpected argument {arg}"))),
+        }
+        i += 1;
+    }
+
+    base.window_length = window_length.unwrap_or(base.window_length);
+    base.radius = radius.unwrap_or(base.radius);
+    base.radmin = radmin.unwrap_or(base.radmin);
+    let input_dir = input_dir.ok_or_else(|| invalid_input("train requires an input directory"))?;
+    let output = output.ok_or_else(|| invalid_input("train requires --output <file>"))?;
+    let inputs = collect_inputs(&input_dir, recursive)?;
+    if inputs.is_empty() {
+        return Err(invalid_input("no structure files found in input directory"));
+    }
+
+    let report = in_pool(threads, || errat::train_calibration(&inputs, &base))??;
+
+    for skipped in &report.skipped {
+        eprintln!("Skipped {skipped}");
+    }
+    let mut out = BufWriter::new(File::create(&output)?);
+    writeln!(
+        out,
+        "# ERRAT calibration trained on {} structures ({} windows)",
+        report.structure_count, report.window_count
+    )?;
+    report.calibration.write_to(&mut out)?;
+    out.flush()?;
+    println!(
+        "Calibration written to {} from {} structures ({} windows).",
+        output.display(),
+        report.structure_count,
+        report.window_count
+    );
+    Ok(())
+}
+
 fn main() {
     let args: Vec<String> = env::args().collect();
     if args.len() == 1 || args.iter().any(|a| a == "-h" || a == "--help") {
@@ -81,6 +819,26 @@ fn main() {
         return;
     }
 
+    if matches!(
+        args[1].as_str(),
+        "analyze" | "compare" | "explain" | "rank" | "trajectory" | "reference" | "train"
+    ) {
+        let result = match args[1].as_str() {
+            "analyze" => run_analyze(&args[2..]),
+            "compare" => run_compare(&args[2..]),
+            "rank" => run_rank(&args[2..]),
+            "trajectory" => run_trajectory(&args[2..]),
+            "reference" => run_reference(&args[2..]),
+            "explain" => run_explain(&args[2..]),
+            _ => run_train(&args[2..]),
+        };
+        if let Err(err) = result {
+            eprintln!("ERRAT failed: {err}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
     let mut input_pdb: Option<PathBuf> = None;
     let mut output_dir: Option<PathBuf> = None;
     let mut protein_id: Option<String> = None;
@@ -135,14 +893,12 @@ fn main() {
         i += 1;
     }
 
-    if protein_id.is_none() {
-        if let Some(input_pdb) = input_pdb.as_ref() {
-            if let Some(stem) = input_pdb.file_stem().and_then(|s| s.to_str()) {
-                if !stem.is_empty() {
-                    protein_id = Some(stem.to_string());
-                }
-            }
-        }
+    if protein_id.is_none()
+        && let Some(input_pdb) = input_pdb.as_ref()
+        && let Some(stem) = input_pdb.file_stem().and_then(|s| s.to_str())
+        && !stem.is_empty()
+    {
+        protein_id = Some(stem.to_string());
     }
 
     if jobs_dir.is_some() && input_dir.is_some() {
@@ -1,33 +1,88 @@
 use std::path::PathBuf;
 
+use crate::api::{SecondaryStructure, WindowInteractions};
+use crate::calibration::Calibration;
+
 pub(crate) const SIZE: usize = 250_000;
 pub(crate) const BXMX: usize = 200_000;
 pub(crate) const CHAINDIF: i32 = 10_000;
-pub(crate) const BOXSIZE: f64 = 4.0;
-pub(crate) const RADIUS: f64 = 3.75;
-pub(crate) const RADMIN: f64 = 3.25;
-pub(crate) const MAXWIN: f64 = 100.694;
-pub(crate) const LMT_95: f64 = 11.526_684_477_428_809;
-pub(crate) const LMT_99: f64 = 17.190_823_041_860_433;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct CrystalInfo {
+    pub(crate) cell: Option<[f64; 6]>,
+    pub(crate) space_group: Option<String>,
+    pub(crate) fractional_ops: Vec<String>,
+    pub(crate) cartesian_ops: Vec<[[f64; 4]; 3]>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct AssemblyPart {
+    pub(crate) chains: Vec<u8>,
+    pub(crate) operators: Vec<[[f64; 4]; 3]>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct Assembly {
+    pub(crate) id: String,
+    pub(crate) parts: Vec<AssemblyPart>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct Experiment {
+    pub(crate) method: Option<String>,
+    pub(crate) resolution: Option<f64>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct SecondaryRange {
+    pub(crate) kind: SecondaryStructure,
+    pub(crate) chain_id: u8,
+    pub(crate) start: i32,
+    pub(crate) end: i32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct HeteroAtom {
+    pub(crate) name: i32,
+    pub(crate) atom_name: [u8; 4],
+    pub(crate) res_name: [u8; 3],
+    pub(crate) chain_id: u8,
+    pub(crate) res_seq: i32,
+    pub(crate) source_index: usize,
+    pub(crate) point: [f64; 3],
+}
 
 #[derive(Clone, Debug)]
 pub(crate) struct AtomData {
     pub(crate) atmnum: usize,
+    pub(crate) environment_atoms: usize,
+    pub(crate) records: usize,
     pub(crate) name: Vec<i32>,
     pub(crate) bnam: Vec<i32>,
+    pub(crate) atom_name: Vec<[u8; 4]>,
+    pub(crate) res_name: Vec<[u8; 3]>,
     pub(crate) chain_id: Vec<u8>,
     pub(crate) res_seq: Vec<i32>,
     pub(crate) resnum: Vec<i32>,
     pub(crate) xyz_x: Vec<f64>,
     pub(crate) xyz_y: Vec<f64>,
     pub(crate) xyz_z: Vec<f64>,
+    pub(crate) b_factor: Vec<f64>,
     pub(crate) errat: Vec<f64>,
+    pub(crate) source_index: Vec<usize>,
+    pub(crate) crystal: CrystalInfo,
+    pub(crate) assemblies: Vec<Assembly>,
+    pub(crate) hetero_atoms: Vec<HeteroAtom>,
+    pub(crate) experiment: Experiment,
+    pub(crate) secondary: Vec<SecondaryRange>,
 }
 
 #[derive(Clone, Copy, Debug)]
 pub(crate) struct FrameScoreRaw {
     pub(crate) center_residue: i32,
+    pub(crate) residue_offset: i32,
     pub(crate) error_value: f64,
+    pub(crate) interactions: WindowInteractions,
 }
 
 #[derive(Clone, Debug)]
@@ -42,6 +97,13 @@ pub(crate) struct ErratStats {
     pub(crate) atmnum: usize,
     pub(crate) warning_frames: Vec<i32>,
     pub(crate) scored_frames: Vec<FrameScoreRaw>,
+    pub(crate) interface_residues: Vec<(i32, u8)>,
+    pub(crate) experiment: Experiment,
+    pub(crate) quality_percentile: Option<f64>,
+    pub(crate) plddt: Vec<(i32, u8, f64)>,
+    pub(crate) plddt_cutoff: Option<f64>,
+    pub(crate) secondary: Vec<(i32, SecondaryStructure)>,
+    pub(crate) calibration: Calibration,
 }
 
 #[derive(Clone, Debug)]
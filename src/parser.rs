@@ -1,46 +1,51 @@
 use std::fs::File;
-use std::io::{self, BufRead, BufReader, Read, Write};
+use std::io::{self, BufRead, BufReader, Write};
 use std::path::PathBuf;
 
 use memmap2::MmapOptions;
 
-use crate::model::{AtomData, CHAINDIF, SIZE};
+use crate::api::InputFormat;
+use crate::assembly::{AssemblyRecords, assembly_record};
+use crate::bcif::read_bcif;
+use crate::cif::{CifVisitor, Flow, read_cif, split_tag};
+use crate::model::{Assembly, AtomData, CrystalInfo, Experiment, SecondaryRange};
+use crate::resolution::{experiment_item, experiment_record, is_experiment_category};
+use crate::secondary::{is_secondary_category, secondary_record, secondary_row};
+use crate::sink::{AtomRecord, AtomSink};
+use crate::symmetry::{crystal_item, crystal_record, is_crystal_category};
 
 pub(crate) fn parse_structure<W: Write>(
     path: &PathBuf,
     logw: &mut W,
     use_mmap: bool,
 ) -> io::Result<AtomData> {
-    let ext = path
-        .extension()
-        .and_then(|s| s.to_str())
-        .unwrap_or("")
-        .to_ascii_lowercase();
-    if ext == "cif" || ext == "mmcif" {
-        let pdbf = File::open(path)?;
-        let mut reader = BufReader::new(pdbf);
-        parse_mmcif(&mut reader, logw)
-    } else if use_mmap {
-        parse_pdb_mmap(path, logw)
-    } else {
-        let pdbf = File::open(path)?;
-        let mut reader = BufReader::new(pdbf);
-        parse_pdb(&mut reader, logw)
+    match InputFormat::from_path(path) {
+        InputFormat::BinaryCif => {
+            let bytes = std::fs::read(path)?;
+            parse_bcif(&bytes, logw)
+        }
+        InputFormat::Pdb if use_mmap => parse_pdb_mmap(path, logw),
+        format => {
+            let pdbf = File::open(path)?;
+            let mut reader = BufReader::new(pdbf);
+            parse_reader(&mut reader, format, logw)
+        }
     }
 }
 
-fn empty_atom_data() -> AtomData {
-    AtomData {
-        atmnum: 0,
-        name: vec![0i32; SIZE + 2],
-        bnam: vec![0i32; SIZE + 2],
-        chain_id: vec![b' '; SIZE + 2],
-        res_seq: vec![0i32; SIZE + 2],
-        resnum: vec![0i32; SIZE + 2],
-        xyz_x: vec![0.0f64; SIZE + 2],
-        xyz_y: vec![0.0f64; SIZE + 2],
-        xyz_z: vec![0.0f64; SIZE + 2],
-        errat: vec![0.0f64; SIZE + 8],
+pub(crate) fn parse_reader<R: BufRead, W: Write>(
+    reader: &mut R,
+    format: InputFormat,
+    logw: &mut W,
+) -> io::Result<AtomData> {
+    match format {
+        InputFormat::Pdb => parse_pdb(reader, logw),
+        InputFormat::Mmcif => parse_mmcif(reader, logw),
+        InputFormat::BinaryCif => {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes)?;
+            parse_bcif(&bytes, logw)
+        }
     }
 }
 
@@ -50,633 +55,330 @@ fn parse_pdb_mmap<W: Write>(path: &PathBuf, logw: &mut W) -> io::Result<AtomData
     parse_pdb_bytes(&mmap, logw)
 }
 
-fn parse_pdb_bytes<W: Write>(bytes: &[u8], logw: &mut W) -> io::Result<AtomData> {
-    let mut data = empty_atom_data();
-    let mut i: usize = 0;
-    let mut atmnum: usize = 0;
-    let mut kadd: i32 = 0;
-    let mut flag = false;
-    let mut flag2 = false;
-
-    let mut start = 0usize;
-    while start < bytes.len() && !flag2 {
-        let mut end = start;
-        while end < bytes.len() && bytes[end] != b'\n' {
-            end += 1;
-        }
-        let mut line = &bytes[start..end];
-        if line.ends_with(b"\r") {
-            line = &line[..line.len().saturating_sub(1)];
-        }
-        start = end + 1;
-
-        if line.len() < 6 || &line[..6] != b"ATOM  " {
-            continue;
-        }
-        if i + 1 > SIZE - 1 {
-            writeln!(
-                logw,
-                "ERROR: PDB WITH TOO MANY ATOMS. CUT OFF FURTHER INPUT."
-            )?;
-            break;
-        }
-        i += 1;
-        if line.len() < 54 {
-            i -= 1;
-            continue;
-        }
+fn trim_line_end(line: &[u8]) -> &[u8] {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
 
-        let name_temp = line[13];
-        data.name[i] = match name_temp {
-            b'C' => 1,
-            b'N' => 2,
-            b'O' => 3,
-            _ => 0,
-        };
+fn name_element(atom_name: &[u8]) -> &[u8] {
+    let name = atom_name.trim_ascii_start();
+    let start = name
+        .iter()
+        .position(|b| !b.is_ascii_digit())
+        .unwrap_or(name.len());
+    &name[start..name.len().min(start + 1)]
+}
 
-        if line.len() < 16 {
-            i -= 1;
-            continue;
-        }
-        let name_temp2 = &line[13..16];
-        data.bnam[i] = if name_temp2 == b"N  " || name_temp2 == b"C  " {
-            1
+fn pdb_record(line: &[u8]) -> Option<AtomRecord<'_>> {
+    let hetero = match line.get(..6)? {
+        b"ATOM  " => false,
+        b"HETATM" => true,
+        _ => return None,
+    };
+    if line.len() < 54 {
+        return None;
+    }
+    let number = |start: usize, end: usize| {
+        std::str::from_utf8(&line[start..end])
+            .unwrap_or("")
+            .trim()
+            .parse::<f64>()
+            .unwrap_or(0.0)
+    };
+    let element = match line.get(76..).map(|e| e.get(..2).unwrap_or(e).trim_ascii()) {
+        Some(element) if !element.is_empty() => element,
+        _ => name_element(&line[12..16]),
+    };
+    Some(AtomRecord {
+        hetero,
+        element,
+        backbone: matches!(&line[13..16], b"N  " | b"C  "),
+        atom_name: &line[12..16],
+        alt_loc: line[16],
+        res_name: &line[17..20],
+        chain_id: line[21],
+        res_seq: number(22, 26) as i32,
+        x: number(30, 38),
+        y: number(38, 46),
+        z: number(46, 54),
+        b_factor: if line.len() >= 66 {
+            number(60, 66)
         } else {
-            0
-        };
-
-        let alt_loc = line[16] as char;
-        let res_name = &line[17..20];
-        data.chain_id[i] = line[21];
-
-        let res_seq_temp = std::str::from_utf8(&line[22..26]).unwrap_or("");
-        let res_seq_val = res_seq_temp.trim().parse::<f64>().unwrap_or(0.0);
-        data.res_seq[i] = res_seq_val as i32;
-
-        let x_temp = std::str::from_utf8(&line[30..38]).unwrap_or("");
-        let y_temp = std::str::from_utf8(&line[38..46]).unwrap_or("");
-        let z_temp = std::str::from_utf8(&line[46..54]).unwrap_or("");
-        data.xyz_x[i] = x_temp.trim().parse::<f64>().unwrap_or(0.0);
-        data.xyz_y[i] = y_temp.trim().parse::<f64>().unwrap_or(0.0);
-        data.xyz_z[i] = z_temp.trim().parse::<f64>().unwrap_or(0.0);
-
-        if !(alt_loc == ' ' || alt_loc == 'A' || alt_loc == 'a' || alt_loc == 'P') {
-            writeln!(
-                logw,
-                "Reject 2' Conformation atom#\t{}\tchain\t{}",
-                i, data.chain_id[i] as char
-            )?;
-            i -= 1;
-            flag = true;
-        }
-
-        if !is_standard_residue(res_name) {
-            i -= 1;
-            flag = true;
-            let res_name_str = std::str::from_utf8(res_name).unwrap_or("???");
-            writeln!(
-                logw,
-                "***Warning: Reject Nonstardard Residue - {}",
-                res_name_str
-            )?;
-        }
-
-        if i >= 2 && !flag && data.chain_id[i] != data.chain_id[i - 1] {
-            kadd += 1;
-            writeln!(logw, "INCREMENTING CHAIN (kadd) {}", kadd)?;
-        }
-
-        if !flag {
-            data.resnum[i] = data.res_seq[i] + (kadd * CHAINDIF);
-            atmnum = i;
-        }
+            0.0
+        },
+    })
+}
 
-        if i >= 2
-            && !flag
-            && data.chain_id[i] == data.chain_id[i - 1]
-            && data.resnum[i] < data.resnum[i - 1]
-        {
-            writeln!(
-                logw,
-                "ERROR: RESNUM DECREASE. TERMINATE ANALYSIS{}\t{}",
-                data.resnum[i],
-                data.resnum[i - 1]
-            )?;
-            flag2 = true;
-        }
+fn pdb_header(
+    line: &[u8],
+    crystal: &mut CrystalInfo,
+    assemblies: &mut Vec<Assembly>,
+    experiment: &mut Experiment,
+    secondary: &mut Vec<SecondaryRange>,
+) {
+    if line.starts_with(b"EXPDTA") || line.starts_with(b"REMARK   2") {
+        experiment_record(line, experiment);
+    } else if line.starts_with(b"HELIX") || line.starts_with(b"SHEET") {
+        secondary_record(line, secondary);
+    } else if line.starts_with(b"CRYST1") || line.starts_with(b"REMARK 290") {
+        crystal_record(line, crystal);
+    } else if line.starts_with(b"REMARK 350") {
+        assembly_record(line, assemblies);
+    }
+}
 
-        if i > 2
-            && !flag
-            && data.chain_id[i] == data.chain_id[i - 1]
-            && data.resnum[i] != data.resnum[i - 1]
-            && (data.resnum[i] - data.resnum[i - 1]) > 1
+fn parse_pdb_bytes<W: Write>(bytes: &[u8], logw: &mut W) -> io::Result<AtomData> {
+    let mut sink = AtomSink::new(logw);
+    let mut crystal = CrystalInfo::default();
+    let mut assemblies = Vec::new();
+    let mut experiment = Experiment::default();
+    let mut secondary = Vec::new();
+    for line in bytes.split(|&b| b == b'\n') {
+        let line = trim_line_end(line);
+        pdb_header(
+            line,
+            &mut crystal,
+            &mut assemblies,
+            &mut experiment,
+            &mut secondary,
+        );
+        if let Some(atom) = pdb_record(line)
+            && sink.push(&atom)? == Flow::Stop
         {
-            writeln!(
-                logw,
-                "WARNING: Missing Residues{}>>>{}",
-                data.resnum[i - 1],
-                data.resnum[i]
-            )?;
-        }
-
-        if !flag {
-            let idx = (data.resnum[i] + 4) as usize;
-            if idx >= data.errat.len() {
-                data.errat.resize(idx + 1, 0.0);
-            }
-            data.errat[idx] = 0.0;
+            break;
         }
-
-        flag = false;
     }
-
-    data.atmnum = atmnum;
+    let mut data = sink.finish()?;
+    data.crystal = crystal;
+    data.assemblies = assemblies;
+    data.experiment = experiment;
+    data.secondary = secondary;
     Ok(data)
 }
 
 fn parse_pdb<R: BufRead, W: Write>(reader: &mut R, logw: &mut W) -> io::Result<AtomData> {
-    let mut data = empty_atom_data();
-    let mut i: usize = 0;
-    let mut atmnum: usize = 0;
-    let mut kadd: i32 = 0;
-    let mut flag = false;
-    let mut flag2 = false;
-    let mut line = String::new();
-    while !flag2 {
+    let mut sink = AtomSink::new(logw);
+    let mut crystal = CrystalInfo::default();
+    let mut assemblies = Vec::new();
+    let mut experiment = Experiment::default();
+    let mut secondary = Vec::new();
+    let mut line = Vec::new();
+    loop {
         line.clear();
-        let bytes_read = reader.read_line(&mut line)?;
-        if bytes_read == 0 {
-            break;
-        }
-        let bytes = line.as_bytes();
-        if bytes.len() < 6 || &bytes[..6] != b"ATOM  " {
-            continue;
-        }
-        if i + 1 > SIZE - 1 {
-            writeln!(
-                logw,
-                "ERROR: PDB WITH TOO MANY ATOMS. CUT OFF FURTHER INPUT."
-            )?;
+        if reader.read_until(b'\n', &mut line)? == 0 {
             break;
         }
-        i += 1;
-        if bytes.len() < 54 {
-            i -= 1;
-            continue;
-        }
-
-        let name_temp = bytes[13];
-        data.name[i] = match name_temp {
-            b'C' => 1,
-            b'N' => 2,
-            b'O' => 3,
-            _ => 0,
-        };
-
-        if bytes.len() < 16 {
-            i -= 1;
-            continue;
-        }
-        let name_temp2 = &bytes[13..16];
-        data.bnam[i] = if name_temp2 == b"N  " || name_temp2 == b"C  " {
-            1
-        } else {
-            0
-        };
-
-        let alt_loc = bytes[16] as char;
-        let res_name = &bytes[17..20];
-        data.chain_id[i] = bytes[21];
-
-        let res_seq_temp = std::str::from_utf8(&bytes[22..26]).unwrap_or("");
-        let res_seq_val = res_seq_temp.trim().parse::<f64>().unwrap_or(0.0);
-        data.res_seq[i] = res_seq_val as i32;
-
-        let x_temp = std::str::from_utf8(&bytes[30..38]).unwrap_or("");
-        let y_temp = std::str::from_utf8(&bytes[38..46]).unwrap_or("");
-        let z_temp = std::str::from_utf8(&bytes[46..54]).unwrap_or("");
-        data.xyz_x[i] = x_temp.trim().parse::<f64>().unwrap_or(0.0);
-        data.xyz_y[i] = y_temp.trim().parse::<f64>().unwrap_or(0.0);
-        data.xyz_z[i] = z_temp.trim().parse::<f64>().unwrap_or(0.0);
-
-        if !(alt_loc == ' ' || alt_loc == 'A' || alt_loc == 'a' || alt_loc == 'P') {
-            writeln!(
-                logw,
-                "Reject 2' Conformation atom#\t{}\tchain\t{}",
-                i, data.chain_id[i] as char
-            )?;
-            i -= 1;
-            flag = true;
-        }
-
-        if !is_standard_residue(res_name) {
-            i -= 1;
-            flag = true;
-            let res_name_str = std::str::from_utf8(res_name).unwrap_or("???");
-            writeln!(
-                logw,
-                "***Warning: Reject Nonstardard Residue - {}",
-                res_name_str
-            )?;
-        }
-
-        if i >= 2 && !flag && data.chain_id[i] != data.chain_id[i - 1] {
-            kadd += 1;
-            writeln!(logw, "INCREMENTING CHAIN (kadd) {}", kadd)?;
-        }
-
-        if !flag {
-            data.resnum[i] = data.res_seq[i] + (kadd * CHAINDIF);
-            atmnum = i;
-        }
-
-        if i >= 2
-            && !flag
-            && data.chain_id[i] == data.chain_id[i - 1]
-            && data.resnum[i] < data.resnum[i - 1]
-        {
-            writeln!(
-                logw,
-                "ERROR: RESNUM DECREASE. TERMINATE ANALYSIS{}\t{}",
-                data.resnum[i],
-                data.resnum[i - 1]
-            )?;
-            flag2 = true;
-        }
-
-        if i > 2
-            && !flag
-            && data.chain_id[i] == data.chain_id[i - 1]
-            && data.resnum[i] != data.resnum[i - 1]
-            && (data.resnum[i] - data.resnum[i - 1]) > 1
+        let line = trim_line_end(&line);
+        pdb_header(
+            line,
+            &mut crystal,
+            &mut assemblies,
+            &mut experiment,
+            &mut secondary,
+        );
+        if let Some(atom) = pdb_record(line)
+            && sink.push(&atom)? == Flow::Stop
         {
-            writeln!(
-                logw,
-                "WARNING: Missing Residues{}>>>{}",
-                data.resnum[i - 1],
-                data.resnum[i]
-            )?;
-        }
-
-        if !flag {
-            let idx = (data.resnum[i] + 4) as usize;
-            if idx >= data.errat.len() {
-                data.errat.resize(idx + 1, 0.0);
-            }
-            data.errat[idx] = 0.0;
+            break;
         }
-
-        flag = false;
     }
-
-    data.atmnum = atmnum;
+    let mut data = sink.finish()?;
+    data.crystal = crystal;
+    data.assemblies = assemblies;
+    data.experiment = experiment;
+    data.secondary = secondary;
     Ok(data)
 }
 
-fn parse_mmcif<R: Read, W: Write>(reader: &mut R, logw: &mut W) -> io::Result<AtomData> {
-    let mut data = empty_atom_data();
-    let mut input = String::new();
-    reader.read_to_string(&mut input)?;
-    let tokens = tokenize_cif(&input);
-
-    let mut i: usize = 0;
-    let mut atmnum: usize = 0;
-    let mut kadd: i32 = 0;
-    let mut flag = false;
-    let mut flag2 = false;
-
-    let mut idx = 0;
-    while idx < tokens.len() {
-        if tokens[idx] != "loop_" {
-            idx += 1;
-            continue;
-        }
-        idx += 1;
-        let mut cols = Vec::new();
-        while idx < tokens.len() && tokens[idx].starts_with('_') {
-            cols.push(tokens[idx].clone());
-            idx += 1;
-        }
-        if cols.is_empty() {
-            continue;
-        }
-
-        let is_atom_site = cols.iter().any(|c| c.starts_with("_atom_site."));
-        let col_count = cols.len();
-
-        if !is_atom_site {
-            while idx + col_count <= tokens.len() {
-                let t = &tokens[idx];
-                if t == "loop_"
-                    || t.starts_with('_')
-                    || t.starts_with("data_")
-                    || t.starts_with("save_")
-                    || t == "stop_"
-                {
-                    break;
-                }
-                idx += col_count;
-            }
-            continue;
-        }
+struct AtomSiteColumns {
+    group: Option<usize>,
+    atom: usize,
+    element: Option<usize>,
+    alt: Option<usize>,
+    res: usize,
+    chain: usize,
+    label_chain: Option<usize>,
+    seq: usize,
+    x: usize,
+    y: usize,
+    z: usize,
+    b_factor: Option<usize>,
+}
 
+impl AtomSiteColumns {
+    fn resolve(columns: &[String]) -> io::Result<Self> {
         let col_index = |name: &str| -> Option<usize> {
-            cols.iter().position(|c| {
-                if c == name {
-                    true
-                } else if name.starts_with("_atom_site.") {
-                    false
-                } else {
-                    c.ends_with(&format!(".{name}"))
-                }
-            })
+            columns
+                .iter()
+                .position(|c| split_tag(c).1.eq_ignore_ascii_case(name))
         };
 
-        let idx_group = col_index("group_PDB");
-        let idx_atom = col_index("label_atom_id");
-        let idx_type = col_index("type_symbol");
-        let idx_alt = col_index("label_alt_id");
-        let idx_res = col_index("label_comp_id");
-        let idx_chain = col_index("auth_asym_id").or_else(|| col_index("label_asym_id"));
-        let idx_seq = col_index("auth_seq_id").or_else(|| col_index("label_seq_id"));
-        let idx_x = col_index("Cartn_x");
-        let idx_y = col_index("Cartn_y");
-        let idx_z = col_index("Cartn_z");
-
-        if idx_atom.is_none() || idx_res.is_none() || idx_chain.is_none() || idx_seq.is_none() {
+        let (Some(atom), Some(res), Some(chain), Some(seq)) = (
+            col_index("label_atom_id"),
+            col_index("label_comp_id"),
+            col_index("auth_asym_id").or_else(|| col_index("label_asym_id")),
+            col_index("auth_seq_id").or_else(|| col_index("label_seq_id")),
+        ) else {
             return Err(io::Error::new(
                 io::ErrorKind::InvalidData,
                 "mmCIF missing required _atom_site columns",
             ));
-        }
-        if idx_x.is_none() || idx_y.is_none() || idx_z.is_none() {
+        };
+        let (Some(x), Some(y), Some(z)) = (
+            col_index("Cartn_x"),
+            col_index("Cartn_y"),
+            col_index("Cartn_z"),
+        ) else {
             return Err(io::Error::new(
                 io::ErrorKind::InvalidData,
                 "mmCIF missing coordinate columns",
             ));
-        }
-
-        while idx + col_count <= tokens.len() {
-            let t = &tokens[idx];
-            if t == "loop_"
-                || t.starts_with('_')
-                || t.starts_with("data_")
-                || t.starts_with("save_")
-                || t == "stop_"
-            {
-                break;
-            }
-
-            let row = &tokens[idx..idx + col_count];
-            idx += col_count;
-
-            if let Some(g) = idx_group {
-                if row[g].as_str() != "ATOM" {
-                    continue;
-                }
-            }
-
-            if i + 1 > SIZE - 1 {
-                writeln!(
-                    logw,
-                    "ERROR: PDB WITH TOO MANY ATOMS. CUT OFF FURTHER INPUT."
-                )?;
-                break;
-            }
-            i += 1;
-
-            let atom_name = row[idx_atom.expect("idx_atom checked above")].as_str();
-            let element = idx_type
-                .and_then(|k| row.get(k))
-                .map(|s| s.as_str())
-                .unwrap_or(atom_name);
-            let element_char = element.chars().next().unwrap_or(' ');
-            data.name[i] = match element_char {
-                'C' | 'c' => 1,
-                'N' | 'n' => 2,
-                'O' | 'o' => 3,
-                _ => 0,
-            };
-            data.bnam[i] = if atom_name == "N" || atom_name == "C" {
-                1
-            } else {
-                0
-            };
-
-            let alt_loc = idx_alt
-                .and_then(|k| row.get(k))
-                .map(|s| s.as_str())
-                .unwrap_or(".");
-            let alt_loc_char = alt_loc.chars().next().unwrap_or(' ');
-            let alt_loc_char = match alt_loc_char {
-                '.' | '?' => ' ',
-                c => c,
-            };
-
-            let res_name_str = row[idx_res.expect("idx_res checked above")].as_str();
-            let res_name_upper = res_name_str.to_ascii_uppercase();
-            let res_name = res_name_upper.as_bytes();
-            let chain = row[idx_chain.expect("idx_chain checked above")].as_bytes();
-            data.chain_id[i] = if chain.is_empty() { b' ' } else { chain[0] };
-
-            let res_seq_val = row[idx_seq.expect("idx_seq checked above")]
-                .parse::<f64>()
-                .unwrap_or(0.0);
-            data.res_seq[i] = res_seq_val as i32;
-
-            data.xyz_x[i] = row[idx_x.expect("idx_x checked above")]
-                .parse::<f64>()
-                .unwrap_or(0.0);
-            data.xyz_y[i] = row[idx_y.expect("idx_y checked above")]
-                .parse::<f64>()
-                .unwrap_or(0.0);
-            data.xyz_z[i] = row[idx_z.expect("idx_z checked above")]
-                .parse::<f64>()
-                .unwrap_or(0.0);
-
-            if !(alt_loc_char == ' '
-                || alt_loc_char == 'A'
-                || alt_loc_char == 'a'
-                || alt_loc_char == 'P')
-            {
-                writeln!(
-                    logw,
-                    "Reject 2' Conformation atom#\t{}\tchain\t{}",
-                    i, data.chain_id[i] as char
-                )?;
-                i -= 1;
-                flag = true;
-            }
-
-            if !is_standard_residue(res_name) {
-                i -= 1;
-                flag = true;
-                let res_name_str = std::str::from_utf8(res_name).unwrap_or("???");
-                writeln!(
-                    logw,
-                    "***Warning: Reject Nonstardard Residue - {}",
-                    res_name_str
-                )?;
-            }
-
-            if i >= 2 && !flag && data.chain_id[i] != data.chain_id[i - 1] {
-                kadd += 1;
-                writeln!(logw, "INCREMENTING CHAIN (kadd) {}", kadd)?;
-            }
-
-            if !flag {
-                data.resnum[i] = data.res_seq[i] + (kadd * CHAINDIF);
-                atmnum = i;
-            }
-
-            if i >= 2
-                && !flag
-                && data.chain_id[i] == data.chain_id[i - 1]
-                && data.resnum[i] < data.resnum[i - 1]
-            {
-                writeln!(
-                    logw,
-                    "ERROR: RESNUM DECREASE. TERMINATE ANALYSIS{}\t{}",
-                    data.resnum[i],
-                    data.resnum[i - 1]
-                )?;
-                flag2 = true;
-            }
-
-            if i > 2
-                && !flag
-                && data.chain_id[i] == data.chain_id[i - 1]
-                && data.resnum[i] != data.resnum[i - 1]
-                && (data.resnum[i] - data.resnum[i - 1]) > 1
-            {
-                writeln!(
-                    logw,
-                    "WARNING: Missing Residues{}>>>{}",
-                    data.resnum[i - 1],
-                    data.resnum[i]
-                )?;
-            }
+        };
 
-            if !flag {
-                let idx = (data.resnum[i] + 4) as usize;
-                if idx >= data.errat.len() {
-                    data.errat.resize(idx + 1, 0.0);
-                }
-                data.errat[idx] = 0.0;
-            }
+        Ok(AtomSiteColumns {
+            group: col_index("group_PDB"),
+            atom,
+            element: col_index("type_symbol"),
+            alt: col_index("label_alt_id"),
+            res,
+            chain,
+            label_chain: col_index("label_asym_id"),
+            seq,
+            x,
+            y,
+            z,
+            b_factor: col_index("B_iso_or_equiv"),
+        })
+    }
+}
 
-            flag = false;
-            if flag2 {
-                break;
-            }
-        }
+struct MmcifAtoms<'w, W: Write> {
+    sink: AtomSink<'w, W>,
+    columns: Option<AtomSiteColumns>,
+    crystal: CrystalInfo,
+    assembly: AssemblyRecords,
+    experiment: Experiment,
+    secondary: Vec<SecondaryRange>,
+}
 
-        if atmnum > 0 || flag2 {
-            break;
+impl<W: Write> CifVisitor for MmcifAtoms<'_, W> {
+    fn begin_block(&mut self, _name: &str) -> io::Result<Flow> {
+        if self.sink.has_atoms() {
+            Ok(Flow::Stop)
+        } else {
+            self.crystal = CrystalInfo::default();
+            self.assembly = AssemblyRecords::default();
+            self.experiment = Experiment::default();
+            self.secondary.clear();
+            Ok(Flow::Continue)
         }
     }
 
-    data.atmnum = atmnum;
-    Ok(data)
-}
+    fn wants(&self, category: &str) -> bool {
+        category.eq_ignore_ascii_case("_atom_site")
+            || is_crystal_category(category)
+            || AssemblyRecords::wants(category)
+            || is_experiment_category(category)
+            || is_secondary_category(category)
+    }
 
-fn tokenize_cif(input: &str) -> Vec<String> {
-    let bytes = input.as_bytes();
-    let mut tokens = Vec::new();
-    let mut i = 0;
-    let mut at_line_start = true;
-
-    while i < bytes.len() {
-        let c = bytes[i] as char;
-        if c.is_whitespace() {
-            if c == '\n' {
-                at_line_start = true;
-            }
-            i += 1;
-            continue;
+    fn begin_category(&mut self, category: &str, columns: &[String]) -> io::Result<()> {
+        if category.eq_ignore_ascii_case("_atom_site") {
+            self.columns = Some(AtomSiteColumns::resolve(columns)?);
         }
+        Ok(())
+    }
 
-        if c == '#' {
-            while i < bytes.len() && bytes[i] as char != '\n' {
-                i += 1;
+    fn row(&mut self, category: &str, columns: &[String], row: &[String]) -> io::Result<Flow> {
+        if AssemblyRecords::wants(category) {
+            let items: Vec<&str> = columns.iter().map(|column| split_tag(column).1).collect();
+            self.assembly.row(category, &items, row);
+            return Ok(Flow::Continue);
+        }
+        if is_secondary_category(category) {
+            let items: Vec<&str> = columns.iter().map(|column| split_tag(column).1).collect();
+            secondary_row(category, &items, row, &mut self.secondary);
+            return Ok(Flow::Continue);
+        }
+        if !category.eq_ignore_ascii_case("_atom_site") {
+            for (column, value) in columns.iter().zip(row) {
+                let item = split_tag(column).1;
+                crystal_item(category, item, value, &mut self.crystal);
+                experiment_item(category, item, value, &mut self.experiment);
             }
-            at_line_start = true;
-            continue;
+            return Ok(Flow::Continue);
         }
+        let cols = self.columns.as_ref().expect("begin_category precedes rows");
+        let field = |k: Option<usize>| k.and_then(|k| row.get(k)).map(|s| s.as_str());
 
-        if c == ';' && at_line_start {
-            i += 1;
-            let start = i;
-            let mut end = None;
-            while i + 1 < bytes.len() {
-                if bytes[i] as char == '\n' && bytes[i + 1] as char == ';' {
-                    end = Some(i);
-                    break;
-                }
-                i += 1;
-            }
-            if let Some(end_pos) = end {
-                let val = &input[start..end_pos];
-                tokens.push(val.to_string());
-                i = end_pos + 2;
-                while i < bytes.len() && bytes[i] as char != '\n' {
-                    i += 1;
-                }
-                at_line_start = true;
-                continue;
-            }
-        }
+        let atom_name = row[cols.atom].as_str();
+        let element = match field(cols.element) {
+            Some(symbol) if symbol != "." && symbol != "?" => symbol.as_bytes(),
+            _ => name_element(atom_name.as_bytes()),
+        };
+        let alt_loc = match field(cols.alt).unwrap_or(".").bytes().next() {
+            None | Some(b'.') | Some(b'?') => b' ',
+            Some(c) => c,
+        };
+        let res_name = row[cols.res].to_ascii_uppercase();
+        let hetero = field(cols.group).is_some_and(|g| g != "ATOM");
+        let chain_id = row[cols.chain].bytes().next().unwrap_or(b' ');
+        if !hetero && let Some(label) = field(cols.label_chain) {
+            self.assembly.label_chain(label, chain_id);
+        }
+        let number = |k: usize| row[k].parse::<f64>().unwrap_or(0.0);
+
+        self.sink.push(&AtomRecord {
+            hetero,
+            element,
+            backbone: atom_name == "N" || atom_name == "C",
+            atom_name: atom_name.as_bytes(),
+            alt_loc,
+            res_name: res_name.as_bytes(),
+            chain_id,
+            res_seq: number(cols.seq) as i32,
+            x: number(cols.x),
+            y: number(cols.y),
+            z: number(cols.z),
+            b_factor: field(cols.b_factor)
+                .and_then(|value| value.parse::<f64>().ok())
+                .unwrap_or(0.0),
+        })
+    }
+}
 
-        if c == '\'' || c == '"' {
-            let quote = c;
-            i += 1;
-            let start = i;
-            while i < bytes.len() && bytes[i] as char != quote {
-                i += 1;
-            }
-            let val = &input[start..i];
-            tokens.push(val.to_string());
-            i += 1;
-            at_line_start = false;
-            continue;
+impl<'w, W: Write> MmcifAtoms<'w, W> {
+    fn new(logw: &'w mut W) -> Self {
+        MmcifAtoms {
+            sink: AtomSink::new(logw),
+            columns: None,
+            crystal: CrystalInfo::default(),
+            assembly: AssemblyRecords::default(),
+            experiment: Experiment::default(),
+            secondary: Vec::new(),
         }
+    }
 
-        let start = i;
-        while i < bytes.len() && !(bytes[i] as char).is_whitespace() {
-            i += 1;
-        }
-        let val = &input[start..i];
-        tokens.push(val.to_string());
-        at_line_start = false;
+    fn finish(self) -> io::Result<AtomData> {
+        let mut data = self.sink.finish()?;
+        data.crystal = self.crystal;
+        data.assemblies = self.assembly.resolve();
+        data.experiment = self.experiment;
+        data.secondary = self.secondary;
+        Ok(data)
     }
+}
 
-    tokens
+fn parse_mmcif<R: BufRead, W: Write>(reader: &mut R, logw: &mut W) -> io::Result<AtomData> {
+    let mut atoms = MmcifAtoms::new(logw);
+    read_cif(reader, &mut atoms)?;
+    atoms.finish()
 }
 
-fn is_standard_residue(res_name: &[u8]) -> bool {
-    matches!(
-        res_name,
-        b"GLY"
-            | b"ALA"
-            | b"VAL"
-            | b"LEU"
-            | b"ILE"
-            | b"TYR"
-            | b"CYS"
-            | b"MET"
-            | b"TRP"
-            | b"PHE"
-            | b"HIS"
-            | b"PRO"
-            | b"SER"
-            | b"THR"
-            | b"LYS"
-            | b"ARG"
-            | b"GLU"
-            | b"ASP"
-            | b"GLN"
-            | b"ASN"
-    )
+fn parse_bcif<W: Write>(bytes: &[u8], logw: &mut W) -> io::Result<AtomData> {
+    let mut atoms = MmcifAtoms::new(logw);
+    read_bcif(bytes, &mut atoms)?;
+    atoms.finish()
 }
 
 #[cfg(test)]
@@ -696,6 +398,75 @@ ATOM      3  CA BALA A   3      13.000  13.000   2.000  1.00 20.00           C\n
         assert_eq!(data.atmnum, 1);
     }
 
+    #[test]
+    fn parse_pdb_types_atoms_by_element_column() {
+        let pdb = b"\
+ATOM      1  CA  ALA A   1      11.104  13.207   2.100  1.00 20.00          CA\n\
+ATOM      2  XN  ALA A   1      11.504  13.607   2.500  1.00 20.00           N\n\
+ATOM      3  HA  ALA A   1      11.604  13.707   2.600  1.00 20.00           H\n\
+ATOM      4  D   ALA A   1      11.704  13.807   2.700  1.00 20.00\n\
+ATOM      5  O   ALA A   1      12.000  14.000   3.000\n";
+        let mut log = Vec::new();
+        let data = parse_pdb(&mut Cursor::new(pdb.as_ref()), &mut log).unwrap();
+        assert_eq!(data.atmnum, 3);
+        assert_eq!(&data.name[1..4], &[0, 2, 3]);
+        let log = String::from_utf8(log).unwrap();
+        assert!(log.contains("Stripped hydrogen atoms: 1\tdeuterium atoms: 1"));
+    }
+
+    #[test]
+    fn element_fallbacks_handle_short_lines_and_numbered_names() {
+        let line = |serial: usize, name: &str, tail: &str| {
+            format!(
+                "ATOM  {serial:>5} {name:<4} ALA A   1    {:>8.3}{:>8.3}{:>8.3}  1.00 20.00{tail}\n",
+                serial as f64, 0.0, 0.0
+            )
+        };
+        let pdb = [
+            line(1, " XN", "          N"),
+            line(2, "HG11", ""),
+            line(3, "1HB", ""),
+            line(4, " O", ""),
+        ]
+        .concat();
+        assert_eq!(pdb.lines().next().unwrap().len(), 77);
+        let mut log = Vec::new();
+        let data = parse_pdb(&mut Cursor::new(pdb.as_bytes()), &mut log).unwrap();
+        assert_eq!(data.atmnum, 2);
+        assert_eq!(&data.name[1..3], &[2, 3]);
+        assert!(
+            String::from_utf8(log)
+                .unwrap()
+                .contains("Stripped hydrogen atoms: 2")
+        );
+
+        let mmcif = "\
+data_demo
+loop_
+_atom_site.group_PDB
+_atom_site.label_atom_id
+_atom_site.label_comp_id
+_atom_site.auth_asym_id
+_atom_site.auth_seq_id
+_atom_site.Cartn_x
+_atom_site.Cartn_y
+_atom_site.Cartn_z
+ATOM N ALA A 1 1.0 0.0 0.0
+ATOM 1HB ALA A 1 2.0 0.0 0.0
+ATOM 2HG1 ALA A 1 3.0 0.0 0.0
+ATOM O ALA A 1 4.0 0.0 0.0
+";
+        let mut log = Vec::new();
+        let data = parse_mmcif(&mut Cursor::new(mmcif.as_bytes()), &mut log).unwrap();
+        assert_eq!(data.atmnum, 2);
+        assert_eq!(&data.name[1..3], &[2, 3]);
+        assert!(
+            String::from_utf8(log)
+                .unwrap()
+                .contains("Stripped hydrogen atoms: 2")
+        );
+    }
+
     #[test]
     fn parse_mmcif_reads_basic_atom_loop() {
         let mmcif = "\
@@ -724,10 +495,152 @@ ATOM O O . ALA A 1 11.904 14.007 2.900
     }
 
     #[test]
-    fn tokenize_cif_preserves_semicolon_blocks() {
-        let input = "data_demo\n;hello\nworld\n;\n";
-        let tokens = tokenize_cif(input);
-        assert_eq!(tokens[0], "data_demo");
-        assert_eq!(tokens[1], "hello\nworld");
+    fn parse_mmcif_reads_single_row_pairs_after_other_blocks() {
+        let mmcif = "\
+data_ligand
+_chem_comp.id HOH
+data_model
+_atom_site.group_PDB ATOM
+_atom_site.label_atom_id \"O5'\"
+_atom_site.type_symbol O
+_atom_site.label_comp_id GLY
+_atom_site.auth_asym_id B
+_atom_site.auth_seq_id 7
+_atom_site.Cartn_x 1.0
+_atom_site.Cartn_y 2.0
+_atom_site.Cartn_z 3.0
+data_ignored
+_atom_site.group_PDB ATOM
+";
+        let mut reader = Cursor::new(mmcif.as_bytes());
+        let mut log = Vec::new();
+        let data = parse_mmcif(&mut reader, &mut log).unwrap();
+        assert_eq!(data.atmnum, 1);
+        assert_eq!(data.chain_id[1], b'B');
+        assert_eq!(data.name[1], 3);
+        assert_eq!(data.xyz_z[1], 3.0);
+    }
+
+    fn assert_same_atoms(a: &AtomData, b: &AtomData) {
+        assert_eq!(a.atmnum, b.atmnum);
+        let n = a.atmnum + 1;
+        assert_eq!(a.name[..n], b.name[..n]);
+        assert_eq!(a.bnam[..n], b.bnam[..n]);
+        assert_eq!(a.atom_name[..n], b.atom_name[..n]);
+        assert_eq!(a.res_name[..n], b.res_name[..n]);
+        assert_eq!(a.chain_id[..n], b.chain_id[..n]);
+        assert_eq!(a.res_seq[..n], b.res_seq[..n]);
+        assert_eq!(a.resnum[..n], b.resnum[..n]);
+        assert_eq!(a.xyz_x[..n], b.xyz_x[..n]);
+        assert_eq!(a.xyz_y[..n], b.xyz_y[..n]);
+        assert_eq!(a.xyz_z[..n], b.xyz_z[..n]);
+        assert_eq!(a.errat, b.errat);
+    }
+
+    #[test]
+    fn pdb_mmap_and_mmcif_paths_build_identical_atoms() {
+        let pdb = b"\
+HEADER    TEST\r\n\
+ATOM      1  N   ALA A   1      11.104  13.207   2.100  1.00 20.00           N\r\n\
+ATOM      2  CA  ALA A   1      11.504  13.607   2.500  1.00 20.00           C\r\n\
+ATOM      3  CA BALA A   1      11.604  13.707   2.600  1.00 20.00           C\r\n\
+ATOM      4  C   MSE A   2      12.000  14.000   3.000  1.00 20.00           C\r\n\
+ATOM      5  O   GLY A   4      12.500  14.500  -3.500  1.00 20.00           O\r\n\
+HETATM    6  O   HOH W   1       1.000   2.000   3.000  1.00 20.00           O\r\n\
+ATOM      7  N   SER B   1       5.000   6.000   7.000  1.00 20.00           N\r\n\
+ATOM      8  C   SER B   0       5.500   6.500   7.500  1.00 20.00           C\r\n\
+ATOM      9  O   SER B   3       5.900   6.900   7.900  1.00 20.00           O\r\n";
+        let mmcif = "\
+data_TEST
+loop_
+_atom_site.group_PDB
+_atom_site.label_atom_id
+_atom_site.type_symbol
+_atom_site.label_alt_id
+_atom_site.label_comp_id
+_atom_site.auth_asym_id
+_atom_site.auth_seq_id
+_atom_site.Cartn_x
+_atom_site.Cartn_y
+_atom_site.Cartn_z
+ATOM N N . ALA A 1 11.104 13.207 2.100
+ATOM CA C A ALA A 1 11.504 13.607 2.500
+ATOM CA C B ALA A 1 11.604 13.707 2.600
+ATOM C C . MSE A 2 12.000 14.000 3.000
+ATOM O O . GLY A 4 12.500 14.500 -3.500
+HETATM O O . HOH W 1 1.000 2.000 3.000
+ATOM N N . SER B 1 5.000 6.000 7.000
+ATOM C C . SER B 0 5.500 6.500 7.500
+ATOM O O . SER B 3 5.900 6.900 7.900
+";
+
+        let mut pdb_log = Vec::new();
+        let from_reader = parse_pdb(&mut Cursor::new(pdb.as_ref()), &mut pdb_log).unwrap();
+        let mut bytes_log = Vec::new();
+        let from_bytes = parse_pdb_bytes(pdb, &mut bytes_log).unwrap();
+        let mut cif_log = Vec::new();
+        let from_cif = parse_mmcif(&mut Cursor::new(mmcif.as_bytes()), &mut cif_log).unwrap();
+
+        assert_eq!(from_reader.atmnum, 5);
+        assert_eq!(from_reader.resnum[4], 10_001);
+        assert_same_atoms(&from_reader, &from_bytes);
+        assert_same_atoms(&from_reader, &from_cif);
+        assert_eq!(pdb_log, bytes_log);
+        assert_eq!(pdb_log, cif_log);
+    }
+
+    #[test]
+    fn parse_bcif_matches_text_mmcif() {
+        use crate::bcif::tests::{bcif_file, fixed_point_column, int32_column, string_column};
+
+        let bcif = bcif_file(vec![(
+            "_atom_site",
+            4,
+            vec![
+                string_column("group_PDB", &["ATOM", "ATOM", "ATOM", "HETATM"], &[]),
+                string_column("label_atom_id", &["N", "C", "O", "O"], &[]),
+                string_column("type_symbol", &["N", "C", "O", "O"], &[]),
+                string_column("label_alt_id", &["", "", "A", ""], &[1, 1, 0, 1]),
+                string_column("label_comp_id", &["ALA", "ALA", "GLY", "HOH"], &[]),
+                string_column("auth_asym_id", &["A", "A", "A", "W"], &[]),
+                int32_column("auth_seq_id", &[1, 1, 2, 9]),
+                fixed_point_column("Cartn_x", &[11.104, 11.504, 11.904, 1.0]),
+                fixed_point_column("Cartn_y", &[13.207, 13.607, 14.007, 2.0]),
+                fixed_point_column("Cartn_z", &[2.1, 2.5, -2.9, 3.0]),
+            ],
+        )]);
+        let mmcif = "\
+data_TEST
+loop_
+_atom_site.group_PDB
+_atom_site.label_atom_id
+_atom_site.type_symbol
+_atom_site.label_alt_id
+_atom_site.label_comp_id
+_atom_site.auth_asym_id
+_atom_site.auth_seq_id
+_atom_site.Cartn_x
+_atom_site.Cartn_y
+_atom_site.Cartn_z
+ATOM N N . ALA A 1 11.104 13.207 2.100
+ATOM C C . ALA A 1 11.504 13.607 2.500
+ATOM O O A GLY A 2 11.904 14.007 -2.900
+HETATM O O . HOH W 9 1.0 2.0 3.0
+";
+
+        let mut log = Vec::new();
+        let binary = parse_bcif(&bcif, &mut log).unwrap();
+        let text = parse_mmcif(&mut Cursor::new(mmcif.as_bytes()), &mut Vec::new()).unwrap();
+        assert_eq!(binary.atmnum, 3);
+        assert_eq!(binary.atmnum, text.atmnum);
+        for i in 1..=text.atmnum {
+            assert_eq!(binary.name[i], text.name[i]);
+            assert_eq!(binary.bnam[i], text.bnam[i]);
+            assert_eq!(binary.chain_id[i], text.chain_id[i]);
+            assert_eq!(binary.resnum[i], text.resnum[i]);
+            assert_eq!(binary.xyz_x[i], text.xyz_x[i]);
+            assert_eq!(binary.xyz_y[i], text.xyz_y[i]);
+            assert_eq!(binary.xyz_z[i], text.xyz_z[i]);
+        }
     }
 }
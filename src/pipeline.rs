@@ -1,11 +1,18 @@
 use std::fs::File;
-use std::io::{self, BufWriter, Write};
+use std::io::{self, BufRead, BufWriter, Write};
 
 use crate::Config;
-use crate::model::{ErratStats, Paths};
-use crate::parser::parse_structure;
-use crate::render::{write_pdf, write_ps};
+use crate::api::{AnalysisOptions, AnalysisResult, InputFormat, ReportFormat};
+use crate::confidence::mark_confidence;
+use crate::export::{write_csv, write_json, write_problem_regions, write_summary_csv};
+use crate::interface::mark_interface;
+use crate::model::{AtomData, ErratStats, Paths};
+use crate::parser::{parse_reader, parse_structure};
+use crate::render::{write_pdf, write_ps, write_svg};
+use crate::resolution::mark_quality_percentile;
+use crate::secondary::mark_secondary_structure;
 use crate::stats::compute_errat;
+use crate::symmetry::add_environment_atoms;
 
 pub(crate) fn resolve_paths(config: &Config) -> Paths {
     if let (Some(input_pdb), Some(output_dir)) = (&config.input_pdb, &config.output_dir) {
@@ -48,16 +55,87 @@ pub(crate) fn resolve_paths(config: &Config) -> Paths {
     Paths { pdb, logf, plot }
 }
 
+pub(crate) fn finish_analysis<W: Write>(
+    data: &AtomData,
+    stats: &mut ErratStats,
+    options: &AnalysisOptions,
+    logw: &mut W,
+) -> io::Result<()> {
+    mark_interface(data, stats, options);
+    mark_quality_percentile(stats, options);
+    mark_confidence(data, stats, options, logw)?;
+    mark_secondary_structure(data, stats, options, logw)
+}
+
 pub(crate) fn process_structure_data(
     path: &std::path::PathBuf,
     use_mmap: bool,
+    options: &AnalysisOptions,
 ) -> io::Result<(ErratStats, Vec<u8>)> {
+    options.validate()?;
     let mut log = Vec::new();
-    let atom_data = parse_structure(path, &mut log, use_mmap)?;
-    let stats = compute_errat(&atom_data, &mut log)?;
+    let mut atom_data = parse_structure(path, &mut log, use_mmap)?;
+    add_environment_atoms(&mut atom_data, options, &mut log)?;
+    let mut stats = compute_errat(&atom_data, &options.calibration, &mut log)?;
+    finish_analysis(&atom_data, &mut stats, options, &mut log)?;
     Ok((stats, log))
 }
 
+pub(crate) fn process_reader<R: BufRead>(
+    reader: &mut R,
+    format: InputFormat,
+    options: &AnalysisOptions,
+) -> io::Result<(ErratStats, Vec<u8>)> {
+    options.validate()?;
+    let mut log = Vec::new();
+    let mut atom_data = parse_reader(reader, format, &mut log)?;
+    add_environment_atoms(&mut atom_data, options, &mut log)?;
+    let mut stats = compute_errat(&atom_data, &options.calibration, &mut log)?;
+    finish_analysis(&atom_data, &mut stats, options, &mut log)?;
+    Ok((stats, log))
+}
+
+pub(crate) fn write_reports(
+    outputs: &mut [(ReportFormat, &mut dyn Write)],
+    file_string: &str,
+    stats: &ErratStats,
+    analysis: &AnalysisResult,
+    log: &mut Vec<u8>,
+) -> io::Result<()> {
+    let regions = &analysis.problem_regions;
+    let mut plotted = false;
+    for (format, out) in outputs.iter_mut() {
+        if !matches!(
+            format,
+            ReportFormat::Ps | ReportFormat::Pdf | ReportFormat::Svg
+        ) || stats.stat <= 0.0
+        {
+            continue;
+        }
+        let mut sink = io::sink();
+        let mut logw: &mut dyn Write = if plotted { &mut sink } else { log };
+        match format {
+            ReportFormat::Ps => write_ps(out, &mut logw, file_string, stats, regions, None)?,
+            ReportFormat::Pdf => write_pdf(out, &mut logw, file_string, stats, regions, None)?,
+            _ => write_svg(out, &mut logw, file_string, stats, regions, None)?,
+        }
+        plotted = true;
+    }
+    write_problem_regions(log, regions)?;
+
+    for (format, out) in outputs.iter_mut() {
+        match format {
+            ReportFormat::Json => write_json(out, analysis)?,
+            ReportFormat::Csv => write_csv(out, analysis)?,
+            ReportFormat::SummaryCsv => write_summary_csv(out, analysis)?,
+            ReportFormat::Log => out.write_all(log)?,
+            _ => {}
+        }
+        out.flush()?;
+    }
+    Ok(())
+}
+
 pub(crate) fn persist_outputs(
     paths: &Paths,
     file_string: &str,
@@ -73,9 +151,9 @@ pub(crate) fn persist_outputs(
     let mut plotw = BufWriter::new(plotf);
     if stats.stat > 0.0 {
         if output_pdf {
-            write_pdf(&mut plotw, log, file_string, stats)?;
+            write_pdf(&mut plotw, log, file_string, stats, &[], None)?;
         } else {
-            write_ps(&mut plotw, log, file_string, stats)?;
+            write_ps(&mut plotw, log, file_string, stats, &[], None)?;
         }
     }
     plotw.flush()?;
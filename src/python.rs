@@ -6,7 +6,11 @@ use pyo3::types::{PyDict, PyList};
 use pyo3::wrap_pyfunction;
 
 use crate::api::frame_status_name;
-use crate::{AnalysisResult, analyze_and_write, analyze_file, write_report};
+use crate::{
+    AnalysisOptions, AnalysisResult, AssemblyMode, AssemblySelection, Calibration, CorpusReference,
+    DomainRange, ErratSession, HeteroEnvironment, ResolutionReference, SecondaryStructure,
+    analyze_and_write, analyze_file_with, write_report,
+};
 
 fn io_err_to_py(err: io::Error) -> PyErr {
     PyRuntimeError::new_err(err.to_string())
@@ -20,7 +24,23 @@ fn analysis_to_pydict(py: Python<'_>, analysis: &AnalysisResult) -> PyResult<Py<
     result.set_item("rejected_frame_count", analysis.rejected_frame_count)?;
     result.set_item("rejected_frame_ratio", analysis.rejected_frame_ratio)?;
     result.set_item("overall_quality_factor", analysis.overall_quality_factor)?;
-    result.set_item("average_probability", analysis.average_probability)?;
+    result.set_item("average_error_value", analysis.average_error_value)?;
+    result.set_item("average_probability", analysis.average_error_value)?;
+    result.set_item("experimental_method", &analysis.experimental_method)?;
+    result.set_item("resolution", analysis.resolution)?;
+    result.set_item("quality_percentile", analysis.quality_percentile)?;
+    if let Some(score) = &analysis.corpus_score {
+        let item = PyDict::new(py);
+        item.set_item("method", &score.method)?;
+        item.set_item("residue_count", score.residue_count)?;
+        item.set_item("min_residues", score.min_residues)?;
+        item.set_item("max_residues", score.max_residues)?;
+        item.set_item("structure_count", score.structure_count)?;
+        item.set_item("mean_quality_factor", score.mean_quality_factor)?;
+        item.set_item("z_score", score.z_score)?;
+        item.set_item("percentile", score.percentile)?;
+        result.set_item("corpus_score", item)?;
+    }
     result.set_item(
         "below_interaction_limit_frames",
         &analysis.below_interaction_limit_frames,
@@ -34,10 +54,88 @@ fn analysis_to_pydict(py: Python<'_>, analysis: &AnalysisResult) -> PyResult<Py<
         item.set_item("chain_id", &chain.chain_id)?;
         item.set_item("start_residue", chain.start_residue)?;
         item.set_item("end_residue", chain.end_residue)?;
+        item.set_item("min_p_value", chain.min_p_value)?;
+        item.set_item("scored_frame_count", chain.scored_frame_count)?;
+        item.set_item("rejected_frame_count", chain.rejected_frame_count)?;
+        item.set_item("quality_factor", chain.quality_factor)?;
+        item.set_item("average_error_value", chain.average_error_value)?;
         chain_summaries.append(item)?;
     }
     result.set_item("chain_summaries", chain_summaries)?;
 
+    let domain_summaries = PyList::empty(py);
+    for domain in &analysis.domain_summaries {
+        let item = PyDict::new(py);
+        item.set_item("name", &domain.name)?;
+        item.set_item("chain_id", &domain.chain_id)?;
+        item.set_item("start_residue", domain.start_residue)?;
+        item.set_item("end_residue", domain.end_residue)?;
+        item.set_item("scored_frame_count", domain.scored_frame_count)?;
+        item.set_item("rejected_frame_count", domain.rejected_frame_count)?;
+        item.set_item("quality_factor", domain.quality_factor)?;
+        item.set_item("average_error_value", domain.average_error_value)?;
+        domain_summaries.append(item)?;
+    }
+    result.set_item("domain_summaries", domain_summaries)?;
+
+    if let Some(interface) = &analysis.interface {
+        let item = PyDict::new(py);
+        let residues: Vec<(&str, i32)> = interface
+            .residues
+            .iter()
+            .map(|residue| (residue.chain_id.as_str(), residue.residue))
+            .collect();
+        item.set_item("distance", interface.distance)?;
+        item.set_item("residues", residues)?;
+        item.set_item("scored_frame_count", interface.scored_frame_count)?;
+        item.set_item("rejected_frame_count", interface.rejected_frame_count)?;
+        item.set_item("quality_factor", interface.quality_factor)?;
+        item.set_item("average_error_value", interface.average_error_value)?;
+        item.set_item("inter_chain_fraction", interface.inter_chain_fraction)?;
+        result.set_item("interface", item)?;
+    }
+    result.set_item(
+        "hetero_environment",
+        analysis.hetero_environment.map(HeteroEnvironment::name),
+    )?;
+    if let Some(confidence) = &analysis.confidence {
+        let item = PyDict::new(py);
+        let residues: Vec<(&str, i32, f64)> = confidence
+            .residues
+            .iter()
+            .map(|residue| (residue.chain_id.as_str(), residue.residue, residue.plddt))
+            .collect();
+        item.set_item("cutoff", confidence.cutoff)?;
+        item.set_item("mean_plddt", confidence.mean_plddt)?;
+        item.set_item("residues", residues)?;
+        for (key, group) in [
+            ("confident", &confidence.confident),
+            ("low_confidence", &confidence.low_confidence),
+        ] {
+            let tally = PyDict::new(py);
+            tally.set_item("scored_frame_count", group.scored_frame_count)?;
+            tally.set_item("rejected_frame_count", group.rejected_frame_count)?;
+            tally.set_item("quality_factor", group.quality_factor)?;
+            tally.set_item("average_error_value", group.average_error_value)?;
+            item.set_item(key, tally)?;
+        }
+        result.set_item("confidence", item)?;
+    }
+
+    let problem_regions = PyList::empty(py);
+    for region in &analysis.problem_regions {
+        let item = PyDict::new(py);
+        item.set_item("chain_id", &region.chain_id)?;
+        item.set_item("start_residue", region.start_residue)?;
+        item.set_item("end_residue", region.end_residue)?;
+        item.set_item("length", region.length)?;
+        item.set_item("peak_error_value", region.peak_error_value)?;
+        item.set_item("mean_error_value", region.mean_error_value)?;
+        item.set_item("severity", frame_status_name(region.severity))?;
+        problem_regions.append(item)?;
+    }
+    result.set_item("problem_regions", problem_regions)?;
+
     let frame_scores = PyList::empty(py);
     for frame in &analysis.frame_scores {
         let item = PyDict::new(py);
@@ -45,10 +143,43 @@ fn analysis_to_pydict(py: Python<'_>, analysis: &AnalysisResult) -> PyResult<Py<
         item.set_item("center_residue", frame.center_residue)?;
         item.set_item("error_value", frame.error_value)?;
         item.set_item("status", frame_status_name(frame.status))?;
+        item.set_item("p_value", frame.p_value)?;
+        item.set_item(
+            "secondary_structure",
+            frame.secondary_structure.map(SecondaryStructure::name),
+        )?;
         frame_scores.append(item)?;
     }
     result.set_item("frame_scores", frame_scores)?;
 
+    let residue_p_values = PyList::empty(py);
+    for residue in &analysis.residue_p_values {
+        let item = PyDict::new(py);
+        item.set_item("chain_id", &residue.chain_id)?;
+        item.set_item("residue", residue.residue)?;
+        item.set_item("p_value", residue.p_value)?;
+        residue_p_values.append(item)?;
+    }
+    result.set_item("residue_p_values", residue_p_values)?;
+
+    let detailed: Vec<_> = analysis
+        .frame_scores
+        .iter()
+        .filter_map(|frame| frame.interactions)
+        .collect();
+    if !detailed.is_empty() {
+        let interactions = PyDict::new(py);
+        let counts: Vec<[f64; 5]> = detailed.iter().map(|i| i.counts).collect();
+        let totals: Vec<f64> = detailed.iter().map(|i| i.total).collect();
+        let fractions: Vec<[f64; 5]> = detailed.iter().map(|i| i.fractions).collect();
+        let inter_chain: Vec<[f64; 5]> = detailed.iter().map(|i| i.inter_chain_counts).collect();
+        interactions.set_item("counts", counts)?;
+        interactions.set_item("totals", totals)?;
+        interactions.set_item("fractions", fractions)?;
+        interactions.set_item("inter_chain_counts", inter_chain)?;
+        result.set_item("interactions", interactions)?;
+    }
+
     Ok(result.unbind())
 }
 
@@ -65,14 +196,75 @@ fn report_to_pydict(
     Ok(result.unbind())
 }
 
-#[pyfunction(name = "analyze", signature = (input_path, protein_id=None, use_mmap=false))]
+#[pyfunction(name = "analyze", signature = (input_path, protein_id=None, use_mmap=false, calibration_path=None, detailed=false, window_length=None, radius=None, radmin=None, domains=Vec::new(), region_gap=0, crystal_contacts=false, assembly=None, assembly_mode="full", interface_distance=None, hetero_environment=None, resolution_reference_path=None, reference_path=None, plddt_cutoff=None, secondary_structure=false))]
+#[allow(clippy::too_many_arguments)]
 fn analyze_py(
     py: Python<'_>,
     input_path: &str,
     protein_id: Option<&str>,
     use_mmap: bool,
+    calibration_path: Option<&str>,
+    detailed: bool,
+    window_length: Option<usize>,
+    radius: Option<f64>,
+    radmin: Option<f64>,
+    domains: Vec<(String, String, i32, i32)>,
+    region_gap: usize,
+    crystal_contacts: bool,
+    assembly: Option<String>,
+    assembly_mode: &str,
+    interface_distance: Option<f64>,
+    hetero_environment: Option<&str>,
+    resolution_reference_path: Option<&str>,
+    reference_path: Option<&str>,
+    plddt_cutoff: Option<f64>,
+    secondary_structure: bool,
 ) -> PyResult<Py<PyDict>> {
-    let analysis = analyze_file(input_path, protein_id, use_mmap).map_err(io_err_to_py)?;
+    let mode = AssemblyMode::from_name(assembly_mode)
+        .ok_or_else(|| PyValueError::new_err("assembly_mode must be either 'full' or 'context'"))?;
+    let hetero_environment = hetero_environment
+        .map(|name| {
+            HeteroEnvironment::from_name(name).ok_or_else(|| {
+                PyValueError::new_err(
+                    "hetero_environment must be either 'ligands' or 'ligands+water'",
+                )
+            })
+        })
+        .transpose()?;
+    let mut options = AnalysisOptions {
+        detailed,
+        window_length,
+        radius,
+        radmin,
+        domains: domains
+            .into_iter()
+            .map(|(name, chain_id, start_residue, end_residue)| DomainRange {
+                name,
+                chain_id,
+                start_residue,
+                end_residue,
+            })
+            .collect(),
+        region_gap,
+        crystal_contacts,
+        assembly: assembly.map(|id| AssemblySelection { id, mode }),
+        interface_distance,
+        hetero_environment,
+        plddt_cutoff,
+        secondary_structure,
+        ..AnalysisOptions::default()
+    };
+    if let Some(path) = calibration_path {
+        options.calibration = Calibration::load(path).map_err(io_err_to_py)?;
+    }
+    if let Some(path) = resolution_reference_path {
+        options.resolution_reference = ResolutionReference::load(path).map_err(io_err_to_py)?;
+    }
+    if let Some(path) = reference_path {
+        options.corpus_reference = Some(CorpusReference::load(path).map_err(io_err_to_py)?);
+    }
+    let analysis =
+        analyze_file_with(input_path, protein_id, use_mmap, &options).map_err(io_err_to_py)?;
     analysis_to_pydict(py, &analysis)
 }
 
@@ -143,10 +335,62 @@ fn analyze_and_write_py(
     Ok(payload)
 }
 
+#[pyclass(name = "Session", module = "errat_rs._native")]
+struct SessionPy {
+    session: ErratSession,
+}
+
+#[pymethods]
+impl SessionPy {
+    #[new]
+    #[pyo3(signature = (input_path, protein_id=None, use_mmap=false, calibration_path=None))]
+    fn new(
+        input_path: &str,
+        protein_id: Option<&str>,
+        use_mmap: bool,
+        calibration_path: Option<&str>,
+    ) -> PyResult<Self> {
+        let mut options = AnalysisOptions::default();
+        if let Some(path) = calibration_path {
+            options.calibration = Calibration::load(path).map_err(io_err_to_py)?;
+        }
+        let session =
+            ErratSession::open(input_path, protein_id, use_mmap, &options).map_err(io_err_to_py)?;
+        Ok(SessionPy { session })
+    }
+
+    fn atom_count(&self) -> usize {
+        self.session.atom_count()
+    }
+
+    fn atom_index(&self, chain_id: &str, residue: i32, atom_name: &str) -> Option<usize> {
+        self.session.atom_index(chain_id, residue, atom_name)
+    }
+
+    fn coordinates(&self, atom: usize) -> Option<[f64; 3]> {
+        self.session.coordinates(atom)
+    }
+
+    fn update_coordinates(&mut self, updates: Vec<(usize, [f64; 3])>) -> PyResult<usize> {
+        self.session
+            .update_coordinates(&updates)
+            .map_err(io_err_to_py)
+    }
+
+    fn overall_quality_factor(&self) -> Option<f64> {
+        self.session.overall_quality_factor()
+    }
+
+    fn analysis(&self, py: Python<'_>) -> PyResult<Py<PyDict>> {
+        analysis_to_pydict(py, &self.session.analysis())
+    }
+}
+
 pub(crate) fn register(module: &Bound<'_, PyModule>) -> PyResult<()> {
     module.add_function(wrap_pyfunction!(analyze_py, module)?)?;
     module.add_function(wrap_pyfunction!(analyze_and_write_py, module)?)?;
     module.add_function(wrap_pyfunction!(write_report_py, module)?)?;
+    module.add_class::<SessionPy>()?;
     module.add("__version__", env!("CARGO_PKG_VERSION"))?;
     Ok(())
 }
@@ -0,0 +1,363 @@
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use rayon::prelude::*;
+
+use crate::api::{
+    AnalysisOptions, AnalysisResult, FrameStatus, build_analysis_result, derive_file_string,
+};
+use crate::export::{csv_field, json_number};
+use crate::model::{AtomData, ErratStats, Paths};
+use crate::parser::parse_structure;
+use crate::pipeline::{persist_outputs, process_structure_data};
+use crate::stats::compute_errat;
+use crate::symmetry::add_environment_atoms;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RankCriterion {
+    QualityFactor,
+    MeanError,
+    WorstRegion,
+    Warning99Frames,
+}
+
+impl RankCriterion {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "quality" | "quality-factor" => Some(RankCriterion::QualityFactor),
+            "mean" | "mean-error" => Some(RankCriterion::MeanError),
+            "worst-region" => Some(RankCriterion::WorstRegion),
+            "frames99" | "warning99" => Some(RankCriterion::Warning99Frames),
+            _ => None,
+        }
+    }
+
+    fn badness(self, model: &RankedModel) -> f64 {
+        let analysis = &model.analysis;
+        if analysis.scored_frame_count == 0 {
+            return f64::INFINITY;
+        }
+        match self {
+            RankCriterion::QualityFactor => analysis
+                .overall_quality_factor
+                .map_or(f64::INFINITY, |value| -value),
+            RankCriterion::MeanError => analysis.average_error_value.unwrap_or(f64::INFINITY),
+            RankCriterion::WorstRegion => model.worst_region_peak.unwrap_or(0.0),
+            RankCriterion::Warning99Frames => model.warning99_frame_count as f64,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RankedModel {
+    pub rank: usize,
+    pub analysis: AnalysisResult,
+    pub worst_region_peak: Option<f64>,
+    pub warning99_frame_count: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct RankingReport {
+    pub models: Vec<RankedModel>,
+    pub skipped: Vec<String>,
+}
+
+fn score_model(
+    path: &Path,
+    options: &AnalysisOptions,
+    use_mmap: bool,
+    report_dir: Option<&Path>,
+    output_pdf: bool,
+) -> io::Result<RankedModel> {
+    let input_path = path.to_path_buf();
+    let (stats, mut log) = process_structure_data(&input_path, use_mmap, options)?;
+    let protein_id = derive_file_string(&input_path, None);
+    let log_text = String::from_utf8_lossy(&log).into_owned();
+    let analysis = build_analysis_result(
+        input_path.clone(),
+        protein_id.clone(),
+        &stats,
+        log_text,
+        options,
+    );
+    if let Some(dir) = report_dir {
+        let extension = if output_pdf { "pdf" } else { "ps" };
+        let paths = Paths {
+            pdb: input_path,
+            logf: dir.join(format!("{protein_id}.logf")),
+            plot: dir.join(format!("{protein_id}.{extension}")),
+        };
+        persist_outputs(&paths, &protein_id, &stats, &mut log, output_pdf)?;
+    }
+    Ok(RankedModel {
+        rank: 0,
+        worst_region_peak: analysis
+            .problem_regions
+            .iter()
+            .map(|region| region.peak_error_value)
+            .reduce(f64::max),
+        warning99_frame_count: analysis
+            .frame_scores
+            .iter()
+            .filter(|frame| frame.status == FrameStatus::Warning99)
+            .count(),
+        analysis,
+    })
+}
+
+pub(crate) fn sort_models(models: &mut [RankedModel], criterion: RankCriterion) {
+    let quality = |model: &RankedModel| RankCriterion::QualityFactor.badness(model);
+    models.sort_by(|a, b| {
+        criterion
+            .badness(a)
+            .total_cmp(&criterion.badness(b))
+            .then_with(|| quality(a).total_cmp(&quality(b)))
+            .then_with(|| a.analysis.input_path.cmp(&b.analysis.input_path))
+    });
+    for (idx, model) in models.iter_mut().enumerate() {
+        model.rank = idx + 1;
+    }
+}
+
+pub fn rank_models(
+    inputs: &[PathBuf],
+    criterion: RankCriterion,
+    options: &AnalysisOptions,
+    use_mmap: bool,
+    report_dir: Option<&Path>,
+    output_pdf: bool,
+) -> io::Result<RankingReport> {
+    options.validate()?;
+    if let Some(dir) = report_dir {
+        std::fs::create_dir_all(dir)?;
+    }
+    let results: Vec<(&PathBuf, io::Result<RankedModel>)> = inputs
+        .par_iter()
+        .map(|path| {
+            (
+                path,
+                score_model(path, options, use_mmap, report_dir, output_pdf),
+            )
+        })
+        .collect();
+
+    let mut models = Vec::new();
+    let mut skipped = Vec::new();
+    for (path, result) in results {
+        match result {
+            Ok(model) => models.push(model),
+            Err(err) => skipped.push(format!("{}: {err}", path.display())),
+        }
+    }
+    sort_models(&mut models, criterion);
+    Ok(RankingReport { models, skipped })
+}
+
+pub fn write_ranking_csv<W: Write>(out: &mut W, models: &[RankedModel]) -> io::Result<()> {
+    writeln!(
+        out,
+        "rank,protein_id,input_path,quality_factor,average_error_value,worst_region_peak,warning99_frames,scored_frames,rejected_frames"
+    )?;
+    let number = |value: Option<f64>| value.map(json_number).unwrap_or_default();
+    for model in models {
+        let analysis = &model.analysis;
+        writeln!(
+            out,
+            "{},{},{},{},{},{},{},{},{}",
+            model.rank,
+            csv_field(&analysis.protein_id),
+            csv_field(&analysis.input_path.to_string_lossy()),
+            number(analysis.overall_quality_factor),
+            number(analysis.average_error_value),
+            number(model.worst_region_peak),
+            model.warning99_frame_count,
+            analysis.scored_frame_count,
+            analysis.rejected_frame_count
+        )?;
+    }
+    Ok(())
+}
+
+fn element_symbol(data: &AtomData, idx: usize) -> String {
+    match data.name[idx] {
+        1 => "C".to_string(),
+        2 => "N".to_string(),
+        3 => "O".to_string(),
+        _ => data.atom_name[idx]
+            .iter()
+            .find(|byte| byte.is_ascii_alphabetic())
+            .map(|byte| (*byte as char).to_string())
+            .unwrap_or_default(),
+    }
+}
+
+pub(crate) fn write_annotated_atoms<W: Write>(
+    out: &mut W,
+    data: &AtomData,
+    stats: &ErratStats,
+) -> io::Result<()> {
+    writeln!(
+        out,
+        "REMARK   1 B-FACTOR COLUMN HOLDS THE ERRAT ERROR VALUE OF THE WINDOW CENTRED ON EACH RESIDUE"
+    )?;
+    for idx in 1..=data.atmnum {
+        let atom_name = String::from_utf8_lossy(data.atom_name[idx].trim_ascii()).into_owned();
+        let atom_name = if atom_name.len() < 4 {
+            format!(" {atom_name:<3}")
+        } else {
+            atom_name
+        };
+        let error_value = stats
+            .errat
+            .get(data.resnum[idx] as usize)
+            .copied()
+            .unwrap_or(0.0)
+            .min(999.99);
+        writeln!(
+            out,
+            "ATOM  {:>5} {} {:>3} {}{:>4}    {:>8.3}{:>8.3}{:>8.3}{:>6.2}{:>6.2}          {:>2}",
+            idx % 100_000,
+            atom_name,
+            String::from_utf8_lossy(data.res_name[idx].trim_ascii()),
+            data.chain_id[idx] as char,
+            data.res_seq[idx],
+            data.xyz_x[idx],
+            data.xyz_y[idx],
+            data.xyz_z[idx],
+            1.0,
+            error_value,
+            element_symbol(data, idx)
+        )?;
+    }
+    writeln!(out, "END")?;
+    Ok(())
+}
+
+pub fn write_annotated_structure<W: Write, P: AsRef<Path>>(
+    out: &mut W,
+    input: P,
+    use_mmap: bool,
+    options: &AnalysisOptions,
+) -> io::Result<()> {
+    options.validate()?;
+    let mut sink = io::sink();
+    let mut data = parse_structure(&input.as_ref().to_path_buf(), &mut sink, use_mmap)?;
+    add_environment_atoms(&mut data, options, &mut sink)?;
+    let stats = compute_errat(&data, &options.calibration, &mut sink)?;
+    write_annotated_atoms(out, &data, &stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{InputFormat, ProblemRegion};
+    use crate::calibration::Calibration;
+    use crate::model::Experiment;
+    use crate::parser::parse_reader;
+    use std::io::Cursor;
+
+    fn model(
+        path: &str,
+        quality: f64,
+        mean: f64,
+        peak: Option<f64>,
+        frames99: usize,
+    ) -> RankedModel {
+        let stats = ErratStats {
+            stat: 10.0,
+            pstat: 0.0,
+            avg_probability: Some(mean),
+            overall_quality_factor: Some(quality),
+            errat: Vec::new(),
+            resnum: vec![0; 2],
+            chain_id: vec![b'A'; 2],
+            atmnum: 0,
+            warning_frames: Vec::new(),
+            scored_frames: Vec::new(),
+            interface_residues: Vec::new(),
+            experiment: Experiment::default(),
+            quality_percentile: None,
+            plddt: Vec::new(),
+            plddt_cutoff: None,
+            secondary: Vec::new(),
+            calibration: Calibration::classic(),
+        };
+        let mut analysis = build_analysis_result(
+            PathBuf::from(path),
+            path.to_string(),
+            &stats,
+            String::new(),
+            &AnalysisOptions::default(),
+        );
+        analysis.problem_regions = peak
+            .map(|peak_error_value| ProblemRegion {
+                chain_id: "A".to_string(),
+                start_residue: 5,
+                end_residue: 5,
+                length: 1,
+                peak_error_value,
+                mean_error_value: peak_error_value,
+                severity: FrameStatus::Warning99,
+            })
+            .into_iter()
+            .collect();
+        RankedModel {
+            rank: 0,
+            analysis,
+            worst_region_peak: peak,
+            warning99_frame_count: frames99,
+        }
+    }
+
+    fn order(criterion: RankCriterion) -> Vec<String> {
+        let mut models = vec![
+            model("a", 90.0, 6.0, Some(30.0), 3),
+            model("b", 95.0, 8.0, Some(20.0), 1),
+            model("c", 80.0, 5.0, None, 0),
+            model("d", 95.0, 7.0, Some(40.0), 2),
+        ];
+        sort_models(&mut models, criterion);
+        assert_eq!(models[0].rank, 1);
+        models
+            .into_iter()
+            .map(|model| model.analysis.protein_id)
+            .collect()
+    }
+
+    #[test]
+    fn models_sort_by_each_criterion() {
+        assert_eq!(order(RankCriterion::QualityFactor), ["b", "d", "a", "c"]);
+        assert_eq!(order(RankCriterion::MeanError), ["c", "a", "d", "b"]);
+        assert_eq!(order(RankCriterion::WorstRegion), ["c", "b", "a", "d"]);
+        assert_eq!(order(RankCriterion::Warning99Frames), ["c", "b", "d", "a"]);
+        assert_eq!(
+            RankCriterion::from_name("Worst-Region"),
+            Some(RankCriterion::WorstRegion)
+        );
+    }
+
+    #[test]
+    fn annotated_atoms_carry_window_errors() {
+        let pdb = concat!(
+            "ATOM      1  N   ALA A   7      11.104  13.207   2.100  1.00 20.00           N\n",
+            "ATOM      2  CA  ALA A   7      11.504  13.607   2.500  1.00 20.00           C\n",
+            "ATOM      3  SD  MET A   8      11.904  14.007   2.900  1.00 20.00           S\n",
+        );
+        let mut sink = io::sink();
+        let data = parse_reader(&mut Cursor::new(pdb), InputFormat::Pdb, &mut sink).unwrap();
+        let mut stats = compute_errat(&data, &Calibration::classic(), &mut sink).unwrap();
+        stats.errat[data.resnum[1] as usize] = 12.5;
+
+        let mut out = Vec::new();
+        write_annotated_atoms(&mut out, &data, &stats).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(
+            lines[1],
+            "ATOM      1  N   ALA A   7      11.104  13.207   2.100  1.00 12.50           N"
+        );
+        assert_eq!(&lines[3][12..26], " SD  MET A   8");
+        assert!(lines[3].ends_with(" 0.00           S"));
+        assert_eq!(lines[4], "END");
+    }
+}
@@ -1,20 +1,61 @@
+use std::collections::BTreeMap;
 use std::fmt::Write as FmtWrite;
 use std::io::{self, Write};
 
-use crate::api::compute_chain_ranges;
-use crate::model::{CHAINDIF, ErratStats, LMT_95, LMT_99};
+use crate::api::SecondaryStructure;
+use crate::api::{FrameStatus, FrameTally, ProblemRegion, compute_chain_ranges, frames_in};
+use crate::confidence::residue_confidence;
+use crate::model::{CHAINDIF, ErratStats};
+use crate::secondary::residue_secondary;
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 struct PageSlice {
     chain_id: u8,
     start_residue: i32,
     end_residue: i32,
+    quality_factor: f64,
 }
 
 #[derive(Clone, Debug, PartialEq)]
 struct PlotLayout {
     scale: f64,
     pages: Vec<PageSlice>,
+    quality_label: &'static str,
+}
+
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Overlay {
+    pub(crate) errat: BTreeMap<i32, f64>,
+    pub(crate) overall_quality_factor: Option<f64>,
+    pub(crate) chain_quality_factors: BTreeMap<u8, Option<f64>>,
+}
+
+impl PlotLayout {
+    fn quality_line(&self, page: &PageSlice, overlay: Option<&Overlay>) -> String {
+        match overlay {
+            None => format!(
+                "{} quality factor**: {:.3}",
+                self.quality_label, page.quality_factor
+            ),
+            Some(overlay) => {
+                let before = if self.quality_label == "Chain" {
+                    overlay
+                        .chain_quality_factors
+                        .get(&page.chain_id)
+                        .copied()
+                        .flatten()
+                } else {
+                    overlay.overall_quality_factor
+                };
+                format!(
+                    "{} quality factor**: {:.3} -> {:.3}",
+                    self.quality_label,
+                    before.unwrap_or(0.0),
+                    page.quality_factor
+                )
+            }
+        }
+    }
 }
 
 fn build_plot_layout(stats: &ErratStats) -> PlotLayout {
@@ -23,6 +64,7 @@ fn build_plot_layout(stats: &ErratStats) -> PlotLayout {
         return PlotLayout {
             scale: 1.0,
             pages: Vec::new(),
+            quality_label: "Overall",
         };
     }
 
@@ -39,8 +81,15 @@ fn build_plot_layout(stats: &ErratStats) -> PlotLayout {
         }
     }
 
+    let multi_chain = chain_ranges.len() > 1;
     let mut pages = Vec::new();
     for chain in &chain_ranges {
+        let quality_factor = if multi_chain {
+            FrameTally::of(frames_in(stats, chain), &stats.calibration).quality_factor()
+        } else {
+            stats.overall_quality_factor
+        }
+        .unwrap_or(0.0);
         let np = 1 + ((chain.end_residue - chain.start_residue + 1) as f64 / mst) as i32;
         for page_index in 1..=np {
             let start_residue = chain.start_residue + (mst as i32) * (page_index - 1);
@@ -52,6 +101,7 @@ fn build_plot_layout(stats: &ErratStats) -> PlotLayout {
                 chain_id: chain.chain_id,
                 start_residue,
                 end_residue,
+                quality_factor,
             });
         }
     }
@@ -59,7 +109,92 @@ fn build_plot_layout(stats: &ErratStats) -> PlotLayout {
     PlotLayout {
         scale: 200.0 / mst,
         pages,
+        quality_label: if multi_chain { "Chain" } else { "Overall" },
+    }
+}
+
+fn region_bands(page: &PageSlice, regions: &[ProblemRegion]) -> Vec<(f64, f64, [f64; 3])> {
+    regions
+        .iter()
+        .filter_map(|region| {
+            let start = region.start_residue.max(page.start_residue);
+            let end = region.end_residue.min(page.end_residue);
+            let rgb = match region.severity {
+                FrameStatus::Warning99 => [1.0, 0.8, 0.8],
+                _ => [1.0, 1.0, 0.8],
+            };
+            (start <= end).then_some((
+                (start - page.start_residue) as f64,
+                (end - start + 1) as f64,
+                rgb,
+            ))
+        })
+        .collect()
+}
+
+fn plddt_color(plddt: f64) -> [f64; 3] {
+    if plddt >= 90.0 {
+        [0.0, 0.325, 0.839]
+    } else if plddt >= 70.0 {
+        [0.396, 0.796, 0.953]
+    } else if plddt >= 50.0 {
+        [1.0, 0.859, 0.075]
+    } else {
+        [1.0, 0.494, 0.271]
+    }
+}
+
+struct Track {
+    label: &'static str,
+    y: f64,
+    cells: Vec<(f64, f64, f64, [f64; 3])>,
+}
+
+fn page_tracks(stats: &ErratStats, page: &PageSlice) -> Vec<Track> {
+    let residues = page.start_residue..=page.end_residue;
+    let x = |residue: i32| (residue - page.start_residue) as f64;
+    let mut tracks = Vec::new();
+    if !stats.secondary.is_empty() {
+        tracks.push(Track {
+            label: "SS",
+            y: -48.0,
+            cells: residues
+                .clone()
+                .filter_map(|residue| {
+                    residue_secondary(stats, residue).map(|kind| match kind {
+                        SecondaryStructure::Helix => (x(residue), -48.0, 6.0, [0.8, 0.2, 0.6]),
+                        SecondaryStructure::Strand => (x(residue), -48.0, 6.0, [0.2, 0.6, 0.3]),
+                        SecondaryStructure::Loop => (x(residue), -45.5, 1.0, [0.5, 0.5, 0.5]),
+                    })
+                })
+                .collect(),
+        });
     }
+    if stats.plddt_cutoff.is_some() {
+        tracks.push(Track {
+            label: "pLDDT",
+            y: -58.0,
+            cells: residues
+                .filter_map(|residue| {
+                    residue_confidence(stats, residue)
+                        .map(|plddt| (x(residue), -58.0, 6.0, plddt_color(plddt)))
+                })
+                .collect(),
+        });
+    }
+    tracks
+}
+
+fn percentile_note(stats: &ErratStats) -> Option<String> {
+    let resolution = stats.experiment.resolution?;
+    let percentile = stats.quality_percentile?;
+    Some(format!(
+        "At {resolution:.2}A the overall quality factor is at percentile {percentile:.0} of comparable structures."
+    ))
+}
+
+fn plot_limit(limit: f64) -> f64 {
+    (limit * 1000.0).round() / 1000.0
 }
 
 pub(crate) fn write_ps<P: Write, L: Write>(
@@ -67,6 +202,8 @@ pub(crate) fn write_ps<P: Write, L: Write>(
     logw: &mut L,
     file_string: &str,
     stats: &ErratStats,
+    regions: &[ProblemRegion],
+    overlay: Option<&Overlay>,
 ) -> io::Result<()> {
     let layout = build_plot_layout(stats);
     if layout.pages.is_empty() {
@@ -74,7 +211,7 @@ pub(crate) fn write_ps<P: Write, L: Write>(
     }
 
     for page in &layout.pages {
-        let overall_quality = stats.overall_quality_factor.unwrap_or(0.0);
+        let quality_line = layout.quality_line(page, overlay);
 
         writeln!(
             logw,
@@ -87,7 +224,8 @@ pub(crate) fn write_ps<P: Write, L: Write>(
         writeln!(psw, "/sce {{8}} def /scr {{3}} def")?;
         writeln!(
             psw,
-            "90 rotate 110 -380 translate /e95 {{11.527}} def /e99 {{17.191}} def"
+            "90 rotate 110 -380 translate /e95 {{{:.3}}} def /e99 {{{:.3}}} def",
+            stats.calibration.limit_95, stats.calibration.limit_99
         )?;
         writeln!(
             psw,
@@ -110,6 +248,25 @@ pub(crate) fn write_ps<P: Write, L: Write>(
         writeln!(psw, " fill grestore stroke}} def")?;
         writeln!(psw, "/tick {{newpath 0.5 sub scr mul 0 moveto 0 -3 rlineto")?;
         writeln!(psw, " currentpoint stroke moveto -10 -12 rmoveto}} def")?;
+        if overlay.is_some() {
+            writeln!(
+                psw,
+                "/hbar {{sce mul /yval exch def scr mul /xval exch def newpath xval 0 moveto"
+            )?;
+            writeln!(
+                psw,
+                " xval yval lineto scr -2 div 0 rlineto 0 yval -1 mul rlineto closepath"
+            )?;
+            writeln!(psw, " gsave g setrgbcolor fill grestore stroke}} def")?;
+            writeln!(
+                psw,
+                "/bar0 {{/g {{0.7 0.7 0.7}} def hbar}} def /hbar1 {{/g {{1 1 1}} def hbar}} def"
+            )?;
+            writeln!(
+                psw,
+                "/hbar2 {{/g {{1 1 0}} def hbar}} def /hbar3 {{/g {{1 0 0}} def hbar}} def"
+            )?;
+        }
 
         writeln!(psw, "% VARIABLE")?;
         writeln!(
@@ -119,14 +276,16 @@ pub(crate) fn write_ps<P: Write, L: Write>(
             layout.scale,
             page.end_residue - page.start_residue + 1
         )?;
+        for (x, width, [r, g, b]) in region_bands(page, regions) {
+            writeln!(
+                psw,
+                "gsave {r:.3} {g:.3} {b:.3} setrgbcolor {x} scr mul 0 {width} scr mul 27 sce mul rectfill grestore"
+            )?;
+        }
         writeln!(psw, "gsave 0 30 sce mul 20 add translate ")?;
         writeln!(psw, "0 30 moveto (Chain#:{}) show ", page.chain_id as char)?;
         writeln!(psw, "0 50 moveto (File: {}) show ", file_string)?;
-        writeln!(
-            psw,
-            "0 10 moveto (Overall quality factor**: {:.3})show",
-            overall_quality
-        )?;
+        writeln!(psw, "0 10 moveto ({})show", quality_line)?;
         writeln!(psw, "0 70 moveto (Program: ERRAT2) show")?;
         writeln!(psw, "() show")?;
 
@@ -187,6 +346,15 @@ pub(crate) fn write_ps<P: Write, L: Write>(
             psw,
             "0 -136 moveto (resolutions (2.5 to 3A) the average overall quality factor is around 91\\%. ) show"
         )?;
+        for track in page_tracks(stats, page) {
+            writeln!(psw, "-40 {} moveto ({}) show", track.y, track.label)?;
+            for (x, y, height, [r, g, b]) in track.cells {
+                writeln!(
+                    psw,
+                    "gsave {r:.3} {g:.3} {b:.3} setrgbcolor {x} scr mul {y} scr {height} rectfill grestore"
+                )?;
+            }
+        }
         writeln!(
             psw,
             "/Helvetica findfont 18 scalefont setfont 0.5 setlinewidth"
@@ -216,23 +384,25 @@ pub(crate) fn write_ps<P: Write, L: Write>(
 
         for residue in page.start_residue..=page.end_residue {
             let mut bar = "bar1";
-            if stats.errat[residue as usize] > LMT_95 {
+            if stats.errat[residue as usize] > stats.calibration.limit_95 {
                 bar = "bar2";
             }
-            if stats.errat[residue as usize] > LMT_99 {
+            if stats.errat[residue as usize] > stats.calibration.limit_99 {
                 bar = "bar3";
             }
             let mut val = stats.errat[residue as usize];
             if val > 27.0 {
                 val = 27.0;
             }
-            writeln!(
-                psw,
-                "{}\t{:.3} {}",
-                residue - page.start_residue + 1,
-                val,
-                bar
-            )?;
+            let x = residue - page.start_residue + 1;
+            if let Some(overlay) = overlay {
+                if let Some(before) = overlay.errat.get(&residue) {
+                    writeln!(psw, "{}.5\t{:.3} bar0", x - 1, before.min(27.0))?;
+                }
+                writeln!(psw, "{}\t{:.3} h{}", x, val, bar)?;
+                continue;
+            }
+            writeln!(psw, "{}\t{:.3} {}", x, val, bar)?;
         }
         writeln!(psw, "showpage")?;
     }
@@ -245,8 +415,10 @@ pub(crate) fn write_pdf<P: Write, L: Write>(
     logw: &mut L,
     file_string: &str,
     stats: &ErratStats,
+    regions: &[ProblemRegion],
+    overlay: Option<&Overlay>,
 ) -> io::Result<()> {
-    let pages = build_pdf_pages(logw, file_string, stats)?;
+    let pages = build_pdf_pages(logw, file_string, stats, regions, overlay)?;
     let pdf = build_pdf_document(&pages);
     pdfw.write_all(&pdf)?;
     Ok(())
@@ -256,6 +428,8 @@ fn build_pdf_pages<L: Write>(
     logw: &mut L,
     file_string: &str,
     stats: &ErratStats,
+    regions: &[ProblemRegion],
+    overlay: Option<&Overlay>,
 ) -> io::Result<Vec<Vec<u8>>> {
     let layout = build_plot_layout(stats);
     if layout.pages.is_empty() {
@@ -264,7 +438,7 @@ fn build_pdf_pages<L: Write>(
 
     let mut pages = Vec::new();
     for page in &layout.pages {
-        let overall_quality = stats.overall_quality_factor.unwrap_or(0.0);
+        let quality_line = layout.quality_line(page, overlay);
 
         writeln!(
             logw,
@@ -280,8 +454,11 @@ fn build_pdf_pages<L: Write>(
             page.start_residue,
             page.end_residue,
             page.chain_id,
-            overall_quality,
+            &quality_line,
             layout.scale,
+            &region_bands(page, regions),
+            &page_tracks(stats, page),
+            overlay,
         );
         pages.push(page_buf);
     }
@@ -289,6 +466,7 @@ fn build_pdf_pages<L: Write>(
     Ok(pages)
 }
 
+#[allow(clippy::too_many_arguments)]
 fn write_pdf_page(
     buf: &mut Vec<u8>,
     file_string: &str,
@@ -296,20 +474,34 @@ fn write_pdf_page(
     ir0: i32,
     ir: i32,
     chain_id: u8,
-    overall_quality: f64,
+    quality_line: &str,
     sz: f64,
+    bands: &[(f64, f64, [f64; 3])],
+    tracks: &[Track],
+    overlay: Option<&Overlay>,
 ) {
     let scr = 3.0;
     let sce = 8.0;
-    let e95 = 11.527;
-    let e99 = 17.191;
+    let e95 = plot_limit(stats.calibration.limit_95);
+    let e99 = plot_limit(stats.calibration.limit_99);
     let rlim = (ir - ir0 + 1) as f64;
 
-    let _ = write!(
+    let _ = writeln!(
         buf,
-        "q\n0 1 -1 0 0 0 cm\n1 0 0 1 110 -380 cm\n{:.3} 0 0 {:.3} 0 0 cm\n0.5 w\n0 0 0 RG\n0 0 0 rg\n",
+        "q\n0 1 -1 0 0 0 cm\n1 0 0 1 110 -380 cm\n{:.3} 0 0 {:.3} 0 0 cm\n0.5 w\n0 0 0 RG\n0 0 0 rg",
         sz, sz
     );
+    for &(x, width, [r, g, b]) in bands {
+        let _ = writeln!(buf, "q");
+        pdf_set_fill_rgb(buf, r, g, b);
+        let _ = writeln!(
+            buf,
+            "{:.3} 0.000 {:.3} {:.3} re f\nQ",
+            x * scr,
+            width * scr,
+            27.0 * sce
+        );
+    }
 
     let header_y = 30.0 * sce + 20.0;
     pdf_text(
@@ -326,13 +518,7 @@ fn write_pdf_page(
         18.0,
         &format!("File: {}", file_string),
     );
-    pdf_text(
-        buf,
-        0.0,
-        header_y + 10.0,
-        18.0,
-        &format!("Overall quality factor**: {:.3}", overall_quality),
-    );
+    pdf_text(buf, 0.0, header_y + 10.0, 18.0, quality_line);
     pdf_text(buf, 0.0, header_y + 70.0, 18.0, "Program: ERRAT2");
 
     pdf_line(buf, 0.0, 0.0, 0.0, 27.0 * sce);
@@ -394,10 +580,18 @@ fn write_pdf_page(
         12.0,
         "resolutions (2.5 to 3A) the average overall quality factor is around 91%. )",
     );
+    for track in tracks {
+        pdf_text(buf, -40.0, track.y, 12.0, track.label);
+        for &(x, y, height, [r, g, b]) in &track.cells {
+            let _ = writeln!(buf, "q");
+            pdf_set_fill_rgb(buf, r, g, b);
+            let _ = writeln!(buf, "{:.3} {y:.3} {scr:.3} {height:.3} re f\nQ", x * scr);
+        }
+    }
 
-    let _ = write!(buf, "q 0 1 -1 0 -40 -5 cm\n");
+    let _ = writeln!(buf, "q 0 1 -1 0 -40 -5 cm");
     pdf_text(buf, 80.0, 0.0, 18.0, "Error value*");
-    let _ = write!(buf, "Q\n");
+    let _ = writeln!(buf, "Q");
 
     for residue in ir0..=ir {
         let x = (residue - ir0 + 1) as f64;
@@ -414,10 +608,10 @@ fn write_pdf_page(
 
     for residue in ir0..=ir {
         let mut bar = 1;
-        if stats.errat[residue as usize] > LMT_95 {
+        if stats.errat[residue as usize] > stats.calibration.limit_95 {
             bar = 2;
         }
-        if stats.errat[residue as usize] > LMT_99 {
+        if stats.errat[residue as usize] > stats.calibration.limit_99 {
             bar = 3;
         }
         let mut val = stats.errat[residue as usize];
@@ -426,15 +620,298 @@ fn write_pdf_page(
         }
         let x = (residue - ir0 + 1) as f64 * scr;
         let y = val * sce;
+        let mut width = scr;
+        if let Some(overlay) = overlay {
+            width = scr / 2.0;
+            if let Some(before) = overlay.errat.get(&residue) {
+                pdf_set_fill_rgb(buf, 0.7, 0.7, 0.7);
+                pdf_rect_fill_stroke(buf, x - scr, 0.0, width, before.min(27.0) * sce);
+            }
+        }
         match bar {
             1 => pdf_set_fill_rgb(buf, 1.0, 1.0, 1.0),
             2 => pdf_set_fill_rgb(buf, 1.0, 1.0, 0.0),
             _ => pdf_set_fill_rgb(buf, 1.0, 0.0, 0.0),
         }
-        pdf_rect_fill_stroke(buf, x - scr, 0.0, scr, y);
+        pdf_rect_fill_stroke(buf, x - width, 0.0, width, y);
     }
 
-    let _ = write!(buf, "Q\n");
+    let _ = writeln!(buf, "Q");
+}
+
+pub(crate) fn write_svg<P: Write, L: Write>(
+    svgw: &mut P,
+    logw: &mut L,
+    file_string: &str,
+    stats: &ErratStats,
+    regions: &[ProblemRegion],
+    overlay: Option<&Overlay>,
+) -> io::Result<()> {
+    let layout = build_plot_layout(stats);
+    if layout.pages.is_empty() {
+        return Ok(());
+    }
+
+    let height = SVG_PAGE_HEIGHT * layout.pages.len() as f64;
+    writeln!(
+        svgw,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\" font-family=\"Helvetica, Arial, sans-serif\">",
+        w = SVG_PAGE_WIDTH,
+        h = height
+    )?;
+    for (index, page) in layout.pages.iter().enumerate() {
+        let quality_line = layout.quality_line(page, overlay);
+
+        writeln!(
+            logw,
+            "# Chain Label {}:    Residue range {} to {}",
+            page.chain_id as char, page.start_residue, page.end_residue
+        )?;
+
+        let mut buf = String::new();
+        write_svg_page(
+            &mut buf,
+            file_string,
+            stats,
+            page,
+            &quality_line,
+            layout.scale,
+            &region_bands(page, regions),
+            &page_tracks(stats, page),
+            overlay,
+        );
+        writeln!(
+            svgw,
+            "<g transform=\"translate(0 {})\">",
+            SVG_PAGE_HEIGHT * index as f64
+        )?;
+        svgw.write_all(buf.as_bytes())?;
+        writeln!(svgw, "</g>")?;
+    }
+    writeln!(svgw, "</svg>")?;
+    Ok(())
+}
+
+const SVG_PAGE_WIDTH: f64 = 792.0;
+const SVG_PAGE_HEIGHT: f64 = 612.0;
+
+struct SvgCanvas<'a> {
+    buf: &'a mut String,
+    sz: f64,
+}
+
+impl SvgCanvas<'_> {
+    fn x(&self, x: f64) -> f64 {
+        110.0 + x * self.sz
+    }
+
+    fn y(&self, y: f64) -> f64 {
+        400.0 - y * self.sz
+    }
+
+    fn text(&mut self, x: f64, y: f64, size: f64, text: &str) {
+        let _ = writeln!(
+            self.buf,
+            "<text x=\"{:.3}\" y=\"{:.3}\" font-size=\"{:.2}\">{}</text>",
+            self.x(x),
+            self.y(y),
+            size * self.sz,
+            svg_escape(text)
+        );
+    }
+
+    fn line(&mut self, x1: f64, y1: f64, x2: f64, y2: f64) {
+        let _ = writeln!(
+            self.buf,
+            "<line x1=\"{:.3}\" y1=\"{:.3}\" x2=\"{:.3}\" y2=\"{:.3}\" stroke=\"black\" stroke-width=\"{:.3}\"/>",
+            self.x(x1),
+            self.y(y1),
+            self.x(x2),
+            self.y(y2),
+            0.5 * self.sz
+        );
+    }
+
+    fn band(&mut self, x: f64, w: f64, h: f64, rgb: [f64; 3]) {
+        self.fill(x, 0.0, w, h, rgb);
+    }
+
+    fn fill(&mut self, x: f64, y: f64, w: f64, h: f64, rgb: [f64; 3]) {
+        let [r, g, b] = rgb.map(|c| (c * 255.0).round() as u8);
+        let _ = writeln!(
+            self.buf,
+            "<rect x=\"{:.3}\" y=\"{:.3}\" width=\"{:.3}\" height=\"{:.3}\" fill=\"#{r:02x}{g:02x}{b:02x}\"/>",
+            self.x(x),
+            self.y(y + h),
+            w * self.sz,
+            h * self.sz
+        );
+    }
+
+    fn rect(&mut self, x: f64, y: f64, w: f64, h: f64, fill: &str) {
+        let _ = writeln!(
+            self.buf,
+            "<rect x=\"{:.3}\" y=\"{:.3}\" width=\"{:.3}\" height=\"{:.3}\" fill=\"{}\" stroke=\"black\" stroke-width=\"{:.3}\"/>",
+            self.x(x),
+            self.y(y + h),
+            w * self.sz,
+            h * self.sz,
+            fill,
+            0.5 * self.sz
+        );
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn write_svg_page(
+    buf: &mut String,
+    file_string: &str,
+    stats: &ErratStats,
+    page: &PageSlice,
+    quality_line: &str,
+    sz: f64,
+    bands: &[(f64, f64, [f64; 3])],
+    tracks: &[Track],
+    overlay: Option<&Overlay>,
+) {
+    let scr = 3.0;
+    let sce = 8.0;
+    let e95 = plot_limit(stats.calibration.limit_95);
+    let e99 = plot_limit(stats.calibration.limit_99);
+    let ir0 = page.start_residue;
+    let ir = page.end_residue;
+    let rlim = (ir - ir0 + 1) as f64;
+    let mut canvas = SvgCanvas { buf, sz };
+    for &(x, width, [r, g, b]) in bands {
+        canvas.band(x * scr, width * scr, 27.0 * sce, [r, g, b]);
+    }
+
+    let header_y = 30.0 * sce + 20.0;
+    canvas.text(
+        0.0,
+        header_y + 30.0,
+        18.0,
+        &format!("Chain#:{}", page.chain_id as char),
+    );
+    canvas.text(
+        0.0,
+        header_y + 50.0,
+        18.0,
+        &format!("File: {}", file_string),
+    );
+    canvas.text(0.0, header_y + 10.0, 18.0, quality_line);
+    canvas.text(0.0, header_y + 70.0, 18.0, "Program: ERRAT2");
+
+    canvas.line(0.0, 0.0, 0.0, 27.0 * sce);
+    canvas.line(rlim * scr, 0.0, rlim * scr, 27.0 * sce);
+    canvas.line(0.0, 0.0, rlim * scr, 0.0);
+    canvas.line(-3.0, e95 * sce, rlim * scr + 3.0, e95 * sce);
+    canvas.line(-3.0, e99 * sce, rlim * scr + 3.0, e99 * sce);
+    canvas.line(0.0, 27.0 * sce, rlim * scr, 27.0 * sce);
+
+    canvas.text(
+        rlim * scr / 2.0 - 100.0,
+        -34.0,
+        18.0,
+        "Residue # (window center)",
+    );
+    canvas.text(-34.0, e95 * sce - 4.0, 14.0, "95%");
+    canvas.text(-34.0, e99 * sce - 4.0, 14.0, "99%");
+    for (y, line) in FOOTNOTES {
+        canvas.text(0.0, y, 12.0, line);
+    }
+    if let Some(note) = percentile_note(stats) {
+        canvas.text(0.0, -154.0, 12.0, &note);
+    }
+    for track in tracks {
+        canvas.text(-40.0, track.y, 12.0, track.label);
+        for &(x, y, height, rgb) in &track.cells {
+            canvas.fill(x * scr, y, scr, height, rgb);
+        }
+    }
+
+    let _ = writeln!(
+        canvas.buf,
+        "<text transform=\"translate({:.3} {:.3}) rotate(-90)\" x=\"{:.3}\" y=\"0\" font-size=\"{:.2}\">Error value*</text>",
+        canvas.x(-40.0),
+        canvas.y(-5.0),
+        80.0 * sz,
+        18.0 * sz
+    );
+
+    for residue in ir0..=ir {
+        let x = (residue - ir0 + 1) as f64;
+        if residue % 20 == 0 {
+            let tick_x = (x - 0.5) * scr;
+            canvas.line(tick_x, 0.0, tick_x, -3.0);
+            let label = residue - (CHAINDIF * (residue / CHAINDIF));
+            canvas.text(tick_x - 10.0, -15.0, 16.0, &label.to_string());
+        } else if residue % 10 == 0 {
+            let tick_x = (x - 0.5) * scr;
+            canvas.line(tick_x, 0.0, tick_x, -3.0);
+        }
+    }
+
+    for residue in ir0..=ir {
+        let value = stats.errat[residue as usize];
+        let fill = if value > stats.calibration.limit_99 {
+            "#ff0000"
+        } else if value > stats.calibration.limit_95 {
+            "#ffff00"
+        } else {
+            "#ffffff"
+        };
+        let x = (residue - ir0 + 1) as f64 * scr;
+        let mut width = scr;
+        if let Some(overlay) = overlay {
+            width = scr / 2.0;
+            if let Some(before) = overlay.errat.get(&residue) {
+                canvas.rect(x - scr, 0.0, width, before.min(27.0) * sce, "#b3b3b3");
+            }
+        }
+        canvas.rect(x - width, 0.0, width, value.min(27.0) * sce, fill);
+    }
+}
+
+const FOOTNOTES: [(f64, &str); 6] = [
+    (
+        -70.0,
+        "*On the error axis, two lines are drawn to indicate the confidence with",
+    ),
+    (
+        -82.0,
+        "which it is possible to reject regions that exceed that error value.",
+    ),
+    (
+        -100.0,
+        "**Expressed as the percentage of the protein for which the calculated",
+    ),
+    (
+        -112.0,
+        "error value falls below the 95% rejection limit.  Good high resolution",
+    ),
+    (
+        -124.0,
+        "structures generally produce values around 95% or higher.  For lower",
+    ),
+    (
+        -136.0,
+        "resolutions (2.5 to 3A) the average overall quality factor is around 91%.",
+    ),
+];
+
+fn svg_escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            _ => out.push(ch),
+        }
+    }
+    out
 }
 
 fn build_pdf_document(pages: &[Vec<u8>]) -> Vec<u8> {
@@ -452,9 +929,9 @@ fn build_pdf_document(pages: &[Vec<u8>]) -> Vec<u8> {
     let first_content_id = first_page_id + page_count;
 
     offsets.push(buf.len());
-    let _ = write!(
+    let _ = writeln!(
         buf,
-        "{} 0 obj\n<< /Type /Catalog /Pages {} 0 R >>\nendobj\n",
+        "{} 0 obj\n<< /Type /Catalog /Pages {} 0 R >>\nendobj",
         catalog_id, pages_id
     );
 
@@ -463,16 +940,16 @@ fn build_pdf_document(pages: &[Vec<u8>]) -> Vec<u8> {
         let _ = write!(kids, "{} 0 R ", first_page_id + i);
     }
     offsets.push(buf.len());
-    let _ = write!(
+    let _ = writeln!(
         buf,
-        "{} 0 obj\n<< /Type /Pages /Kids [{}] /Count {} >>\nendobj\n",
+        "{} 0 obj\n<< /Type /Pages /Kids [{}] /Count {} >>\nendobj",
         pages_id, kids, page_count
     );
 
     offsets.push(buf.len());
-    let _ = write!(
+    let _ = writeln!(
         buf,
-        "{} 0 obj\n<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>\nendobj\n",
+        "{} 0 obj\n<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>\nendobj",
         font_id
     );
 
@@ -480,9 +957,9 @@ fn build_pdf_document(pages: &[Vec<u8>]) -> Vec<u8> {
         let page_id = first_page_id + i;
         let content_id = first_content_id + i;
         offsets.push(buf.len());
-        let _ = write!(
+        let _ = writeln!(
             buf,
-            "{} 0 obj\n<< /Type /Page /Parent {} 0 R /MediaBox [0 0 612 792] /Resources << /Font << /F1 {} 0 R >> >> /Contents {} 0 R >>\nendobj\n",
+            "{} 0 obj\n<< /Type /Page /Parent {} 0 R /MediaBox [0 0 612 792] /Resources << /Font << /F1 {} 0 R >> >> /Contents {} 0 R >>\nendobj",
             page_id, pages_id, font_id, content_id
         );
     }
@@ -496,7 +973,7 @@ fn build_pdf_document(pages: &[Vec<u8>]) -> Vec<u8> {
         stream.push(b'\n');
         stream.extend_from_slice(b"endstream");
         offsets.push(buf.len());
-        let _ = write!(buf, "{} 0 obj\n", content_id);
+        let _ = writeln!(buf, "{} 0 obj", content_id);
         buf.extend_from_slice(&stream);
         buf.extend_from_slice(b"\nendobj\n");
     }
@@ -505,11 +982,11 @@ fn build_pdf_document(pages: &[Vec<u8>]) -> Vec<u8> {
     let _ = write!(buf, "xref\n0 {}\n", total_objects + 1);
     buf.extend_from_slice(b"0000000000 65535 f \n");
     for offset in offsets.iter().take(total_objects) {
-        let _ = write!(buf, "{:010} 00000 n \n", offset);
+        let _ = writeln!(buf, "{:010} 00000 n ", offset);
     }
-    let _ = write!(
+    let _ = writeln!(
         buf,
-        "trailer\n<< /Size {} /Root {} 0 R >>\nstartxref\n{}\n%%EOF\n",
+        "trailer\n<< /Size {} /Root {} 0 R >>\nstartxref\n{}\n%%EOF",
         total_objects + 1,
         catalog_id,
         xref_start
@@ -533,28 +1010,30 @@ fn pdf_escape(text: &str) -> String {
 
 fn pdf_text(buf: &mut Vec<u8>, x: f64, y: f64, size: f64, text: &str) {
     let escaped = pdf_escape(text);
-    let _ = write!(
+    let _ = writeln!(
         buf,
-        "BT /F1 {:.2} Tf 1 0 0 1 {:.3} {:.3} Tm ({}) Tj ET\n",
+        "BT /F1 {:.2} Tf 1 0 0 1 {:.3} {:.3} Tm ({}) Tj ET",
         size, x, y, escaped
     );
 }
 
 fn pdf_line(buf: &mut Vec<u8>, x1: f64, y1: f64, x2: f64, y2: f64) {
-    let _ = write!(buf, "{:.3} {:.3} m {:.3} {:.3} l S\n", x1, y1, x2, y2);
+    let _ = writeln!(buf, "{:.3} {:.3} m {:.3} {:.3} l S", x1, y1, x2, y2);
 }
 
 fn pdf_rect_fill_stroke(buf: &mut Vec<u8>, x: f64, y: f64, w: f64, h: f64) {
-    let _ = write!(buf, "{:.3} {:.3} {:.3} {:.3} re B\n", x, y, w, h);
+    let _ = writeln!(buf, "{:.3} {:.3} {:.3} {:.3} re B", x, y, w, h);
 }
 
 fn pdf_set_fill_rgb(buf: &mut Vec<u8>, r: f64, g: f64, b: f64) {
-    let _ = write!(buf, "{:.3} {:.3} {:.3} rg\n", r, g, b);
+    let _ = writeln!(buf, "{:.3} {:.3} {:.3} rg", r, g, b);
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::calibration::Calibration;
+    use crate::model::Experiment;
 
     #[test]
     fn build_pdf_document_writes_valid_header() {
@@ -584,9 +1063,184 @@ mod tests {
             atmnum: 3,
             warning_frames: Vec::new(),
             scored_frames: Vec::new(),
+            interface_residues: Vec::new(),
+            experiment: Experiment::default(),
+            quality_percentile: None,
+            plddt: Vec::new(),
+            plddt_cutoff: None,
+            secondary: Vec::new(),
+            calibration: Calibration::classic(),
         };
         let layout = build_plot_layout(&stats);
         assert!(!layout.pages.is_empty());
         assert!(layout.scale > 0.0);
     }
+
+    #[test]
+    fn write_ps_uses_calibration_limits() {
+        let mut resnum = vec![0; 30];
+        for (i, r) in resnum.iter_mut().enumerate().take(21).skip(1) {
+            *r = i as i32;
+        }
+        let mut errat = vec![0.0; 40];
+        errat[10] = 9.0;
+        let mut calibration = Calibration::classic();
+        calibration.limit_95 = 8.0;
+        calibration.limit_99 = 12.25;
+        let stats = ErratStats {
+            stat: 1.0,
+            pstat: 1.0,
+            avg_probability: Some(9.0),
+            overall_quality_factor: Some(0.0),
+            errat,
+            resnum,
+            chain_id: vec![b'A'; 30],
+            atmnum: 20,
+            warning_frames: Vec::new(),
+            scored_frames: Vec::new(),
+            interface_residues: Vec::new(),
+            experiment: Experiment::default(),
+            quality_percentile: None,
+            plddt: Vec::new(),
+            plddt_cutoff: None,
+            secondary: Vec::new(),
+            calibration,
+        };
+        let mut ps = Vec::new();
+        write_ps(&mut ps, &mut io::sink(), "demo", &stats, &[], None).unwrap();
+        let ps = String::from_utf8(ps).unwrap();
+        assert!(ps.contains("/e95 {8.000} def /e99 {12.250} def"));
+        assert!(ps.contains("\t9.000 bar2"));
+    }
+
+    #[test]
+    fn multi_chain_pages_show_chain_quality() {
+        let mut resnum = vec![0; 50];
+        let mut chain_id = vec![b'A'; 50];
+        for i in 1..=40 {
+            resnum[i] = if i <= 20 {
+                i as i32
+            } else {
+                CHAINDIF + i as i32 - 20
+            };
+            if i > 20 {
+                chain_id[i] = b'B';
+            }
+        }
+        let interactions = crate::api::WindowInteractions {
+            counts: [0.0; 5],
+            total: 0.0,
+            fractions: [0.0; 5],
+            inter_chain_counts: [0.0; 5],
+        };
+        let frame = |center_residue, error_value| crate::model::FrameScoreRaw {
+            center_residue,
+            residue_offset: 0,
+            error_value,
+            interactions,
+        };
+        let stats = ErratStats {
+            stat: 2.0,
+            pstat: 1.0,
+            avg_probability: Some(12.5),
+            overall_quality_factor: Some(50.0),
+            errat: vec![0.0; CHAINDIF as usize + 40],
+            resnum,
+            chain_id,
+            atmnum: 40,
+            warning_frames: Vec::new(),
+            scored_frames: vec![frame(6, 5.0), frame(CHAINDIF + 10, 20.0)],
+            interface_residues: Vec::new(),
+            experiment: Experiment::default(),
+            quality_percentile: None,
+            plddt: Vec::new(),
+            plddt_cutoff: None,
+            secondary: Vec::new(),
+            calibration: Calibration::classic(),
+        };
+        let mut ps = Vec::new();
+        write_ps(&mut ps, &mut io::sink(), "demo", &stats, &[], None).unwrap();
+        let ps = String::from_utf8(ps).unwrap();
+        assert!(ps.contains("(Chain quality factor**: 100.000)show"));
+        assert!(ps.contains("(Chain quality factor**: 0.000)show"));
+        assert!(!ps.contains("Overall quality factor**"));
+    }
+
+    #[test]
+    fn problem_regions_shade_plot_pages() {
+        let mut resnum = vec![0; 30];
+        for (i, r) in resnum.iter_mut().enumerate().take(21).skip(1) {
+            *r = i as i32;
+        }
+        let stats = ErratStats {
+            stat: 1.0,
+            pstat: 1.0,
+            avg_probability: Some(20.0),
+            overall_quality_factor: Some(0.0),
+            errat: vec![0.0; 40],
+            resnum,
+            chain_id: vec![b'A'; 30],
+            atmnum: 20,
+            warning_frames: Vec::new(),
+            scored_frames: Vec::new(),
+            interface_residues: Vec::new(),
+            experiment: Experiment::default(),
+            quality_percentile: None,
+            plddt: Vec::new(),
+            plddt_cutoff: None,
+            secondary: Vec::new(),
+            calibration: Calibration::classic(),
+        };
+        let regions = [ProblemRegion {
+            chain_id: "A".to_string(),
+            start_residue: 2,
+            end_residue: 8,
+            length: 7,
+            peak_error_value: 20.0,
+            mean_error_value: 15.0,
+            severity: FrameStatus::Warning99,
+        }];
+
+        let mut ps = Vec::new();
+        write_ps(&mut ps, &mut io::sink(), "demo", &stats, &regions, None).unwrap();
+        let ps = String::from_utf8(ps).unwrap();
+        assert!(ps.contains(
+            "gsave 1.000 0.800 0.800 setrgbcolor 0 scr mul 0 4 scr mul 27 sce mul rectfill grestore"
+        ));
+
+        let mut svg = Vec::new();
+        write_svg(&mut svg, &mut io::sink(), "demo", &stats, &regions, None).unwrap();
+        assert!(
+            String::from_utf8(svg)
+                .unwrap()
+                .contains("fill=\"#ffcccc\"/>")
+        );
+
+        let mut pdf = Vec::new();
+        write_pdf(&mut pdf, &mut io::sink(), "demo", &stats, &regions, None).unwrap();
+        let pdf = String::from_utf8_lossy(&pdf).into_owned();
+        assert!(pdf.contains("0.000 0.000 12.000 216.000 re f"));
+        assert!(!ps.contains("percentile"));
+
+        let stats = ErratStats {
+            experiment: Experiment {
+                method: None,
+                resolution: Some(2.0),
+            },
+            quality_percentile: Some(42.4),
+            plddt: Vec::new(),
+            plddt_cutoff: None,
+            secondary: Vec::new(),
+            ..stats
+        };
+        let mut ps = Vec::new();
+        write_ps(&mut ps, &mut io::sink(), "demo", &stats, &regions, None).unwrap();
+        assert!(!String::from_utf8(ps).unwrap().contains("percentile"));
+        let mut pdf = Vec::new();
+        write_pdf(&mut pdf, &mut io::sink(), "demo", &stats, &regions, None).unwrap();
+        assert!(!String::from_utf8_lossy(&pdf).contains("percentile"));
+        let mut svg = Vec::new();
+        write_svg(&mut svg, &mut io::sink(), "demo", &stats, &regions, None).unwrap();
+        assert!(String::from_utf8(svg).unwrap().contains("percentile 42"));
+    }
 }
@@ -0,0 +1,360 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use crate::api::AnalysisOptions;
+use crate::model::{ErratStats, Experiment};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ResolutionBin {
+    pub max_resolution: f64,
+    pub quality_factors: [f64; 11],
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ResolutionReference {
+    pub bins: Vec<ResolutionBin>,
+}
+
+impl Default for ResolutionReference {
+    fn default() -> Self {
+        ResolutionReference::builtin()
+    }
+}
+
+fn reference_error(line: usize, message: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("resolution reference line {line}: {message}"),
+    )
+}
+
+impl ResolutionReference {
+    /// Placeholder deciles, not fitted to any survey of deposited structures.
+    ///
+    /// The values are hand-picked so that the medians sit near the guidance in
+    /// the plot footnotes (about 95% at high resolution, about 91% at 2.5-3 A).
+    /// Pass a measured table with `--resolution-reference` for real percentiles.
+    pub fn builtin() -> Self {
+        let bin = |max_resolution, quality_factors| ResolutionBin {
+            max_resolution,
+            quality_factors,
+        };
+        ResolutionReference {
+            bins: vec![
+                bin(
+                    1.5,
+                    [
+                        70.0, 89.5, 92.6, 94.4, 95.6, 96.5, 97.3, 98.0, 98.7, 99.4, 100.0,
+                    ],
+                ),
+                bin(
+                    2.0,
+                    [
+                        65.0, 86.8, 90.4, 92.6, 94.0, 95.1, 96.1, 97.0, 97.9, 98.9, 100.0,
+                    ],
+                ),
+                bin(
+                    2.5,
+                    [
+                        60.0, 83.5, 87.6, 90.1, 91.9, 93.3, 94.6, 95.8, 97.0, 98.3, 100.0,
+                    ],
+                ),
+                bin(
+                    3.0,
+                    [
+                        55.0, 79.4, 84.1, 87.1, 89.3, 91.0, 92.6, 94.1, 95.6, 97.4, 100.0,
+                    ],
+                ),
+                bin(
+                    3.5,
+                    [
+                        45.0, 72.8, 78.4, 82.0, 84.8, 87.0, 89.0, 91.0, 93.0, 95.6, 100.0,
+                    ],
+                ),
+                bin(
+                    4.5,
+                    [
+                        35.0, 64.0, 70.5, 75.0, 78.4, 81.3, 84.0, 86.7, 89.5, 93.0, 100.0,
+                    ],
+                ),
+                bin(
+                    f64::INFINITY,
+                    [
+                        20.0, 52.0, 60.0, 65.6, 70.0, 73.8, 77.4, 81.0, 84.8, 89.6, 100.0,
+                    ],
+                ),
+            ],
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let file = File::open(path)?;
+        ResolutionReference::read_from(BufReader::new(file))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        self.write_to(&mut out)?;
+        out.flush()
+    }
+
+    pub fn read_from<R: BufRead>(reader: R) -> io::Result<Self> {
+        let mut bins = Vec::new();
+        for (idx, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line_no = idx + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields[0] != "bin" {
+                return Err(reference_error(
+                    line_no,
+                    &format!("unknown key {}", fields[0]),
+                ));
+            }
+            if fields.len() != 13 {
+                return Err(reference_error(
+                    line_no,
+                    &format!(
+                        "expected a resolution and 11 quality factors, found {} values",
+                        fields.len() - 1
+                    ),
+                ));
+            }
+            let mut values = [0.0f64; 12];
+            for (value, field) in values.iter_mut().zip(&fields[1..]) {
+                *value = match *field {
+                    "inf" => f64::INFINITY,
+                    field => field
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|v| v.is_finite())
+                        .ok_or_else(|| {
+                            reference_error(line_no, &format!("invalid number {field}"))
+                        })?,
+                };
+            }
+            bins.push(ResolutionBin {
+                max_resolution: values[0],
+                quality_factors: std::array::from_fn(|k| values[k + 1]),
+            });
+        }
+        let reference = ResolutionReference { bins };
+        reference.validate()?;
+        Ok(reference)
+    }
+
+    pub fn validate(&self) -> io::Result<()> {
+        let invalid = |message: &str| {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("resolution reference {message}"),
+            ))
+        };
+        if self.bins.is_empty() {
+            return invalid("needs at least one bin");
+        }
+        if self
+            .bins
+            .windows(2)
+            .any(|pair| pair[0].max_resolution >= pair[1].max_resolution)
+            || self.bins[0].max_resolution <= 0.0
+        {
+            return invalid("bins must have positive, increasing resolution limits");
+        }
+        if self.bins.iter().any(|bin| {
+            bin.quality_factors[0] < 0.0
+                || bin.quality_factors[10] > 100.0
+                || bin.quality_factors.windows(2).any(|pair| pair[0] > pair[1])
+        }) {
+            return invalid("quality factors must be non-decreasing between 0 and 100");
+        }
+        Ok(())
+    }
+
+    pub fn write_to<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        writeln!(
+            out,
+            "# max_resolution quality factor at percentiles 0 10 ... 100"
+        )?;
+        for bin in &self.bins {
+            let values = bin
+                .quality_factors
+                .iter()
+                .map(|v| v.to_string())
+                .collect::<Vec<_>>()
+                .join(" ");
+            writeln!(out, "bin {} {values}", bin.max_resolution)?;
+        }
+        Ok(())
+    }
+
+    pub fn percentile(&self, resolution: f64, quality_factor: f64) -> Option<f64> {
+        let bin = self
+            .bins
+            .iter()
+            .find(|bin| resolution <= bin.max_resolution)
+            .or(self.bins.last())?;
+        Some(decile_percentile(&bin.quality_factors, quality_factor))
+    }
+}
+
+pub(crate) fn decile_percentile(q: &[f64; 11], value: f64) -> f64 {
+    if value >= q[10] {
+        return 100.0;
+    }
+    if value <= q[0] {
+        return 0.0;
+    }
+    let k = (0..10).rfind(|&k| q[k] <= value).unwrap_or(0);
+    10.0 * k as f64 + 10.0 * (value - q[k]) / (q[k + 1] - q[k])
+}
+
+pub(crate) fn experiment_record(line: &[u8], experiment: &mut Experiment) {
+    let text = String::from_utf8_lossy(line);
+    if let Some(rest) = text.strip_prefix("EXPDTA") {
+        let method = rest.trim_start_matches(|c: char| c.is_ascii_digit() || c == ' ');
+        let method = method.trim();
+        if !method.is_empty() {
+            match &mut experiment.method {
+                Some(existing) => {
+                    existing.push(' ');
+                    existing.push_str(method);
+                }
+                None => experiment.method = Some(method.to_string()),
+            }
+        }
+    } else if let Some(rest) = text.strip_prefix("REMARK   2 RESOLUTION.")
+        && experiment.resolution.is_none()
+    {
+        experiment.resolution = rest
+            .split_whitespace()
+            .next()
+            .and_then(|value| value.parse::<f64>().ok())
+            .filter(|value| *value > 0.0);
+    }
+}
+
+pub(crate) fn is_experiment_category(category: &str) -> bool {
+    ["_refine", "_em_3d_reconstruction", "_exptl"]
+        .iter()
+        .any(|name| category.eq_ignore_ascii_case(name))
+}
+
+pub(crate) fn experiment_item(
+    category: &str,
+    item: &str,
+    value: &str,
+    experiment: &mut Experiment,
+) {
+    if value == "?" || value == "." {
+        return;
+    }
+    let category = category.to_ascii_lowercase();
+    let item = item.to_ascii_lowercase();
+    match (category.as_str(), item.as_str()) {
+        ("_refine", "ls_d_res_high") | ("_em_3d_reconstruction", "resolution")
+            if experiment.resolution.is_none() =>
+        {
+            experiment.resolution = value.parse::<f64>().ok().filter(|value| *value > 0.0);
+        }
+        ("_exptl", "method") => match &mut experiment.method {
+            Some(existing) => {
+                existing.push_str("; ");
+                existing.push_str(value.trim());
+            }
+            None => experiment.method = Some(value.trim().to_string()),
+        },
+        _ => {}
+    }
+}
+
+pub(crate) fn mark_quality_percentile(stats: &mut ErratStats, options: &AnalysisOptions) {
+    let Some(resolution) = stats.experiment.resolution else {
+        return;
+    };
+    stats.quality_percentile = stats
+        .overall_quality_factor
+        .and_then(|quality| options.resolution_reference.percentile(resolution, quality));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::InputFormat;
+    use crate::parser::parse_reader;
+    use std::io::Cursor;
+
+    #[test]
+    fn reference_round_trips_and_interpolates() {
+        let reference = ResolutionReference::builtin();
+        let mut text = Vec::new();
+        reference.write_to(&mut text).unwrap();
+        assert_eq!(
+            ResolutionReference::read_from(Cursor::new(text)).unwrap(),
+            reference
+        );
+
+        assert_eq!(reference.percentile(1.8, 95.1), Some(50.0));
+        assert_eq!(reference.percentile(2.7, 100.0), Some(100.0));
+        assert_eq!(reference.percentile(2.7, 10.0), Some(0.0));
+        let high = reference.percentile(1.2, 93.0).unwrap();
+        let low = reference.percentile(2.8, 93.0).unwrap();
+        assert!(high < low);
+        assert_eq!(reference.percentile(12.0, 77.4), Some(60.0));
+
+        let err = ResolutionReference::read_from(Cursor::new("bin 2.0 1 2 3\n")).unwrap_err();
+        assert!(err.to_string().contains("line 1"));
+        let err =
+            ResolutionReference::read_from(Cursor::new("bin 2.0 90 80 70 60 50 40 30 20 10 5 0\n"))
+                .unwrap_err();
+        assert!(err.to_string().contains("non-decreasing"));
+    }
+
+    #[test]
+    fn parsers_read_resolution_and_method() {
+        let pdb = "\
+EXPDTA    X-RAY DIFFRACTION
+REMARK   2
+REMARK   2 RESOLUTION.    1.80 ANGSTROMS.
+ATOM      1  CA  ALA A   1       1.000   2.000   3.000  1.00 20.00           C
+";
+        let data = parse_reader(&mut Cursor::new(pdb), InputFormat::Pdb, &mut io::sink()).unwrap();
+        assert_eq!(data.experiment.resolution, Some(1.8));
+        assert_eq!(data.experiment.method.as_deref(), Some("X-RAY DIFFRACTION"));
+
+        let nmr = pdb
+            .replace("X-RAY DIFFRACTION", "SOLUTION NMR")
+            .replace("1.80 ANGSTROMS.", "NOT APPLICABLE.");
+        let data = parse_reader(&mut Cursor::new(nmr), InputFormat::Pdb, &mut io::sink()).unwrap();
+        assert_eq!(data.experiment.resolution, None);
+        assert_eq!(data.experiment.method.as_deref(), Some("SOLUTION NMR"));
+
+        let cif = "\
+data_test
+_exptl.method 'ELECTRON MICROSCOPY'
+_em_3d_reconstruction.resolution 3.2
+loop_
+_atom_site.group_PDB
+_atom_site.type_symbol
+_atom_site.label_atom_id
+_atom_site.label_comp_id
+_atom_site.auth_asym_id
+_atom_site.auth_seq_id
+_atom_site.Cartn_x
+_atom_site.Cartn_y
+_atom_site.Cartn_z
+ATOM C CA ALA A 1 1.0 2.0 3.0
+";
+        let data =
+            parse_reader(&mut Cursor::new(cif), InputFormat::Mmcif, &mut io::sink()).unwrap();
+        assert_eq!(data.experiment.resolution, Some(3.2));
+        assert_eq!(
+            data.experiment.method.as_deref(),
+            Some("ELECTRON MICROSCOPY")
+        );
+    }
+}
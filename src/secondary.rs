@@ -0,0 +1,461 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Write};
+
+use crate::api::{AnalysisOptions, SecondaryStructure};
+use crate::model::{AtomData, ErratStats, SecondaryRange};
+
+pub(crate) fn secondary_record(line: &[u8], ranges: &mut Vec<SecondaryRange>) {
+    let (kind, chain, start, end) = if line.starts_with(b"HELIX ") {
+        (SecondaryStructure::Helix, 19, 21..25, 33..37)
+    } else if line.starts_with(b"SHEET ") {
+        (SecondaryStructure::Strand, 21, 22..26, 33..37)
+    } else {
+        return;
+    };
+    let number = |range: std::ops::Range<usize>| {
+        line.get(range)
+            .and_then(|field| std::str::from_utf8(field).ok())
+            .and_then(|field| field.trim().parse::<i32>().ok())
+    };
+    if let (Some(&chain_id), Some(start), Some(end)) = (line.get(chain), number(start), number(end))
+    {
+        ranges.push(SecondaryRange {
+            kind,
+            chain_id,
+            start,
+            end,
+        });
+    }
+}
+
+pub(crate) fn is_secondary_category(category: &str) -> bool {
+    category.eq_ignore_ascii_case("_struct_conf")
+        || category.eq_ignore_ascii_case("_struct_sheet_range")
+}
+
+pub(crate) fn secondary_row(
+    category: &str,
+    items: &[&str],
+    row: &[String],
+    ranges: &mut Vec<SecondaryRange>,
+) {
+    let value = |names: &[&str]| {
+        names.iter().find_map(|name| {
+            items
+                .iter()
+                .position(|item| item.eq_ignore_ascii_case(name))
+                .and_then(|k| row.get(k))
+                .map(|value| value.as_str())
+                .filter(|value| *value != "?" && *value != ".")
+        })
+    };
+    let kind = if category.eq_ignore_ascii_case("_struct_sheet_range") {
+        SecondaryStructure::Strand
+    } else if value(&["conf_type_id"])
+        .is_some_and(|kind| kind.to_ascii_uppercase().starts_with("HELX"))
+    {
+        SecondaryStructure::Helix
+    } else {
+        return;
+    };
+    let chain_id = value(&["beg_auth_asym_id", "beg_label_asym_id"]).and_then(|c| c.bytes().next());
+    let start = value(&["beg_auth_seq_id", "beg_label_seq_id"]).and_then(|v| v.parse().ok());
+    let end = value(&["end_auth_seq_id", "end_label_seq_id"]).and_then(|v| v.parse().ok());
+    if let (Some(chain_id), Some(start), Some(end)) = (chain_id, start, end) {
+        ranges.push(SecondaryRange {
+            kind,
+            chain_id,
+            start,
+            end,
+        });
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Backbone {
+    resnum: i32,
+    chain_id: u8,
+    res_seq: i32,
+    proline: bool,
+    n: Option<[f64; 3]>,
+    c: Option<[f64; 3]>,
+    o: Option<[f64; 3]>,
+}
+
+fn distance(a: [f64; 3], b: [f64; 3]) -> f64 {
+    ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)).sqrt()
+}
+
+fn backbone_residues(data: &AtomData) -> Vec<Backbone> {
+    let mut residues: Vec<Backbone> = Vec::new();
+    for atom in 1..=data.atmnum {
+        let (resnum, chain_id) = (data.resnum[atom], data.chain_id[atom]);
+        if residues
+            .last()
+            .is_none_or(|last| last.resnum != resnum || last.chain_id != chain_id)
+        {
+            residues.push(Backbone {
+                resnum,
+                chain_id,
+                res_seq: data.res_seq[atom],
+                proline: &data.res_name[atom] == b"PRO",
+                ..Backbone::default()
+            });
+        }
+        let residue = residues.last_mut().expect("pushed above");
+        let point = [data.xyz_x[atom], data.xyz_y[atom], data.xyz_z[atom]];
+        match &data.atom_name[atom] {
+            b"N   " => residue.n = Some(point),
+            b"C   " => residue.c = Some(point),
+            b"O   " => residue.o = Some(point),
+            _ => {}
+        }
+    }
+    residues
+}
+
+fn assign_from_backbone(residues: &[Backbone]) -> Vec<SecondaryStructure> {
+    let count = residues.len();
+    let linked: Vec<bool> = (0..count)
+        .map(|k| {
+            let (this, next) = (&residues[k], residues.get(k + 1));
+            next.is_some_and(|next| {
+                this.chain_id == next.chain_id
+                    && matches!((this.c, next.n), (Some(c), Some(n)) if distance(c, n) < 2.5)
+            })
+        })
+        .collect();
+    let hydrogen: Vec<Option<[f64; 3]>> = (0..count)
+        .map(|k| {
+            let residue = &residues[k];
+            if k == 0 || !linked[k - 1] || residue.proline {
+                return None;
+            }
+            let (n, c, o) = (residue.n?, residues[k - 1].c?, residues[k - 1].o?);
+            let length = distance(c, o);
+            Some(std::array::from_fn(|d| n[d] + (c[d] - o[d]) / length))
+        })
+        .collect();
+
+    let size = 5.2;
+    let cell = |p: [f64; 3]| p.map(|v| (v / size).floor() as i32);
+    let mut donors: HashMap<[i32; 3], Vec<usize>> = HashMap::new();
+    for (k, residue) in residues.iter().enumerate() {
+        if let (Some(n), Some(_)) = (residue.n, hydrogen[k]) {
+            donors.entry(cell(n)).or_default().push(k);
+        }
+    }
+    let mut hbonds: HashSet<(usize, usize)> = HashSet::new();
+    for (i, acceptor) in residues.iter().enumerate() {
+        let (Some(c), Some(o)) = (acceptor.c, acceptor.o) else {
+            continue;
+        };
+        let [cx, cy, cz] = cell(o);
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                for dz in -1..=1 {
+                    let Some(near) = donors.get(&[cx + dx, cy + dy, cz + dz]) else {
+                        continue;
+                    };
+                    for &j in near {
+                        let (Some(n), Some(h)) = (residues[j].n, hydrogen[j]) else {
+                            continue;
+                        };
+                        if j == i || j == i + 1 || distance(o, n) >= size {
+                            continue;
+                        }
+                        let energy = 0.084
+                            * 332.0
+                            * (1.0 / distance(o, n) + 1.0 / distance(c, h)
+                                - 1.0 / distance(o, h)
+                                - 1.0 / distance(c, n));
+                        if energy < -0.5 {
+                            hbonds.insert((i, j));
+                        }
+                    }
+                }
+            }
+        }
+    }
+    let hb = |i: isize, j: isize| i >= 0 && j >= 0 && hbonds.contains(&(i as usize, j as usize));
+    let chained = |from: usize, span: usize| {
+        from + span < count && linked[from..from + span].iter().all(|&link| link)
+    };
+
+    let mut assignment = vec![SecondaryStructure::Loop; count];
+    let turn = |i: usize| chained(i, 4) && hb(i as isize, i as isize + 4);
+    for i in 1..count {
+        if turn(i - 1) && turn(i) {
+            for slot in &mut assignment[i..=i + 3] {
+                *slot = SecondaryStructure::Helix;
+            }
+        }
+    }
+
+    let mut bridged = vec![false; count];
+    let candidates: HashSet<(usize, usize)> = hbonds
+        .iter()
+        .flat_map(|&(a, d)| {
+            (-1isize..=1).flat_map(move |da| {
+                (-1isize..=1).flat_map(move |dd| {
+                    let (i, j) = (a as isize + da, d as isize + dd);
+                    [(i, j), (j, i)]
+                })
+            })
+        })
+        .filter(|&(i, j)| i >= 1 && j >= 1 && (i - j).abs() > 2)
+        .map(|(i, j)| (i as usize, j as usize))
+        .filter(|&(i, j)| chained(i - 1, 2) && chained(j - 1, 2))
+        .collect();
+    for (i, j) in candidates {
+        let (si, sj) = (i as isize, j as isize);
+        let parallel = (hb(si - 1, sj) && hb(sj, si + 1)) || (hb(sj - 1, si) && hb(si, sj + 1));
+        let antiparallel = (hb(si, sj) && hb(sj, si)) || (hb(si - 1, sj + 1) && hb(sj - 1, si + 1));
+        if parallel || antiparallel {
+            bridged[i] = true;
+            bridged[j] = true;
+        }
+    }
+    for k in 0..count {
+        let ladder = (k > 0 && bridged[k - 1]) || bridged.get(k + 1).copied().unwrap_or(false);
+        if bridged[k] && ladder && assignment[k] == SecondaryStructure::Loop {
+            assignment[k] = SecondaryStructure::Strand;
+        }
+    }
+
+    assignment
+}
+
+pub(crate) fn residue_secondary(stats: &ErratStats, resnum: i32) -> Option<SecondaryStructure> {
+    stats
+        .secondary
+        .binary_search_by_key(&resnum, |&(resnum, _)| resnum)
+        .ok()
+        .map(|idx| stats.secondary[idx].1)
+}
+
+pub(crate) fn mark_secondary_structure<W: Write>(
+    data: &AtomData,
+    stats: &mut ErratStats,
+    options: &AnalysisOptions,
+    logw: &mut W,
+) -> io::Result<()> {
+    if !options.secondary_structure {
+        return Ok(());
+    }
+    let residues = backbone_residues(data);
+    let mut chains: Vec<u8> = residues.iter().map(|residue| residue.chain_id).collect();
+    chains.dedup();
+    let recorded: Vec<u8> = chains
+        .iter()
+        .copied()
+        .filter(|&chain_id| {
+            data.secondary
+                .iter()
+                .any(|range| range.chain_id == chain_id)
+        })
+        .collect();
+    let computed = (recorded.len() < chains.len()).then(|| assign_from_backbone(&residues));
+    stats.secondary = residues
+        .iter()
+        .enumerate()
+        .map(|(k, residue)| {
+            let kind = match &computed {
+                Some(computed) if !recorded.contains(&residue.chain_id) => computed[k],
+                _ => data
+                    .secondary
+                    .iter()
+                    .find(|range| {
+                        range.chain_id == residue.chain_id
+                            && residue.res_seq >= range.start
+                            && residue.res_seq <= range.end
+                    })
+                    .map_or(SecondaryStructure::Loop, |range| range.kind),
+            };
+            (residue.resnum, kind)
+        })
+        .collect();
+    let count = |kind| stats.secondary.iter().filter(|(_, k)| *k == kind).count();
+    writeln!(
+        logw,
+        "Secondary structure: {} helix, {} strand, {} loop residues ({} of {} chains from records)",
+        count(SecondaryStructure::Helix),
+        count(SecondaryStructure::Strand),
+        count(SecondaryStructure::Loop),
+        recorded.len(),
+        chains.len()
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::{InputFormat, build_analysis_result};
+    use crate::parser::parse_reader;
+    use crate::render::write_svg;
+    use crate::stats::compute_errat;
+    use std::io::Cursor;
+    use std::path::PathBuf;
+
+    fn place(
+        a: [f64; 3],
+        b: [f64; 3],
+        c: [f64; 3],
+        bond: f64,
+        angle: f64,
+        torsion: f64,
+    ) -> [f64; 3] {
+        let sub = |p: [f64; 3], q: [f64; 3]| std::array::from_fn::<f64, 3, _>(|d| p[d] - q[d]);
+        let cross = |p: [f64; 3], q: [f64; 3]| {
+            [
+                p[1] * q[2] - p[2] * q[1],
+                p[2] * q[0] - p[0] * q[2],
+                p[0] * q[1] - p[1] * q[0],
+            ]
+        };
+        let unit = |p: [f64; 3]| p.map(|v| v / distance(p, [0.0; 3]));
+        let bc = unit(sub(c, b));
+        let n = unit(cross(sub(b, a), bc));
+        let m = cross(n, bc);
+        let (angle, torsion) = (angle.to_radians(), torsion.to_radians());
+        let local = [
+            -bond * angle.cos(),
+            bond * angle.sin() * torsion.cos(),
+            bond * angle.sin() * torsion.sin(),
+        ];
+        std::array::from_fn(|d| c[d] + bc[d] * local[0] + m[d] * local[1] + n[d] * local[2])
+    }
+
+    fn ideal_helix(chain: char, shift: f64, serial: &mut usize) -> String {
+        let (mut n, mut ca) = ([shift, 0.0, 0.0], [shift + 1.458, 0.0, 0.0]);
+        let mut c = place([shift, 1.0, 0.0], n, ca, 1.525, 111.2, -60.0);
+        let mut pdb = String::new();
+        for residue in 1..=20 {
+            let o = place(n, ca, c, 1.231, 120.5, 133.0);
+            for (name, [x, y, z]) in [("N", n), ("CA", ca), ("C", c), ("O", o)] {
+                pdb.push_str(&format!(
+                    "ATOM  {serial:>5} {name:<4} ALA {chain}{residue:>4}    {x:>8.3}{y:>8.3}{z:>8.3}  1.00 20.00           {}\n",
+                    &name[..1]
+                ));
+                *serial += 1;
+            }
+            let next_n = place(n, ca, c, 1.329, 116.2, -47.0);
+            let next_ca = place(ca, c, next_n, 1.458, 121.7, 180.0);
+            c = place(c, next_n, next_ca, 1.525, 111.2, -57.0);
+            (n, ca) = (next_n, next_ca);
+        }
+        pdb
+    }
+
+    #[test]
+    fn records_and_backbone_assign_secondary_structure() {
+        let mut serial = 1;
+        let pdb = format!(
+            "HELIX    1   1 ALA B    3  ALA B    8  1                                   6\n\
+             SHEET    1   A 2 ALA B  12  ALA B  15  0\n{}{}",
+            ideal_helix('A', 0.0, &mut serial),
+            ideal_helix('B', 60.0, &mut serial)
+        );
+        let data = parse_reader(&mut Cursor::new(pdb), InputFormat::Pdb, &mut io::sink()).unwrap();
+        assert_eq!(data.secondary.len(), 2);
+        assert_eq!(
+            data.secondary[1],
+            SecondaryRange {
+                kind: SecondaryStructure::Strand,
+                chain_id: b'B',
+                start: 12,
+                end: 15
+            }
+        );
+
+        let options = AnalysisOptions {
+            secondary_structure: true,
+            ..AnalysisOptions::default()
+        };
+        let mut stats = compute_errat(&data, &options.calibration, &mut io::sink()).unwrap();
+        let mut log = Vec::new();
+        mark_secondary_structure(&data, &mut stats, &options, &mut log).unwrap();
+        let log = String::from_utf8(log).unwrap();
+        assert!(log.contains("(1 of 2 chains from records)"));
+
+        let resnum = |chain: u8, res_seq: i32| {
+            let atom = (1..=data.atmnum)
+                .find(|&i| data.chain_id[i] == chain && data.res_seq[i] == res_seq)
+                .unwrap();
+            data.resnum[atom]
+        };
+        for res_seq in 2..=18 {
+            assert_eq!(
+                residue_secondary(&stats, resnum(b'A', res_seq)),
+                Some(SecondaryStructure::Helix)
+            );
+        }
+        assert_eq!(
+            residue_secondary(&stats, resnum(b'B', 5)),
+            Some(SecondaryStructure::Helix)
+        );
+        assert_eq!(
+            residue_secondary(&stats, resnum(b'B', 13)),
+            Some(SecondaryStructure::Strand)
+        );
+        assert_eq!(
+            residue_secondary(&stats, resnum(b'B', 10)),
+            Some(SecondaryStructure::Loop)
+        );
+
+        let result = build_analysis_result(
+            PathBuf::from("-"),
+            "model".into(),
+            &stats,
+            String::new(),
+            &options,
+        );
+        assert!(
+            result
+                .frame_scores
+                .iter()
+                .all(|frame| frame.secondary_structure.is_some())
+        );
+        let mut svg = Vec::new();
+        write_svg(&mut svg, &mut io::sink(), "model", &stats, &[], None).unwrap();
+        assert!(String::from_utf8(svg).unwrap().contains(">SS</text>"));
+
+        let cif = "\
+data_test
+loop_
+_struct_conf.conf_type_id
+_struct_conf.id
+_struct_conf.beg_label_asym_id
+_struct_conf.beg_label_seq_id
+_struct_conf.end_label_seq_id
+_struct_conf.beg_auth_asym_id
+_struct_conf.beg_auth_seq_id
+_struct_conf.end_auth_seq_id
+HELX_P HELX_P1 A 1 6 C 101 106
+TURN_P TURN_P1 A 7 9 C 107 109
+loop_
+_atom_site.group_PDB
+_atom_site.type_symbol
+_atom_site.label_atom_id
+_atom_site.label_comp_id
+_atom_site.auth_asym_id
+_atom_site.auth_seq_id
+_atom_site.Cartn_x
+_atom_site.Cartn_y
+_atom_site.Cartn_z
+ATOM C CA ALA C 101 1.0 2.0 3.0
+";
+        let data =
+            parse_reader(&mut Cursor::new(cif), InputFormat::Mmcif, &mut io::sink()).unwrap();
+        assert_eq!(
+            data.secondary,
+            vec![SecondaryRange {
+                kind: SecondaryStructure::Helix,
+                chain_id: b'C',
+                start: 101,
+                end: 106
+            }]
+        );
+    }
+}
@@ -0,0 +1,306 @@
+use std::collections::BTreeSet;
+use std::io::{self, BufRead};
+use std::path::{Path, PathBuf};
+
+use crate::api::{
+    AnalysisOptions, AnalysisResult, InputFormat, build_analysis_result, derive_file_string,
+};
+use crate::model::{AtomData, ErratStats};
+use crate::parser::{parse_reader, parse_structure};
+use crate::pipeline::finish_analysis;
+use crate::stats::{
+    BoxGrid, WindowOutcome, build_grid, outcomes_by_start, summarize_outcomes, window_end,
+    window_starts,
+};
+
+pub struct ErratSession {
+    input_path: PathBuf,
+    protein_id: String,
+    options: AnalysisOptions,
+    data: AtomData,
+    parse_log: Vec<u8>,
+    log: Vec<u8>,
+    slots: Vec<Option<usize>>,
+    starts: Vec<usize>,
+    ends: Vec<Option<usize>>,
+    grid: Option<BoxGrid>,
+    outcomes: Vec<Option<WindowOutcome>>,
+    stats: ErratStats,
+}
+
+impl ErratSession {
+    pub fn open<P: AsRef<Path>>(
+        input: P,
+        protein_id: Option<&str>,
+        use_mmap: bool,
+        options: &AnalysisOptions,
+    ) -> io::Result<Self> {
+        options.validate()?;
+        let input_path = input.as_ref().to_path_buf();
+        let mut parse_log = Vec::new();
+        let data = parse_structure(&input_path, &mut parse_log, use_mmap)?;
+        let protein_id = derive_file_string(&input_path, protein_id);
+        Self::build(input_path, protein_id, options, data, parse_log)
+    }
+
+    pub fn from_reader<R: BufRead>(
+        reader: &mut R,
+        format: InputFormat,
+        protein_id: &str,
+        options: &AnalysisOptions,
+    ) -> io::Result<Self> {
+        options.validate()?;
+        let mut parse_log = Vec::new();
+        let data = parse_reader(reader, format, &mut parse_log)?;
+        Self::build(
+            PathBuf::from("-"),
+            protein_id.to_string(),
+            options,
+            data,
+            parse_log,
+        )
+    }
+
+    fn build(
+        input_path: PathBuf,
+        protein_id: String,
+        options: &AnalysisOptions,
+        data: AtomData,
+        parse_log: Vec<u8>,
+    ) -> io::Result<Self> {
+        if options.crystal_contacts
+            || options.assembly.is_some()
+            || options.hetero_environment.is_some()
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "crystal contacts, assemblies and hetero environments are not supported in incremental sessions",
+            ));
+        }
+        let calibration = &options.calibration;
+        let mut slots = vec![None; data.records];
+        for slot in 1..=data.atmnum {
+            slots[data.source_index[slot]] = Some(slot);
+        }
+        let starts = window_starts(&data);
+        let ends = starts
+            .iter()
+            .map(|&i| window_end(i, &data, calibration))
+            .collect();
+        let mut log = Vec::new();
+        let grid = build_grid(&data, calibration, &mut log)?;
+        let outcomes = match &grid {
+            Some(grid) => outcomes_by_start(&starts, &data, calibration, grid),
+            None => vec![None; starts.len()],
+        };
+        let mut stats = summarize_outcomes(
+            &data,
+            calibration,
+            outcomes.iter().flatten().copied(),
+            &mut log,
+        )?;
+        finish_analysis(&data, &mut stats, options, &mut log)?;
+        Ok(ErratSession {
+            input_path,
+            protein_id,
+            options: options.clone(),
+            data,
+            parse_log,
+            log,
+            slots,
+            starts,
+            ends,
+            grid,
+            outcomes,
+            stats,
+        })
+    }
+
+    pub fn atom_count(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn atom_index(&self, chain_id: &str, residue: i32, atom_name: &str) -> Option<usize> {
+        let data = &self.data;
+        (1..=data.atmnum)
+            .find(|&slot| {
+                data.res_seq[slot] == residue
+                    && chain_id.as_bytes().first().copied().unwrap_or(b' ') == data.chain_id[slot]
+                    && data.atom_name[slot].trim_ascii() == atom_name.trim().as_bytes()
+            })
+            .map(|slot| data.source_index[slot])
+    }
+
+    pub fn coordinates(&self, atom: usize) -> Option<[f64; 3]> {
+        let slot = (*self.slots.get(atom)?)?;
+        Some([
+            self.data.xyz_x[slot],
+            self.data.xyz_y[slot],
+            self.data.xyz_z[slot],
+        ])
+    }
+
+    pub fn overall_quality_factor(&self) -> Option<f64> {
+        self.stats.overall_quality_factor
+    }
+
+    pub fn analysis(&self) -> AnalysisResult {
+        let mut log = self.parse_log.clone();
+        log.extend_from_slice(&self.log);
+        build_analysis_result(
+            self.input_path.clone(),
+            self.protein_id.clone(),
+            &self.stats,
+            String::from_utf8_lossy(&log).into_owned(),
+            &self.options,
+        )
+    }
+
+    pub fn update_coordinates(&mut self, updates: &[(usize, [f64; 3])]) -> io::Result<usize> {
+        if let Some((atom, _)) = updates.iter().find(|(atom, _)| *atom >= self.slots.len()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "atom index {atom} out of range (structure has {} atoms)",
+                    self.slots.len()
+                ),
+            ));
+        }
+
+        let data = &mut self.data;
+        let mut moved = Vec::new();
+        for &(atom, [x, y, z]) in updates {
+            let Some(slot) = self.slots[atom] else {
+                continue;
+            };
+            let old = [data.xyz_x[slot], data.xyz_y[slot], data.xyz_z[slot]];
+            data.xyz_x[slot] = x;
+            data.xyz_y[slot] = y;
+            data.xyz_z[slot] = z;
+            moved.push((slot, old, [x, y, z]));
+        }
+        if moved.is_empty() {
+            return Ok(0);
+        }
+
+        let data = &self.data;
+        let calibration = &self.options.calibration;
+        let mut log = Vec::new();
+        let grid = build_grid(data, calibration, &mut log)?;
+        let rescored: Vec<usize> = match (&self.grid, &grid) {
+            (Some(old_grid), Some(new_grid)) if old_grid.same_layout(new_grid) => {
+                let mut touched = BTreeSet::new();
+                for &(slot, old, new) in &moved {
+                    touched.insert(slot);
+                    old_grid.atoms_near(data, calibration, old, |n| {
+                        touched.insert(n);
+                    });
+                    new_grid.atoms_near(data, calibration, new, |n| {
+                        touched.insert(n);
+                    });
+                }
+                self.starts
+                    .iter()
+                    .zip(&self.ends)
+                    .enumerate()
+                    .filter_map(|(window, (&i, end))| {
+                        let v = (*end)?;
+                        touched.range(i..=v).next().map(|_| window)
+                    })
+                    .collect()
+            }
+            _ => (0..self.starts.len()).collect(),
+        };
+
+        match &grid {
+            Some(grid) => {
+                let starts: Vec<usize> = rescored.iter().map(|&w| self.starts[w]).collect();
+                let outcomes = outcomes_by_start(&starts, data, calibration, grid);
+                for (window, outcome) in rescored.iter().zip(outcomes) {
+                    self.outcomes[*window] = outcome;
+                }
+            }
+            None => self.outcomes.iter_mut().for_each(|outcome| *outcome = None),
+        }
+        self.grid = grid;
+        self.stats = summarize_outcomes(
+            data,
+            calibration,
+            self.outcomes.iter().flatten().copied(),
+            &mut log,
+        )?;
+        finish_analysis(data, &mut self.stats, &self.options, &mut log)?;
+        self.log = log;
+        Ok(rescored.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::Ribbon;
+    use crate::stats::compute_errat;
+    use std::io::Cursor;
+
+    fn assert_matches_full_recompute(session: &ErratSession) {
+        let full =
+            compute_errat(&session.data, &session.options.calibration, &mut io::sink()).unwrap();
+        let frames = |stats: &ErratStats| -> Vec<(i32, u64)> {
+            stats
+                .scored_frames
+                .iter()
+                .map(|frame| (frame.center_residue, frame.error_value.to_bits()))
+                .collect()
+        };
+        assert_eq!(frames(&session.stats), frames(&full));
+        assert_eq!(session.stats.warning_frames, full.warning_frames);
+        assert_eq!(session.stats.errat, full.errat);
+        assert_eq!(
+            session.stats.overall_quality_factor,
+            full.overall_quality_factor
+        );
+    }
+
+    #[test]
+    fn local_updates_rescore_nearby_windows_only() {
+        let pdb = Ribbon {
+            residues: 40,
+            hydrogens: true,
+            ..Ribbon::default()
+        }
+        .pdb();
+        let mut session = ErratSession::from_reader(
+            &mut Cursor::new(pdb),
+            InputFormat::Pdb,
+            "ribbon",
+            &AnalysisOptions::default(),
+        )
+        .unwrap();
+        assert!(!session.stats.scored_frames.is_empty());
+        assert_eq!(session.atom_count(), 200);
+
+        let atom = session.atom_index("A", 20, "CA").unwrap();
+        assert_eq!(atom, 96);
+        assert_eq!(session.atom_index("A", 20, "H"), None);
+        let [x, y, z] = session.coordinates(atom).unwrap();
+        let rescored = session
+            .update_coordinates(&[(atom, [x + 0.7, y + 0.4, z + 0.3]), (atom + 3, [x, y, z])])
+            .unwrap();
+        assert!(rescored > 0 && rescored < session.starts.len());
+        assert_eq!(session.coordinates(atom), Some([x + 0.7, y + 0.4, z + 0.3]));
+        assert_matches_full_recompute(&session);
+
+        let rescored = session
+            .update_coordinates(&[(0, [-20.0, 0.0, 0.0])])
+            .unwrap();
+        assert_eq!(rescored, session.starts.len());
+        assert_matches_full_recompute(&session);
+
+        assert_eq!(session.update_coordinates(&[(4, [0.0; 3])]).unwrap(), 0);
+        assert!(session.update_coordinates(&[(200, [0.0; 3])]).is_err());
+        assert_eq!(
+            session.analysis().overall_quality_factor,
+            session.overall_quality_factor()
+        );
+    }
+}
@@ -0,0 +1,323 @@
+use std::io::{self, Write};
+
+use crate::cif::Flow;
+use crate::model::{AtomData, CHAINDIF, CrystalInfo, Experiment, HeteroAtom, SIZE};
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct AtomRecord<'a> {
+    pub(crate) hetero: bool,
+    pub(crate) element: &'a [u8],
+    pub(crate) backbone: bool,
+    pub(crate) atom_name: &'a [u8],
+    pub(crate) alt_loc: u8,
+    pub(crate) res_name: &'a [u8],
+    pub(crate) chain_id: u8,
+    pub(crate) res_seq: i32,
+    pub(crate) x: f64,
+    pub(crate) y: f64,
+    pub(crate) z: f64,
+    pub(crate) b_factor: f64,
+}
+
+pub(crate) struct AtomSink<'w, W: Write> {
+    logw: &'w mut W,
+    data: AtomData,
+    i: usize,
+    atmnum: usize,
+    kadd: i32,
+    hydrogens: usize,
+    deuteriums: usize,
+}
+
+pub(crate) fn empty_atom_data() -> AtomData {
+    AtomData {
+        atmnum: 0,
+        environment_atoms: 0,
+        records: 0,
+        name: vec![0i32; SIZE + 2],
+        bnam: vec![0i32; SIZE + 2],
+        atom_name: vec![[b' '; 4]; SIZE + 2],
+        res_name: vec![[b' '; 3]; SIZE + 2],
+        chain_id: vec![b' '; SIZE + 2],
+        res_seq: vec![0i32; SIZE + 2],
+        resnum: vec![0i32; SIZE + 2],
+        xyz_x: vec![0.0f64; SIZE + 2],
+        xyz_y: vec![0.0f64; SIZE + 2],
+        xyz_z: vec![0.0f64; SIZE + 2],
+        b_factor: vec![0.0f64; SIZE + 2],
+        errat: vec![0.0f64; SIZE + 8],
+        crystal: CrystalInfo::default(),
+        assemblies: Vec::new(),
+        hetero_atoms: Vec::new(),
+        experiment: Experiment::default(),
+        secondary: Vec::new(),
+        source_index: vec![0usize; SIZE + 2],
+    }
+}
+
+impl<'w, W: Write> AtomSink<'w, W> {
+    pub(crate) fn new(logw: &'w mut W) -> Self {
+        AtomSink {
+            logw,
+            data: empty_atom_data(),
+            i: 0,
+            atmnum: 0,
+            kadd: 0,
+            hydrogens: 0,
+            deuteriums: 0,
+        }
+    }
+
+    pub(crate) fn has_atoms(&self) -> bool {
+        self.atmnum > 0
+    }
+
+    pub(crate) fn push(&mut self, atom: &AtomRecord<'_>) -> io::Result<Flow> {
+        self.data.records += 1;
+        if atom.hetero {
+            self.push_hetero(atom);
+            return Ok(Flow::Continue);
+        }
+        if atom.element.eq_ignore_ascii_case(b"H") {
+            self.hydrogens += 1;
+            return Ok(Flow::Continue);
+        }
+        if atom.element.eq_ignore_ascii_case(b"D") {
+            self.deuteriums += 1;
+            return Ok(Flow::Continue);
+        }
+
+        let data = &mut self.data;
+        let logw = &mut *self.logw;
+        let mut i = self.i;
+        let mut flag = false;
+
+        if i + 1 > SIZE - 1 {
+            writeln!(
+                logw,
+                "ERROR: PDB WITH TOO MANY ATOMS. CUT OFF FURTHER INPUT."
+            )?;
+            return Ok(Flow::Stop);
+        }
+        i += 1;
+
+        data.name[i] = match atom.element.to_ascii_uppercase().as_slice() {
+            b"C" => 1,
+            b"N" => 2,
+            b"O" => 3,
+            _ => 0,
+        };
+        data.bnam[i] = if atom.backbone { 1 } else { 0 };
+        data.atom_name[i] = fixed_name(atom.atom_name);
+        data.res_name[i] = fixed_name(atom.res_name);
+        data.chain_id[i] = atom.chain_id;
+        data.res_seq[i] = atom.res_seq;
+        data.xyz_x[i] = atom.x;
+        data.xyz_y[i] = atom.y;
+        data.xyz_z[i] = atom.z;
+        data.b_factor[i] = atom.b_factor;
+        data.source_index[i] = data.records - 1;
+
+        if !matches!(atom.alt_loc, b' ' | b'A' | b'a' | b'P') {
+            writeln!(
+                logw,
+                "Reject 2' Conformation atom#\t{}\tchain\t{}",
+                i, data.chain_id[i] as char
+            )?;
+            i -= 1;
+            flag = true;
+        }
+
+        if !is_standard_residue(atom.res_name) {
+            i -= 1;
+            flag = true;
+            let res_name_str = std::str::from_utf8(atom.res_name).unwrap_or("???");
+            writeln!(
+                logw,
+                "***Warning: Reject Nonstardard Residue - {}",
+                res_name_str
+            )?;
+        }
+
+        if i >= 2 && !flag && data.chain_id[i] != data.chain_id[i - 1] {
+            self.kadd += 1;
+            writeln!(logw, "INCREMENTING CHAIN (kadd) {}", self.kadd)?;
+        }
+
+        if !flag {
+            data.resnum[i] = data.res_seq[i] + (self.kadd * CHAINDIF);
+            self.atmnum = i;
+        }
+
+        let mut stop = false;
+        if i >= 2
+            && !flag
+            && data.chain_id[i] == data.chain_id[i - 1]
+            && data.resnum[i] < data.resnum[i - 1]
+        {
+            writeln!(
+                logw,
+                "ERROR: RESNUM DECREASE. TERMINATE ANALYSIS{}\t{}",
+                data.resnum[i],
+                data.resnum[i - 1]
+            )?;
+            stop = true;
+        }
+
+        if i > 2
+            && !flag
+            && data.chain_id[i] == data.chain_id[i - 1]
+            && data.resnum[i] != data.resnum[i - 1]
+            && (data.resnum[i] - data.resnum[i - 1]) > 1
+        {
+            writeln!(
+                logw,
+                "WARNING: Missing Residues{}>>>{}",
+                data.resnum[i - 1],
+                data.resnum[i]
+            )?;
+        }
+
+        if !flag {
+            let idx = (data.resnum[i] + 4) as usize;
+            if idx >= data.errat.len() {
+                data.errat.resize(idx + 1, 0.0);
+            }
+            data.errat[idx] = 0.0;
+        }
+
+        self.i = i;
+        if stop {
+            return Ok(Flow::Stop);
+        }
+        Ok(Flow::Continue)
+    }
+
+    fn push_hetero(&mut self, atom: &AtomRecord<'_>) {
+        let name = match atom.element.to_ascii_uppercase().as_slice() {
+            b"C" => 1,
+            b"N" => 2,
+            b"O" => 3,
+            _ => return,
+        };
+        if !matches!(atom.alt_loc, b' ' | b'A' | b'a' | b'P') {
+            return;
+        }
+        self.data.hetero_atoms.push(HeteroAtom {
+            name,
+            atom_name: fixed_name(atom.atom_name),
+            res_name: fixed_name(atom.res_name),
+            chain_id: atom.chain_id,
+            res_seq: atom.res_seq,
+            source_index: self.data.records - 1,
+            point: [atom.x, atom.y, atom.z],
+        });
+    }
+
+    pub(crate) fn finish(mut self) -> io::Result<AtomData> {
+        if self.hydrogens > 0 || self.deuteriums > 0 {
+            writeln!(
+                self.logw,
+                "Stripped hydrogen atoms: {}\tdeuterium atoms: {}",
+                self.hydrogens, self.deuteriums
+            )?;
+        }
+        self.data.atmnum = self.atmnum;
+        Ok(self.data)
+    }
+}
+
+fn fixed_name<const N: usize>(name: &[u8]) -> [u8; N] {
+    let mut out = [b' '; N];
+    for (slot, byte) in out.iter_mut().zip(name.trim_ascii()) {
+        *slot = *byte;
+    }
+    out
+}
+
+pub(crate) fn is_standard_residue(res_name: &[u8]) -> bool {
+    matches!(
+        res_name,
+        b"GLY"
+            | b"ALA"
+            | b"VAL"
+            | b"LEU"
+            | b"ILE"
+            | b"TYR"
+            | b"CYS"
+            | b"MET"
+            | b"TRP"
+            | b"PHE"
+            | b"HIS"
+            | b"PRO"
+            | b"SER"
+            | b"THR"
+            | b"LYS"
+            | b"ARG"
+            | b"GLU"
+            | b"ASP"
+            | b"GLN"
+            | b"ASN"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(res_name: &'static [u8], chain_id: u8, res_seq: i32) -> AtomRecord<'static> {
+        AtomRecord {
+            hetero: false,
+            element: b"C",
+            backbone: false,
+            atom_name: b"CB",
+            alt_loc: b' ',
+            res_name,
+            chain_id,
+            res_seq,
+            x: 0.0,
+            y: 0.0,
+            z: 0.0,
+            b_factor: 0.0,
+        }
+    }
+
+    #[test]
+    fn sink_numbers_chains_and_stops_on_resnum_decrease() {
+        let mut log = Vec::new();
+        let mut sink = AtomSink::new(&mut log);
+        assert_eq!(sink.push(&record(b"ALA", b'A', 5)).unwrap(), Flow::Continue);
+        let hetero = AtomRecord {
+            hetero: true,
+            ..record(b"HOH", b'W', 1)
+        };
+        assert_eq!(sink.push(&hetero).unwrap(), Flow::Continue);
+        assert_eq!(sink.push(&record(b"GLY", b'B', 1)).unwrap(), Flow::Continue);
+        assert_eq!(sink.push(&record(b"GLY", b'B', 0)).unwrap(), Flow::Stop);
+        let data = sink.finish().unwrap();
+        assert_eq!(data.atmnum, 3);
+        assert_eq!(data.resnum[1], 5);
+        assert_eq!(data.resnum[2], CHAINDIF + 1);
+        let log = String::from_utf8(log).unwrap();
+        assert!(log.contains("INCREMENTING CHAIN (kadd) 1"));
+        assert!(log.contains("RESNUM DECREASE"));
+    }
+
+    #[test]
+    fn sink_strips_hydrogens_and_deuteriums_before_numbering() {
+        let mut log = Vec::new();
+        let mut sink = AtomSink::new(&mut log);
+        for element in [b"N".as_ref(), b"H", b"d", b"CA", b"O"] {
+            let atom = AtomRecord {
+                element,
+                ..record(b"ALA", b'A', 1)
+            };
+            sink.push(&atom).unwrap();
+        }
+        let data = sink.finish().unwrap();
+        assert_eq!(data.atmnum, 3);
+        assert_eq!(&data.name[1..4], &[2, 0, 3]);
+        let log = String::from_utf8(log).unwrap();
+        assert!(log.contains("Stripped hydrogen atoms: 1\tdeuterium atoms: 1"));
+    }
+}
@@ -2,50 +2,61 @@ use std::io::{self, Write};
 
 use rayon::prelude::*;
 
-use crate::model::{
-    AtomData, BOXSIZE, BXMX, ErratStats, FrameScoreRaw, LMT_95, LMT_99, MAXWIN, RADIUS, RADMIN,
-};
+use crate::api::WindowInteractions;
+use crate::calibration::Calibration;
+use crate::hetero::HETERO_RESNUM;
+use crate::model::{AtomData, BXMX, ErratStats, FrameScoreRaw};
+use crate::symmetry::SYMMETRY_RESNUM;
 
 #[derive(Clone, Copy)]
-enum WindowOutcome {
+pub(crate) enum WindowOutcome {
     Warn(i32),
-    Value { idx: usize, mtrx: f64 },
+    Value {
+        idx: usize,
+        residue_offset: i32,
+        interactions: WindowInteractions,
+    },
 }
 
-fn compute_window(
-    i: usize,
-    data: &AtomData,
-    min: &[f64; 4],
-    nbx: &[i32; 4],
-    ibox_counts: &[i32],
-    ibox_atoms: &[i32],
-    box_slots: usize,
+pub(crate) struct BoxGrid {
+    min: [f64; 4],
+    nbx: [i32; 4],
+    counts: Vec<i32>,
+    atoms: Vec<i32>,
+    slots: usize,
     rsq: f64,
-    ssq: f64,
     ndelta: i32,
-) -> Option<WindowOutcome> {
+}
+
+pub(crate) fn window_end(i: usize, data: &AtomData, calibration: &Calibration) -> Option<usize> {
+    let residues = calibration.window_length + 1;
     let mut s = 1;
     let mut v = i;
-    while s < 10 && v <= data.atmnum {
+    while s < residues && v <= data.atmnum {
         let diff = data.resnum[v + 1] - data.resnum[v];
         if ((diff < 100) && (diff > 0)) || v == data.atmnum {
             s += 1;
         }
         v += 1;
     }
-    if v > 0 {
-        v -= 1;
-    }
+    v = v.saturating_sub(1);
 
-    if s != 10 || data.res_seq[v] <= data.res_seq[i] {
+    if s != residues || data.res_seq[v] <= data.res_seq[i] {
         return None;
     }
+    Some(v)
+}
 
-    let mut c = [[0.0f64; 4]; 4];
-    for rer in i..=v {
-        let jbx = ((data.xyz_x[rer] - (min[1] - 0.00001)) / BOXSIZE).floor() as i32;
-        let jby = ((data.xyz_y[rer] - (min[2] - 0.00001)) / BOXSIZE).floor() as i32;
-        let jbz = ((data.xyz_z[rer] - (min[3] - 0.00001)) / BOXSIZE).floor() as i32;
+impl BoxGrid {
+    fn box_of(&self, point: [f64; 3], box_size: f64) -> [i32; 3] {
+        std::array::from_fn(|axis| {
+            ((point[axis] - (self.min[axis + 1] - 0.00001)) / box_size).floor() as i32
+        })
+    }
+
+    fn neighbour_boxes(&self, point: [f64; 3], box_size: f64, mut visit: impl FnMut(usize)) {
+        let (nbx, ndelta) = (&self.nbx, self.ndelta);
+        let [jbx, jby, jbz] = self.box_of(point, box_size);
 
         let mut ibz1 = jbz - ndelta;
         if ibz1 < 0 {
@@ -74,87 +85,183 @@ fn compute_window(
             ibx2 = nbx[1] - 1;
         }
 
+        for j in ibz1..=ibz2 {
+            for k in iby1..=iby2 {
+                for l in ibx1..=ibx2 {
+                    visit((1 + l + k * nbx[1] + j * nbx[1] * nbx[2]) as usize);
+                }
+            }
+        }
+    }
+
+    fn box_atoms(&self, ind: usize) -> &[i32] {
+        let count = self.counts[ind] as usize;
+        let base = ind * self.slots;
+        &self.atoms[base..base + count.min(self.slots)]
+    }
+
+    pub(crate) fn same_layout(&self, other: &BoxGrid) -> bool {
+        self.min == other.min && self.nbx == other.nbx && self.ndelta == other.ndelta
+    }
+
+    pub(crate) fn atoms_near(
+        &self,
+        data: &AtomData,
+        calibration: &Calibration,
+        point: [f64; 3],
+        mut visit: impl FnMut(usize),
+    ) {
+        self.neighbour_boxes(point, calibration.box_size, |ind| {
+            for &n in self.box_atoms(ind) {
+                let n = n as usize;
+                let dx = data.xyz_x[n] - point[0];
+                let dy = data.xyz_y[n] - point[1];
+                let dz = data.xyz_z[n] - point[2];
+                if dx * dx + dy * dy + dz * dz < self.rsq {
+                    visit(n);
+                }
+            }
+        });
+    }
+}
+
+fn contact_weight(calibration: &Calibration, dsq: f64) -> f64 {
+    if dsq <= calibration.radmin * calibration.radmin {
+        1.0
+    } else {
+        (calibration.radius - dsq.sqrt()) / (calibration.radius - calibration.radmin)
+    }
+}
+
+pub(crate) fn for_each_contact(
+    i: usize,
+    v: usize,
+    data: &AtomData,
+    calibration: &Calibration,
+    grid: &BoxGrid,
+    mut visit: impl FnMut(usize, usize, f64, f64),
+) {
+    for rer in i..=v {
         let rer_x = data.xyz_x[rer];
         let rer_y = data.xyz_y[rer];
         let rer_z = data.xyz_z[rer];
 
-        for j in ibz1..=ibz2 {
-            for k in iby1..=iby2 {
-                for l in ibx1..=ibx2 {
-                    let ind = (1 + l + k * nbx[1] + j * nbx[1] * nbx[2]) as usize;
-                    let count = ibox_counts[ind] as usize;
-                    let limit = count.min(box_slots);
-                    let base = ind * box_slots;
-                    for m in 0..limit {
-                        let n = ibox_atoms[base + m] as usize;
-
-                        if data.resnum[rer] == data.resnum[n] {
-                            continue;
-                        }
-
-                        let dx = data.xyz_x[n] - rer_x;
-                        let dy = data.xyz_y[n] - rer_y;
-                        let dz = data.xyz_z[n] - rer_z;
-                        let dsq = dx * dx + dy * dy + dz * dz;
-                        if dsq >= rsq {
-                            continue;
-                        }
-
-                        if data.bnam[rer] == 1
-                            && data.bnam[n] == 1
-                            && (((data.resnum[n] == data.resnum[rer] + 1)
-                                && (data.name[rer] == 1)
-                                && (data.name[n] == 2))
-                                || ((data.resnum[rer] == data.resnum[n] + 1)
-                                    && (data.name[rer] == 2)
-                                    && (data.name[n] == 1)))
-                        {
-                            continue;
-                        }
-
-                        let temp1 = if dsq <= ssq {
-                            1.0
-                        } else {
-                            2.0 * (RADIUS - dsq.sqrt())
-                        };
-
-                        if n >= i && n <= v {
-                            if data.resnum[rer] > data.resnum[n] {
-                                c[data.name[rer] as usize][data.name[n] as usize] += temp1;
-                            }
-                        } else {
-                            c[data.name[rer] as usize][data.name[n] as usize] += temp1;
-                        }
+        grid.neighbour_boxes([rer_x, rer_y, rer_z], calibration.box_size, |ind| {
+            for &n in grid.box_atoms(ind) {
+                let n = n as usize;
+
+                if data.resnum[rer] == data.resnum[n] {
+                    continue;
+                }
+
+                let dx = data.xyz_x[n] - rer_x;
+                let dy = data.xyz_y[n] - rer_y;
+                let dz = data.xyz_z[n] - rer_z;
+                let dsq = dx * dx + dy * dy + dz * dz;
+                if dsq >= grid.rsq {
+                    continue;
+                }
+
+                if data.bnam[rer] == 1
+                    && data.bnam[n] == 1
+                    && (((data.resnum[n] == data.resnum[rer] + 1)
+                        && (data.name[rer] == 1)
+                        && (data.name[n] == 2))
+                        || ((data.resnum[rer] == data.resnum[n] + 1)
+                            && (data.name[rer] == 2)
+                            && (data.name[n] == 1)))
+                {
+                    continue;
+                }
+
+                let temp1 = contact_weight(calibration, dsq);
+
+                if n >= i && n <= v {
+                    if data.resnum[rer] > data.resnum[n] {
+                        visit(rer, n, dsq, temp1);
                     }
+                } else {
+                    visit(rer, n, dsq, temp1);
                 }
             }
-        }
+        });
     }
+}
+
+pub(crate) fn is_inter_chain(data: &AtomData, atom: usize, n: usize) -> bool {
+    data.resnum[n] != HETERO_RESNUM
+        && data.resnum[n] != SYMMETRY_RESNUM
+        && (n > data.atmnum || data.chain_id[n] != data.chain_id[atom])
+}
+
+pub(crate) fn compute_window(
+    i: usize,
+    data: &AtomData,
+    calibration: &Calibration,
+    grid: &BoxGrid,
+) -> Option<WindowOutcome> {
+    let v = window_end(i, data, calibration)?;
+
+    let mut c = [[0.0f64; 4]; 4];
+    let mut inter = [[0.0f64; 4]; 4];
+    for_each_contact(i, v, data, calibration, grid, |rer, n, _, weight| {
+        let (a, b) = (data.name[rer] as usize, data.name[n] as usize);
+        c[a][b] += weight;
+        if is_inter_chain(data, rer, n) {
+            inter[a][b] += weight;
+        }
+    });
 
     let mut temp2 = 0.0f64;
-    for q in 1..=3 {
-        for r in 1..=3 {
-            temp2 += c[q][r];
+    for row in &c[1..=3] {
+        for value in &row[1..=3] {
+            temp2 += value;
         }
     }
 
-    if temp2 > MAXWIN {
-        let mut matrix = [0.0f64; 6];
-        matrix[1] = c[1][1] / temp2;
-        matrix[2] = (c[1][2] + c[2][1]) / temp2;
-        matrix[3] = (c[1][3] + c[3][1]) / temp2;
-        matrix[4] = c[2][2] / temp2;
-        matrix[5] = (c[2][3] + c[3][2]) / temp2;
-
-        let mtrx = matrixdb(&matrix);
-        let idx = (data.resnum[i] + 4) as usize;
-        Some(WindowOutcome::Value { idx, mtrx })
+    if temp2 > calibration.maxwin {
+        let pairs = |m: &[[f64; 4]; 4]| {
+            [
+                m[1][1],
+                m[1][2] + m[2][1],
+                m[1][3] + m[3][1],
+                m[2][2],
+                m[2][3] + m[3][2],
+            ]
+        };
+        let counts = pairs(&c);
+        let interactions = WindowInteractions {
+            counts,
+            total: temp2,
+            fractions: counts.map(|count| count / temp2),
+            inter_chain_counts: pairs(&inter),
+        };
+
+        let idx = (data.resnum[i] + calibration.half_window()) as usize;
+        Some(WindowOutcome::Value {
+            idx,
+            residue_offset: data.resnum[i] - data.res_seq[i],
+            interactions,
+        })
     } else {
-        Some(WindowOutcome::Warn(data.resnum[i] + 4))
+        Some(WindowOutcome::Warn(
+            data.resnum[i] + calibration.half_window(),
+        ))
     }
 }
 
-pub(crate) fn compute_errat<W: Write>(data: &AtomData, logw: &mut W) -> io::Result<ErratStats> {
+pub(crate) fn window_starts(data: &AtomData) -> Vec<usize> {
+    (1..=data.atmnum)
+        .filter(|&i| i == 1 || data.resnum[i] > data.resnum[i - 1])
+        .collect()
+}
+
+pub(crate) fn build_grid<W: Write>(
+    data: &AtomData,
+    calibration: &Calibration,
+    logw: &mut W,
+) -> io::Result<Option<BoxGrid>> {
+    let box_size = calibration.box_size;
     let mut min = [0.0f64; 4];
     let mut max = [0.0f64; 4];
     for i in 1..=3 {
@@ -163,21 +270,10 @@ pub(crate) fn compute_errat<W: Write>(data: &AtomData, logw: &mut W) -> io::Resu
     }
 
     if data.atmnum == 0 {
-        return Ok(ErratStats {
-            stat: 0.0,
-            pstat: 0.0,
-            avg_probability: None,
-            overall_quality_factor: None,
-            errat: data.errat.clone(),
-            resnum: data.resnum.clone(),
-            chain_id: data.chain_id.clone(),
-            atmnum: data.atmnum,
-            warning_frames: Vec::new(),
-            scored_frames: Vec::new(),
-        });
+        return Ok(None);
     }
 
-    for i in 1..=data.atmnum {
+    for i in 1..=data.atmnum + data.environment_atoms {
         let vx = data.xyz_x[i];
         let vy = data.xyz_y[i];
         let vz = data.xyz_z[i];
@@ -201,54 +297,110 @@ pub(crate) fn compute_errat<W: Write>(data: &AtomData, logw: &mut W) -> io::Resu
         }
     }
 
-    for j in 1..=3 {
-        write!(logw, "{}\t", fmt_sig6(min[j]))?;
+    for value in &min[1..=3] {
+        write!(logw, "{}\t", fmt_sig6(*value))?;
     }
-    for j in 1..=3 {
-        write!(logw, "{}\t", fmt_sig6(max[j]))?;
+    for value in &max[1..=3] {
+        write!(logw, "{}\t", fmt_sig6(*value))?;
     }
     writeln!(logw)?;
 
     let mut nbx = [0i32; 4];
     for i in 1..=3 {
-        nbx[i] = ((max[i] - min[i]) / BOXSIZE) as i32 + 1;
+        nbx[i] = ((max[i] - min[i]) / box_size) as i32 + 1;
     }
 
     let box_count = (nbx[1] * nbx[2] * nbx[3]) as i64;
-    let mut flag2 = false;
     if box_count > (BXMX as i64 - 1) {
         writeln!(logw, "ERROR: TOO MANY BOXES")?;
-        flag2 = true;
+        return Ok(None);
     }
 
-    let box_slots = 15usize;
+    let slots = 15usize;
     let ibox_len = (box_count.max(0) as usize) + 1;
-    let mut ibox_counts = vec![0i32; ibox_len];
-    let mut ibox_atoms = vec![0i32; ibox_len * box_slots];
-
-    if !flag2 {
-        for i in 1..=data.atmnum {
-            let ix = ((data.xyz_x[i] - (min[1] - 0.00001)) / BOXSIZE).floor() as i32;
-            let iy = ((data.xyz_y[i] - (min[2] - 0.00001)) / BOXSIZE).floor() as i32;
-            let iz = ((data.xyz_z[i] - (min[3] - 0.00001)) / BOXSIZE).floor() as i32;
-            let ind = (1 + ix + iy * nbx[1] + iz * nbx[1] * nbx[2]) as usize;
-
-            let temp = ibox_counts[ind] as usize;
-            ibox_counts[ind] += 1;
-            if temp < box_slots {
-                let base = ind * box_slots;
-                ibox_atoms[base + temp] = i as i32;
-            }
+    let mut counts = vec![0i32; ibox_len];
+    let mut atoms = vec![0i32; ibox_len * slots];
+
+    for i in 1..=data.atmnum + data.environment_atoms {
+        let ix = ((data.xyz_x[i] - (min[1] - 0.00001)) / box_size).floor() as i32;
+        let iy = ((data.xyz_y[i] - (min[2] - 0.00001)) / box_size).floor() as i32;
+        let iz = ((data.xyz_z[i] - (min[3] - 0.00001)) / box_size).floor() as i32;
+        let ind = (1 + ix + iy * nbx[1] + iz * nbx[1] * nbx[2]) as usize;
+
+        let temp = counts[ind] as usize;
+        counts[ind] += 1;
+        if temp < slots {
+            let base = ind * slots;
+            atoms[base + temp] = i as i32;
         }
+    }
 
-        for i in 1..ibox_counts.len() {
-            if ibox_counts[i] > 15 {
-                writeln!(logw, "TOO MANY ATOMS IN BOX #:\t{}", ibox_counts[i])?;
-                flag2 = true;
-            }
+    let mut overflow = false;
+    for count in counts.iter().skip(1) {
+        if *count > 15 {
+            writeln!(logw, "TOO MANY ATOMS IN BOX #:\t{}", count)?;
+            overflow = true;
         }
     }
+    if overflow {
+        return Ok(None);
+    }
+
+    Ok(Some(BoxGrid {
+        min,
+        nbx,
+        counts,
+        atoms,
+        slots,
+        rsq: calibration.radius * calibration.radius,
+        ndelta: (calibration.radius / box_size).ceil() as i32,
+    }))
+}
+
+pub(crate) fn outcomes_by_start(
+    starts: &[usize],
+    data: &AtomData,
+    calibration: &Calibration,
+    grid: &BoxGrid,
+) -> Vec<Option<WindowOutcome>> {
+    starts
+        .par_iter()
+        .map(|&i| compute_window(i, data, calibration, grid))
+        .collect()
+}
+
+pub(crate) fn window_outcomes<W: Write>(
+    data: &AtomData,
+    calibration: &Calibration,
+    logw: &mut W,
+) -> io::Result<Vec<WindowOutcome>> {
+    let Some(grid) = build_grid(data, calibration, logw)? else {
+        return Ok(Vec::new());
+    };
+
+    Ok(
+        outcomes_by_start(&window_starts(data), data, calibration, &grid)
+            .into_iter()
+            .flatten()
+            .collect(),
+    )
+}
 
+pub(crate) fn compute_errat<W: Write>(
+    data: &AtomData,
+    calibration: &Calibration,
+    logw: &mut W,
+) -> io::Result<ErratStats> {
+    let outcomes = window_outcomes(data, calibration, logw)?;
+    summarize_outcomes(data, calibration, outcomes, logw)
+}
+
+pub(crate) fn summarize_outcomes<W: Write>(
+    data: &AtomData,
+    calibration: &Calibration,
+    outcomes: impl IntoIterator<Item = WindowOutcome>,
+    logw: &mut W,
+) -> io::Result<ErratStats> {
     let mut stat = 0.0f64;
     let mut pstat = 0.0f64;
     let mut mtrxstat = 0.0f64;
@@ -256,61 +408,39 @@ pub(crate) fn compute_errat<W: Write>(data: &AtomData, logw: &mut W) -> io::Resu
     let mut warning_frames = Vec::new();
     let mut scored_frames = Vec::new();
 
-    if !flag2 {
-        let rsq = RADIUS * RADIUS;
-        let ssq = RADMIN * RADMIN;
-        let ndelta = (RADIUS / BOXSIZE).ceil() as i32;
-        let window_starts: Vec<usize> = (1..=data.atmnum)
-            .filter(|&i| i == 1 || data.resnum[i] > data.resnum[i - 1])
-            .collect();
-
-        let results: Vec<Option<WindowOutcome>> = window_starts
-            .par_iter()
-            .map(|&i| {
-                compute_window(
-                    i,
-                    data,
-                    &min,
-                    &nbx,
-                    &ibox_counts,
-                    &ibox_atoms,
-                    box_slots,
-                    rsq,
-                    ssq,
-                    ndelta,
-                )
-            })
-            .collect();
-
-        for outcome in results {
-            if let Some(outcome) = outcome {
-                match outcome {
-                    WindowOutcome::Warn(frame) => {
-                        warning_frames.push(frame);
-                        writeln!(
-                            logw,
-                            "WARNING: Frame\t{}\tBelow Minimum Interaction Limit.",
-                            frame
-                        )?;
-                    }
-                    WindowOutcome::Value { idx, mtrx } => {
-                        stat += 1.0;
-                        mtrxstat += mtrx;
-                        scored_frames.push(FrameScoreRaw {
-                            center_residue: idx as i32,
-                            error_value: mtrx,
-                        });
-
-                        if mtrx > LMT_99 || mtrx > LMT_95 {
-                            pstat += 1.0;
-                        }
-
-                        if idx >= errat.len() {
-                            errat.resize(idx + 1, 0.0);
-                        }
-                        errat[idx] = mtrx;
-                    }
+    for outcome in outcomes {
+        match outcome {
+            WindowOutcome::Warn(frame) => {
+                warning_frames.push(frame);
+                writeln!(
+                    logw,
+                    "WARNING: Frame\t{}\tBelow Minimum Interaction Limit.",
+                    frame
+                )?;
+            }
+            WindowOutcome::Value {
+                idx,
+                residue_offset,
+                interactions,
+            } => {
+                let mtrx = calibration.score(&interactions.fractions);
+                stat += 1.0;
+                mtrxstat += mtrx;
+                scored_frames.push(FrameScoreRaw {
+                    center_residue: idx as i32,
+                    residue_offset,
+                    error_value: mtrx,
+                    interactions,
+                });
+
+                if mtrx > calibration.limit_99 || mtrx > calibration.limit_95 {
+                    pstat += 1.0;
+                }
+
+                if idx >= errat.len() {
+                    errat.resize(idx + 1, 0.0);
                 }
+                errat[idx] = mtrx;
             }
         }
     }
@@ -350,85 +480,16 @@ pub(crate) fn compute_errat<W: Write>(data: &AtomData, logw: &mut W) -> io::Resu
         atmnum: data.atmnum,
         warning_frames,
         scored_frames,
+        interface_residues: Vec::new(),
+        experiment: data.experiment.clone(),
+        quality_percentile: None,
+        plddt: Vec::new(),
+        plddt_cutoff: None,
+        secondary: Vec::new(),
+        calibration: calibration.clone(),
     })
 }
 
-fn matrixdb(matrix: &[f64; 6]) -> f64 {
-    let b1: [[f64; 6]; 6] = [
-        [0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
-        [
-            0.0,
-            5040.279078850848,
-            3408.8051415836494,
-            4152.904423767301,
-            4236.20000417189,
-            5054.7812102046255,
-        ],
-        [
-            0.0,
-            3408.805141583649,
-            8491.906094010221,
-            5958.88177787795,
-            1521.3873527184862,
-            4304.078200827222,
-        ],
-        [
-            0.0,
-            4152.9044237673015,
-            5958.881777877952,
-            7637.16708933505,
-            6620.7157382230725,
-            5287.691183798411,
-        ],
-        [
-            0.0,
-            4236.20000417189,
-            1521.3873527184862,
-            6620.7157382230725,
-            18368.34377429841,
-            4050.7978111188067,
-        ],
-        [
-            0.0,
-            5054.7812102046255,
-            4304.078200827221,
-            5287.69118379841,
-            4050.7978111188067,
-            6666.856740479165,
-        ],
-    ];
-
-    let avg = [
-        0.0,
-        0.192765509919262,
-        0.195575208778518,
-        0.275322406824210,
-        0.059102357035642,
-        0.233154192767480,
-    ];
-
-    let mut v = [0.0f64; 6];
-    for u in 1..6 {
-        v[u] = matrix[u] - avg[u];
-    }
-
-    let mut c = [0.0f64; 6];
-    for j in 1..6 {
-        let mut x = 0.0;
-        for k in 1..6 {
-            x += v[k] * b1[k][j];
-        }
-        c[j] = x;
-    }
-
-    let mut total = 0.0;
-    for k in 1..6 {
-        total += c[k] * v[k];
-    }
-
-    total
-}
-
 fn fmt_sig6(value: f64) -> String {
     if value == 0.0 {
         return "0".to_string();
@@ -454,35 +515,69 @@ fn fmt_sig6(value: f64) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::model::{CrystalInfo, Experiment};
 
     #[test]
     fn matrixdb_zero_at_avg() {
-        let mut matrix = [0.0f64; 6];
-        matrix[1] = 0.192765509919262;
-        matrix[2] = 0.195575208778518;
-        matrix[3] = 0.275322406824210;
-        matrix[4] = 0.059102357035642;
-        matrix[5] = 0.233154192767480;
-        let out = matrixdb(&matrix);
+        let fractions = [
+            0.192765509919262,
+            0.195575208778518,
+            0.275322406824210,
+            0.059102357035642,
+            0.233154192767480,
+        ];
+        let out = Calibration::classic().score(&fractions);
         assert!(out.abs() < 1e-9, "expected near-zero, got {}", out);
     }
 
+    #[test]
+    fn contact_weight_is_continuous_for_any_radius_pair() {
+        let classic = Calibration::classic();
+        assert!((contact_weight(&classic, 3.5 * 3.5) - 2.0 * (3.75 - 3.5)).abs() < 1e-12);
+
+        let calibration = Calibration {
+            radius: 4.0,
+            radmin: 3.0,
+            ..Calibration::classic()
+        };
+        let weight = |d: f64| contact_weight(&calibration, d * d);
+        assert_eq!(weight(3.0), 1.0);
+        assert!((weight(3.0 + 1e-9) - 1.0).abs() < 1e-6);
+        assert!((weight(3.5) - 0.5).abs() < 1e-12);
+        assert!(weight(4.0).abs() < 1e-12);
+        for step in 0..=100 {
+            let w = weight(2.5 + step as f64 * 0.015);
+            assert!((0.0..=1.0).contains(&w), "weight {w} out of range");
+        }
+    }
+
     #[test]
     fn compute_errat_empty_structure_returns_zero_stats() {
         let data = AtomData {
             atmnum: 0,
+            environment_atoms: 0,
+            records: 0,
             name: vec![0; 4],
             bnam: vec![0; 4],
+            atom_name: vec![[b' '; 4]; 4],
+            res_name: vec![[b' '; 3]; 4],
             chain_id: vec![b' '; 4],
             res_seq: vec![0; 4],
             resnum: vec![0; 4],
             xyz_x: vec![0.0; 4],
             xyz_y: vec![0.0; 4],
             xyz_z: vec![0.0; 4],
+            b_factor: vec![0.0; 4],
             errat: vec![0.0; 8],
+            crystal: CrystalInfo::default(),
+            assemblies: Vec::new(),
+            hetero_atoms: Vec::new(),
+            experiment: Experiment::default(),
+            secondary: Vec::new(),
+            source_index: vec![0; 4],
         };
         let mut log = Vec::new();
-        let stats = compute_errat(&data, &mut log).unwrap();
+        let stats = compute_errat(&data, &Calibration::classic(), &mut log).unwrap();
         assert_eq!(stats.stat, 0.0);
         assert!(stats.avg_probability.is_none());
         assert!(stats.warning_frames.is_empty());
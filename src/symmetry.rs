@@ -0,0 +1,763 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use crate::api::AnalysisOptions;
+use crate::assembly::apply_assembly;
+use crate::calibration::Calibration;
+use crate::hetero::add_hetero_atoms;
+use crate::model::{AtomData, CrystalInfo};
+
+pub(crate) type Operator = [[f64; 4]; 3];
+
+pub(crate) const ENVIRONMENT_RESNUM: i32 = i32::MIN / 2;
+/// Residue number of crystal symmetry mates, which are never counted as another chain.
+pub(crate) const SYMMETRY_RESNUM: i32 = ENVIRONMENT_RESNUM - 1;
+
+/// Symmetry operators of the common primitive, C- and I-centred protein space groups,
+/// used only when the file has no SMTRY or symop records. Centring shifts are added by
+/// [`centring`], so the C and I entries list the primitive operators only.
+const SPACE_GROUPS: &[(&str, &[&str])] = &[
+    ("P1", &["x,y,z"]),
+    ("P21", &["x,y,z", "-x,y+1/2,-z"]),
+    ("P1211", &["x,y,z", "-x,y+1/2,-z"]),
+    ("C2", &["x,y,z", "-x,y,-z"]),
+    ("C121", &["x,y,z", "-x,y,-z"]),
+    (
+        "P212121",
+        &[
+            "x,y,z",
+            "-x+1/2,-y,z+1/2",
+            "-x,y+1/2,-z+1/2",
+            "x+1/2,-y+1/2,-z",
+        ],
+    ),
+    (
+        "P21212",
+        &["x,y,z", "-x,-y,z", "-x+1/2,y+1/2,-z", "x+1/2,-y+1/2,-z"],
+    ),
+    ("C2221", &["x,y,z", "-x,-y,z+1/2", "-x,y,-z+1/2", "x,-y,-z"]),
+    ("I222", &["x,y,z", "-x,-y,z", "-x,y,-z", "x,-y,-z"]),
+    ("P31", &["x,y,z", "-y,x-y,z+1/3", "-x+y,-x,z+2/3"]),
+    ("P32", &["x,y,z", "-y,x-y,z+2/3", "-x+y,-x,z+1/3"]),
+    (
+        "P3121",
+        &[
+            "x,y,z",
+            "-y,x-y,z+1/3",
+            "-x+y,-x,z+2/3",
+            "y,x,-z",
+            "x-y,-y,-z+2/3",
+            "-x,-x+y,-z+1/3",
+        ],
+    ),
+    (
+        "P3221",
+        &[
+            "x,y,z",
+            "-y,x-y,z+2/3",
+            "-x+y,-x,z+1/3",
+            "y,x,-z",
+            "x-y,-y,-z+1/3",
+            "-x,-x+y,-z+2/3",
+        ],
+    ),
+    ("P41", &["x,y,z", "-x,-y,z+1/2", "-y,x,z+1/4", "y,-x,z+3/4"]),
+    ("P43", &["x,y,z", "-x,-y,z+1/2", "-y,x,z+3/4", "y,-x,z+1/4"]),
+    (
+        "P41212",
+        &[
+            "x,y,z",
+            "-x,-y,z+1/2",
+            "-y+1/2,x+1/2,z+1/4",
+            "y+1/2,-x+1/2,z+3/4",
+            "-x+1/2,y+1/2,-z+1/4",
+            "x+1/2,-y+1/2,-z+3/4",
+            "y,x,-z",
+            "-y,-x,-z+1/2",
+        ],
+    ),
+    (
+        "P43212",
+        &[
+            "x,y,z",
+            "-x,-y,z+1/2",
+            "-y+1/2,x+1/2,z+3/4",
+            "y+1/2,-x+1/2,z+1/4",
+            "-x+1/2,y+1/2,-z+3/4",
+            "x+1/2,-y+1/2,-z+1/4",
+            "y,x,-z",
+            "-y,-x,-z+1/2",
+        ],
+    ),
+    (
+        "P61",
+        &[
+            "x,y,z",
+            "-y,x-y,z+1/3",
+            "-x+y,-x,z+2/3",
+            "-x,-y,z+1/2",
+            "y,-x+y,z+5/6",
+            "x-y,x,z+1/6",
+        ],
+    ),
+    (
+        "P65",
+        &[
+            "x,y,z",
+            "-y,x-y,z+2/3",
+            "-x+y,-x,z+1/3",
+            "-x,-y,z+1/2",
+            "y,-x+y,z+1/6",
+            "x-y,x,z+5/6",
+        ],
+    ),
+    (
+        "P6122",
+        &[
+            "x,y,z",
+            "-y,x-y,z+1/3",
+            "-x+y,-x,z+2/3",
+            "-x,-y,z+1/2",
+            "y,-x+y,z+5/6",
+            "x-y,x,z+1/6",
+            "y,x,-z+1/3",
+            "x-y,-y,-z",
+            "-x,-x+y,-z+2/3",
+            "-y,-x,-z+5/6",
+            "-x+y,y,-z+1/2",
+            "x,x-y,-z+1/6",
+        ],
+    ),
+    (
+        "P6522",
+        &[
+            "x,y,z",
+            "-y,x-y,z+2/3",
+            "-x+y,-x,z+1/3",
+            "-x,-y,z+1/2",
+            "y,-x+y,z+1/6",
+            "x-y,x,z+5/6",
+            "y,x,-z+2/3",
+            "x-y,-y,-z",
+            "-x,-x+y,-z+1/3",
+            "-y,-x,-z+1/6",
+            "-x+y,y,-z+1/2",
+            "x,x-y,-z+5/6",
+        ],
+    ),
+];
+
+fn invalid_input(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+pub(crate) fn crystal_record(line: &[u8], crystal: &mut CrystalInfo) {
+    let text = String::from_utf8_lossy(line);
+    if text.starts_with("CRYST1") {
+        let number = |start: usize, end: usize| {
+            text.get(start..end.min(text.len()))
+                .and_then(|field| field.trim().parse::<f64>().ok())
+                .unwrap_or(0.0)
+        };
+        crystal.cell = Some([
+            number(6, 15),
+            number(15, 24),
+            number(24, 33),
+            number(33, 40),
+            number(40, 47),
+            number(47, 54),
+        ]);
+        crystal.space_group = text
+            .get(55..66.min(text.len()))
+            .map(|group| group.trim().to_string())
+            .filter(|group| !group.is_empty());
+        return;
+    }
+    let fields: Vec<&str> = text.split_whitespace().collect();
+    if fields.len() < 8 || fields[0] != "REMARK" || fields[1] != "290" {
+        return;
+    }
+    let Some(row) = fields[2]
+        .strip_prefix("SMTRY")
+        .and_then(|row| row.parse::<usize>().ok())
+        .filter(|row| (1..=3).contains(row))
+    else {
+        return;
+    };
+    let Ok(op) = fields[3].parse::<usize>() else {
+        return;
+    };
+    let values: Vec<f64> = fields[4..8]
+        .iter()
+        .filter_map(|value| value.parse().ok())
+        .collect();
+    if op == 0 || values.len() != 4 {
+        return;
+    }
+    if crystal.cartesian_ops.len() < op {
+        crystal.cartesian_ops.resize(op, [[0.0; 4]; 3]);
+    }
+    crystal.cartesian_ops[op - 1][row - 1].copy_from_slice(&values);
+}
+
+pub(crate) fn crystal_item(category: &str, item: &str, value: &str, crystal: &mut CrystalInfo) {
+    if value == "?" || value == "." {
+        return;
+    }
+    let category = category.to_ascii_lowercase();
+    let item = item.to_ascii_lowercase();
+    match (category.as_str(), item.as_str()) {
+        ("_cell", name) => {
+            let index = match name {
+                "length_a" => 0,
+                "length_b" => 1,
+                "length_c" => 2,
+                "angle_alpha" => 3,
+                "angle_beta" => 4,
+                "angle_gamma" => 5,
+                _ => return,
+            };
+            let value = value
+                .split('(')
+                .next()
+                .and_then(|number| number.parse::<f64>().ok())
+                .unwrap_or(0.0);
+            crystal.cell.get_or_insert([0.0; 6])[index] = value;
+        }
+        ("_symmetry", "space_group_name_h-m") | ("_space_group", "name_h-m_alt") => {
+            crystal.space_group = Some(value.trim().to_string());
+        }
+        ("_space_group_symop", "operation_xyz") | ("_symmetry_equiv", "pos_as_xyz") => {
+            crystal.fractional_ops.push(value.to_string());
+        }
+        _ => {}
+    }
+}
+
+pub(crate) fn is_crystal_category(category: &str) -> bool {
+    [
+        "_cell",
+        "_symmetry",
+        "_space_group",
+        "_space_group_symop",
+        "_symmetry_equiv",
+    ]
+    .iter()
+    .any(|name| category.eq_ignore_ascii_case(name))
+}
+
+fn parse_term(term: &str, row: &mut [f64; 4]) -> Option<()> {
+    let (sign, body) = match term.as_bytes().first()? {
+        b'-' => (-1.0, &term[1..]),
+        b'+' => (1.0, &term[1..]),
+        _ => (1.0, term),
+    };
+    match body {
+        "x" => row[0] += sign,
+        "y" => row[1] += sign,
+        "z" => row[2] += sign,
+        _ => {
+            let value = match body.split_once('/') {
+                Some((num, den)) => num.parse::<f64>().ok()? / den.parse::<f64>().ok()?,
+                None => body.parse::<f64>().ok()?,
+            };
+            row[3] += sign * value;
+        }
+    }
+    Some(())
+}
+
+pub(crate) fn parse_xyz(op: &str) -> Option<Operator> {
+    let op: String = op
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '\'')
+        .collect::<String>()
+        .to_ascii_lowercase();
+    let parts: Vec<&str> = op.split(',').collect();
+    if parts.len() != 3 {
+        return None;
+    }
+    let mut matrix = [[0.0; 4]; 3];
+    for (row, part) in matrix.iter_mut().zip(parts) {
+        let mut start = 0;
+        for (idx, c) in part.char_indices().skip(1) {
+            if c == '+' || c == '-' {
+                parse_term(&part[start..idx], row)?;
+                start = idx;
+            }
+        }
+        parse_term(&part[start..], row)?;
+    }
+    Some(matrix)
+}
+
+type Matrix = [[f64; 3]; 3];
+
+fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    std::array::from_fn(|i| std::array::from_fn(|j| (0..3).map(|k| a[i][k] * b[k][j]).sum()))
+}
+
+fn apply(m: &Matrix, v: [f64; 3]) -> [f64; 3] {
+    std::array::from_fn(|i| (0..3).map(|k| m[i][k] * v[k]).sum())
+}
+
+fn invert(m: &Matrix) -> Option<Matrix> {
+    let cofactor = |i: usize, j: usize| {
+        let (r0, r1) = ((i + 1) % 3, (i + 2) % 3);
+        let (c0, c1) = ((j + 1) % 3, (j + 2) % 3);
+        m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0]
+    };
+    let det: f64 = (0..3).map(|j| m[0][j] * cofactor(0, j)).sum();
+    if det.abs() < 1e-12 {
+        return None;
+    }
+    Some(std::array::from_fn(|i| {
+        std::array::from_fn(|j| cofactor(j, i) / det)
+    }))
+}
+
+fn orthogonalization(cell: [f64; 6]) -> Option<(Matrix, Matrix)> {
+    let [a, b, c, alpha, beta, gamma] = cell;
+    if a <= 0.0 || b <= 0.0 || c <= 0.0 {
+        return None;
+    }
+    let (ca, cb, cg) = (
+        alpha.to_radians().cos(),
+        beta.to_radians().cos(),
+        gamma.to_radians().cos(),
+    );
+    let sg = gamma.to_radians().sin();
+    let volume_term = 1.0 - ca * ca - cb * cb - cg * cg + 2.0 * ca * cb * cg;
+    if volume_term <= 0.0 || sg.abs() < 1e-9 {
+        return None;
+    }
+    let volume = a * b * c * volume_term.sqrt();
+    let orth = [
+        [a, b * cg, c * cb],
+        [0.0, b * sg, c * (ca - cb * cg) / sg],
+        [0.0, 0.0, volume / (a * b * sg)],
+    ];
+    Some((orth, invert(&orth)?))
+}
+
+fn centring(space_group: &str) -> Option<[f64; 3]> {
+    match space_group.as_bytes().first()? {
+        b'C' => Some([0.5, 0.5, 0.0]),
+        b'I' => Some([0.5, 0.5, 0.5]),
+        _ => None,
+    }
+}
+
+pub(crate) fn fractional_operators(crystal: &CrystalInfo) -> io::Result<Vec<Operator>> {
+    if !crystal.fractional_ops.is_empty() {
+        return crystal
+            .fractional_ops
+            .iter()
+            .map(|op| {
+                parse_xyz(op)
+                    .ok_or_else(|| invalid_input(format!("cannot parse symmetry operator {op}")))
+            })
+            .collect();
+    }
+    let name = crystal.space_group.as_deref().unwrap_or("");
+    let key: String = name
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_ascii_uppercase();
+    let Some((_, ops)) = SPACE_GROUPS.iter().find(|(symbol, _)| *symbol == key) else {
+        return Err(invalid_input(format!(
+            "no symmetry operators for space group {name:?}; supply REMARK 290 SMTRY or _space_group_symop records"
+        )));
+    };
+    let mut operators: Vec<Operator> = ops.iter().filter_map(|op| parse_xyz(op)).collect();
+    if let Some(shift) = centring(&key) {
+        let centred: Vec<Operator> = operators
+            .iter()
+            .map(|op| {
+                let mut op = *op;
+                for (row, delta) in op.iter_mut().zip(shift) {
+                    row[3] += delta;
+                }
+                op
+            })
+            .collect();
+        operators.extend(centred);
+    }
+    Ok(operators)
+}
+
+fn cartesian_operators(
+    crystal: &CrystalInfo,
+    orth: &Matrix,
+    frac: &Matrix,
+) -> io::Result<Vec<Operator>> {
+    if crystal.fractional_ops.is_empty() && !crystal.cartesian_ops.is_empty() {
+        return Ok(crystal.cartesian_ops.clone());
+    }
+    Ok(fractional_operators(crystal)?
+        .into_iter()
+        .map(|op| {
+            let rotation: Matrix = std::array::from_fn(|i| [op[i][0], op[i][1], op[i][2]]);
+            let rotation = multiply(orth, &multiply(&rotation, frac));
+            let shift = apply(orth, [op[0][3], op[1][3], op[2][3]]);
+            std::array::from_fn(|i| [rotation[i][0], rotation[i][1], rotation[i][2], shift[i]])
+        })
+        .collect())
+}
+
+pub(crate) fn transform(op: &Operator, p: [f64; 3]) -> [f64; 3] {
+    std::array::from_fn(|i| op[i][0] * p[0] + op[i][1] * p[1] + op[i][2] * p[2] + op[i][3])
+}
+
+pub(crate) fn compose(outer: &Operator, inner: &Operator) -> Operator {
+    std::array::from_fn(|i| {
+        std::array::from_fn(|j| {
+            let product: f64 = (0..3).map(|k| outer[i][k] * inner[k][j]).sum();
+            if j == 3 {
+                product + outer[i][3]
+            } else {
+                product
+            }
+        })
+    })
+}
+
+pub(crate) fn is_identity(op: &Operator) -> bool {
+    (0..3).all(|i| {
+        (0..4).all(|j| {
+            let expected = if i == j { 1.0 } else { 0.0 };
+            (op[i][j] - expected).abs() < 1e-6
+        })
+    })
+}
+
+pub(crate) fn grow(data: &mut AtomData, len: usize) {
+    if data.name.len() > len {
+        return;
+    }
+    let len = (len + 1).max(data.name.len() * 2);
+    data.name.resize(len, 0);
+    data.bnam.resize(len, 0);
+    data.atom_name.resize(len, [b' '; 4]);
+    data.res_name.resize(len, [b' '; 3]);
+    data.chain_id.resize(len, b' ');
+    data.res_seq.resize(len, 0);
+    data.resnum.resize(len, 0);
+    data.xyz_x.resize(len, 0.0);
+    data.xyz_y.resize(len, 0.0);
+    data.xyz_z.resize(len, 0.0);
+    data.b_factor.resize(len, 0.0);
+    data.source_index.resize(len, 0);
+}
+
+pub(crate) fn push_environment_atom(
+    data: &mut AtomData,
+    from: usize,
+    point: [f64; 3],
+    resnum: i32,
+) {
+    let idx = data.atmnum + data.environment_atoms + 1;
+    grow(data, idx + 1);
+    data.name[idx] = data.name[from];
+    data.bnam[idx] = data.bnam[from];
+    data.atom_name[idx] = data.atom_name[from];
+    data.res_name[idx] = data.res_name[from];
+    data.chain_id[idx] = data.chain_id[from];
+    data.res_seq[idx] = data.res_seq[from];
+    data.resnum[idx] = resnum;
+    data.source_index[idx] = data.source_index[from];
+    data.b_factor[idx] = data.b_factor[from];
+    data.xyz_x[idx] = point[0];
+    data.xyz_y[idx] = point[1];
+    data.xyz_z[idx] = point[2];
+    data.environment_atoms += 1;
+}
+
+pub(crate) struct NeighbourCells {
+    cells: HashMap<[i32; 3], Vec<[f64; 3]>>,
+    size: f64,
+}
+
+impl NeighbourCells {
+    pub(crate) fn new(data: &AtomData, size: f64) -> Self {
+        let mut cells: HashMap<[i32; 3], Vec<[f64; 3]>> = HashMap::new();
+        for atom in 1..=data.atmnum {
+            let point = [data.xyz_x[atom], data.xyz_y[atom], data.xyz_z[atom]];
+            cells
+                .entry(point.map(|v| (v / size).floor() as i32))
+                .or_default()
+                .push(point);
+        }
+        NeighbourCells { cells, size }
+    }
+
+    pub(crate) fn nearest_sq(&self, point: [f64; 3]) -> f64 {
+        let cell = point.map(|v| (v / self.size).floor() as i32);
+        let mut best = f64::INFINITY;
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                for dz in -1..=1 {
+                    let Some(atoms) = self.cells.get(&[cell[0] + dx, cell[1] + dy, cell[2] + dz])
+                    else {
+                        continue;
+                    };
+                    for atom in atoms {
+                        let dsq = (0..3).map(|k| (atom[k] - point[k]).powi(2)).sum::<f64>();
+                        best = best.min(dsq);
+                    }
+                }
+            }
+        }
+        best
+    }
+}
+
+pub(crate) fn add_symmetry_mates<W: Write>(
+    data: &mut AtomData,
+    calibration: &Calibration,
+    logw: &mut W,
+) -> io::Result<usize> {
+    let crystal = &data.crystal;
+    let (orth, frac) = crystal.cell.and_then(orthogonalization).ok_or_else(|| {
+        invalid_input("crystal contacts need a unit cell (CRYST1 or _cell)".to_string())
+    })?;
+    let operators = cartesian_operators(crystal, &orth, &frac)?;
+    if data.atmnum == 0 {
+        return Ok(0);
+    }
+
+    let radius = calibration.radius;
+    let mut low = [f64::INFINITY; 3];
+    let mut high = [f64::NEG_INFINITY; 3];
+    let points: Vec<[f64; 3]> = (1..=data.atmnum)
+        .map(|atom| [data.xyz_x[atom], data.xyz_y[atom], data.xyz_z[atom]])
+        .collect();
+    for point in &points {
+        for k in 0..3 {
+            low[k] = low[k].min(point[k] - radius);
+            high[k] = high[k].max(point[k] + radius);
+        }
+    }
+    let mut box_low = [f64::INFINITY; 3];
+    let mut box_high = [f64::NEG_INFINITY; 3];
+    for corner in 0..8 {
+        let point = std::array::from_fn(|k| {
+            if corner >> k & 1 == 0 {
+                low[k]
+            } else {
+                high[k]
+            }
+        });
+        let fractional = apply(&frac, point);
+        for k in 0..3 {
+            box_low[k] = box_low[k].min(fractional[k]);
+            box_high[k] = box_high[k].max(fractional[k]);
+        }
+    }
+
+    let cells = NeighbourCells::new(data, radius);
+    let rsq = radius * radius;
+    let mut mates = Vec::new();
+    for op in &operators {
+        let moved: Vec<[f64; 3]> = points.iter().map(|point| transform(op, *point)).collect();
+        let mut copy_low = [f64::INFINITY; 3];
+        let mut copy_high = [f64::NEG_INFINITY; 3];
+        for point in &moved {
+            let fractional = apply(&frac, *point);
+            for k in 0..3 {
+                copy_low[k] = copy_low[k].min(fractional[k]);
+                copy_high[k] = copy_high[k].max(fractional[k]);
+            }
+        }
+        let range = |k: usize| {
+            (box_low[k] - copy_high[k]).floor() as i32..=(box_high[k] - copy_low[k]).ceil() as i32
+        };
+        for nx in range(0) {
+            for ny in range(1) {
+                for nz in range(2) {
+                    if (nx, ny, nz) == (0, 0, 0) && is_identity(op) {
+                        continue;
+                    }
+                    let shift = apply(&orth, [nx as f64, ny as f64, nz as f64]);
+                    for (atom, point) in moved.iter().enumerate() {
+                        let point: [f64; 3] = std::array::from_fn(|k| point[k] + shift[k]);
+                        if (0..3).any(|k| point[k] < low[k] || point[k] > high[k]) {
+                            continue;
+                        }
+                        let dsq = cells.nearest_sq(point);
+                        if dsq < rsq && dsq > 0.01 {
+                            mates.push((atom + 1, point));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    for &(from, point) in &mates {
+        push_environment_atom(data, from, point, SYMMETRY_RESNUM);
+    }
+    writeln!(
+        logw,
+        "Crystal contacts: {} symmetry atoms from {} operators",
+        mates.len(),
+        operators.len()
+    )?;
+    Ok(mates.len())
+}
+
+pub(crate) fn add_environment_atoms<W: Write>(
+    data: &mut AtomData,
+    options: &AnalysisOptions,
+    logw: &mut W,
+) -> io::Result<()> {
+    if let Some(selection) = &options.assembly {
+        apply_assembly(data, selection, &options.calibration, logw)?;
+    }
+    if options.crystal_contacts {
+        add_symmetry_mates(data, &options.calibration, logw)?;
+    }
+    if let Some(mode) = options.hetero_environment {
+        add_hetero_atoms(data, mode, logw)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::InputFormat;
+    use crate::fixtures;
+    use crate::parser::parse_reader;
+    use crate::stats::compute_errat;
+    use std::io::Cursor;
+
+    fn ribbon(residues: i32, header: &str) -> String {
+        format!("{header}{}", fixtures::ribbon(residues))
+    }
+
+    fn parse(text: &str, format: InputFormat) -> AtomData {
+        parse_reader(&mut Cursor::new(text), format, &mut io::sink()).unwrap()
+    }
+
+    #[test]
+    fn parses_symmetry_operators() {
+        let op = parse_xyz("-X+1/2, y-x ,z+0.25").unwrap();
+        assert_eq!(
+            op,
+            [
+                [-1.0, 0.0, 0.0, 0.5],
+                [-1.0, 1.0, 0.0, 0.0],
+                [0.0, 0.0, 1.0, 0.25]
+            ]
+        );
+        assert!(parse_xyz("x,y").is_none());
+        assert!(parse_xyz("x,y,w").is_none());
+
+        let crystal = CrystalInfo {
+            space_group: Some("C 1 2 1".to_string()),
+            ..CrystalInfo::default()
+        };
+        assert_eq!(fractional_operators(&crystal).unwrap().len(), 4);
+        let crystal = CrystalInfo {
+            space_group: Some("P 1 21 1".to_string()),
+            ..CrystalInfo::default()
+        };
+        assert_eq!(fractional_operators(&crystal).unwrap().len(), 2);
+        let crystal = CrystalInfo {
+            space_group: Some("F 2 3".to_string()),
+            ..CrystalInfo::default()
+        };
+        assert!(fractional_operators(&crystal).is_err());
+    }
+
+    #[test]
+    fn reads_cell_and_operators_from_pdb_and_mmcif() {
+        let header = "CRYST1   52.000   60.500   71.250  90.00  90.00  90.00 P 21 21 21    4\n\
+REMARK 290   SMTRY1   1  1.000000  0.000000  0.000000        0.00000\n\
+REMARK 290   SMTRY2   1  0.000000  1.000000  0.000000        0.00000\n\
+REMARK 290   SMTRY3   1  0.000000  0.000000  1.000000        0.00000\n\
+REMARK 290   SMTRY1   2 -1.000000  0.000000  0.000000       26.00000\n\
+REMARK 290   SMTRY2   2  0.000000 -1.000000  0.000000        0.00000\n\
+REMARK 290   SMTRY3   2  0.000000  0.000000  1.000000       35.62500\n";
+        let data = parse(&ribbon(2, header), InputFormat::Pdb);
+        let crystal = &data.crystal;
+        assert_eq!(crystal.cell, Some([52.0, 60.5, 71.25, 90.0, 90.0, 90.0]));
+        assert_eq!(crystal.space_group.as_deref(), Some("P 21 21 21"));
+        assert_eq!(crystal.cartesian_ops.len(), 2);
+        assert_eq!(crystal.cartesian_ops[1][0], [-1.0, 0.0, 0.0, 26.0]);
+        assert_eq!(crystal.cartesian_ops[1][2][3], 35.625);
+
+        let cif = "data_test\n\
+_cell.length_a 40.0(2)\n\
+_cell.length_b 50.0\n\
+_cell.length_c 60.0\n\
+_cell.angle_alpha 90\n\
+_cell.angle_beta 101.5\n\
+_cell.angle_gamma 90\n\
+_symmetry.space_group_name_H-M 'P 1 21 1'\n\
+loop_\n\
+_space_group_symop.id\n\
+_space_group_symop.operation_xyz\n\
+1 x,y,z\n\
+2 -x,y+1/2,-z\n\
+loop_\n\
+_atom_site.group_PDB\n\
+_atom_site.id\n\
+_atom_site.type_symbol\n\
+_atom_site.label_atom_id\n\
+_atom_site.label_comp_id\n\
+_atom_site.label_asym_id\n\
+_atom_site.label_seq_id\n\
+_atom_site.Cartn_x\n\
+_atom_site.Cartn_y\n\
+_atom_site.Cartn_z\n\
+_atom_site.pdbx_PDB_model_num\n\
+ATOM 1 N N ALA A 1 1.0 2.0 3.0 1\n";
+        let data = parse(cif, InputFormat::Mmcif);
+        let crystal = &data.crystal;
+        assert_eq!(crystal.cell, Some([40.0, 50.0, 60.0, 90.0, 101.5, 90.0]));
+        assert_eq!(crystal.space_group.as_deref(), Some("P 1 21 1"));
+        assert_eq!(crystal.fractional_ops, ["x,y,z", "-x,y+1/2,-z"]);
+        let (orth, frac) = orthogonalization(crystal.cell.unwrap()).unwrap();
+        let operators = cartesian_operators(crystal, &orth, &frac).unwrap();
+        assert!(is_identity(&operators[0]));
+        let moved = transform(&operators[1], [0.0, 0.0, 0.0]);
+        assert!((moved[1] - 25.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn lattice_neighbours_add_contacts() {
+        let calibration = Calibration::classic();
+        let plain = parse(&ribbon(20, ""), InputFormat::Pdb);
+        let header = "CRYST1   36.000    6.500   16.000  90.00  90.00  90.00 P 1           1\n";
+        let mut packed = parse(&ribbon(20, header), InputFormat::Pdb);
+        let mates = add_symmetry_mates(&mut packed, &calibration, &mut io::sink()).unwrap();
+        assert!(mates > 0);
+        assert_eq!(packed.environment_atoms, mates);
+        assert_eq!(packed.atmnum, plain.atmnum);
+
+        let total = |data: &AtomData| -> f64 {
+            compute_errat(data, &calibration, &mut io::sink())
+                .unwrap()
+                .scored_frames
+                .iter()
+                .map(|frame| frame.interactions.total)
+                .sum()
+        };
+        assert!(total(&packed) > total(&plain));
+        assert!(crate::interface::interface_residues(&packed, 5.0).is_empty());
+        let stats = compute_errat(&packed, &calibration, &mut io::sink()).unwrap();
+        assert!(stats.scored_frames.iter().all(|frame| {
+            frame
+                .interactions
+                .inter_chain_counts
+                .iter()
+                .all(|&count| count == 0.0)
+        }));
+
+        let mut bare = plain;
+        assert!(add_symmetry_mates(&mut bare, &calibration, &mut io::sink()).is_err());
+    }
+}
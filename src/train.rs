@@ -0,0 +1,184 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use rayon::prelude::*;
+
+use crate::calibration::{CHI2_5_95, CHI2_5_99, Calibration};
+use crate::parser::parse_structure;
+use crate::stats::{WindowOutcome, window_outcomes};
+
+#[derive(Clone, Debug)]
+pub struct TrainingReport {
+    pub calibration: Calibration,
+    pub structure_count: usize,
+    pub window_count: usize,
+    pub skipped: Vec<String>,
+}
+
+fn structure_windows(path: &Path, base: &Calibration) -> io::Result<Vec<[f64; 5]>> {
+    let mut sink = io::sink();
+    let data = parse_structure(&path.to_path_buf(), &mut sink, false)?;
+    Ok(window_outcomes(&data, base, &mut sink)?
+        .into_iter()
+        .filter_map(|outcome| match outcome {
+            WindowOutcome::Value { interactions, .. } => Some(interactions.fractions),
+            WindowOutcome::Warn(_) => None,
+        })
+        .collect())
+}
+
+fn invert(matrix: [[f64; 5]; 5]) -> Option<[[f64; 5]; 5]> {
+    let mut a = matrix;
+    let mut inv = [[0.0f64; 5]; 5];
+    for (i, row) in inv.iter_mut().enumerate() {
+        row[i] = 1.0;
+    }
+    for col in 0..5 {
+        let pivot = (col..5).max_by(|&x, &y| a[x][col].abs().total_cmp(&a[y][col].abs()))?;
+        if a[pivot][col].abs() < 1e-300 {
+            return None;
+        }
+        a.swap(col, pivot);
+        inv.swap(col, pivot);
+        let scale = a[col][col];
+        for k in 0..5 {
+            a[col][k] /= scale;
+            inv[col][k] /= scale;
+        }
+        for row in 0..5 {
+            if row == col {
+                continue;
+            }
+            let factor = a[row][col];
+            for k in 0..5 {
+                a[row][k] -= factor * a[col][k];
+                inv[row][k] -= factor * inv[col][k];
+            }
+        }
+    }
+    Some(inv)
+}
+
+pub(crate) fn fit_calibration(windows: &[[f64; 5]], base: &Calibration) -> io::Result<Calibration> {
+    if windows.len() <= 5 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "training needs more than 5 scored windows, found {}",
+                windows.len()
+            ),
+        ));
+    }
+
+    let n = windows.len() as f64;
+    let mut mean = [0.0f64; 5];
+    for window in windows {
+        for (m, value) in mean.iter_mut().zip(window) {
+            *m += value;
+        }
+    }
+    for m in &mut mean {
+        *m /= n;
+    }
+
+    let mut covariance = [[0.0f64; 5]; 5];
+    for window in windows {
+        for j in 0..5 {
+            for k in 0..5 {
+                covariance[j][k] += (window[j] - mean[j]) * (window[k] - mean[k]);
+            }
+        }
+    }
+    for row in &mut covariance {
+        for value in row.iter_mut() {
+            *value /= n - 1.0;
+        }
+    }
+
+    let inverse_covariance = invert(covariance).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            "training windows have a singular covariance matrix",
+        )
+    })?;
+
+    Ok(Calibration {
+        mean,
+        inverse_covariance,
+        limit_95: CHI2_5_95,
+        limit_99: CHI2_5_99,
+        ..base.clone()
+    })
+}
+
+pub fn train_calibration<P: AsRef<Path> + Sync>(
+    inputs: &[P],
+    base: &Calibration,
+) -> io::Result<TrainingReport> {
+    base.validate()?;
+    let results: Vec<(PathBuf, io::Result<Vec<[f64; 5]>>)> = inputs
+        .par_iter()
+        .map(|path| {
+            let path = path.as_ref();
+            (path.to_path_buf(), structure_windows(path, base))
+        })
+        .collect();
+
+    let mut windows = Vec::new();
+    let mut structure_count = 0usize;
+    let mut skipped = Vec::new();
+    for (path, result) in results {
+        match result {
+            Ok(found) if !found.is_empty() => {
+                structure_count += 1;
+                windows.extend(found);
+            }
+            Ok(_) => skipped.push(format!("{}: no scored windows", path.display())),
+            Err(err) => skipped.push(format!("{}: {err}", path.display())),
+        }
+    }
+
+    let calibration = fit_calibration(&windows, base)?;
+    Ok(TrainingReport {
+        calibration,
+        structure_count,
+        window_count: windows.len(),
+        skipped,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fit_calibration_recovers_mean_and_inverse() {
+        let mut windows = Vec::new();
+        for i in 0..200 {
+            let t = i as f64;
+            windows.push([
+                0.2 + 0.01 * (t * 0.7).sin(),
+                0.2 + 0.01 * (t * 1.3).cos(),
+                0.3 + 0.01 * (t * 2.1).sin(),
+                0.05 + 0.01 * (t * 0.3).cos(),
+                0.25 + 0.01 * (t * 1.7).sin() * (t * 0.2).cos(),
+            ]);
+        }
+        let mut base = Calibration::classic();
+        base.radius = 4.5;
+        let calibration = fit_calibration(&windows, &base).unwrap();
+        assert_eq!(calibration.radius, 4.5);
+        assert!(calibration.score(&calibration.mean).abs() < 1e-9);
+
+        let scores: f64 = windows.iter().map(|w| calibration.score(w)).sum();
+        let expected = 5.0 * (windows.len() as f64 - 1.0) / windows.len() as f64;
+        assert!((scores / windows.len() as f64 - expected).abs() < 1e-6);
+        assert_eq!(calibration.limit_95, CHI2_5_95);
+    }
+
+    #[test]
+    fn fit_calibration_rejects_too_few_windows() {
+        let err = fit_calibration(&[[0.2; 5]; 3], &Calibration::classic()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+}
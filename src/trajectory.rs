@@ -0,0 +1,1059 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::api::{AnalysisOptions, chain_label, compute_chain_ranges};
+use crate::export::{csv_field, json_number};
+use crate::model::AtomData;
+use crate::parser::parse_structure;
+use crate::stats::compute_errat;
+
+const AKMA_PICOSECONDS: f64 = 0.048_888_21;
+/// Largest DCD title or unit cell record accepted; real files use a few 80-byte lines.
+const DCD_SMALL_RECORD: usize = 80 * 64 + 4;
+const XTC_MAGIC: i32 = 1995;
+const XTC_FIRST_IDX: usize = 9;
+const XTC_MAGIC_INTS: [i32; 73] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 8, 10, 12, 16, 20, 25, 32, 40, 50, 64, 80, 101, 128, 161, 203, 256,
+    322, 406, 512, 645, 812, 1024, 1290, 1625, 2048, 2580, 3250, 4096, 5060, 6501, 8192, 10321,
+    13003, 16384, 20642, 26007, 32768, 41285, 52015, 65536, 82570, 104031, 131072, 165140, 208063,
+    262144, 330280, 416127, 524287, 660561, 832255, 1048576, 1321122, 1664510, 2097152, 2642245,
+    3329021, 4194304, 5284491, 6658042, 8388607, 10568983, 13316085, 16777216,
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TrajectoryFormat {
+    Dcd,
+    Xtc,
+    Pdb,
+}
+
+impl TrajectoryFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "dcd" => Some(TrajectoryFormat::Dcd),
+            "xtc" => Some(TrajectoryFormat::Xtc),
+            "pdb" | "ent" => Some(TrajectoryFormat::Pdb),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TrajectoryResidue {
+    pub chain_id: String,
+    pub residue: i32,
+}
+
+#[derive(Clone, Debug)]
+pub struct TrajectoryFrame {
+    pub index: usize,
+    pub time: Option<f64>,
+    pub quality_factor: Option<f64>,
+    pub average_error_value: Option<f64>,
+    pub scored_frame_count: usize,
+    pub rejected_frame_count: usize,
+    pub error_values: Vec<Option<f64>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct TrajectoryAnalysis {
+    pub topology_path: PathBuf,
+    pub trajectory_path: PathBuf,
+    pub residues: Vec<TrajectoryResidue>,
+    pub frames: Vec<TrajectoryFrame>,
+}
+
+pub(crate) struct CoordinateFrame {
+    pub(crate) time: Option<f64>,
+    pub(crate) coords: Vec<[f64; 3]>,
+}
+
+pub(crate) trait FrameSource {
+    fn next_frame(&mut self) -> io::Result<Option<CoordinateFrame>>;
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+fn read_exact_or_eof<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<bool> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..])? {
+            0 if filled == 0 => return Ok(false),
+            0 => return Err(invalid_data("trajectory ends in the middle of a frame")),
+            n => filled += n,
+        }
+    }
+    Ok(true)
+}
+
+pub(crate) struct DcdReader<R: Read> {
+    reader: R,
+    big_endian: bool,
+    atoms: usize,
+    unit_cell: bool,
+    four_dims: bool,
+    start: i32,
+    interval: i32,
+    timestep: f64,
+    frame: usize,
+}
+
+impl<R: Read> DcdReader<R> {
+    pub(crate) fn new(mut reader: R) -> io::Result<Self> {
+        let mut marker = [0u8; 4];
+        reader.read_exact(&mut marker)?;
+        let big_endian = match (i32::from_le_bytes(marker), i32::from_be_bytes(marker)) {
+            (84, _) => false,
+            (_, 84) => true,
+            _ => return Err(invalid_data("not a DCD file: bad header record")),
+        };
+        let mut dcd = DcdReader {
+            reader,
+            big_endian,
+            atoms: 0,
+            unit_cell: false,
+            four_dims: false,
+            start: 0,
+            interval: 0,
+            timestep: 0.0,
+            frame: 0,
+        };
+
+        let header = dcd.record_body(84)?;
+        if &header[..4] != b"CORD" {
+            return Err(invalid_data("not a DCD file: missing CORD signature"));
+        }
+        let control: Vec<i32> = header[4..]
+            .chunks_exact(4)
+            .map(|chunk| dcd.int(chunk))
+            .collect();
+        let charmm = control[19] != 0;
+        if control[8] != 0 {
+            return Err(invalid_data("DCD files with fixed atoms are not supported"));
+        }
+        dcd.start = control[1];
+        dcd.interval = control[2];
+        dcd.timestep = if charmm {
+            dcd.float(&header[40..44]) as f64
+        } else {
+            f64::from_bits(if big_endian {
+                u64::from_be_bytes(header[40..48].try_into().unwrap())
+            } else {
+                u64::from_le_bytes(header[40..48].try_into().unwrap())
+            })
+        };
+        dcd.unit_cell = charmm && control[10] != 0;
+        dcd.four_dims = charmm && control[11] != 0;
+
+        dcd.record(DCD_SMALL_RECORD)?;
+        let atoms = dcd.record(4)?;
+        if atoms.len() != 4 {
+            return Err(invalid_data("DCD atom count record has the wrong size"));
+        }
+        dcd.atoms = usize::try_from(dcd.int(&atoms))
+            .map_err(|_| invalid_data("DCD atom count is negative"))?;
+        Ok(dcd)
+    }
+
+    fn int(&self, bytes: &[u8]) -> i32 {
+        let bytes: [u8; 4] = bytes.try_into().unwrap();
+        if self.big_endian {
+            i32::from_be_bytes(bytes)
+        } else {
+            i32::from_le_bytes(bytes)
+        }
+    }
+
+    fn float(&self, bytes: &[u8]) -> f32 {
+        f32::from_bits(self.int(bytes) as u32)
+    }
+
+    fn record_body(&mut self, length: usize) -> io::Result<Vec<u8>> {
+        let mut body = vec![0u8; length + 4];
+        self.reader.read_exact(&mut body)?;
+        if self.int(&body[length..]) as usize != length {
+            return Err(invalid_data("DCD record markers do not match"));
+        }
+        body.truncate(length);
+        Ok(body)
+    }
+
+    fn record(&mut self, limit: usize) -> io::Result<Vec<u8>> {
+        let mut marker = [0u8; 4];
+        self.reader.read_exact(&mut marker)?;
+        let length = usize::try_from(self.int(&marker))
+            .map_err(|_| invalid_data("DCD record has a negative length"))?;
+        if length > limit {
+            return Err(invalid_data(format!(
+                "DCD record of {length} bytes exceeds the expected {limit}"
+            )));
+        }
+        self.record_body(length)
+    }
+
+    fn coordinate_bytes(&self) -> io::Result<usize> {
+        self.atoms
+            .checked_mul(4)
+            .ok_or_else(|| invalid_data("DCD atom count is too large"))
+    }
+
+    fn coordinate_record(&mut self, first: bool) -> io::Result<Option<Vec<f32>>> {
+        let mut marker = [0u8; 4];
+        if first {
+            if !read_exact_or_eof(&mut self.reader, &mut marker)? {
+                return Ok(None);
+            }
+        } else {
+            self.reader.read_exact(&mut marker)?;
+        }
+        let length = self.int(&marker) as usize;
+        if length != self.coordinate_bytes()? {
+            return Err(invalid_data(format!(
+                "DCD frame {} has {} coordinates, expected {}",
+                self.frame + 1,
+                length / 4,
+                self.atoms
+            )));
+        }
+        let body = self.record_body(length)?;
+        Ok(Some(
+            body.chunks_exact(4)
+                .map(|chunk| self.float(chunk))
+                .collect(),
+        ))
+    }
+}
+
+impl<R: Read> FrameSource for DcdReader<R> {
+    fn next_frame(&mut self) -> io::Result<Option<CoordinateFrame>> {
+        let mut first = true;
+        if self.unit_cell {
+            let mut marker = [0u8; 4];
+            if !read_exact_or_eof(&mut self.reader, &mut marker)? {
+                return Ok(None);
+            }
+            let length = usize::try_from(self.int(&marker))
+                .map_err(|_| invalid_data("DCD unit cell record has a negative length"))?;
+            if length > DCD_SMALL_RECORD {
+                return Err(invalid_data(format!(
+                    "DCD unit cell record of {length} bytes is too large"
+                )));
+            }
+            self.record_body(length)?;
+            first = false;
+        }
+        let Some(x) = self.coordinate_record(first)? else {
+            return Ok(None);
+        };
+        let y = self.coordinate_record(false)?.unwrap_or_default();
+        let z = self.coordinate_record(false)?.unwrap_or_default();
+        if self.four_dims {
+            let limit = self.coordinate_bytes()?;
+            self.record(limit)?;
+        }
+        let time = (self.interval > 0).then_some(
+            (self.start as f64 + (self.frame as f64) * self.interval as f64)
+                * self.timestep
+                * AKMA_PICOSECONDS,
+        );
+        self.frame += 1;
+        Ok(Some(CoordinateFrame {
+            time,
+            coords: (0..self.atoms)
+                .map(|k| [x[k] as f64, y[k] as f64, z[k] as f64])
+                .collect(),
+        }))
+    }
+}
+
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    count: usize,
+    last_bits: u32,
+    last_byte: u32,
+}
+
+impl BitReader<'_> {
+    fn bits(&mut self, mut num_of_bits: u32) -> io::Result<i32> {
+        let mask = if num_of_bits >= 32 {
+            u32::MAX
+        } else {
+            (1u32 << num_of_bits) - 1
+        };
+        let mut next = || {
+            let byte = *self
+                .bytes
+                .get(self.count)
+                .ok_or_else(|| invalid_data("XTC compressed block is truncated"))?;
+            self.count += 1;
+            Ok::<u32, io::Error>(byte as u32)
+        };
+        let mut num = 0u32;
+        let mut last_byte = self.last_byte;
+        let mut last_bits = self.last_bits;
+        while num_of_bits >= 8 {
+            last_byte = (last_byte << 8) | next()?;
+            num |= (last_byte >> last_bits) << (num_of_bits - 8);
+            num_of_bits -= 8;
+        }
+        if num_of_bits > 0 {
+            if last_bits < num_of_bits {
+                last_bits += 8;
+                last_byte = (last_byte << 8) | next()?;
+            }
+            last_bits -= num_of_bits;
+            num |= (last_byte >> last_bits) & ((1 << num_of_bits) - 1);
+        }
+        self.last_byte = last_byte;
+        self.last_bits = last_bits;
+        Ok((num & mask) as i32)
+    }
+
+    fn ints(&mut self, mut num_of_bits: u32, sizes: [u32; 3]) -> io::Result<[i32; 3]> {
+        let mut bytes = [0u32; 32];
+        let mut num_of_bytes = 0;
+        while num_of_bits > 8 {
+            bytes[num_of_bytes] = self.bits(8)? as u32;
+            num_of_bytes += 1;
+            num_of_bits -= 8;
+        }
+        if num_of_bits > 0 {
+            bytes[num_of_bytes] = self.bits(num_of_bits)? as u32;
+            num_of_bytes += 1;
+        }
+        let mut nums = [0i32; 3];
+        for i in (1..3).rev() {
+            let mut num = 0u64;
+            for j in (0..num_of_bytes).rev() {
+                num = (num << 8) | bytes[j] as u64;
+                let p = num / sizes[i] as u64;
+                bytes[j] = p as u32;
+                num -= p * sizes[i] as u64;
+            }
+            nums[i] = num as i32;
+        }
+        nums[0] = (bytes[0] | (bytes[1] << 8) | (bytes[2] << 16) | (bytes[3] << 24)) as i32;
+        Ok(nums)
+    }
+}
+
+fn size_of_int(size: u32) -> u32 {
+    let mut num = 1u64;
+    let mut bits = 0;
+    while size as u64 >= num && bits < 32 {
+        bits += 1;
+        num <<= 1;
+    }
+    bits
+}
+
+fn size_of_ints(sizes: [u32; 3]) -> u32 {
+    let mut bytes = [0u32; 32];
+    bytes[0] = 1;
+    let mut num_of_bytes = 1;
+    for size in sizes {
+        let mut tmp = 0u32;
+        let mut count = 0;
+        while count < num_of_bytes {
+            tmp = tmp.wrapping_add(bytes[count].wrapping_mul(size));
+            bytes[count] = tmp & 0xff;
+            tmp >>= 8;
+            count += 1;
+        }
+        while tmp != 0 {
+            bytes[count] = tmp & 0xff;
+            tmp >>= 8;
+            count += 1;
+        }
+        num_of_bytes = count;
+    }
+    let mut num = 1u32;
+    let mut bits = 0;
+    num_of_bytes -= 1;
+    while bytes[num_of_bytes] >= num {
+        bits += 1;
+        num *= 2;
+    }
+    bits + num_of_bytes as u32 * 8
+}
+
+pub(crate) struct XtcReader<R: Read> {
+    reader: R,
+}
+
+impl<R: Read> XtcReader<R> {
+    pub(crate) fn new(reader: R) -> Self {
+        XtcReader { reader }
+    }
+
+    fn int(&mut self) -> io::Result<i32> {
+        let mut bytes = [0u8; 4];
+        self.reader.read_exact(&mut bytes)?;
+        Ok(i32::from_be_bytes(bytes))
+    }
+
+    fn float(&mut self) -> io::Result<f32> {
+        Ok(f32::from_bits(self.int()? as u32))
+    }
+
+    fn coordinates(&mut self, atoms: usize) -> io::Result<Vec<[f64; 3]>> {
+        if self.int()? as usize != atoms {
+            return Err(invalid_data("XTC coordinate block atom count mismatch"));
+        }
+        if atoms <= 9 {
+            let mut coords = Vec::with_capacity(atoms);
+            for _ in 0..atoms {
+                coords.push([
+                    self.float()? as f64,
+                    self.float()? as f64,
+                    self.float()? as f64,
+                ]);
+            }
+            return Ok(coords);
+        }
+
+        let precision = self.float()? as f64;
+        let mut min_int = [0i32; 3];
+        let mut max_int = [0i32; 3];
+        for value in &mut min_int {
+            *value = self.int()?;
+        }
+        for value in &mut max_int {
+            *value = self.int()?;
+        }
+        let mut sizes = [0u32; 3];
+        for ((size, max), min) in sizes.iter_mut().zip(max_int).zip(min_int) {
+            *size = u32::try_from(max as i64 - min as i64 + 1)
+                .ok()
+                .filter(|size| *size > 0)
+                .ok_or_else(|| invalid_data("XTC coordinate range is empty or too large"))?;
+        }
+        let large = (sizes[0] | sizes[1] | sizes[2]) > 0xff_ffff;
+        let bit_sizes = sizes.map(size_of_int);
+        let bit_size = if large { 0 } else { size_of_ints(sizes) };
+
+        let mut small_idx = usize::try_from(self.int()?)
+            .ok()
+            .filter(|idx| *idx < XTC_MAGIC_INTS.len())
+            .ok_or_else(|| invalid_data("XTC precision index out of range"))?;
+        let mut smaller = XTC_MAGIC_INTS[XTC_FIRST_IDX.max(small_idx.saturating_sub(1))] / 2;
+        let mut small_num = XTC_MAGIC_INTS[small_idx] / 2;
+        let mut size_small = [XTC_MAGIC_INTS[small_idx] as u32; 3];
+
+        let byte_count = usize::try_from(self.int()?)
+            .map_err(|_| invalid_data("XTC compressed block has a negative size"))?;
+        if atoms
+            .checked_mul(3 * 4)
+            .is_none_or(|limit| byte_count > limit)
+        {
+            return Err(invalid_data(format!(
+                "XTC compressed block of {byte_count} bytes is too large for {atoms} atoms"
+            )));
+        }
+        let mut bytes = vec![0u8; byte_count.div_ceil(4) * 4];
+        self.reader.read_exact(&mut bytes)?;
+        let mut bits = BitReader {
+            bytes: &bytes,
+            count: 0,
+            last_bits: 0,
+            last_byte: 0,
+        };
+
+        let scale = 1.0 / precision;
+        let to_coord = |c: [i32; 3]| c.map(|v| v as f64 * scale);
+        let mut coords = Vec::with_capacity(atoms);
+        let mut run = 0;
+        while coords.len() < atoms {
+            let mut this = if large {
+                [
+                    bits.bits(bit_sizes[0])?,
+                    bits.bits(bit_sizes[1])?,
+                    bits.bits(bit_sizes[2])?,
+                ]
+            } else {
+                bits.ints(bit_size, sizes)?
+            };
+            for k in 0..3 {
+                this[k] += min_int[k];
+            }
+            let mut prev = this;
+
+            let mut is_smaller = 0i32;
+            if bits.bits(1)? == 1 {
+                run = bits.bits(5)?;
+                is_smaller = run % 3;
+                run -= is_smaller;
+                is_smaller -= 1;
+            }
+            if run > 0 {
+                for k in (0..run).step_by(3) {
+                    let mut next = bits.ints(small_idx as u32, size_small)?;
+                    for axis in 0..3 {
+                        next[axis] += prev[axis] - small_num;
+                    }
+                    if k == 0 {
+                        std::mem::swap(&mut next, &mut prev);
+                        coords.push(to_coord(prev));
+                    } else {
+                        prev = next;
+                    }
+                    coords.push(to_coord(next));
+                }
+            } else {
+                coords.push(to_coord(this));
+            }
+
+            let shifted = small_idx as i32 + is_smaller;
+            if shifted < 0 || shifted as usize >= XTC_MAGIC_INTS.len() {
+                return Err(invalid_data("XTC precision index out of range"));
+            }
+            small_idx = shifted as usize;
+            if is_smaller < 0 {
+                small_num = smaller;
+                smaller = if small_idx > XTC_FIRST_IDX {
+                    XTC_MAGIC_INTS[small_idx - 1] / 2
+                } else {
+                    0
+                };
+            } else if is_smaller > 0 {
+                smaller = small_num;
+                small_num = XTC_MAGIC_INTS[small_idx] / 2;
+            }
+            size_small = [XTC_MAGIC_INTS[small_idx] as u32; 3];
+        }
+        coords.truncate(atoms);
+        Ok(coords)
+    }
+}
+
+impl<R: Read> FrameSource for XtcReader<R> {
+    fn next_frame(&mut self) -> io::Result<Option<CoordinateFrame>> {
+        let mut magic = [0u8; 4];
+        if !read_exact_or_eof(&mut self.reader, &mut magic)? {
+            return Ok(None);
+        }
+        if i32::from_be_bytes(magic) != XTC_MAGIC {
+            return Err(invalid_data("not an XTC file: bad frame magic"));
+        }
+        let atoms =
+            usize::try_from(self.int()?).map_err(|_| invalid_data("XTC atom count is negative"))?;
+        let _step = self.int()?;
+        let time = self.float()? as f64;
+        for _ in 0..9 {
+            self.float()?;
+        }
+        let coords = self
+            .coordinates(atoms)?
+            .into_iter()
+            .map(|c| c.map(|v| v * 10.0))
+            .collect();
+        Ok(Some(CoordinateFrame {
+            time: Some(time),
+            coords,
+        }))
+    }
+}
+
+pub(crate) struct ModelReader<R: BufRead> {
+    reader: R,
+    line: String,
+}
+
+impl<R: BufRead> ModelReader<R> {
+    pub(crate) fn new(reader: R) -> Self {
+        ModelReader {
+            reader,
+            line: String::new(),
+        }
+    }
+}
+
+impl<R: BufRead> FrameSource for ModelReader<R> {
+    fn next_frame(&mut self) -> io::Result<Option<CoordinateFrame>> {
+        let mut coords = Vec::new();
+        loop {
+            self.line.clear();
+            if self.reader.read_line(&mut self.line)? == 0 {
+                break;
+            }
+            let line = self.line.trim_end_matches(['\n', '\r']);
+            if line.starts_with("ENDMDL") {
+                if coords.is_empty() {
+                    continue;
+                }
+                break;
+            }
+            if !(line.starts_with("ATOM  ") || line.starts_with("HETATM")) {
+                continue;
+            }
+            let number = |start: usize, end: usize| {
+                line.get(start..end)
+                    .and_then(|field| field.trim().parse::<f64>().ok())
+                    .ok_or_else(|| invalid_data(format!("bad coordinate in PDB record: {line}")))
+            };
+            coords.push([number(30, 38)?, number(38, 46)?, number(46, 54)?]);
+        }
+        Ok((!coords.is_empty()).then_some(CoordinateFrame { time: None, coords }))
+    }
+}
+
+fn open_frames(path: &Path) -> io::Result<Box<dyn FrameSource>> {
+    let format = TrajectoryFormat::from_path(path).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "trajectory must be a .dcd, .xtc or multi-model .pdb file",
+        )
+    })?;
+    let reader = BufReader::new(File::open(path)?);
+    Ok(match format {
+        TrajectoryFormat::Dcd => Box::new(DcdReader::new(reader)?),
+        TrajectoryFormat::Xtc => Box::new(XtcReader::new(reader)),
+        TrajectoryFormat::Pdb => Box::new(ModelReader::new(reader)),
+    })
+}
+
+pub(crate) fn score_frames(
+    data: &mut AtomData,
+    frames: &mut dyn FrameSource,
+    options: &AnalysisOptions,
+    stride: usize,
+) -> io::Result<(Vec<TrajectoryResidue>, Vec<TrajectoryFrame>)> {
+    let stride = stride.max(1);
+    let mut sink = io::sink();
+    let mut columns: BTreeMap<i32, TrajectoryResidue> = BTreeMap::new();
+    let mut scored: Vec<(TrajectoryFrame, BTreeMap<i32, f64>)> = Vec::new();
+    let mut count = 0usize;
+    while let Some(frame) = frames.next_frame()? {
+        let index = count;
+        count += 1;
+        if !index.is_multiple_of(stride) {
+            continue;
+        }
+        if frame.coords.len() != data.records {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "trajectory frame {index} has {} atoms but the topology has {}",
+                    frame.coords.len(),
+                    data.records
+                ),
+            ));
+        }
+        for atom in 1..=data.atmnum {
+            let [x, y, z] = frame.coords[data.source_index[atom]];
+            data.xyz_x[atom] = x;
+            data.xyz_y[atom] = y;
+            data.xyz_z[atom] = z;
+        }
+        let stats = compute_errat(data, &options.calibration, &mut sink)?;
+        let chain_ranges = compute_chain_ranges(&stats);
+        let mut values = BTreeMap::new();
+        for raw in &stats.scored_frames {
+            columns.entry(raw.center_residue).or_insert_with(|| {
+                let chain_id = chain_ranges
+                    .iter()
+                    .find(|range| {
+                        (range.start_residue..=range.end_residue).contains(&raw.center_residue)
+                    })
+                    .map(|range| chain_label(range.chain_id))
+                    .unwrap_or_default();
+                TrajectoryResidue {
+                    chain_id,
+                    residue: raw.center_residue - raw.residue_offset,
+                }
+            });
+            values.insert(raw.center_residue, raw.error_value);
+        }
+        scored.push((
+            TrajectoryFrame {
+                index,
+                time: frame.time,
+                quality_factor: stats.overall_quality_factor,
+                average_error_value: stats.avg_probability,
+                scored_frame_count: stats.scored_frames.len(),
+                rejected_frame_count: stats.warning_frames.len(),
+                error_values: Vec::new(),
+            },
+            values,
+        ));
+    }
+    if scored.is_empty() {
+        return Err(invalid_data("trajectory contains no frames"));
+    }
+
+    let frames = scored
+        .into_iter()
+        .map(|(mut frame, values)| {
+            frame.error_values = columns.keys().map(|key| values.get(key).copied()).collect();
+            frame
+        })
+        .collect();
+    Ok((columns.into_values().collect(), frames))
+}
+
+pub fn analyze_trajectory<P: AsRef<Path>, Q: AsRef<Path>>(
+    topology: P,
+    trajectory: Q,
+    use_mmap: bool,
+    options: &AnalysisOptions,
+    stride: usize,
+) -> io::Result<TrajectoryAnalysis> {
+    options.validate()?;
+    if options.crystal_contacts
+        || options.assembly.is_some()
+        || options.hetero_environment.is_some()
+        || options.plddt_cutoff.is_some()
+        || options.secondary_structure
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "crystal contacts, assemblies, hetero environments, pLDDT masking and secondary structure are not supported for trajectories",
+        ));
+    }
+    let topology_path = topology.as_ref().to_path_buf();
+    let trajectory_path = trajectory.as_ref().to_path_buf();
+    let mut frames = open_frames(&trajectory_path)?;
+    let mut data = parse_structure(&topology_path, &mut io::sink(), use_mmap)?;
+    let (residues, frames) = score_frames(&mut data, frames.as_mut(), options, stride)?;
+    Ok(TrajectoryAnalysis {
+        topology_path,
+        trajectory_path,
+        residues,
+        frames,
+    })
+}
+
+fn optional_number(value: Option<f64>) -> String {
+    value.map(json_number).unwrap_or_default()
+}
+
+pub fn write_trajectory_matrix_csv<W: Write>(
+    out: &mut W,
+    analysis: &TrajectoryAnalysis,
+) -> io::Result<()> {
+    write!(out, "frame,time")?;
+    for residue in &analysis.residues {
+        let label = if residue.chain_id.is_empty() {
+            residue.residue.to_string()
+        } else {
+            format!("{}:{}", residue.chain_id, residue.residue)
+        };
+        write!(out, ",{}", csv_field(&label))?;
+    }
+    writeln!(out)?;
+    for frame in &analysis.frames {
+        write!(out, "{},{}", frame.index, optional_number(frame.time))?;
+        for value in &frame.error_values {
+            write!(out, ",{}", optional_number(*value))?;
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+pub fn write_trajectory_series_csv<W: Write>(
+    out: &mut W,
+    analysis: &TrajectoryAnalysis,
+) -> io::Result<()> {
+    writeln!(
+        out,
+        "frame,time,quality_factor,average_error_value,scored_frames,rejected_frames"
+    )?;
+    for frame in &analysis.frames {
+        writeln!(
+            out,
+            "{},{},{},{},{},{}",
+            frame.index,
+            optional_number(frame.time),
+            optional_number(frame.quality_factor),
+            optional_number(frame.average_error_value),
+            frame.scored_frame_count,
+            frame.rejected_frame_count
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::InputFormat;
+    use crate::fixtures::Ribbon;
+    use crate::parser::parse_reader;
+    use std::io::Cursor;
+
+    fn record(out: &mut Vec<u8>, body: &[u8]) {
+        out.extend((body.len() as i32).to_le_bytes());
+        out.extend(body);
+        out.extend((body.len() as i32).to_le_bytes());
+    }
+
+    fn floats(values: impl IntoIterator<Item = f32>) -> Vec<u8> {
+        values.into_iter().flat_map(f32::to_le_bytes).collect()
+    }
+
+    #[test]
+    fn dcd_frames_skip_unit_cells_and_report_time() {
+        let mut header = b"CORD".to_vec();
+        let mut control = [0i32; 20];
+        control[0] = 2;
+        control[2] = 10;
+        control[10] = 1;
+        control[19] = 24;
+        for value in control {
+            header.extend(value.to_le_bytes());
+        }
+        header[40..44].copy_from_slice(&2.0f32.to_le_bytes());
+
+        let mut dcd = Vec::new();
+        record(&mut dcd, &header);
+        let mut title = 1i32.to_le_bytes().to_vec();
+        title.extend([b' '; 80]);
+        record(&mut dcd, &title);
+        record(&mut dcd, &2i32.to_le_bytes());
+        for shift in [0.0f32, 1.5] {
+            record(&mut dcd, &[0u8; 48]);
+            record(&mut dcd, &floats([1.0 + shift, 2.0 + shift]));
+            record(&mut dcd, &floats([3.0, 4.0]));
+            record(&mut dcd, &floats([5.0, 6.0]));
+        }
+
+        let mut reader = DcdReader::new(Cursor::new(dcd)).unwrap();
+        let first = reader.next_frame().unwrap().unwrap();
+        assert_eq!(first.coords, vec![[1.0, 3.0, 5.0], [2.0, 4.0, 6.0]]);
+        assert_eq!(first.time, Some(0.0));
+        let second = reader.next_frame().unwrap().unwrap();
+        assert_eq!(second.coords[0], [2.5, 3.0, 5.0]);
+        let time = second.time.unwrap();
+        assert!((time - 10.0 * 2.0 * AKMA_PICOSECONDS).abs() < 1e-9);
+        assert!(reader.next_frame().unwrap().is_none());
+    }
+
+    #[test]
+    fn dcd_rejects_negative_unit_cell_length() {
+        let mut header = b"CORD".to_vec();
+        let mut control = [0i32; 20];
+        control[0] = 1;
+        control[10] = 1;
+        control[19] = 24;
+        for value in control {
+            header.extend(value.to_le_bytes());
+        }
+        let mut dcd = Vec::new();
+        record(&mut dcd, &header);
+        record(&mut dcd, &0i32.to_le_bytes());
+        record(&mut dcd, &1i32.to_le_bytes());
+        dcd.extend((-48i32).to_le_bytes());
+
+        let mut reader = DcdReader::new(Cursor::new(dcd)).unwrap();
+        let err = reader.next_frame().err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn dcd_rejects_oversized_title_record() {
+        let mut header = b"CORD".to_vec();
+        let mut control = [0i32; 20];
+        control[0] = 1;
+        control[19] = 24;
+        for value in control {
+            header.extend(value.to_le_bytes());
+        }
+        let mut dcd = Vec::new();
+        record(&mut dcd, &header);
+        dcd.extend(i32::MAX.to_le_bytes());
+
+        let err = DcdReader::new(Cursor::new(dcd)).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("exceeds"), "{err}");
+    }
+
+    #[derive(Default)]
+    struct BitWriter {
+        bytes: Vec<u8>,
+        bits: usize,
+    }
+
+    impl BitWriter {
+        fn push(&mut self, num_of_bits: u32, value: u64) {
+            for bit in (0..num_of_bits).rev() {
+                if self.bits.is_multiple_of(8) {
+                    self.bytes.push(0);
+                }
+                if (value >> bit) & 1 == 1 {
+                    *self.bytes.last_mut().unwrap() |= 0x80 >> (self.bits % 8);
+                }
+                self.bits += 1;
+            }
+        }
+
+        fn ints(&mut self, mut num_of_bits: u32, sizes: [u64; 3], nums: [u64; 3]) {
+            let mut value = (nums[0] * sizes[1] + nums[1]) * sizes[2] + nums[2];
+            while num_of_bits > 8 {
+                self.push(8, value & 0xff);
+                value >>= 8;
+                num_of_bits -= 8;
+            }
+            self.push(num_of_bits, value);
+        }
+    }
+
+    fn xtc_header(out: &mut Vec<u8>, atoms: i32, time: f32) {
+        out.extend(XTC_MAGIC.to_be_bytes());
+        out.extend(atoms.to_be_bytes());
+        out.extend(7i32.to_be_bytes());
+        out.extend(time.to_be_bytes());
+        out.extend([0u8; 36]);
+        out.extend(atoms.to_be_bytes());
+    }
+
+    #[test]
+    fn xtc_frames_decode_raw_and_compressed_coordinates() {
+        let mut xtc = Vec::new();
+        xtc_header(&mut xtc, 1, 2.5);
+        for value in [0.1f32, 0.2, 0.3] {
+            xtc.extend(value.to_be_bytes());
+        }
+
+        let atoms = 10;
+        xtc_header(&mut xtc, atoms, 5.0);
+        let min_int = [100i32, 200, 300];
+        let max_int = [119i32, 219, 319];
+        xtc.extend(1000.0f32.to_be_bytes());
+        for value in min_int.iter().chain(&max_int) {
+            xtc.extend(value.to_be_bytes());
+        }
+        let small_idx = 12usize;
+        xtc.extend((small_idx as i32).to_be_bytes());
+        let sizes = [20u64; 3];
+        let bit_size = size_of_ints([20; 3]);
+        let small = XTC_MAGIC_INTS[small_idx] as u64;
+
+        let mut bits = BitWriter::default();
+        bits.ints(bit_size, sizes, [1, 2, 3]);
+        bits.push(1, 1);
+        bits.push(5, 4);
+        bits.ints(
+            small_idx as u32,
+            [small; 3],
+            [small / 2 + 1, small / 2, small / 2 - 1],
+        );
+        for k in 0..8u64 {
+            bits.ints(bit_size, sizes, [k, k + 1, k + 2]);
+            bits.push(1, 1);
+            bits.push(5, 1);
+        }
+        xtc.extend((bits.bytes.len() as i32).to_be_bytes());
+        xtc.extend(&bits.bytes);
+        xtc.resize(xtc.len() + (4 - bits.bytes.len() % 4) % 4, 0);
+
+        let mut reader = XtcReader::new(Cursor::new(xtc));
+        let raw = reader.next_frame().unwrap().unwrap();
+        assert_eq!(raw.time, Some(2.5));
+        assert!((raw.coords[0][2] - 3.0).abs() < 1e-5);
+
+        let compressed = reader.next_frame().unwrap().unwrap();
+        assert_eq!(compressed.time, Some(5.0));
+        assert_eq!(compressed.coords.len(), 10);
+        let expected = |c: [i32; 3]| c.map(|v| v as f64 / 100.0);
+        let close = |a: [f64; 3], b: [f64; 3]| (0..3).all(|k| (a[k] - b[k]).abs() < 1e-9);
+        assert!(close(compressed.coords[0], expected([102, 202, 302])));
+        assert!(close(compressed.coords[1], expected([101, 202, 303])));
+        assert!(close(compressed.coords[9], expected([107, 208, 309])));
+        assert!(reader.next_frame().unwrap().is_none());
+    }
+
+    #[test]
+    fn xtc_rejects_oversized_compressed_block() {
+        let mut xtc = Vec::new();
+        xtc_header(&mut xtc, 10, 0.0);
+        xtc.extend(1000.0f32.to_be_bytes());
+        for value in [100i32, 200, 300, 110, 210, 310, 9, i32::MAX] {
+            xtc.extend(value.to_be_bytes());
+        }
+        let err = XtcReader::new(Cursor::new(xtc)).next_frame().err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("too large"), "{err}");
+    }
+
+    #[test]
+    fn xtc_rejects_inverted_coordinate_range() {
+        let mut xtc = Vec::new();
+        xtc_header(&mut xtc, 10, 0.0);
+        xtc.extend(1000.0f32.to_be_bytes());
+        for value in [100i32, 200, 300, 99, 219, 319] {
+            xtc.extend(value.to_be_bytes());
+        }
+        let err = XtcReader::new(Cursor::new(xtc)).next_frame().err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    fn packed_model(scale: f64) -> String {
+        let mut pdb = Ribbon {
+            residues: 15,
+            scale,
+            hydrogens: true,
+            ..Ribbon::default()
+        }
+        .pdb();
+        pdb.push_str(&format!(
+            "HETATM{:>5}  O   HOH W   1    {:>8.3}{:>8.3}{:>8.3}  1.00 20.00           O\n",
+            76, 50.0, 50.0, 50.0
+        ));
+        pdb
+    }
+
+    #[test]
+    fn model_frames_build_residue_matrix_and_quality_series() {
+        let topology = packed_model(1.0);
+        let mut sink = io::sink();
+        let mut data =
+            parse_reader(&mut Cursor::new(&topology), InputFormat::Pdb, &mut sink).unwrap();
+        assert_eq!(data.records, 76);
+
+        let frames = format!(
+            "MODEL        1\n{}ENDMDL\nMODEL        2\n{}ENDMDL\nMODEL        3\n{}ENDMDL\n",
+            topology,
+            packed_model(4.0),
+            topology
+        );
+        let mut reader = ModelReader::new(Cursor::new(frames));
+        let (residues, frames) =
+            score_frames(&mut data, &mut reader, &AnalysisOptions::default(), 1).unwrap();
+        assert!(!residues.is_empty());
+        assert_eq!(residues[0].chain_id, "A");
+        assert_eq!(residues[0].residue, 6);
+        assert_eq!(frames.len(), 3);
+        assert_eq!(
+            frames.iter().map(|frame| frame.index).collect::<Vec<_>>(),
+            [0, 1, 2]
+        );
+        assert!(frames[0].quality_factor.is_some());
+        assert_eq!(frames[1].scored_frame_count, 0);
+        assert!(frames[1].error_values.iter().all(Option::is_none));
+        assert_eq!(frames[0].error_values, frames[2].error_values);
+
+        let analysis = TrajectoryAnalysis {
+            topology_path: PathBuf::from("top.pdb"),
+            trajectory_path: PathBuf::from("traj.pdb"),
+            residues,
+            frames,
+        };
+        let mut matrix = Vec::new();
+        write_trajectory_matrix_csv(&mut matrix, &analysis).unwrap();
+        let matrix = String::from_utf8(matrix).unwrap();
+        assert!(matrix.starts_with("frame,time,A:6,"));
+        assert_eq!(matrix.lines().count(), 4);
+
+        let mut truncated =
+            ModelReader::new(Cursor::new(packed_model(1.0).replace("HETATM", "REMARK")));
+        let err =
+            score_frames(&mut data, &mut truncated, &AnalysisOptions::default(), 1).unwrap_err();
+        assert!(err.to_string().contains("75 atoms but the topology has 76"));
+    }
+}
@@ -1,6 +1,6 @@
 use std::fs::{self, File};
 use std::io::Write;
-use std::process::Command;
+use std::process::{Command, Stdio};
 
 fn write_minimal_pdb(path: &std::path::Path) {
     let mut file = File::create(path).unwrap();
@@ -47,3 +47,42 @@ fn cli_generates_outputs() {
     let log_meta = fs::metadata(log_path).unwrap();
     assert!(log_meta.len() > 0);
 }
+
+#[test]
+fn cli_analyze_streams_stdin_to_stdout() {
+    let temp_dir = std::env::temp_dir().join("errat_test_stream");
+    let _ = fs::remove_dir_all(&temp_dir);
+    fs::create_dir_all(&temp_dir).unwrap();
+    let pdb_path = temp_dir.join("input.pdb");
+    write_minimal_pdb(&pdb_path);
+
+    let exe = env!("CARGO_BIN_EXE_errat");
+    let mut child = Command::new(exe)
+        .args(["analyze", "-", "--format", "pdb", "--json"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to run errat binary");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(&fs::read(&pdb_path).unwrap())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with('{'));
+    assert!(stdout.contains("\"protein_id\": \"errat\""));
+    assert!(!output.stderr.is_empty());
+
+    let status = Command::new(exe)
+        .args(["analyze", "-", "--json"])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(!status.success());
+}